@@ -0,0 +1,319 @@
+//! Interactively stage the changes in a git working copy, as a replacement
+//! for `git add -p`.
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[cfg(feature = "cli")]
+use clap::Parser;
+
+use tug_record::helpers::CrosstermInput;
+use tug_record::{
+    File, FileMode, RecordError, RecordState, Recorder, SelectedChanges, SelectedContents,
+};
+
+use crate::{kept_version, print_dry_run, render, Error, KeptVersion, RealFilesystem, Result};
+
+/// Interactively select the changes in the working copy of a git repository
+/// to stage in the index, like `git add -p`.
+#[derive(Debug)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+pub struct GitAddOpts {
+    /// Only show the changes to the files matching these pathspecs.
+    pub pathspecs: Vec<String>,
+
+    /// Disable all editing controls and do not stage anything.
+    #[cfg_attr(feature = "cli", clap(long = "read-only"))]
+    pub read_only: bool,
+
+    /// Show what would have been staged, but do not actually stage it.
+    #[cfg_attr(feature = "cli", clap(short = 'N', long = "dry-run"))]
+    pub dry_run: bool,
+}
+
+/// The unstaged changes of a git working copy.
+///
+/// The staged version of each changed file is checked out to a temporary
+/// directory, which is removed when this is dropped.
+#[derive(Debug)]
+pub struct GitChanges {
+    /// The root of the working copy.
+    pub root: PathBuf,
+
+    /// The directory containing the staged version of each changed file.
+    pub index_dir: PathBuf,
+
+    /// The changes between the staged and working copy versions of each
+    /// file, with paths relative to `root`.
+    pub files: Vec<File<'static>>,
+}
+
+impl Drop for GitChanges {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.index_dir);
+    }
+}
+
+/// Run `git` in `dir` and return its output.
+fn git(dir: &Path, args: &[&OsStr], stdin: Option<&[u8]>) -> Result<Vec<u8>> {
+    let display_args = || {
+        args.iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|source| Error::RunGit {
+            args: display_args(),
+            source,
+        })?;
+    if let (Some(input), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+        child_stdin
+            .write_all(input)
+            .map_err(|source| Error::RunGit {
+                args: display_args(),
+                source,
+            })?;
+    }
+    let output = child.wait_with_output().map_err(|source| Error::RunGit {
+        args: display_args(),
+        source,
+    })?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(Error::GitFailed {
+            args: display_args(),
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        })
+    }
+}
+
+/// Split NUL-terminated paths, as printed by git with `-z`.
+fn split_paths(output: &[u8]) -> Vec<PathBuf> {
+    output
+        .split(|byte| *byte == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(String::from_utf8_lossy(path).into_owned()))
+        .collect()
+}
+
+/// Read the unstaged changes of the working copy containing `dir`, only
+/// including the files matching `pathspecs`, if any.
+pub fn read_git_changes(dir: &Path, pathspecs: &[String]) -> Result<GitChanges> {
+    let root = git(
+        dir,
+        &["rev-parse".as_ref(), "--show-toplevel".as_ref()],
+        None,
+    )?;
+    let root = PathBuf::from(String::from_utf8_lossy(&root).trim_end());
+
+    // Renames aren't detected, so that each path is compared with its own
+    // staged version.
+    let mut args: Vec<&OsStr> = ["diff", "--name-only", "--no-renames", "-z", "--"]
+        .into_iter()
+        .map(OsStr::new)
+        .collect();
+    args.extend(pathspecs.iter().map(OsStr::new));
+    let paths = split_paths(&git(dir, &args, None)?);
+
+    let index_dir = env::temp_dir().join(format!("tug-git-add-{}", std::process::id()));
+    let mut changes = GitChanges {
+        root,
+        index_dir,
+        files: Vec::new(),
+    };
+    if paths.is_empty() {
+        return Ok(changes);
+    }
+
+    let mut prefix = changes.index_dir.clone().into_os_string();
+    prefix.push(std::path::MAIN_SEPARATOR_STR);
+    let mut checkout_index_args = vec![OsStr::new("checkout-index"), OsStr::new("--prefix")];
+    checkout_index_args.extend([prefix.as_os_str(), OsStr::new("-z"), OsStr::new("--stdin")]);
+    let mut stdin = Vec::new();
+    for path in &paths {
+        stdin.extend(path.to_string_lossy().as_bytes());
+        stdin.push(0);
+    }
+    git(&changes.root, &checkout_index_args, Some(&stdin))?;
+
+    for path in paths {
+        changes.files.push(render::create_file(
+            &RealFilesystem {
+                preserve_mtimes: false,
+            },
+            changes.index_dir.join(&path),
+            path.clone(),
+            changes.root.join(&path),
+            path,
+        )?);
+    }
+    Ok(changes)
+}
+
+/// Stage the selected changes of each file in the index.
+pub fn stage_changes(changes: &GitChanges, state: RecordState) -> Result<()> {
+    let RecordState {
+        is_read_only,
+        commits: _,
+        files,
+    } = state;
+    if is_read_only {
+        return Ok(());
+    }
+    for file in files {
+        let (selected_changes, _unselected_changes) = file.get_selected_contents();
+        let SelectedChanges {
+            contents,
+            file_mode,
+        } = selected_changes;
+        let path = file.path.to_string_lossy().into_owned();
+
+        let mode = match file_mode {
+            FileMode::Absent => {
+                if file.file_mode != FileMode::Absent {
+                    git(
+                        &changes.root,
+                        &["update-index", "--force-remove", "--", &path].map(OsStr::new),
+                        None,
+                    )?;
+                }
+                continue;
+            }
+            FileMode::Unix(mode) => mode,
+        };
+        let read_file =
+            |path: PathBuf| fs::read(&path).map_err(|source| Error::ReadFile { path, source });
+        let contents = match contents {
+            SelectedContents::Unchanged if file_mode == file.file_mode => continue,
+            SelectedContents::Unchanged => read_file(changes.index_dir.join(file.path.as_ref()))?,
+            // Binary files can only be staged as a whole.
+            SelectedContents::Binary {
+                old_description: _,
+                new_description: _,
+            } => read_file(changes.root.join(file.path.as_ref()))?,
+            SelectedContents::Text { contents } => match kept_version(&file)? {
+                Some(KeptVersion::Old) if file.file_mode == FileMode::Absent => Vec::new(),
+                Some(KeptVersion::Old) => read_file(changes.index_dir.join(file.path.as_ref()))?,
+                Some(KeptVersion::New) => read_file(changes.root.join(file.path.as_ref()))?,
+                None => contents.into_bytes(),
+            },
+        };
+
+        let blob = git(
+            &changes.root,
+            &["hash-object", "-w", "--stdin"].map(OsStr::new),
+            Some(&contents),
+        )?;
+        let cache_info = format!("{mode:o},{},{path}", String::from_utf8_lossy(&blob).trim());
+        git(
+            &changes.root,
+            &["update-index", "--add", "--cacheinfo", &cache_info].map(OsStr::new),
+            None,
+        )?;
+    }
+    Ok(())
+}
+
+/// Select changes in the working copy interactively and stage them.
+pub fn run_git_add(opts: GitAddOpts) -> Result<()> {
+    let GitAddOpts {
+        pathspecs,
+        read_only,
+        dry_run,
+    } = opts;
+    let mut changes = read_git_changes(Path::new("."), &pathspecs)?;
+    if changes.files.is_empty() {
+        println!("No unstaged changes");
+        return Ok(());
+    }
+
+    let state = RecordState {
+        is_read_only: read_only,
+        commits: Default::default(),
+        files: std::mem::take(&mut changes.files),
+    };
+    let mut input = CrosstermInput;
+    let recorder = Recorder::new(state, &mut input);
+    match recorder.run() {
+        Ok(state) => {
+            if dry_run {
+                print_dry_run(&changes.root, state);
+                Err(Error::DryRun)
+            } else {
+                stage_changes(&changes, state)
+            }
+        }
+        Err(RecordError::Cancelled) => Err(Error::Cancelled),
+        Err(err) => Err(Error::Record { source: err }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tug_record::Section;
+
+    use super::*;
+    use crate::testing::select_all;
+
+    /// Make an empty directory containing one file, to create a repository
+    /// in.
+    fn make_repo(name: &str) -> std::io::Result<PathBuf> {
+        let dir = env::temp_dir().join(format!("tug-git-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("foo"), "a\nb\nc\n")?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn test_stage_changes() -> Result<()> {
+        let dir = make_repo("stage").unwrap();
+        if git(&dir, &["init", "-q"].map(OsStr::new), None).is_err() {
+            // Without git, there's nothing to test.
+            return Ok(());
+        }
+        git(&dir, &["add", "foo"].map(OsStr::new), None)?;
+        fs::write(dir.join("foo"), "a\nB\nc\nd\n").unwrap();
+
+        let mut changes = read_git_changes(&dir, &[])?;
+        assert_eq!(changes.files.len(), 1);
+        let mut files = std::mem::take(&mut changes.files);
+        select_all(&mut files);
+        // Leave the last added line unstaged.
+        if let Some(Section::Changed { lines, .. }) = files[0].sections.last_mut() {
+            lines.last_mut().unwrap().is_checked = false;
+        }
+        let state = RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        };
+        stage_changes(&changes, state)?;
+
+        let staged = git(&dir, &["show", ":foo"].map(OsStr::new), None)?;
+        assert_eq!(String::from_utf8_lossy(&staged), "a\nB\nc\n");
+        let unstaged = git(&dir, &["diff", "--name-only"].map(OsStr::new), None)?;
+        assert_eq!(String::from_utf8_lossy(&unstaged), "foo\n");
+
+        drop(changes);
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+use clap::Parser;
+use tug_diff_editor::git::{run_git_add, GitAddOpts};
+use tug_diff_editor::Result;
+
+pub fn main() -> Result<()> {
+    let opts = GitAddOpts::parse();
+    run_git_add(opts)?;
+    Ok(())
+}
@@ -9,34 +9,49 @@
 )]
 #![allow(clippy::too_many_arguments)]
 
+/// Without the `tracing` feature, warnings aren't logged anywhere.
+#[cfg(not(feature = "tracing"))]
+macro_rules! warn {
+    ($($arg:tt)*) => {};
+}
+
+pub mod git;
+pub mod patch;
 mod render;
+pub mod summary;
 pub mod testing;
 
-use std::borrow::Cow;
 use std::collections::BTreeSet;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf, StripPrefixError};
+use std::process::ExitStatus;
+use std::time::SystemTime;
 
+#[cfg(feature = "cli")]
 use clap::Parser;
 use sha1::Digest;
 use thiserror::Error;
 use walkdir::WalkDir;
 
-use tug_record::helpers::CrosstermInput;
+use tug_record::helpers::{decode_line_lossy, CrosstermInput};
 use tug_record::{
-    File, FileMode, RecordError, RecordState, Recorder, SelectedChanges, SelectedContents,
+    Commit, File, FileMode, KeyBinding, RecordError, RecordOptions, RecordState, Recorder, Section,
+    SectionChangedLine, SelectedChanges, SelectedContents, Theme,
 };
 
+use crate::summary::SelectionSummary;
+
 /// Render a partial commit selector for use as a difftool or mergetool.
 ///
 /// This can be used to interactively select changes to include as part of a
 /// commit, to resolve merge conflicts, or to simply display a diff in a
 /// readable way.
-#[derive(Debug, Parser)]
+#[derive(Debug)]
+#[cfg_attr(feature = "cli", derive(Parser))]
 pub struct Opts {
     /// Instead of comparing two files, compare two directories recursively.
-    #[clap(short = 'd', long = "dir-diff")]
+    #[cfg_attr(feature = "cli", clap(short = 'd', long = "dir-diff"))]
     pub dir_diff: bool,
 
     /// The left-hand file to compare (or directory if `--dir-diff` is passed).
@@ -47,28 +62,122 @@ pub struct Opts {
 
     /// Disable all editing controls and do not write the selected commit
     /// contents to disk.
-    #[clap(long = "read-only")]
+    #[cfg_attr(feature = "cli", clap(long = "read-only"))]
     pub read_only: bool,
 
     /// Show what would have been written to disk as part of the commit
     /// selection, but do not actually write it.
-    #[clap(short = 'N', long = "dry-run")]
+    #[cfg_attr(feature = "cli", clap(short = 'N', long = "dry-run"))]
     pub dry_run: bool,
 
     /// Render the interface as a mergetool instead of a difftool and use this
     /// file as the base of a three-way diff as part of resolving merge
     /// conflicts.
-    #[clap(
-        short = 'b',
-        long = "base",
-        requires("output"),
-        conflicts_with("dir_diff")
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            short = 'b',
+            long = "base",
+            requires("output"),
+            conflicts_with("dir_diff")
+        )
     )]
     pub base: Option<PathBuf>,
 
     /// Write the resolved merge conflicts to this file.
-    #[clap(short = 'o', long = "output", conflicts_with("dir_diff"))]
+    #[cfg_attr(
+        feature = "cli",
+        clap(short = 'o', long = "output", conflicts_with("dir_diff"))
+    )]
     pub output: Option<PathBuf>,
+
+    /// Instead of writing the selected changes back to the right-hand
+    /// directory, split the changes between these directories, with one commit
+    /// for each of them in the interface: each directory gets the left-hand
+    /// directory with the changes of its commit and of the commits before it
+    /// applied, so that the last one gets all of the changes. Can be passed
+    /// several times.
+    #[cfg_attr(
+        feature = "cli",
+        clap(long = "split-output", requires("dir_diff"), conflicts_with("output"))
+    )]
+    pub split_outputs: Vec<PathBuf>,
+
+    /// Show this commit message in the interface, so that it can be edited
+    /// along with the selection of changes.
+    #[cfg_attr(feature = "cli", clap(short = 'm', long = "message"))]
+    pub message: Option<String>,
+
+    /// Write the edited commit message to this file when the selection is
+    /// accepted. Unless `--message` is passed, the initial commit message is
+    /// read from this file, if it exists.
+    #[cfg_attr(feature = "cli", clap(long = "message-file"))]
+    pub message_file: Option<PathBuf>,
+
+    /// Write a JSON summary of the selection (the exit status, and the
+    /// selected hunks of each file) to this inherited file descriptor.
+    #[cfg_attr(feature = "cli", clap(long = "result-fd"))]
+    pub result_fd: Option<u32>,
+
+    /// Print the id and location of each hunk, one per line, instead of
+    /// opening the interface. The ids can be passed to `--preselect`.
+    #[cfg_attr(feature = "cli", clap(long = "list-hunks"))]
+    pub list_hunks: bool,
+
+    /// Select these hunks before opening the interface, given as a
+    /// comma-separated list of hunk ids and ranges of them, like `1,3,7-9`.
+    /// See `--list-hunks`.
+    #[cfg_attr(feature = "cli", clap(long = "preselect"))]
+    pub preselect: Option<String>,
+
+    /// Keep the modification time of each file which is written, so that it
+    /// can still be compared with the input files.
+    #[cfg_attr(feature = "cli", clap(long = "preserve-mtimes"))]
+    pub preserve_mtimes: bool,
+
+    /// Bind keys to an action, as `KEYS=ACTION`, such as `g g=focus-first`.
+    /// The keys are separated by spaces, and the action is the name of an
+    /// event in kebab case. Can be passed several times.
+    #[cfg_attr(feature = "cli", clap(long = "keymap"))]
+    pub keymap: Vec<KeyBinding>,
+
+    /// Show this many unchanged lines before and after each change.
+    #[cfg_attr(feature = "cli", clap(long = "context-lines"))]
+    pub context_lines: Option<usize>,
+
+    /// The colors to draw the interface with: `dark`, the default, for
+    /// terminals with a dark background, or `light`.
+    #[cfg_attr(feature = "cli", clap(long = "theme", value_enum))]
+    pub theme: Option<ThemeName>,
+
+    /// Instead of writing the selected changes back to the right-hand file
+    /// or directory, write them to this file as a patch, leaving the inputs
+    /// untouched.
+    #[cfg_attr(
+        feature = "cli",
+        clap(long = "emit-patch", conflicts_with_all(["output", "split_outputs"]))
+    )]
+    pub emit_patch: Option<PathBuf>,
+}
+
+/// The built-in themes, which [`Opts::theme`] can select.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ThemeName {
+    /// [`Theme::dark`].
+    Dark,
+    /// [`Theme::light`].
+    Light,
+}
+
+impl ThemeName {
+    /// The theme with this name.
+    pub fn theme(self) -> Theme {
+        match self {
+            ThemeName::Dark => Theme::dark(),
+            ThemeName::Light => Theme::light(),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -109,12 +218,43 @@ pub enum Error {
     #[error("writing file {path}: {source}")]
     WriteFile { path: PathBuf, source: io::Error },
 
+    #[error("creating symlink {path}: {source}")]
+    CreateSymlink { path: PathBuf, source: io::Error },
+
+    #[error("setting the mode of file {path}: {source}")]
+    SetFileMode { path: PathBuf, source: io::Error },
+
+    #[error("running git {args}: {source}")]
+    RunGit { args: String, source: io::Error },
+
+    #[error("git {args} exited with {status}: {stderr}")]
+    GitFailed {
+        args: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+
+    #[error("commit message file was not text: {path}")]
+    BinaryMessageFile { path: PathBuf },
+
+    #[error("writing the summary to file descriptor {fd}: {source}")]
+    WriteResultFd { fd: u32, source: io::Error },
+
+    #[error("invalid hunk ids, expected a list like 1,3,7-9: {hunk_ids}")]
+    InvalidHunkIds { hunk_ids: String },
+
+    #[error("there is no hunk {hunk_id}, as there are only {num_hunks} hunks")]
+    UnknownHunkId { hunk_id: usize, num_hunks: usize },
+
     #[error("file did not exist: {path}")]
     MissingMergeFile { path: PathBuf },
 
     #[error("file was not text: {path}")]
     BinaryMergeFile { path: PathBuf },
 
+    #[error("only some changes were selected in a file which was not valid UTF-8: {path}")]
+    PartiallySelectedLossyFile { path: PathBuf },
+
     #[error("recording changes: {source}")]
     Record { source: RecordError },
 }
@@ -149,6 +289,11 @@ pub enum FileContents {
 
         /// The size of `contents`, in bytes.
         num_bytes: u64,
+
+        /// Whether the file wasn't valid UTF-8, so that `contents` has
+        /// replacement characters in place of the invalid bytes. See
+        /// [`File::is_lossy`].
+        is_lossy: bool,
     },
 
     /// The file is a binary file (not able to be displayed directly in the UI).
@@ -169,12 +314,17 @@ pub trait Filesystem {
     /// Read the [`FileInfo`] for the provided `path`.
     fn read_file_info(&self, path: &Path) -> Result<FileInfo>;
 
-    /// Write new file contents to `path`.
+    /// Write new file contents to `path`. If `path` is a symlink, it's
+    /// replaced by a regular file instead of writing to its target.
     fn write_file(&mut self, path: &Path, contents: &str) -> Result<()>;
 
+    /// Create a symlink at `path` pointing to `target`, replacing any
+    /// existing file.
+    fn create_symlink(&mut self, path: &Path, target: &str) -> Result<()>;
+
     /// Copy the file at `old_path` to `new_path`. (This can be more efficient
     /// than reading and writing the entire contents, particularly for large
-    /// binary files.)
+    /// binary files.) A symlink is copied as a symlink.
     fn copy_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()>;
 
     /// Delete the file at `path`.
@@ -182,9 +332,48 @@ pub trait Filesystem {
 
     /// Create the directory `path` and any parent directories as necessary.
     fn create_dir_all(&mut self, path: &Path) -> Result<()>;
+
+    /// Set the mode of the existing file at `path`. Only the executable bit
+    /// of the mode is applied.
+    fn set_file_mode(&mut self, path: &Path, file_mode: FileMode) -> Result<()>;
+}
+
+struct RealFilesystem {
+    /// Whether written files keep the modification time of the file they
+    /// replace (or of the file they were copied from, if they're new).
+    preserve_mtimes: bool,
 }
 
-struct RealFilesystem;
+impl RealFilesystem {
+    /// Get the modification time which `path` should keep once it's
+    /// written, if modification times are preserved.
+    fn preserved_mtime(&self, path: &Path, source_path: Option<&Path>) -> Option<SystemTime> {
+        if !self.preserve_mtimes {
+            return None;
+        }
+        fs::metadata(path)
+            .or_else(|err| match source_path {
+                Some(source_path) => fs::metadata(source_path),
+                None => Err(err),
+            })
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    fn restore_mtime(path: &Path, mtime: Option<SystemTime>) -> Result<()> {
+        match mtime {
+            Some(mtime) => fs::File::options()
+                .write(true)
+                .open(path)
+                .and_then(|file| file.set_modified(mtime))
+                .map_err(|err| Error::WriteFile {
+                    path: path.to_owned(),
+                    source: err,
+                }),
+            None => Ok(()),
+        }
+    }
+}
 
 impl Filesystem for RealFilesystem {
     fn read_dir_diff_paths(&self, left: &Path, right: &Path) -> Result<BTreeSet<PathBuf>> {
@@ -218,11 +407,13 @@ impl Filesystem for RealFilesystem {
     }
 
     fn read_file_info(&self, path: &Path) -> Result<FileInfo> {
-        let file_mode = match fs::metadata(path) {
+        // Symlinks are read as themselves, rather than as the file they point
+        // to.
+        let file_mode = match fs::symlink_metadata(path) {
             Ok(metadata) => {
                 // TODO: no support for gitlinks (submodules).
                 if metadata.is_symlink() {
-                    FileMode::Unix(0o120000)
+                    FileMode::SYMLINK
                 } else {
                     let permissions = metadata.permissions();
                     #[cfg(unix)]
@@ -247,7 +438,14 @@ impl Filesystem for RealFilesystem {
                 })
             }
         };
-        let contents = match fs::read(path) {
+        let read_contents = || {
+            if file_mode == FileMode::SYMLINK {
+                fs::read_link(path).map(|target| target.into_os_string().into_encoded_bytes())
+            } else {
+                fs::read(path)
+            }
+        };
+        let contents = match read_contents() {
             Ok(contents) => {
                 let hash = {
                     let mut hasher = sha1::Sha1::new();
@@ -258,13 +456,12 @@ impl Filesystem for RealFilesystem {
                 if contents.contains(&0) {
                     FileContents::Binary { hash, num_bytes }
                 } else {
-                    match String::from_utf8(contents) {
-                        Ok(contents) => FileContents::Text {
-                            contents,
-                            hash,
-                            num_bytes,
-                        },
-                        Err(_) => FileContents::Binary { hash, num_bytes },
+                    let (contents, is_lossy) = decode_line_lossy(&contents);
+                    FileContents::Text {
+                        contents: contents.into_owned(),
+                        hash,
+                        num_bytes,
+                        is_lossy,
                     }
                 }
             }
@@ -283,19 +480,46 @@ impl Filesystem for RealFilesystem {
     }
 
     fn write_file(&mut self, path: &Path, contents: &str) -> Result<()> {
+        let mtime = self.preserved_mtime(path, None);
+        if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_symlink()) {
+            self.remove_file(path)?;
+        }
         fs::write(path, contents).map_err(|err| Error::WriteFile {
             path: path.to_owned(),
             source: err,
+        })?;
+        Self::restore_mtime(path, mtime)
+    }
+
+    fn create_symlink(&mut self, path: &Path, target: &str) -> Result<()> {
+        self.remove_file(path)?;
+        #[cfg(unix)]
+        let result = std::os::unix::fs::symlink(target, path);
+        // Without symlinks, the target is written as the file's contents,
+        // like git does.
+        #[cfg(not(unix))]
+        let result = fs::write(path, target);
+        result.map_err(|err| Error::CreateSymlink {
+            path: path.to_owned(),
+            source: err,
         })
     }
 
     fn copy_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()> {
+        if fs::symlink_metadata(old_path).is_ok_and(|metadata| metadata.is_symlink()) {
+            let target = fs::read_link(old_path).map_err(|err| Error::ReadFile {
+                path: old_path.to_owned(),
+                source: err,
+            })?;
+            return self.create_symlink(new_path, &target.to_string_lossy());
+        }
+        let mtime = self.preserved_mtime(new_path, Some(old_path));
         fs::copy(old_path, new_path).map_err(|err| Error::CopyFile {
             old_path: old_path.to_owned(),
             new_path: new_path.to_owned(),
             source: err,
         })?;
-        Ok(())
+        Self::restore_mtime(new_path, mtime)
     }
 
     fn remove_file(&mut self, path: &Path) -> Result<()> {
@@ -316,6 +540,37 @@ impl Filesystem for RealFilesystem {
         })?;
         Ok(())
     }
+
+    #[cfg(unix)]
+    fn set_file_mode(&mut self, path: &Path, file_mode: FileMode) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let executable = match file_mode {
+            // Symlinks have no permissions of their own.
+            FileMode::SYMLINK | FileMode::Absent => return Ok(()),
+            FileMode::Unix(mode) => mode & 0o111 != 0,
+        };
+        let set_permissions = || -> io::Result<()> {
+            let mut permissions = fs::metadata(path)?.permissions();
+            let mode = permissions.mode();
+            // Whoever can read the file can also execute it, like git does.
+            permissions.set_mode(if executable {
+                mode | ((mode & 0o444) >> 2)
+            } else {
+                mode & !0o111
+            });
+            fs::set_permissions(path, permissions)
+        };
+        set_permissions().map_err(|err| Error::SetFileMode {
+            path: path.to_owned(),
+            source: err,
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn set_file_mode(&mut self, _path: &Path, _file_mode: FileMode) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Information about the files to display/diff in the UI.
@@ -326,6 +581,11 @@ pub struct DiffContext {
     /// - When diffing a directory, this may have many entries (one for each pair of files).
     pub files: Vec<File<'static>>,
 
+    /// This path should be prepended to each `File`'s old path (or its path,
+    /// if it has none) to find the left-hand version of the file. It may be
+    /// empty.
+    pub left_root: PathBuf,
+
     /// When writing results to the filesystem, this path should be prepended to
     /// each `File`'s path. It may be empty (indicating to overwrite the file
     /// in-place).
@@ -343,6 +603,17 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             output: _,
             read_only: _,
             dry_run: _,
+            split_outputs: _,
+            message: _,
+            message_file: _,
+            result_fd: _,
+            list_hunks: _,
+            preselect: _,
+            preserve_mtimes: _,
+            keymap: _,
+            context_lines: _,
+            theme: _,
+            emit_patch: _,
         } => {
             let files = vec![render::create_file(
                 filesystem,
@@ -353,6 +624,7 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             )?];
             DiffContext {
                 files,
+                left_root: PathBuf::new(),
                 write_root: PathBuf::new(),
             }
         }
@@ -365,6 +637,17 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             output: _,
             read_only: _,
             dry_run: _,
+            split_outputs: _,
+            message: _,
+            message_file: _,
+            result_fd: _,
+            list_hunks: _,
+            preselect: _,
+            preserve_mtimes: _,
+            keymap: _,
+            context_lines: _,
+            theme: _,
+            emit_patch: _,
         } => {
             let display_paths = filesystem.read_dir_diff_paths(left, right)?;
             let mut files = Vec::new();
@@ -379,6 +662,7 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             }
             DiffContext {
                 files,
+                left_root: left.clone(),
                 write_root: right.clone(),
             }
         }
@@ -391,6 +675,17 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             output: Some(output),
             read_only: _,
             dry_run: _,
+            split_outputs: _,
+            message: _,
+            message_file: _,
+            result_fd: _,
+            list_hunks: _,
+            preselect: _,
+            preserve_mtimes: _,
+            keymap: _,
+            context_lines: _,
+            theme: _,
+            emit_patch: _,
         } => {
             let files = vec![render::create_merge_file(
                 filesystem,
@@ -401,6 +696,7 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             )?];
             DiffContext {
                 files,
+                left_root: PathBuf::new(),
                 write_root: PathBuf::new(),
             }
         }
@@ -413,6 +709,17 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             output: None,
             read_only: _,
             dry_run: _,
+            split_outputs: _,
+            message: _,
+            message_file: _,
+            result_fd: _,
+            list_hunks: _,
+            preselect: _,
+            preserve_mtimes: _,
+            keymap: _,
+            context_lines: _,
+            theme: _,
+            emit_patch: _,
         } => {
             unreachable!("--output is required when --base is provided");
         }
@@ -425,6 +732,17 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             output: _,
             read_only: _,
             dry_run: _,
+            split_outputs: _,
+            message: _,
+            message_file: _,
+            result_fd: _,
+            list_hunks: _,
+            preselect: _,
+            preserve_mtimes: _,
+            keymap: _,
+            context_lines: _,
+            theme: _,
+            emit_patch: _,
         } => {
             unimplemented!("--base cannot be used with --dir-diff");
         }
@@ -493,10 +811,80 @@ fn print_dry_run(write_root: &Path, state: RecordState) {
     }
 }
 
+/// Write the selected text `contents` of a file to `path`, as the target of a
+/// symlink if `file_mode` is [`FileMode::SYMLINK`].
+fn write_contents(
+    filesystem: &mut dyn Filesystem,
+    path: &Path,
+    contents: &str,
+    file_mode: FileMode,
+) -> Result<()> {
+    if file_mode == FileMode::SYMLINK {
+        filesystem.create_symlink(path, contents)
+    } else {
+        filesystem.write_file(path, contents)
+    }
+}
+
+/// A version of a file which is kept verbatim instead of writing its
+/// selected contents.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum KeptVersion {
+    /// The left-hand version, when none of the changed lines were selected.
+    Old,
+    /// The right-hand version, when all of the changed lines were selected.
+    New,
+}
+
+/// Which version of a file to keep instead of writing its selected text, if
+/// the file wasn't valid UTF-8. Its selected contents have replacement
+/// characters in place of the invalid bytes, so only selecting all or none of
+/// its changed lines can be written without losing them.
+pub(crate) fn kept_version(file: &File) -> Result<Option<KeptVersion>> {
+    kept_version_by(file, |line| line.is_checked)
+}
+
+/// Which version of a file to keep in the commit at `commit_idx` instead of
+/// writing its text. See [`kept_version`].
+fn kept_commit_version(file: &File, commit_idx: usize) -> Result<Option<KeptVersion>> {
+    kept_version_by(file, |line| line.assigned_commit_idx() <= commit_idx)
+}
+
+/// Which version of a file to keep instead of writing its text, given which of
+/// its changed lines are selected by `is_selected`.
+fn kept_version_by(
+    file: &File,
+    is_selected: impl Fn(&SectionChangedLine) -> bool,
+) -> Result<Option<KeptVersion>> {
+    if !file.is_lossy {
+        return Ok(None);
+    }
+    let mut is_checked = file
+        .sections
+        .iter()
+        .flat_map(|section| match section {
+            Section::Changed { lines, .. } => lines.as_slice(),
+            Section::Unchanged { .. } | Section::FileMode { .. } | Section::Binary { .. } => &[],
+        })
+        .map(is_selected);
+    let first = is_checked.next().unwrap_or_default();
+    if is_checked.any(|is_checked| is_checked != first) {
+        return Err(Error::PartiallySelectedLossyFile {
+            path: file.path.clone().into_owned(),
+        });
+    }
+    Ok(Some(if first {
+        KeptVersion::New
+    } else {
+        KeptVersion::Old
+    }))
+}
+
 /// After the user has selected changes in the provided [`RecordState`], write
 /// the results to the provided [`Filesystem`].
 pub fn apply_changes(
     filesystem: &mut dyn Filesystem,
+    left_root: &Path,
     write_root: &Path,
     state: RecordState,
 ) -> Result<()> {
@@ -523,26 +911,189 @@ pub fn apply_changes(
 
         match contents {
             SelectedContents::Unchanged => {
-                // Do nothing.
+                // The right-hand version of a binary file has to be replaced
+                // with the left-hand one if its change wasn't selected.
+                let is_binary = file.sections.iter().any(|section| match section {
+                    Section::Unchanged { .. }
+                    | Section::Changed { .. }
+                    | Section::FileMode { .. } => false,
+                    Section::Binary { .. } => true,
+                });
+                if is_binary && file_mode != FileMode::Absent {
+                    if let Some(parent_dir) = file_path.parent() {
+                        filesystem.create_dir_all(parent_dir)?;
+                    }
+                    if file.file_mode == FileMode::Absent {
+                        // Only the mode change of an added file was
+                        // selected, so there are no contents to keep.
+                        filesystem.write_file(&file_path, "")?;
+                    } else {
+                        let left_path =
+                            left_root.join(file.old_path.as_deref().unwrap_or(&file.path));
+                        filesystem.copy_file(&left_path, &file_path)?;
+                    }
+                }
             }
             SelectedContents::Binary {
                 old_description: _,
                 new_description: _,
             } => {
-                let new_path = file_path;
-                let old_path = match &file.old_path {
-                    Some(old_path) => old_path.clone(),
-                    None => Cow::Borrowed(new_path.as_path()),
-                };
-                filesystem.copy_file(&old_path, &new_path)?;
+                // The right-hand version is already in place, and is kept
+                // verbatim.
             }
             SelectedContents::Text { contents } => {
                 if let Some(parent_dir) = file_path.parent() {
                     filesystem.create_dir_all(parent_dir)?;
                 }
 
-                // TODO: Respect executable bit
-                filesystem.write_file(&file_path, &contents)?;
+                match kept_version(&file)? {
+                    // The right-hand version is already in place.
+                    Some(KeptVersion::New) => {}
+                    // Only the mode change of an added file was selected.
+                    Some(KeptVersion::Old) if file.file_mode == FileMode::Absent => {
+                        filesystem.write_file(&file_path, "")?;
+                    }
+                    Some(KeptVersion::Old) => {
+                        let left_path =
+                            left_root.join(file.old_path.as_deref().unwrap_or(&file.path));
+                        filesystem.copy_file(&left_path, &file_path)?;
+                    }
+                    None => write_contents(filesystem, &file_path, &contents, file_mode)?,
+                }
+            }
+        }
+
+        // The file on disk has the right-hand mode, or the default one if it
+        // was just written, so the selected mode is set whenever the modes
+        // differ.
+        let has_mode_change = file.sections.iter().any(|section| match section {
+            Section::Unchanged { .. } | Section::Changed { .. } | Section::Binary { .. } => false,
+            Section::FileMode { .. } => true,
+        });
+        if has_mode_change && file_mode != FileMode::Absent {
+            filesystem.set_file_mode(&file_path, file_mode)?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the initial commits to show in the recorder: one for each of the
+/// `split_outputs` in `opts`, if any, and with the commit message passed in
+/// `opts`, if any, as the message of the first one.
+pub fn initial_commits(filesystem: &dyn Filesystem, opts: &Opts) -> Result<Vec<Commit>> {
+    let mut commits = match initial_message(filesystem, opts)? {
+        Some(message) => vec![Commit {
+            message: Some(message),
+        }],
+        None => Vec::new(),
+    };
+    if commits.len() < opts.split_outputs.len() {
+        commits.resize_with(opts.split_outputs.len(), Commit::default);
+    }
+    Ok(commits)
+}
+
+/// Get the commit message passed in `opts`, if any.
+fn initial_message(filesystem: &dyn Filesystem, opts: &Opts) -> Result<Option<String>> {
+    let message = match (&opts.message, &opts.message_file) {
+        (Some(message), _) => message.clone(),
+        (None, Some(path)) => match filesystem.read_file_info(path)?.contents {
+            FileContents::Absent => String::new(),
+            FileContents::Text {
+                contents,
+                hash: _,
+                num_bytes: _,
+                is_lossy: false,
+            } => contents,
+            // The edited message is written back to the file, which would
+            // replace its invalid bytes.
+            FileContents::Text {
+                contents: _,
+                hash: _,
+                num_bytes: _,
+                is_lossy: true,
+            }
+            | FileContents::Binary {
+                hash: _,
+                num_bytes: _,
+            } => return Err(Error::BinaryMessageFile { path: path.clone() }),
+        },
+        (None, None) => return Ok(None),
+    };
+    Ok(Some(message))
+}
+
+/// Write the edited message of the first commit in the provided
+/// [`RecordState`] to `message_file`.
+pub fn write_commit_message(
+    filesystem: &mut dyn Filesystem,
+    message_file: &Path,
+    state: &RecordState,
+) -> Result<()> {
+    let message = state
+        .commits
+        .first()
+        .and_then(|commit| commit.message.as_deref())
+        .unwrap_or_default();
+    if let Some(parent_dir) = message_file.parent() {
+        filesystem.create_dir_all(parent_dir)?;
+    }
+    filesystem.write_file(message_file, message)
+}
+
+/// After the user has selected changes in the provided [`RecordState`], write
+/// each of the `split_outputs` directories: the one at index `k` gets the
+/// files of the `left` directory with the changes of the commits up to the one
+/// at index `k` applied, as given by [`File::get_commit_contents`]. There
+/// should be a commit for each directory, as returned by [`initial_commits`].
+pub fn write_split_outputs(
+    filesystem: &mut dyn Filesystem,
+    left: &Path,
+    right: &Path,
+    split_outputs: &[PathBuf],
+    state: RecordState,
+) -> Result<()> {
+    let RecordState {
+        is_read_only,
+        commits: _,
+        files,
+    } = state;
+    if is_read_only {
+        return Ok(());
+    }
+    for (output_idx, output) in split_outputs.iter().enumerate() {
+        for file in &files {
+            let file_path = output.join(&file.path);
+            let old_path = left.join(file.old_path.as_deref().unwrap_or(&file.path));
+            let new_path = right.join(&file.path);
+            let create_parent_dir = |filesystem: &mut dyn Filesystem| match file_path.parent() {
+                Some(parent_dir) => filesystem.create_dir_all(parent_dir),
+                None => Ok(()),
+            };
+            let SelectedChanges {
+                contents,
+                file_mode,
+            } = file.get_commit_contents(output_idx);
+            if file_mode == FileMode::Absent {
+                continue;
+            }
+            create_parent_dir(filesystem)?;
+            match contents {
+                SelectedContents::Unchanged => filesystem.copy_file(&old_path, &file_path)?,
+                // Binary files can only be taken as a whole.
+                SelectedContents::Binary {
+                    old_description: _,
+                    new_description: _,
+                } => filesystem.copy_file(&new_path, &file_path)?,
+                SelectedContents::Text { contents } => match kept_commit_version(file, output_idx)?
+                {
+                    Some(KeptVersion::Old) if file.file_mode == FileMode::Absent => {
+                        filesystem.write_file(&file_path, "")?
+                    }
+                    Some(KeptVersion::Old) => filesystem.copy_file(&old_path, &file_path)?,
+                    Some(KeptVersion::New) => filesystem.copy_file(&new_path, &file_path)?,
+                    None => write_contents(filesystem, &file_path, &contents, file_mode)?,
+                },
             }
         }
     }
@@ -551,23 +1102,95 @@ pub fn apply_changes(
 
 /// Select changes interactively and apply them to disk.
 pub fn run(opts: Opts) -> Result<()> {
-    let filesystem = RealFilesystem;
-    let DiffContext { files, write_root } = process_opts(&filesystem, &opts)?;
+    let result_fd = opts.result_fd;
+    let mut accepted_files = Vec::new();
+    let result = run_selection(opts, &mut accepted_files);
+    if let Some(fd) = result_fd {
+        summary::write_summary(fd, &SelectionSummary::new(&result, &accepted_files))?;
+    }
+    result
+}
+
+/// Run the editor, saving the files of the accepted selection, if any, to
+/// `accepted_files`.
+fn run_selection(opts: Opts, accepted_files: &mut Vec<File<'static>>) -> Result<()> {
+    let filesystem = RealFilesystem {
+        preserve_mtimes: opts.preserve_mtimes,
+    };
+    let DiffContext {
+        mut files,
+        left_root,
+        write_root,
+    } = process_opts(&filesystem, &opts)?;
+    if opts.list_hunks {
+        print!("{}", summary::describe_hunks(&files));
+        return Ok(());
+    }
+    if let Some(preselect) = &opts.preselect {
+        summary::preselect_hunks(&mut files, &summary::parse_hunk_ids(preselect)?)?;
+    }
     let state = RecordState {
         is_read_only: opts.read_only,
-        commits: Default::default(),
+        commits: initial_commits(&filesystem, &opts)?,
         files,
     };
+    let mut options = RecordOptions::default();
+    for binding in &opts.keymap {
+        let KeyBinding {
+            keys,
+            event,
+            description,
+        } = binding;
+        options
+            .keymap
+            .bind(keys.iter().copied(), event.clone(), description.clone());
+    }
+    if let Some(context_lines) = opts.context_lines {
+        options.num_context_lines = context_lines;
+    }
+    if let Some(theme) = opts.theme {
+        options.theme = theme.theme();
+    }
     let mut input = CrosstermInput;
-    let recorder = Recorder::new(state, &mut input);
+    let recorder = Recorder::new_with_options(state, &mut input, options);
     match recorder.run() {
         Ok(state) => {
+            if let (Some(message_file), false, false) =
+                (&opts.message_file, opts.dry_run, state.is_read_only)
+            {
+                write_commit_message(
+                    &mut RealFilesystem {
+                        preserve_mtimes: false,
+                    },
+                    message_file,
+                    &state,
+                )?;
+            }
+            if opts.result_fd.is_some() {
+                accepted_files.clone_from(&state.files);
+            }
             if opts.dry_run {
                 print_dry_run(&write_root, state);
                 Err(Error::DryRun)
+            } else if let Some(patch_path) = &opts.emit_patch {
+                if state.is_read_only {
+                    return Ok(());
+                }
+                let patch = patch::render_patch(&filesystem, &left_root, &state.files)?;
+                let mut filesystem = filesystem;
+                filesystem.write_file(patch_path, &patch)
+            } else if !opts.split_outputs.is_empty() {
+                let mut filesystem = filesystem;
+                write_split_outputs(
+                    &mut filesystem,
+                    &opts.left,
+                    &opts.right,
+                    &opts.split_outputs,
+                    state,
+                )
             } else {
                 let mut filesystem = filesystem;
-                apply_changes(&mut filesystem, &write_root, state)?;
+                apply_changes(&mut filesystem, &left_root, &write_root, state)?;
                 Ok(())
             }
         }
@@ -650,6 +1273,16 @@ mod tests {
             Ok(())
         }
 
+        fn create_symlink(&mut self, path: &Path, target: &str) -> Result<()> {
+            self.assert_parent_dir_exists(path);
+            let file_info = FileInfo {
+                file_mode: FileMode::SYMLINK,
+                ..file_info(target)
+            };
+            self.files.insert(path.to_owned(), file_info);
+            Ok(())
+        }
+
         fn copy_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()> {
             self.assert_parent_dir_exists(new_path);
             let file_info = self.read_file_info(old_path)?;
@@ -666,6 +1299,12 @@ mod tests {
             self.dirs.insert(path.to_owned());
             Ok(())
         }
+
+        fn set_file_mode(&mut self, path: &Path, file_mode: FileMode) -> Result<()> {
+            let file_info = self.files.get_mut(path).expect("file should exist");
+            file_info.file_mode = file_mode;
+            Ok(())
+        }
     }
 
     fn file_info(contents: impl Into<String>) -> FileInfo {
@@ -677,6 +1316,7 @@ mod tests {
                 contents,
                 hash: "abc123".to_string(),
                 num_bytes,
+                is_lossy: false,
             },
         }
     }
@@ -705,6 +1345,7 @@ qux2
         });
         let DiffContext {
             mut files,
+            left_root,
             write_root,
         } = process_opts(
             &filesystem,
@@ -716,6 +1357,17 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                split_outputs: Vec::new(),
+                message: None,
+                message_file: None,
+                result_fd: None,
+                list_hunks: false,
+                preselect: None,
+                preserve_mtimes: false,
+                keymap: Vec::new(),
+                context_lines: None,
+                theme: None,
+                emit_patch: None,
             },
         )?;
         assert_debug_snapshot!(files, @r###"
@@ -733,15 +1385,21 @@ qux2
                         lines: [
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Removed,
                                 line: "foo\n",
+                                annotation: None,
                             },
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Added,
                                 line: "qux1\n",
+                                annotation: None,
                             },
                         ],
+                        label: None,
+                        context: None,
                     },
                     Unchanged {
                         lines: [
@@ -753,17 +1411,27 @@ qux2
                         lines: [
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Removed,
                                 line: "bar\n",
+                                annotation: None,
                             },
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Added,
                                 line: "qux2\n",
+                                annotation: None,
                             },
                         ],
+                        label: None,
+                        context: Some(
+                            "common2",
+                        ),
                     },
                 ],
+                is_deferred: false,
+                is_lossy: false,
             },
         ]
         "###);
@@ -771,6 +1439,7 @@ qux2
         select_all(&mut files);
         apply_changes(
             &mut filesystem,
+            &left_root,
             &write_root,
             RecordState {
                 is_read_only: false,
@@ -789,6 +1458,7 @@ qux2
                         contents: "foo\ncommon1\ncommon2\nbar\n",
                         hash: "abc123",
                         num_bytes: 24,
+                        is_lossy: false,
                     },
                 },
                 "right": FileInfo {
@@ -799,6 +1469,7 @@ qux2
                         contents: "qux1\ncommon1\ncommon2\nqux2\n",
                         hash: "abc123",
                         num_bytes: 26,
+                        is_lossy: false,
                     },
                 },
             },
@@ -827,7 +1498,11 @@ common2
 qux2
 "),
         });
-        let DiffContext { files, write_root } = process_opts(
+        let DiffContext {
+            files,
+            left_root,
+            write_root,
+        } = process_opts(
             &filesystem,
             &Opts {
                 dir_diff: false,
@@ -837,11 +1512,23 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                split_outputs: Vec::new(),
+                message: None,
+                message_file: None,
+                result_fd: None,
+                list_hunks: false,
+                preselect: None,
+                preserve_mtimes: false,
+                keymap: Vec::new(),
+                context_lines: None,
+                theme: None,
+                emit_patch: None,
             },
         )?;
 
         apply_changes(
             &mut filesystem,
+            &left_root,
             &write_root,
             RecordState {
                 is_read_only: false,
@@ -860,6 +1547,7 @@ qux2
                         contents: "foo\ncommon1\ncommon2\nbar\n",
                         hash: "abc123",
                         num_bytes: 24,
+                        is_lossy: false,
                     },
                 },
                 "right": FileInfo {
@@ -870,6 +1558,7 @@ qux2
                         contents: "foo\ncommon1\ncommon2\nbar\n",
                         hash: "abc123",
                         num_bytes: 24,
+                        is_lossy: false,
                     },
                 },
             },
@@ -889,6 +1578,7 @@ qux2
         });
         let DiffContext {
             mut files,
+            left_root,
             write_root,
         } = process_opts(
             &filesystem,
@@ -900,6 +1590,17 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                split_outputs: Vec::new(),
+                message: None,
+                message_file: None,
+                result_fd: None,
+                list_hunks: false,
+                preselect: None,
+                preserve_mtimes: false,
+                keymap: Vec::new(),
+                context_lines: None,
+                theme: None,
+                emit_patch: None,
             },
         )?;
         assert_debug_snapshot!(files, @r###"
@@ -913,6 +1614,7 @@ qux2
                 sections: [
                     FileMode {
                         is_checked: false,
+                        commit_idx: 0,
                         mode: Unix(
                             33188,
                         ),
@@ -921,12 +1623,18 @@ qux2
                         lines: [
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Added,
                                 line: "right\n",
+                                annotation: None,
                             },
                         ],
+                        label: None,
+                        context: None,
                     },
                 ],
+                is_deferred: false,
+                is_lossy: false,
             },
         ]
         "###);
@@ -934,6 +1642,7 @@ qux2
         select_all(&mut files);
         apply_changes(
             &mut filesystem,
+            &left_root,
             &write_root,
             RecordState {
                 is_read_only: false,
@@ -952,6 +1661,7 @@ qux2
                         contents: "right\n",
                         hash: "abc123",
                         num_bytes: 6,
+                        is_lossy: false,
                     },
                 },
             },
@@ -971,6 +1681,7 @@ qux2
         });
         let DiffContext {
             mut files,
+            left_root,
             write_root,
         } = process_opts(
             &filesystem,
@@ -982,6 +1693,17 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                split_outputs: Vec::new(),
+                message: None,
+                message_file: None,
+                result_fd: None,
+                list_hunks: false,
+                preselect: None,
+                preserve_mtimes: false,
+                keymap: Vec::new(),
+                context_lines: None,
+                theme: None,
+                emit_patch: None,
             },
         )?;
         assert_debug_snapshot!(files, @r###"
@@ -997,18 +1719,25 @@ qux2
                 sections: [
                     FileMode {
                         is_checked: false,
+                        commit_idx: 0,
                         mode: Absent,
                     },
                     Changed {
                         lines: [
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Removed,
                                 line: "left\n",
+                                annotation: None,
                             },
                         ],
+                        label: None,
+                        context: None,
                     },
                 ],
+                is_deferred: false,
+                is_lossy: false,
             },
         ]
         "###);
@@ -1016,6 +1745,7 @@ qux2
         select_all(&mut files);
         apply_changes(
             &mut filesystem,
+            &left_root,
             &write_root,
             RecordState {
                 is_read_only: false,
@@ -1034,6 +1764,7 @@ qux2
                         contents: "left\n",
                         hash: "abc123",
                         num_bytes: 5,
+                        is_lossy: false,
                     },
                 },
             },
@@ -1062,6 +1793,17 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                split_outputs: Vec::new(),
+                message: None,
+                message_file: None,
+                result_fd: None,
+                list_hunks: false,
+                preselect: None,
+                preserve_mtimes: false,
+                keymap: Vec::new(),
+                context_lines: None,
+                theme: None,
+                emit_patch: None,
             },
         );
         insta::assert_debug_snapshot!(result, @r###"
@@ -1086,7 +1828,11 @@ qux2
             PathBuf::from("right/foo") => file_info("right contents\n"),
         });
 
-        let DiffContext { files, write_root } = process_opts(
+        let DiffContext {
+            files,
+            left_root,
+            write_root,
+        } = process_opts(
             &filesystem,
             &Opts {
                 dir_diff: false,
@@ -1096,11 +1842,23 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                split_outputs: Vec::new(),
+                message: None,
+                message_file: None,
+                result_fd: None,
+                list_hunks: false,
+                preselect: None,
+                preserve_mtimes: false,
+                keymap: Vec::new(),
+                context_lines: None,
+                theme: None,
+                emit_patch: None,
             },
         )?;
 
         apply_changes(
             &mut filesystem,
+            &left_root,
             &write_root,
             RecordState {
                 is_read_only: false,
@@ -1119,6 +1877,7 @@ qux2
                         contents: "left contents\n",
                         hash: "abc123",
                         num_bytes: 14,
+                        is_lossy: false,
                     },
                 },
                 "right/foo": FileInfo {
@@ -1129,6 +1888,7 @@ qux2
                         contents: "left contents\n",
                         hash: "abc123",
                         num_bytes: 14,
+                        is_lossy: false,
                     },
                 },
             },
@@ -1150,7 +1910,11 @@ qux2
             PathBuf::from("right/foo") => file_info("right contents\n"),
         });
 
-        let DiffContext { files, write_root } = process_opts(
+        let DiffContext {
+            files,
+            left_root,
+            write_root,
+        } = process_opts(
             &filesystem,
             &Opts {
                 dir_diff: false,
@@ -1160,11 +1924,23 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                split_outputs: Vec::new(),
+                message: None,
+                message_file: None,
+                result_fd: None,
+                list_hunks: false,
+                preselect: None,
+                preserve_mtimes: false,
+                keymap: Vec::new(),
+                context_lines: None,
+                theme: None,
+                emit_patch: None,
             },
         )?;
 
         apply_changes(
             &mut filesystem,
+            &left_root,
             &write_root,
             RecordState {
                 is_read_only: false,
@@ -1183,6 +1959,7 @@ qux2
                         contents: "left contents\n",
                         hash: "abc123",
                         num_bytes: 14,
+                        is_lossy: false,
                     },
                 },
                 "right/foo": FileInfo {
@@ -1193,6 +1970,7 @@ qux2
                         contents: "left contents\n",
                         hash: "abc123",
                         num_bytes: 14,
+                        is_lossy: false,
                     },
                 },
             },
@@ -1235,6 +2013,7 @@ Hello world 4
 
         let DiffContext {
             mut files,
+            left_root,
             write_root,
         } = process_opts(
             &filesystem,
@@ -1244,6 +2023,17 @@ Hello world 4
                 right: "right".into(),
                 read_only: false,
                 dry_run: false,
+                split_outputs: Vec::new(),
+                message: None,
+                message_file: None,
+                result_fd: None,
+                list_hunks: false,
+                preselect: None,
+                preserve_mtimes: false,
+                keymap: Vec::new(),
+                context_lines: None,
+                theme: None,
+                emit_patch: None,
                 base: Some("base".into()),
                 output: Some("output".into()),
             },
@@ -1269,20 +2059,28 @@ Hello world 4
                         lines: [
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Added,
                                 line: "Hello world L\n",
+                                annotation: None,
                             },
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Removed,
                                 line: "Hello world 3\n",
+                                annotation: None,
                             },
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Added,
                                 line: "Hello world R\n",
+                                annotation: None,
                             },
                         ],
+                        label: None,
+                        context: None,
                     },
                     Unchanged {
                         lines: [
@@ -1290,6 +2088,8 @@ Hello world 4
                         ],
                     },
                 ],
+                is_deferred: false,
+                is_lossy: false,
             },
         ]
         "###);
@@ -1297,6 +2097,7 @@ Hello world 4
         select_all(&mut files);
         apply_changes(
             &mut filesystem,
+            &left_root,
             &write_root,
             RecordState {
                 is_read_only: false,
@@ -1316,6 +2117,7 @@ Hello world 4
                         contents: "Hello world 1\nHello world 2\nHello world 3\nHello world 4\n",
                         hash: "abc123",
                         num_bytes: 56,
+                        is_lossy: false,
                     },
                 },
                 "left": FileInfo {
@@ -1326,6 +2128,7 @@ Hello world 4
                         contents: "Hello world 1\nHello world 2\nHello world L\nHello world 4\n",
                         hash: "abc123",
                         num_bytes: 56,
+                        is_lossy: false,
                     },
                 },
                 "output": FileInfo {
@@ -1336,6 +2139,7 @@ Hello world 4
                         contents: "Hello world 1\nHello world 2\nHello world L\nHello world R\nHello world 4\n",
                         hash: "abc123",
                         num_bytes: 70,
+                        is_lossy: false,
                     },
                 },
                 "right": FileInfo {
@@ -1346,6 +2150,7 @@ Hello world 4
                         contents: "Hello world 1\nHello world 2\nHello world R\nHello world 4\n",
                         hash: "abc123",
                         num_bytes: 56,
+                        is_lossy: false,
                     },
                 },
             },
@@ -1370,6 +2175,7 @@ Hello world 2
 
         let DiffContext {
             mut files,
+            left_root,
             write_root,
         } = process_opts(
             &filesystem,
@@ -1379,6 +2185,17 @@ Hello world 2
                 right: "right".into(),
                 read_only: false,
                 dry_run: false,
+                split_outputs: Vec::new(),
+                message: None,
+                message_file: None,
+                result_fd: None,
+                list_hunks: false,
+                preselect: None,
+                preserve_mtimes: false,
+                keymap: Vec::new(),
+                context_lines: None,
+                theme: None,
+                emit_patch: None,
                 base: None,
                 output: None,
             },
@@ -1394,6 +2211,7 @@ Hello world 2
                 sections: [
                     FileMode {
                         is_checked: false,
+                        commit_idx: 0,
                         mode: Unix(
                             33188,
                         ),
@@ -1402,17 +2220,25 @@ Hello world 2
                         lines: [
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Added,
                                 line: "Hello world 1\n",
+                                annotation: None,
                             },
                             SectionChangedLine {
                                 is_checked: false,
+                                commit_idx: 0,
                                 change_type: Added,
                                 line: "Hello world 2\n",
+                                annotation: None,
                             },
                         ],
+                        label: None,
+                        context: None,
                     },
                 ],
+                is_deferred: false,
+                is_lossy: false,
             },
         ]
         "###);
@@ -1420,6 +2246,7 @@ Hello world 2
         // Select no changes from new file.
         apply_changes(
             &mut filesystem,
+            &left_root,
             &write_root,
             RecordState {
                 is_read_only: false,
@@ -1440,6 +2267,7 @@ Hello world 2
         select_all(&mut files);
         apply_changes(
             &mut filesystem,
+            &left_root,
             &write_root,
             RecordState {
                 is_read_only: false,
@@ -1458,6 +2286,7 @@ Hello world 2
                         contents: "Hello world 1\nHello world 2\n",
                         hash: "abc123",
                         num_bytes: 28,
+                        is_lossy: false,
                     },
                 },
             },
@@ -1469,11 +2298,12 @@ Hello world 2
 
         // Select only some changes from new file.
         match files[0].sections.get_mut(1).unwrap() {
-            Section::Changed { ref mut lines } => lines[0].is_checked = false,
+            Section::Changed { ref mut lines, .. } => lines[0].is_checked = false,
             _ => panic!("Expected changed section"),
         }
         apply_changes(
             &mut filesystem,
+            &left_root,
             &write_root,
             RecordState {
                 is_read_only: false,
@@ -1492,6 +2322,7 @@ Hello world 2
                         contents: "Hello world 2\n",
                         hash: "abc123",
                         num_bytes: 14,
+                        is_lossy: false,
                     },
                 },
             },
@@ -1503,4 +2334,99 @@ Hello world 2
 
         Ok(())
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_real_filesystem_symlinks() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("tug-symlink-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("target"), "contents\n").unwrap();
+        std::os::unix::fs::symlink("target", dir.join("link")).unwrap();
+
+        let mut filesystem = RealFilesystem {
+            preserve_mtimes: false,
+        };
+        let FileInfo {
+            file_mode,
+            contents,
+        } = filesystem.read_file_info(&dir.join("link"))?;
+        assert_eq!(file_mode, FileMode::SYMLINK);
+        assert_matches_text(contents, "target");
+
+        // Copying a symlink copies the link, not the file it points to.
+        filesystem.copy_file(&dir.join("link"), &dir.join("copy"))?;
+        assert_eq!(
+            fs::read_link(dir.join("copy")).unwrap(),
+            Path::new("target")
+        );
+
+        // Writing to a symlink replaces it, rather than writing through it.
+        filesystem.write_file(&dir.join("link"), "replaced\n")?;
+        assert!(!fs::symlink_metadata(dir.join("link")).unwrap().is_symlink());
+        assert_eq!(
+            fs::read_to_string(dir.join("target")).unwrap(),
+            "contents\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_real_filesystem_invalid_utf8() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("tug-utf8-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("latin1"), b"caf\xe9\n").unwrap();
+        fs::write(dir.join("utf8"), "caf\u{e9}\n").unwrap();
+
+        let filesystem = RealFilesystem {
+            preserve_mtimes: false,
+        };
+        let contents = filesystem.read_file_info(&dir.join("latin1"))?.contents;
+        assert!(matches!(
+            contents,
+            FileContents::Text {
+                is_lossy: true,
+                num_bytes: 5,
+                ..
+            }
+        ));
+        assert_matches_text(contents, "caf\u{FFFD}\n");
+        let contents = filesystem.read_file_info(&dir.join("utf8"))?.contents;
+        assert!(matches!(
+            contents,
+            FileContents::Text {
+                is_lossy: false,
+                ..
+            }
+        ));
+
+        let file = render::create_file(
+            &filesystem,
+            dir.join("utf8"),
+            PathBuf::from("file"),
+            dir.join("latin1"),
+            PathBuf::from("file"),
+        )?;
+        assert!(file.is_lossy);
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    fn assert_matches_text(contents: FileContents, expected: &str) {
+        match contents {
+            FileContents::Text {
+                contents,
+                hash: _,
+                num_bytes: _,
+                is_lossy: _,
+            } => assert_eq!(contents, expected),
+            FileContents::Absent | FileContents::Binary { .. } => {
+                panic!("expected text contents, got {contents:?}")
+            }
+        }
+    }
 }
@@ -0,0 +1,110 @@
+//! Serialize the selected changes as a unified diff, for callers which prefer
+//! applying patches to copying trees.
+use std::path::Path;
+
+use tug_record::{File, FileMode, SelectedChanges, SelectedContents};
+
+use crate::{kept_version, FileContents, FileInfo, Filesystem, KeptVersion, Result};
+
+/// Render the selected changes of `files` as a patch in the format of
+/// `git diff`, which can be applied with `git apply` or `patch -p1`. The
+/// left-hand version of each file is read from `left_root`.
+pub fn render_patch(
+    filesystem: &dyn Filesystem,
+    left_root: &Path,
+    files: &[File],
+) -> Result<String> {
+    let mut patch = String::new();
+    for file in files {
+        let (selected_changes, _unselected_changes) = file.get_selected_contents();
+        let SelectedChanges {
+            contents,
+            file_mode,
+        } = selected_changes;
+        let old_mode = file.file_mode;
+
+        let old_path = file.old_path.as_deref().unwrap_or(&file.path);
+        let old_name = match old_mode {
+            FileMode::Absent => "/dev/null".to_owned(),
+            FileMode::Unix(_) => format!("a/{}", old_path.display()),
+        };
+        let new_name = match file_mode {
+            FileMode::Absent => "/dev/null".to_owned(),
+            FileMode::Unix(_) => format!("b/{}", file.path.display()),
+        };
+
+        let old_contents = match old_mode {
+            FileMode::Absent => String::new(),
+            FileMode::Unix(_) => {
+                let FileInfo {
+                    file_mode: _,
+                    contents,
+                } = filesystem.read_file_info(&left_root.join(old_path))?;
+                match contents {
+                    FileContents::Absent | FileContents::Binary { .. } => String::new(),
+                    FileContents::Text {
+                        contents,
+                        hash: _,
+                        num_bytes: _,
+                        is_lossy: _,
+                    } => contents,
+                }
+            }
+        };
+        // The contents of binary files can't be shown, only that they changed.
+        let (new_contents, is_binary) = match contents {
+            SelectedContents::Unchanged => match file_mode {
+                FileMode::Absent => (String::new(), false),
+                FileMode::Unix(_) => (old_contents.clone(), false),
+            },
+            SelectedContents::Binary {
+                old_description: _,
+                new_description: _,
+            } => (String::new(), true),
+            // Invalid UTF-8 can't be shown either, so a file which wasn't
+            // valid UTF-8 is treated like a binary file.
+            SelectedContents::Text { contents } => match kept_version(file)? {
+                Some(KeptVersion::Old) => (old_contents.clone(), false),
+                Some(KeptVersion::New) => (String::new(), true),
+                None => (contents, false),
+            },
+        };
+        if old_mode == file_mode && old_contents == new_contents && !is_binary {
+            continue;
+        }
+
+        patch.push_str(&format!(
+            "diff --git a/{} b/{}\n",
+            old_path.display(),
+            file.path.display()
+        ));
+        match (old_mode, file_mode) {
+            (FileMode::Absent, FileMode::Absent) => {}
+            (FileMode::Absent, FileMode::Unix(_)) => {
+                patch.push_str(&format!("new file mode {file_mode}\n"));
+            }
+            (FileMode::Unix(_), FileMode::Absent) => {
+                patch.push_str(&format!("deleted file mode {old_mode}\n"));
+            }
+            (FileMode::Unix(_), FileMode::Unix(_)) => {
+                if old_mode != file_mode {
+                    patch.push_str(&format!("old mode {old_mode}\nnew mode {file_mode}\n"));
+                }
+            }
+        }
+        if is_binary {
+            patch.push_str(&format!("Binary files {old_name} and {new_name} differ\n"));
+        } else if old_contents != new_contents {
+            let mut diff_options = diffy::DiffOptions::new();
+            diff_options
+                .set_original_filename(old_name)
+                .set_modified_filename(new_name);
+            patch.push_str(
+                &diff_options
+                    .create_patch(&old_contents, &new_contents)
+                    .to_string(),
+            );
+        }
+    }
+    Ok(patch)
+}
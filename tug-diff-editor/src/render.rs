@@ -1,9 +1,10 @@
 use std::borrow::Cow;
 use std::path::PathBuf;
 
-use tug_record::helpers::make_binary_description;
-use tug_record::{ChangeType, File, Section, SectionChangedLine};
+#[cfg(feature = "tracing")]
 use tracing::warn;
+use tug_record::helpers::{make_binary_description, make_binary_metadata};
+use tug_record::{ChangeType, File, Section, SectionChangedLine};
 
 use super::{Error, FileContents, FileInfo, Filesystem};
 
@@ -15,8 +16,10 @@ fn make_section_changed_lines(
         .split_inclusive('\n')
         .map(|line| SectionChangedLine {
             is_checked: false,
+            commit_idx: 0,
             change_type,
             line: Cow::Owned(line.to_owned()),
+            annotation: None,
         })
         .collect()
 }
@@ -36,11 +39,15 @@ pub fn create_file(
         file_mode: right_file_mode,
         contents: right_contents,
     } = filesystem.read_file_info(&right_path)?;
+    let is_lossy = [&left_contents, &right_contents]
+        .into_iter()
+        .any(|contents| matches!(contents, FileContents::Text { is_lossy: true, .. }));
     let mut sections = Vec::new();
 
     if left_file_mode != right_file_mode {
         sections.push(Section::FileMode {
             is_checked: false,
+            commit_idx: 0,
             mode: right_file_mode,
         });
     }
@@ -53,16 +60,22 @@ pub fn create_file(
                 contents,
                 hash: _,
                 num_bytes: _,
+                is_lossy: _,
             },
         ) => sections.push(Section::Changed {
             lines: make_section_changed_lines(&contents, ChangeType::Added),
+            label: None,
+            context: None,
         }),
 
         (FileContents::Absent, FileContents::Binary { hash, num_bytes }) => {
             sections.push(Section::Binary {
                 is_checked: false,
+                commit_idx: 0,
                 old_description: None,
                 new_description: Some(Cow::Owned(make_binary_description(&hash, num_bytes))),
+                old_metadata: None,
+                new_metadata: Some(make_binary_metadata(&hash, num_bytes)),
             })
         }
 
@@ -71,10 +84,13 @@ pub fn create_file(
                 contents,
                 hash: _,
                 num_bytes: _,
+                is_lossy: _,
             },
             FileContents::Absent,
         ) => sections.push(Section::Changed {
             lines: make_section_changed_lines(&contents, ChangeType::Removed),
+            label: None,
+            context: None,
         }),
 
         (
@@ -82,11 +98,13 @@ pub fn create_file(
                 contents: old_contents,
                 hash: _,
                 num_bytes: _,
+                is_lossy: _,
             },
             FileContents::Text {
                 contents: new_contents,
                 hash: _,
                 num_bytes: _,
+                is_lossy: _,
             },
         ) => {
             sections.extend(create_diff(&old_contents, &new_contents));
@@ -97,6 +115,7 @@ pub fn create_file(
                 contents: _,
                 hash: old_hash,
                 num_bytes: old_num_bytes,
+                is_lossy: _,
             }
             | FileContents::Binary {
                 hash: old_hash,
@@ -106,6 +125,7 @@ pub fn create_file(
                 contents: _,
                 hash: new_hash,
                 num_bytes: new_num_bytes,
+                is_lossy: _,
             }
             | FileContents::Binary {
                 hash: new_hash,
@@ -113,6 +133,7 @@ pub fn create_file(
             },
         ) => sections.push(Section::Binary {
             is_checked: false,
+            commit_idx: 0,
             old_description: Some(Cow::Owned(make_binary_description(
                 &old_hash,
                 old_num_bytes,
@@ -121,13 +142,18 @@ pub fn create_file(
                 &new_hash,
                 new_num_bytes,
             ))),
+            old_metadata: Some(make_binary_metadata(&old_hash, old_num_bytes)),
+            new_metadata: Some(make_binary_metadata(&new_hash, new_num_bytes)),
         }),
 
         (FileContents::Binary { hash, num_bytes }, FileContents::Absent) => {
             sections.push(Section::Binary {
                 is_checked: false,
+                commit_idx: 0,
                 old_description: Some(Cow::Owned(make_binary_description(&hash, num_bytes))),
                 new_description: None,
+                old_metadata: Some(make_binary_metadata(&hash, num_bytes)),
+                new_metadata: None,
             })
         }
     }
@@ -141,6 +167,8 @@ pub fn create_file(
         path: Cow::Owned(right_display_path),
         file_mode: left_file_mode,
         sections,
+        is_deferred: false,
+        is_lossy,
     })
 }
 
@@ -184,21 +212,35 @@ pub fn create_merge_file(
             (_, _, FileContents::Binary { .. }) => {
                 return Err(Error::BinaryMergeFile { path: right_path })
             }
+            // The merged contents are written to the output, so they can't
+            // have replacement characters in place of invalid UTF-8.
+            (FileContents::Text { is_lossy: true, .. }, _, _) => {
+                return Err(Error::BinaryMergeFile { path: base_path })
+            }
+            (_, FileContents::Text { is_lossy: true, .. }, _) => {
+                return Err(Error::BinaryMergeFile { path: left_path })
+            }
+            (_, _, FileContents::Text { is_lossy: true, .. }) => {
+                return Err(Error::BinaryMergeFile { path: right_path })
+            }
             (
                 FileContents::Text {
                     contents: base_contents,
                     hash: _,
                     num_bytes: _,
+                    is_lossy: _,
                 },
                 FileContents::Text {
                     contents: left_contents,
                     hash: _,
                     num_bytes: _,
+                    is_lossy: _,
                 },
                 FileContents::Text {
                     contents: right_contents,
                     hash: _,
                     num_bytes: _,
+                    is_lossy: _,
                 },
             ) => (base_contents, left_contents, right_contents),
         };
@@ -209,6 +251,8 @@ pub fn create_merge_file(
         path: Cow::Owned(output_path),
         file_mode: left_file_mode,
         sections,
+        is_deferred: false,
+        is_lossy: false,
     })
 }
 
@@ -242,30 +286,42 @@ fn create_diff(old_contents: &str, new_contents: &str) -> Vec<Section<'static>>
                 diffy::Line::Delete(line) => {
                     let line = SectionChangedLine {
                         is_checked: false,
+                        commit_idx: 0,
                         change_type: ChangeType::Removed,
                         line: Cow::Owned((*line).to_owned()),
+                        annotation: None,
                     };
                     match acc.last_mut() {
-                        Some(Section::Changed { lines }) => {
+                        Some(Section::Changed { lines, .. }) => {
                             lines.push(line);
                         }
                         _ => {
-                            acc.push(Section::Changed { lines: vec![line] });
+                            acc.push(Section::Changed {
+                                lines: vec![line],
+                                label: None,
+                                context: None,
+                            });
                         }
                     }
                 }
                 diffy::Line::Insert(line) => {
                     let line = SectionChangedLine {
                         is_checked: false,
+                        commit_idx: 0,
                         change_type: ChangeType::Added,
                         line: Cow::Owned((*line).to_owned()),
+                        annotation: None,
                     };
                     match acc.last_mut() {
-                        Some(Section::Changed { lines }) => {
+                        Some(Section::Changed { lines, .. }) => {
                             lines.push(line);
                         }
                         _ => {
-                            acc.push(Section::Changed { lines: vec![line] });
+                            acc.push(Section::Changed {
+                                lines: vec![line],
+                                label: None,
+                                context: None,
+                            });
                         }
                     }
                 }
@@ -273,9 +329,56 @@ fn create_diff(old_contents: &str, new_contents: &str) -> Vec<Section<'static>>
             acc
         }));
     }
+    add_function_context(&mut sections);
     sections
 }
 
+/// Whether the line looks like the start of a function or other declaration,
+/// using the same heuristic as `git diff`: it starts with a letter, `_`, or
+/// `$`.
+fn is_function_context_line(line: &str) -> bool {
+    line.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$')
+}
+
+/// Set the context of each changed section to the closest preceding line of
+/// the old file which looks like the start of a function.
+fn add_function_context(sections: &mut [Section<'static>]) {
+    /// The maximum length of the context, in characters.
+    const MAX_CONTEXT_LEN: usize = 80;
+
+    let mut function_context: Option<&str> = None;
+    let mut section_contexts = Vec::with_capacity(sections.len());
+    for section in sections.iter() {
+        section_contexts.push(function_context.map(|line| {
+            line.trim_end()
+                .chars()
+                .take(MAX_CONTEXT_LEN)
+                .collect::<String>()
+        }));
+        let old_lines: Vec<&str> = match section {
+            Section::Unchanged { lines } => lines.iter().map(|line| line.as_ref()).collect(),
+            Section::Changed { lines, .. } => lines
+                .iter()
+                .filter(|line| line.change_type == ChangeType::Removed)
+                .map(|line| line.line.as_ref())
+                .collect(),
+            Section::FileMode { .. } | Section::Binary { .. } => Vec::new(),
+        };
+        if let Some(line) = old_lines
+            .into_iter()
+            .rev()
+            .find(|line| is_function_context_line(line))
+        {
+            function_context = Some(line);
+        }
+    }
+    for (section, section_context) in sections.iter_mut().zip(section_contexts) {
+        if let Section::Changed { context, .. } = section {
+            *context = section_context.map(Cow::Owned);
+        }
+    }
+}
+
 fn make_conflict_markers(base: &str, left: &str, right: &str) -> (String, String, String, String) {
     let all = [base, left, right].concat();
     let left_char = "<";
@@ -455,10 +558,14 @@ fn create_merge(
                         )
                         .map(|(line, change_type)| SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type,
                             line,
+                            annotation: None,
                         })
                         .collect(),
+                    label: None,
+                    context: None,
                 };
                 (new_state, Some(new_section))
             }
@@ -491,7 +598,7 @@ fn create_merge(
         State::Unchanged { lines } => {
             sections.push(Section::Unchanged { lines });
         }
-        state @ (State::Left { .. } | State::Base { .. } | State::Right { .. }) => {
+        State::Left { .. } | State::Base { .. } | State::Right { .. } => {
             warn!(?state, "Diff section not terminated");
         }
     }
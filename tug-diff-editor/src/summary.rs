@@ -0,0 +1,271 @@
+//! Stable hunk ids and a machine-readable summary of the selection, for
+//! scripts and parent processes which need structured results without
+//! parsing the output tree.
+use std::fs::OpenOptions;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tug_record::{ChangeType, File, FileMode, Section, SectionChangedLine, Tristate};
+
+use crate::{Error, Result};
+
+/// How the editor exited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryStatus {
+    /// The selection was accepted and written out.
+    Accepted,
+
+    /// The user quit without accepting the selection.
+    Cancelled,
+
+    /// The selection was accepted, but nothing was written because of
+    /// `--dry-run`.
+    DryRun,
+
+    /// An error occurred. See [`SelectionSummary::error`].
+    Failed,
+}
+
+/// The selected changes of one file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FileSummary {
+    /// The path of the file, relative to the compared directories.
+    pub path: PathBuf,
+
+    /// The ids of the hunks which were completely selected.
+    pub selected_hunks: Vec<usize>,
+
+    /// The ids of the hunks of which only some lines were selected.
+    pub partially_selected_hunks: Vec<usize>,
+}
+
+/// The summary written to `--result-fd`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SelectionSummary {
+    /// How the editor exited.
+    pub status: SummaryStatus,
+
+    /// The error message, if an error occurred.
+    pub error: Option<String>,
+
+    /// The files with at least one selected change. Empty unless the
+    /// selection was accepted.
+    pub files: Vec<FileSummary>,
+}
+
+impl SelectionSummary {
+    /// Summarize the outcome of the editor, given the files as they were
+    /// when the selection was accepted, if it was.
+    pub fn new(result: &Result<()>, files: &[File]) -> Self {
+        let (status, error) = match result {
+            Ok(()) => (SummaryStatus::Accepted, None),
+            Err(Error::Cancelled) => (SummaryStatus::Cancelled, None),
+            Err(Error::DryRun) => (SummaryStatus::DryRun, None),
+            Err(err) => (SummaryStatus::Failed, Some(err.to_string())),
+        };
+
+        let mut summaries: Vec<FileSummary> = Vec::new();
+        for (hunk_id, file, section) in iter_hunks(files) {
+            let summary = match summaries.last_mut() {
+                Some(summary) if summary.path == file.path.as_ref() => summary,
+                _ => {
+                    summaries.push(FileSummary {
+                        path: file.path.clone().into_owned(),
+                        selected_hunks: Vec::new(),
+                        partially_selected_hunks: Vec::new(),
+                    });
+                    summaries.last_mut().unwrap()
+                }
+            };
+            match section.tristate() {
+                Tristate::False => {}
+                Tristate::Partial => summary.partially_selected_hunks.push(hunk_id),
+                Tristate::True => summary.selected_hunks.push(hunk_id),
+            }
+        }
+        summaries.retain(|summary| {
+            let FileSummary {
+                path: _,
+                selected_hunks,
+                partially_selected_hunks,
+            } = summary;
+            !selected_hunks.is_empty() || !partially_selected_hunks.is_empty()
+        });
+
+        Self {
+            status,
+            error,
+            files: summaries,
+        }
+    }
+}
+
+/// Iterate over the hunks of `files`, which are their editable sections
+/// (changed lines, file mode changes and binary contents), along with the
+/// file containing them. Hunks are numbered from 1, in order, across all of
+/// the files, so the ids are stable for the same inputs.
+pub fn iter_hunks<'a, 'b>(
+    files: &'a [File<'b>],
+) -> impl Iterator<Item = (usize, &'a File<'b>, &'a Section<'b>)> {
+    files
+        .iter()
+        .flat_map(|file| {
+            file.sections
+                .iter()
+                .filter(|section| section.is_editable())
+                .map(move |section| (file, section))
+        })
+        .enumerate()
+        .map(|(hunk_idx, (file, section))| (hunk_idx + 1, file, section))
+}
+
+/// Write `summary` as a line of JSON to the inherited file descriptor `fd`.
+pub fn write_summary(fd: u32, summary: &SelectionSummary) -> Result<()> {
+    let write = || -> io::Result<()> {
+        // Opening `/dev/fd/N` duplicates the descriptor, without needing
+        // `unsafe` to take ownership of it.
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(format!("/dev/fd/{fd}"))?;
+        serde_json::to_writer(&mut file, summary)?;
+        io::Write::write_all(&mut file, b"\n")
+    };
+    write().map_err(|source| Error::WriteResultFd { fd, source })
+}
+
+/// Describe each hunk of `files` on its own line, starting with its id, for
+/// `--list-hunks`. Changed lines are described by their line ranges, in the
+/// style of a unified diff hunk header.
+pub fn describe_hunks(files: &[File]) -> String {
+    let mut output = String::new();
+    let mut hunk_id = 0;
+    for file in files {
+        let mut old_line_num = 1;
+        let mut new_line_num = 1;
+        for section in &file.sections {
+            let description = match section {
+                Section::Unchanged { lines } => {
+                    old_line_num += lines.len();
+                    new_line_num += lines.len();
+                    continue;
+                }
+                Section::Changed {
+                    lines,
+                    label: _,
+                    context: _,
+                } => {
+                    let num_lines_with_type = |change_type: ChangeType| {
+                        lines
+                            .iter()
+                            .filter(|line| {
+                                let SectionChangedLine {
+                                    is_checked: _,
+                                    commit_idx: _,
+                                    change_type: line_change_type,
+                                    line: _,
+                                    annotation: _,
+                                } = line;
+                                *line_change_type == change_type
+                            })
+                            .count()
+                    };
+                    let num_removed = num_lines_with_type(ChangeType::Removed);
+                    let num_added = num_lines_with_type(ChangeType::Added);
+                    // Like in a unified diff, an empty range starts at the
+                    // line before it.
+                    let range_start = |line_num: usize, num_lines: usize| {
+                        if num_lines == 0 {
+                            line_num - 1
+                        } else {
+                            line_num
+                        }
+                    };
+                    let description = format!(
+                        "@@ -{},{num_removed} +{},{num_added} @@",
+                        range_start(old_line_num, num_removed),
+                        range_start(new_line_num, num_added),
+                    );
+                    old_line_num += num_removed;
+                    new_line_num += num_added;
+                    description
+                }
+                Section::FileMode {
+                    is_checked: _,
+                    commit_idx: _,
+                    mode,
+                } => match (file.file_mode, mode) {
+                    (FileMode::Absent, _) => format!("new file mode {mode}"),
+                    (_, FileMode::Absent) => "deleted file".to_owned(),
+                    (old_mode, _) => format!("mode change {old_mode} => {mode}"),
+                },
+                Section::Binary {
+                    is_checked: _,
+                    commit_idx: _,
+                    old_description: _,
+                    new_description: _,
+                    old_metadata: _,
+                    new_metadata: _,
+                } => "binary contents".to_owned(),
+            };
+            hunk_id += 1;
+            output.push_str(&format!(
+                "{hunk_id}\t{}\t{description}\n",
+                file.path.display()
+            ));
+        }
+    }
+    output
+}
+
+/// Parse a list of hunk ids and ranges of hunk ids, like `1,3,7-9`, as passed
+/// to `--preselect`.
+pub fn parse_hunk_ids(hunk_ids: &str) -> Result<Vec<RangeInclusive<usize>>> {
+    hunk_ids
+        .split(',')
+        .map(|range| {
+            let parse_id = |id: &str| match id.trim().parse() {
+                Ok(id) if id > 0 => Ok(id),
+                _ => Err(Error::InvalidHunkIds {
+                    hunk_ids: hunk_ids.to_owned(),
+                }),
+            };
+            let range = match range.split_once('-') {
+                Some((start, end)) => parse_id(start)?..=parse_id(end)?,
+                None => {
+                    let id = parse_id(range)?;
+                    id..=id
+                }
+            };
+            if range.is_empty() {
+                return Err(Error::InvalidHunkIds {
+                    hunk_ids: hunk_ids.to_owned(),
+                });
+            }
+            Ok(range)
+        })
+        .collect()
+}
+
+/// Select the hunks of `files` whose ids are in `ranges`, and unselect the
+/// others.
+pub fn preselect_hunks(files: &mut [File], ranges: &[RangeInclusive<usize>]) -> Result<()> {
+    let mut hunk_id = 0;
+    for file in files.iter_mut() {
+        for section in file.sections.iter_mut() {
+            if section.is_editable() {
+                hunk_id += 1;
+                section.set_checked(ranges.iter().any(|range| range.contains(&hunk_id)));
+            }
+        }
+    }
+    match ranges.iter().map(|range| *range.end()).max() {
+        Some(max_id) if max_id > hunk_id => Err(Error::UnknownHunkId {
+            hunk_id: max_id,
+            num_hunks: hunk_id,
+        }),
+        _ => Ok(()),
+    }
+}
@@ -73,6 +73,16 @@ impl Filesystem for TestFilesystem {
         Ok(())
     }
 
+    fn create_symlink(&mut self, path: &Path, target: &str) -> Result<()> {
+        self.assert_parent_dir_exists(path);
+        let file_info = FileInfo {
+            file_mode: FileMode::SYMLINK,
+            ..file_info(target)
+        };
+        self.files.insert(path.to_owned(), file_info);
+        Ok(())
+    }
+
     fn copy_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()> {
         self.assert_parent_dir_exists(new_path);
         let file_info = self.read_file_info(old_path)?;
@@ -89,6 +99,12 @@ impl Filesystem for TestFilesystem {
         self.dirs.insert(path.to_owned());
         Ok(())
     }
+
+    fn set_file_mode(&mut self, path: &Path, file_mode: FileMode) -> Result<()> {
+        let file_info = self.files.get_mut(path).expect("file should exist");
+        file_info.file_mode = file_mode;
+        Ok(())
+    }
 }
 
 /// Helper function to create a `FileInfo` object containing the provided file
@@ -102,6 +118,7 @@ pub fn file_info(contents: impl Into<String>) -> FileInfo {
             contents,
             hash: "abc123".to_string(),
             num_bytes,
+            is_lossy: false,
         },
     }
 }
@@ -1,11 +1,16 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use insta::assert_debug_snapshot;
 use maplit::btreemap;
 
+use tug_diff_editor::patch::render_patch;
+use tug_diff_editor::summary::{describe_hunks, parse_hunk_ids, preselect_hunks, SelectionSummary};
 use tug_diff_editor::testing::{file_info, select_all, TestFilesystem};
-use tug_diff_editor::{apply_changes, process_opts, DiffContext, Opts, Result};
-use tug_record::{RecordState, Section};
+use tug_diff_editor::{
+    apply_changes, initial_commits, process_opts, write_commit_message, write_split_outputs,
+    DiffContext, Error, FileContents, FileInfo, Filesystem, Opts, Result,
+};
+use tug_record::{FileMode, RecordState, Section};
 
 #[test]
 fn test_diff() -> Result<()> {
@@ -25,6 +30,7 @@ qux2
     });
     let DiffContext {
         mut files,
+        left_root,
         write_root,
     } = process_opts(
         &filesystem,
@@ -36,6 +42,17 @@ qux2
             output: None,
             read_only: false,
             dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
         },
     )?;
     assert_debug_snapshot!(files, @r###"
@@ -53,15 +70,21 @@ qux2
                     lines: [
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Removed,
                             line: "foo\n",
+                            annotation: None,
                         },
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Added,
                             line: "qux1\n",
+                            annotation: None,
                         },
                     ],
+                    label: None,
+                    context: None,
                 },
                 Unchanged {
                     lines: [
@@ -73,17 +96,27 @@ qux2
                     lines: [
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Removed,
                             line: "bar\n",
+                            annotation: None,
                         },
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Added,
                             line: "qux2\n",
+                            annotation: None,
                         },
                     ],
+                    label: None,
+                    context: Some(
+                        "common2",
+                    ),
                 },
             ],
+            is_deferred: false,
+            is_lossy: false,
         },
     ]
     "###);
@@ -91,6 +124,7 @@ qux2
     select_all(&mut files);
     apply_changes(
         &mut filesystem,
+        &left_root,
         &write_root,
         RecordState {
             is_read_only: false,
@@ -109,6 +143,7 @@ qux2
                     contents: "foo\ncommon1\ncommon2\nbar\n",
                     hash: "abc123",
                     num_bytes: 24,
+                    is_lossy: false,
                 },
             },
             "right": FileInfo {
@@ -119,6 +154,7 @@ qux2
                     contents: "qux1\ncommon1\ncommon2\nqux2\n",
                     hash: "abc123",
                     num_bytes: 26,
+                    is_lossy: false,
                 },
             },
         },
@@ -147,7 +183,11 @@ common2
 qux2
 "),
     });
-    let DiffContext { files, write_root } = process_opts(
+    let DiffContext {
+        files,
+        left_root,
+        write_root,
+    } = process_opts(
         &filesystem,
         &Opts {
             dir_diff: false,
@@ -157,11 +197,23 @@ qux2
             output: None,
             read_only: false,
             dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
         },
     )?;
 
     apply_changes(
         &mut filesystem,
+        &left_root,
         &write_root,
         RecordState {
             is_read_only: false,
@@ -180,6 +232,7 @@ qux2
                     contents: "foo\ncommon1\ncommon2\nbar\n",
                     hash: "abc123",
                     num_bytes: 24,
+                    is_lossy: false,
                 },
             },
             "right": FileInfo {
@@ -190,6 +243,7 @@ qux2
                     contents: "foo\ncommon1\ncommon2\nbar\n",
                     hash: "abc123",
                     num_bytes: 24,
+                    is_lossy: false,
                 },
             },
         },
@@ -209,6 +263,7 @@ fn test_diff_absent_left() -> Result<()> {
     });
     let DiffContext {
         mut files,
+        left_root,
         write_root,
     } = process_opts(
         &filesystem,
@@ -220,6 +275,17 @@ fn test_diff_absent_left() -> Result<()> {
             output: None,
             read_only: false,
             dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
         },
     )?;
     assert_debug_snapshot!(files, @r###"
@@ -233,6 +299,7 @@ fn test_diff_absent_left() -> Result<()> {
             sections: [
                 FileMode {
                     is_checked: false,
+                    commit_idx: 0,
                     mode: Unix(
                         33188,
                     ),
@@ -241,12 +308,18 @@ fn test_diff_absent_left() -> Result<()> {
                     lines: [
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Added,
                             line: "right\n",
+                            annotation: None,
                         },
                     ],
+                    label: None,
+                    context: None,
                 },
             ],
+            is_deferred: false,
+            is_lossy: false,
         },
     ]
     "###);
@@ -254,6 +327,7 @@ fn test_diff_absent_left() -> Result<()> {
     select_all(&mut files);
     apply_changes(
         &mut filesystem,
+        &left_root,
         &write_root,
         RecordState {
             is_read_only: false,
@@ -272,6 +346,7 @@ fn test_diff_absent_left() -> Result<()> {
                     contents: "right\n",
                     hash: "abc123",
                     num_bytes: 6,
+                    is_lossy: false,
                 },
             },
         },
@@ -291,6 +366,7 @@ fn test_diff_absent_right() -> Result<()> {
     });
     let DiffContext {
         mut files,
+        left_root,
         write_root,
     } = process_opts(
         &filesystem,
@@ -302,6 +378,17 @@ fn test_diff_absent_right() -> Result<()> {
             output: None,
             read_only: false,
             dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
         },
     )?;
     assert_debug_snapshot!(files, @r###"
@@ -317,18 +404,25 @@ fn test_diff_absent_right() -> Result<()> {
             sections: [
                 FileMode {
                     is_checked: false,
+                    commit_idx: 0,
                     mode: Absent,
                 },
                 Changed {
                     lines: [
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Removed,
                             line: "left\n",
+                            annotation: None,
                         },
                     ],
+                    label: None,
+                    context: None,
                 },
             ],
+            is_deferred: false,
+            is_lossy: false,
         },
     ]
     "###);
@@ -336,6 +430,7 @@ fn test_diff_absent_right() -> Result<()> {
     select_all(&mut files);
     apply_changes(
         &mut filesystem,
+        &left_root,
         &write_root,
         RecordState {
             is_read_only: false,
@@ -354,6 +449,7 @@ fn test_diff_absent_right() -> Result<()> {
                     contents: "left\n",
                     hash: "abc123",
                     num_bytes: 5,
+                    is_lossy: false,
                 },
             },
         },
@@ -382,6 +478,17 @@ fn test_reject_diff_non_files() -> Result<()> {
             output: None,
             read_only: false,
             dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
         },
     );
     insta::assert_debug_snapshot!(result, @r###"
@@ -406,7 +513,11 @@ fn test_diff_files_in_subdirectories() -> Result<()> {
         PathBuf::from("right/foo") => file_info("right contents\n"),
     });
 
-    let DiffContext { files, write_root } = process_opts(
+    let DiffContext {
+        files,
+        left_root,
+        write_root,
+    } = process_opts(
         &filesystem,
         &Opts {
             dir_diff: false,
@@ -416,11 +527,23 @@ fn test_diff_files_in_subdirectories() -> Result<()> {
             output: None,
             read_only: false,
             dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
         },
     )?;
 
     apply_changes(
         &mut filesystem,
+        &left_root,
         &write_root,
         RecordState {
             is_read_only: false,
@@ -439,6 +562,7 @@ fn test_diff_files_in_subdirectories() -> Result<()> {
                     contents: "left contents\n",
                     hash: "abc123",
                     num_bytes: 14,
+                    is_lossy: false,
                 },
             },
             "right/foo": FileInfo {
@@ -449,6 +573,7 @@ fn test_diff_files_in_subdirectories() -> Result<()> {
                     contents: "left contents\n",
                     hash: "abc123",
                     num_bytes: 14,
+                    is_lossy: false,
                 },
             },
         },
@@ -470,7 +595,11 @@ fn test_dir_diff_no_changes() -> Result<()> {
         PathBuf::from("right/foo") => file_info("right contents\n"),
     });
 
-    let DiffContext { files, write_root } = process_opts(
+    let DiffContext {
+        files,
+        left_root,
+        write_root,
+    } = process_opts(
         &filesystem,
         &Opts {
             dir_diff: false,
@@ -480,11 +609,23 @@ fn test_dir_diff_no_changes() -> Result<()> {
             output: None,
             read_only: false,
             dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
         },
     )?;
 
     apply_changes(
         &mut filesystem,
+        &left_root,
         &write_root,
         RecordState {
             is_read_only: false,
@@ -503,6 +644,7 @@ fn test_dir_diff_no_changes() -> Result<()> {
                     contents: "left contents\n",
                     hash: "abc123",
                     num_bytes: 14,
+                    is_lossy: false,
                 },
             },
             "right/foo": FileInfo {
@@ -513,6 +655,7 @@ fn test_dir_diff_no_changes() -> Result<()> {
                     contents: "left contents\n",
                     hash: "abc123",
                     num_bytes: 14,
+                    is_lossy: false,
                 },
             },
         },
@@ -555,6 +698,7 @@ Hello world 4
 
     let DiffContext {
         mut files,
+        left_root,
         write_root,
     } = process_opts(
         &filesystem,
@@ -564,6 +708,17 @@ Hello world 4
             right: "right".into(),
             read_only: false,
             dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
             base: Some("base".into()),
             output: Some("output".into()),
         },
@@ -589,20 +744,28 @@ Hello world 4
                     lines: [
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Added,
                             line: "Hello world L\n",
+                            annotation: None,
                         },
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Removed,
                             line: "Hello world 3\n",
+                            annotation: None,
                         },
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Added,
                             line: "Hello world R\n",
+                            annotation: None,
                         },
                     ],
+                    label: None,
+                    context: None,
                 },
                 Unchanged {
                     lines: [
@@ -610,6 +773,8 @@ Hello world 4
                     ],
                 },
             ],
+            is_deferred: false,
+            is_lossy: false,
         },
     ]
     "###);
@@ -617,6 +782,7 @@ Hello world 4
     select_all(&mut files);
     apply_changes(
         &mut filesystem,
+        &left_root,
         &write_root,
         RecordState {
             is_read_only: false,
@@ -636,6 +802,7 @@ Hello world 4
                     contents: "Hello world 1\nHello world 2\nHello world 3\nHello world 4\n",
                     hash: "abc123",
                     num_bytes: 56,
+                    is_lossy: false,
                 },
             },
             "left": FileInfo {
@@ -646,6 +813,7 @@ Hello world 4
                     contents: "Hello world 1\nHello world 2\nHello world L\nHello world 4\n",
                     hash: "abc123",
                     num_bytes: 56,
+                    is_lossy: false,
                 },
             },
             "output": FileInfo {
@@ -656,6 +824,7 @@ Hello world 4
                     contents: "Hello world 1\nHello world 2\nHello world L\nHello world R\nHello world 4\n",
                     hash: "abc123",
                     num_bytes: 70,
+                    is_lossy: false,
                 },
             },
             "right": FileInfo {
@@ -666,6 +835,7 @@ Hello world 4
                     contents: "Hello world 1\nHello world 2\nHello world R\nHello world 4\n",
                     hash: "abc123",
                     num_bytes: 56,
+                    is_lossy: false,
                 },
             },
         },
@@ -690,6 +860,7 @@ Hello world 2
 
     let DiffContext {
         mut files,
+        left_root,
         write_root,
     } = process_opts(
         &filesystem,
@@ -699,6 +870,17 @@ Hello world 2
             right: "right".into(),
             read_only: false,
             dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
             base: None,
             output: None,
         },
@@ -714,6 +896,7 @@ Hello world 2
             sections: [
                 FileMode {
                     is_checked: false,
+                    commit_idx: 0,
                     mode: Unix(
                         33188,
                     ),
@@ -722,17 +905,25 @@ Hello world 2
                     lines: [
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Added,
                             line: "Hello world 1\n",
+                            annotation: None,
                         },
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: Added,
                             line: "Hello world 2\n",
+                            annotation: None,
                         },
                     ],
+                    label: None,
+                    context: None,
                 },
             ],
+            is_deferred: false,
+            is_lossy: false,
         },
     ]
     "###);
@@ -740,6 +931,7 @@ Hello world 2
     // Select no changes from new file.
     apply_changes(
         &mut filesystem,
+        &left_root,
         &write_root,
         RecordState {
             is_read_only: false,
@@ -760,6 +952,7 @@ Hello world 2
     select_all(&mut files);
     apply_changes(
         &mut filesystem,
+        &left_root,
         &write_root,
         RecordState {
             is_read_only: false,
@@ -778,6 +971,7 @@ Hello world 2
                     contents: "Hello world 1\nHello world 2\n",
                     hash: "abc123",
                     num_bytes: 28,
+                    is_lossy: false,
                 },
             },
         },
@@ -789,11 +983,12 @@ Hello world 2
 
     // Select only some changes from new file.
     match files[0].sections.get_mut(1).unwrap() {
-        Section::Changed { ref mut lines } => lines[0].is_checked = false,
+        Section::Changed { ref mut lines, .. } => lines[0].is_checked = false,
         _ => panic!("Expected changed section"),
     }
     apply_changes(
         &mut filesystem,
+        &left_root,
         &write_root,
         RecordState {
             is_read_only: false,
@@ -812,14 +1007,1031 @@ Hello world 2
                     contents: "Hello world 2\n",
                     hash: "abc123",
                     num_bytes: 14,
+                    is_lossy: false,
+                },
+            },
+        },
+        dirs: {
+            "",
+        },
+    }
+    "###);
+
+    Ok(())
+}
+
+#[test]
+fn test_split_outputs() -> Result<()> {
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/foo") => file_info("foo 1\nfoo 2\n"),
+        PathBuf::from("left/deleted") => file_info("deleted\n"),
+        PathBuf::from("right/foo") => file_info("foo 1\nFOO 2\n"),
+        PathBuf::from("right/dir/added") => file_info("added\n"),
+    });
+
+    let DiffContext {
+        mut files,
+        left_root: _,
+        write_root: _,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            split_outputs: vec![PathBuf::from("first"), PathBuf::from("second")],
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
+        },
+    )?;
+    // Only select the change to `foo`.
+    for file in &mut files {
+        file.set_checked(file.path.as_ref() == Path::new("foo"));
+    }
+
+    write_split_outputs(
+        &mut filesystem,
+        Path::new("left"),
+        Path::new("right"),
+        &[PathBuf::from("first"), PathBuf::from("second")],
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        },
+    )?;
+    assert_debug_snapshot!(filesystem, @r###"
+    TestFilesystem {
+        files: {
+            "first/deleted": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "deleted\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    is_lossy: false,
+                },
+            },
+            "first/foo": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "foo 1\nFOO 2\n",
+                    hash: "abc123",
+                    num_bytes: 12,
+                    is_lossy: false,
+                },
+            },
+            "left/deleted": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "deleted\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    is_lossy: false,
+                },
+            },
+            "left/foo": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "foo 1\nfoo 2\n",
+                    hash: "abc123",
+                    num_bytes: 12,
+                    is_lossy: false,
+                },
+            },
+            "right/dir/added": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "added\n",
+                    hash: "abc123",
+                    num_bytes: 6,
+                    is_lossy: false,
+                },
+            },
+            "right/foo": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "foo 1\nFOO 2\n",
+                    hash: "abc123",
+                    num_bytes: 12,
+                    is_lossy: false,
+                },
+            },
+            "second/dir/added": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "added\n",
+                    hash: "abc123",
+                    num_bytes: 6,
+                    is_lossy: false,
+                },
+            },
+            "second/foo": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "foo 1\nFOO 2\n",
+                    hash: "abc123",
+                    num_bytes: 12,
+                    is_lossy: false,
                 },
             },
         },
         dirs: {
             "",
+            "first",
+            "left",
+            "right",
+            "right/dir",
+            "second",
+            "second/dir",
         },
     }
     "###);
+    Ok(())
+}
+
+#[test]
+fn test_split_outputs_into_three_directories() -> Result<()> {
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/first") => file_info("first\n"),
+        PathBuf::from("left/second") => file_info("second\n"),
+        PathBuf::from("right/first") => file_info("FIRST\n"),
+        PathBuf::from("right/second") => file_info("SECOND\n"),
+        PathBuf::from("right/third") => file_info("third\n"),
+    });
+    let split_outputs = vec![
+        PathBuf::from("out1"),
+        PathBuf::from("out2"),
+        PathBuf::from("out3"),
+    ];
+    let opts = Opts {
+        dir_diff: true,
+        left: PathBuf::from("left"),
+        right: PathBuf::from("right"),
+        base: None,
+        output: None,
+        read_only: false,
+        dry_run: false,
+        split_outputs: split_outputs.clone(),
+        message: None,
+        message_file: None,
+        result_fd: None,
+        list_hunks: false,
+        preselect: None,
+        preserve_mtimes: false,
+        keymap: Vec::new(),
+        context_lines: None,
+        theme: None,
+        emit_patch: None,
+    };
+    let commits = initial_commits(&filesystem, &opts)?;
+    assert_eq!(commits.len(), 3);
 
+    let DiffContext {
+        mut files,
+        left_root: _,
+        write_root: _,
+    } = process_opts(&filesystem, &opts)?;
+    // Each file is changed in the commit with the same name.
+    for file in &mut files {
+        let commit_idx = ["first", "second", "third"]
+            .iter()
+            .position(|name| file.path.as_ref() == Path::new(name))
+            .unwrap();
+        file.assign_to_commit(commit_idx, commits.len());
+    }
+
+    write_split_outputs(
+        &mut filesystem,
+        Path::new("left"),
+        Path::new("right"),
+        &split_outputs,
+        RecordState {
+            is_read_only: false,
+            commits,
+            files,
+        },
+    )?;
+    let contents = |output: &PathBuf| -> Result<Vec<Option<String>>> {
+        ["first", "second", "third"]
+            .iter()
+            .map(
+                |name| match filesystem.read_file_info(&output.join(name))?.contents {
+                    FileContents::Absent => Ok(None),
+                    FileContents::Text { contents, .. } => Ok(Some(contents)),
+                    FileContents::Binary { .. } => panic!("unexpected binary file {name}"),
+                },
+            )
+            .collect()
+    };
+    let some = |contents: &str| Some(contents.to_owned());
+    assert_eq!(
+        contents(&split_outputs[0])?,
+        [some("FIRST\n"), some("second\n"), None]
+    );
+    assert_eq!(
+        contents(&split_outputs[1])?,
+        [some("FIRST\n"), some("SECOND\n"), None]
+    );
+    assert_eq!(
+        contents(&split_outputs[2])?,
+        [some("FIRST\n"), some("SECOND\n"), some("third\n")]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_commit_message_file() -> Result<()> {
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left") => file_info("foo\n"),
+        PathBuf::from("right") => file_info("bar\n"),
+        PathBuf::from("message") => file_info("Initial subject\n\nInitial body\n"),
+    });
+    let mut opts = Opts {
+        dir_diff: false,
+        left: PathBuf::from("left"),
+        right: PathBuf::from("right"),
+        base: None,
+        output: None,
+        read_only: false,
+        dry_run: false,
+        split_outputs: Vec::new(),
+        message: None,
+        message_file: Some(PathBuf::from("message")),
+        result_fd: None,
+        list_hunks: false,
+        preselect: None,
+        preserve_mtimes: false,
+        keymap: Vec::new(),
+        context_lines: None,
+        theme: None,
+        emit_patch: None,
+    };
+    let commits = initial_commits(&filesystem, &opts)?;
+    assert_debug_snapshot!(commits, @r###"
+    [
+        Commit {
+            message: Some(
+                "Initial subject\n\nInitial body\n",
+            ),
+        },
+    ]
+    "###);
+
+    // `--message` takes precedence over the contents of the message file.
+    opts.message = Some("Passed subject".to_owned());
+    let mut commits = initial_commits(&filesystem, &opts)?;
+    assert_debug_snapshot!(commits, @r###"
+    [
+        Commit {
+            message: Some(
+                "Passed subject",
+            ),
+        },
+    ]
+    "###);
+
+    commits[0].set_subject_and_body("Edited subject", "");
+    let state = RecordState {
+        is_read_only: false,
+        commits,
+        files: Vec::new(),
+    };
+    write_commit_message(&mut filesystem, Path::new("message"), &state)?;
+    assert_debug_snapshot!(filesystem.read_file_info(Path::new("message"))?.contents, @r###"
+    Text {
+        contents: "Edited subject",
+        hash: "abc123",
+        num_bytes: 14,
+        is_lossy: false,
+    }
+    "###);
+    Ok(())
+}
+
+#[test]
+fn test_selection_summary() -> Result<()> {
+    let filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/foo") => file_info("foo 1\nfoo 2\nfoo 3\nfoo 4\nfoo 5\nfoo 6\nfoo 7\nfoo 8\n"),
+        PathBuf::from("right/foo") => file_info("FOO 1\nfoo 2\nfoo 3\nfoo 4\nfoo 5\nfoo 6\nfoo 7\nFOO 8\n"),
+        PathBuf::from("left/bar") => file_info("bar\n"),
+        PathBuf::from("right/bar") => file_info("BAR\n"),
+        PathBuf::from("right/baz") => file_info("baz\n"),
+    });
+    let DiffContext {
+        mut files,
+        left_root: _,
+        write_root: _,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: Some(3),
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
+        },
+    )?;
+    select_all(&mut files);
+    // Leave `baz` unselected, and only select part of the last hunk of `foo`.
+    for file in &mut files {
+        if file.path.as_ref() == Path::new("baz") {
+            file.set_checked(false);
+        } else if file.path.as_ref() == Path::new("foo") {
+            if let Some(Section::Changed { lines, .. }) = file.sections.last_mut() {
+                lines[0].is_checked = false;
+            }
+        }
+    }
+
+    let summary = SelectionSummary::new(&Ok(()), &files);
+    insta::assert_snapshot!(serde_json::to_string(&summary).unwrap(), @r###"{"status":"accepted","error":null,"files":[{"path":"bar","selected_hunks":[1],"partially_selected_hunks":[]},{"path":"foo","selected_hunks":[4],"partially_selected_hunks":[5]}]}"###);
+
+    let summary = SelectionSummary::new(&Err(Error::Cancelled), &[]);
+    insta::assert_snapshot!(serde_json::to_string(&summary).unwrap(), @r###"{"status":"cancelled","error":null,"files":[]}"###);
+    Ok(())
+}
+
+#[test]
+fn test_list_and_preselect_hunks() -> Result<()> {
+    let filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/foo") => file_info("foo 1\nfoo 2\nfoo 3\nfoo 4\nfoo 5\nfoo 6\nfoo 7\nfoo 8\n"),
+        PathBuf::from("right/foo") => file_info("FOO 1\nfoo 2\nfoo 3\nfoo 4\nfoo 5\nfoo 6\nfoo 7\nFOO 8\nfoo 9\n"),
+        PathBuf::from("left/bar") => file_info("bar\n"),
+        PathBuf::from("right/baz") => file_info("baz\n"),
+    });
+    let DiffContext {
+        mut files,
+        left_root: _,
+        write_root: _,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: true,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
+        },
+    )?;
+    insta::assert_snapshot!(describe_hunks(&files), @r###"
+    1	bar	deleted file
+    2	bar	@@ -1,1 +0,0 @@
+    3	baz	new file mode 100644
+    4	baz	@@ -0,0 +1,1 @@
+    5	foo	@@ -1,1 +1,1 @@
+    6	foo	@@ -8,1 +8,2 @@
+    "###);
+
+    preselect_hunks(&mut files, &parse_hunk_ids("1,4-5")?)?;
+    let summary = SelectionSummary::new(&Ok(()), &files);
+    insta::assert_snapshot!(serde_json::to_string(&summary).unwrap(), @r###"{"status":"accepted","error":null,"files":[{"path":"bar","selected_hunks":[1],"partially_selected_hunks":[]},{"path":"baz","selected_hunks":[4],"partially_selected_hunks":[]},{"path":"foo","selected_hunks":[5],"partially_selected_hunks":[]}]}"###);
+
+    insta::assert_debug_snapshot!(parse_hunk_ids("2-1"), @r###"
+    Err(
+        InvalidHunkIds {
+            hunk_ids: "2-1",
+        },
+    )
+    "###);
+    insta::assert_debug_snapshot!(preselect_hunks(&mut files, &parse_hunk_ids("3,7")?), @r###"
+    Err(
+        UnknownHunkId {
+            hunk_id: 7,
+            num_hunks: 6,
+        },
+    )
+    "###);
+    Ok(())
+}
+
+#[test]
+fn test_binary_passthrough() -> Result<()> {
+    let binary_file_info = |hash: &str| FileInfo {
+        file_mode: FileMode::Unix(0o100644),
+        contents: FileContents::Binary {
+            hash: hash.to_owned(),
+            num_bytes: 4,
+        },
+    };
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/selected") => binary_file_info("old-selected"),
+        PathBuf::from("right/selected") => binary_file_info("new-selected"),
+        PathBuf::from("left/unselected") => binary_file_info("old-unselected"),
+        PathBuf::from("right/unselected") => binary_file_info("new-unselected"),
+        PathBuf::from("left/dir/deleted") => binary_file_info("deleted"),
+        PathBuf::from("right/added") => binary_file_info("added"),
+    });
+    let DiffContext {
+        mut files,
+        left_root,
+        write_root,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
+        },
+    )?;
+    for file in &mut files {
+        file.set_checked(file.path.as_ref() == Path::new("selected"));
+    }
+    apply_changes(
+        &mut filesystem,
+        &left_root,
+        &write_root,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        },
+    )?;
+    let right_files: Vec<_> = ["selected", "unselected", "dir/deleted", "added"]
+        .into_iter()
+        .map(|path| {
+            let FileInfo {
+                file_mode: _,
+                contents,
+            } = filesystem
+                .read_file_info(&Path::new("right").join(path))
+                .unwrap();
+            (path, contents)
+        })
+        .collect();
+    assert_debug_snapshot!(right_files, @r###"
+    [
+        (
+            "selected",
+            Binary {
+                hash: "new-selected",
+                num_bytes: 4,
+            },
+        ),
+        (
+            "unselected",
+            Binary {
+                hash: "old-unselected",
+                num_bytes: 4,
+            },
+        ),
+        (
+            "dir/deleted",
+            Binary {
+                hash: "deleted",
+                num_bytes: 4,
+            },
+        ),
+        (
+            "added",
+            Absent,
+        ),
+    ]
+    "###);
+    Ok(())
+}
+
+#[test]
+fn test_lossy_passthrough() -> Result<()> {
+    // The contents of files which weren't valid UTF-8, as decoded for display.
+    let lossy_file_info = |contents: &str, hash: &str| FileInfo {
+        file_mode: FileMode::Unix(0o100644),
+        contents: FileContents::Text {
+            contents: contents.to_owned(),
+            hash: hash.to_owned(),
+            num_bytes: contents.len().try_into().unwrap(),
+            is_lossy: true,
+        },
+    };
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/selected") => lossy_file_info("caf\u{FFFD}\nold\n", "old-selected"),
+        PathBuf::from("right/selected") => lossy_file_info("caf\u{FFFD}\nnew\n", "new-selected"),
+        PathBuf::from("left/unselected") => lossy_file_info("caf\u{FFFD}\nold\n", "old-unselected"),
+        PathBuf::from("right/unselected") => lossy_file_info("caf\u{FFFD}\nnew\n", "new-unselected"),
+    });
+    let opts = Opts {
+        dir_diff: true,
+        left: PathBuf::from("left"),
+        right: PathBuf::from("right"),
+        base: None,
+        output: None,
+        read_only: false,
+        dry_run: false,
+        split_outputs: Vec::new(),
+        message: None,
+        message_file: None,
+        result_fd: None,
+        list_hunks: false,
+        preselect: None,
+        preserve_mtimes: false,
+        keymap: Vec::new(),
+        context_lines: None,
+        theme: None,
+        emit_patch: None,
+    };
+    let DiffContext {
+        mut files,
+        left_root,
+        write_root,
+    } = process_opts(&filesystem, &opts)?;
+    assert!(files.iter().all(|file| file.is_lossy));
+
+    // Only selecting a part of the changes would replace the invalid bytes.
+    let mut partial_files = files.clone();
+    let Section::Changed { lines, .. } = &mut partial_files[0].sections[1] else {
+        panic!("expected changed lines: {:?}", partial_files[0].sections);
+    };
+    lines[0].is_checked = true;
+    let result = apply_changes(
+        &mut filesystem,
+        &left_root,
+        &write_root,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files: partial_files,
+        },
+    );
+    assert!(matches!(
+        result,
+        Err(Error::PartiallySelectedLossyFile { path }) if path == Path::new("selected")
+    ));
+
+    for file in &mut files {
+        file.set_checked(file.path.as_ref() == Path::new("selected"));
+    }
+    apply_changes(
+        &mut filesystem,
+        &left_root,
+        &write_root,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        },
+    )?;
+    // The original versions of the files are kept, rather than written from
+    // their decoded contents.
+    let right_hashes: Vec<_> = ["selected", "unselected"]
+        .into_iter()
+        .map(|path| {
+            let FileInfo {
+                file_mode: _,
+                contents,
+            } = filesystem
+                .read_file_info(&Path::new("right").join(path))
+                .unwrap();
+            match contents {
+                FileContents::Text {
+                    contents: _,
+                    hash,
+                    num_bytes: _,
+                    is_lossy,
+                } => (path, hash, is_lossy),
+                FileContents::Absent | FileContents::Binary { .. } => {
+                    panic!("expected text contents, got {contents:?}")
+                }
+            }
+        })
+        .collect();
+    assert_eq!(
+        right_hashes,
+        [
+            ("selected", "new-selected".to_owned(), true),
+            ("unselected", "old-unselected".to_owned(), true),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_apply_file_mode_changes() -> Result<()> {
+    let executable_file_info = |contents: &str| FileInfo {
+        file_mode: FileMode::Unix(0o100755),
+        ..file_info(contents)
+    };
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/selected") => file_info("foo\n"),
+        PathBuf::from("right/selected") => executable_file_info("foo\n"),
+        PathBuf::from("left/unselected") => file_info("bar\n"),
+        PathBuf::from("right/unselected") => executable_file_info("BAR\n"),
+        PathBuf::from("right/added") => executable_file_info("baz\n"),
+    });
+    let DiffContext {
+        mut files,
+        left_root,
+        write_root,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
+        },
+    )?;
+    select_all(&mut files);
+    // Select the contents of `unselected`, but not its mode change.
+    for file in &mut files {
+        if file.path.as_ref() == Path::new("unselected") {
+            for section in &mut file.sections {
+                if let Section::FileMode { is_checked, .. } = section {
+                    *is_checked = false;
+                }
+            }
+        }
+    }
+    apply_changes(
+        &mut filesystem,
+        &left_root,
+        &write_root,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        },
+    )?;
+    let right_files: Vec<_> = ["selected", "unselected", "added"]
+        .into_iter()
+        .map(|path| {
+            let FileInfo {
+                file_mode,
+                contents: _,
+            } = filesystem
+                .read_file_info(&Path::new("right").join(path))
+                .unwrap();
+            (path, file_mode.to_string())
+        })
+        .collect();
+    assert_debug_snapshot!(right_files, @r###"
+    [
+        (
+            "selected",
+            "100755",
+        ),
+        (
+            "unselected",
+            "100644",
+        ),
+        (
+            "added",
+            "100755",
+        ),
+    ]
+    "###);
+    Ok(())
+}
+
+#[test]
+fn test_apply_symlink_changes() -> Result<()> {
+    let symlink_info = |target: &str| FileInfo {
+        file_mode: FileMode::SYMLINK,
+        ..file_info(target)
+    };
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/selected") => symlink_info("old-target"),
+        PathBuf::from("right/selected") => symlink_info("new-target"),
+        PathBuf::from("left/unselected") => symlink_info("old-target"),
+        PathBuf::from("right/unselected") => symlink_info("new-target"),
+        PathBuf::from("left/replaced") => file_info("contents\n"),
+        PathBuf::from("right/replaced") => symlink_info("target"),
+    });
+    let DiffContext {
+        mut files,
+        left_root,
+        write_root,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: None,
+        },
+    )?;
+    for file in &mut files {
+        file.set_checked(file.path.as_ref() == Path::new("selected"));
+    }
+    apply_changes(
+        &mut filesystem,
+        &left_root,
+        &write_root,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        },
+    )?;
+    let right_files: Vec<_> = ["selected", "unselected", "replaced"]
+        .into_iter()
+        .map(|path| {
+            let FileInfo {
+                file_mode,
+                contents,
+            } = filesystem
+                .read_file_info(&Path::new("right").join(path))
+                .unwrap();
+            (path, file_mode.to_string(), contents)
+        })
+        .collect();
+    assert_debug_snapshot!(right_files, @r###"
+    [
+        (
+            "selected",
+            "120000",
+            Text {
+                contents: "new-target",
+                hash: "abc123",
+                num_bytes: 10,
+                is_lossy: false,
+            },
+        ),
+        (
+            "unselected",
+            "120000",
+            Text {
+                contents: "old-target",
+                hash: "abc123",
+                num_bytes: 10,
+                is_lossy: false,
+            },
+        ),
+        (
+            "replaced",
+            "100644",
+            Text {
+                contents: "contents\n",
+                hash: "abc123",
+                num_bytes: 9,
+                is_lossy: false,
+            },
+        ),
+    ]
+    "###);
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_parse_ui_options() {
+    use clap::Parser;
+    use tug_diff_editor::ThemeName;
+
+    let opts = Opts::try_parse_from([
+        "tug-diff-editor",
+        "--keymap",
+        "g g=focus-first",
+        "--keymap",
+        "^n=focus-next-file",
+        "--context-lines",
+        "2",
+        "left",
+        "right",
+    ])
+    .unwrap();
+    assert_debug_snapshot!((opts.keymap, opts.context_lines), @r###"
+    (
+        [
+            KeyBinding {
+                keys: [
+                    Key {
+                        code: Char(
+                            'g',
+                        ),
+                        modifiers: KeyModifiers(
+                            0x0,
+                        ),
+                    },
+                    Key {
+                        code: Char(
+                            'g',
+                        ),
+                        modifiers: KeyModifiers(
+                            0x0,
+                        ),
+                    },
+                ],
+                event: FocusFirst,
+                description: "focus-first",
+            },
+            KeyBinding {
+                keys: [
+                    Key {
+                        code: Char(
+                            'n',
+                        ),
+                        modifiers: KeyModifiers(
+                            CONTROL,
+                        ),
+                    },
+                ],
+                event: FocusNextFile,
+                description: "focus-next-file",
+            },
+        ],
+        Some(
+            2,
+        ),
+    )
+    "###);
+
+    let err = Opts::try_parse_from(["tug-diff-editor", "--keymap", "x=explode", "left", "right"])
+        .unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+    let err = "x=explode".parse::<tug_record::KeyBinding>().unwrap_err();
+    insta::assert_snapshot!(err, @"unknown action: explode");
+
+    let opts =
+        Opts::try_parse_from(["tug-diff-editor", "--theme", "light", "left", "right"]).unwrap();
+    assert_eq!(opts.theme, Some(ThemeName::Light));
+    assert_eq!(
+        opts.theme.map(ThemeName::theme),
+        Some(tug_record::Theme::light())
+    );
+    let opts = Opts::try_parse_from(["tug-diff-editor", "left", "right"]).unwrap();
+    assert_eq!(opts.theme, None);
+    let err =
+        Opts::try_parse_from(["tug-diff-editor", "--theme", "sepia", "left", "right"]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::InvalidValue);
+}
+
+#[test]
+fn test_render_patch() -> Result<()> {
+    let filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/changed") => file_info("foo 1\nfoo 2\nfoo 3\n"),
+        PathBuf::from("right/changed") => file_info("FOO 1\nfoo 2\nFOO 3\n"),
+        PathBuf::from("left/deleted") => file_info("deleted\n"),
+        PathBuf::from("right/added") => file_info("added\n"),
+        PathBuf::from("left/executable") => file_info("script\n"),
+        PathBuf::from("right/executable") => FileInfo {
+            file_mode: FileMode::Unix(0o100755),
+            ..file_info("script\n")
+        },
+        PathBuf::from("left/unselected") => file_info("old\n"),
+        PathBuf::from("right/unselected") => file_info("new\n"),
+    });
+    let DiffContext {
+        mut files,
+        left_root,
+        write_root: _,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            split_outputs: Vec::new(),
+            message: None,
+            message_file: None,
+            result_fd: None,
+            list_hunks: false,
+            preselect: None,
+            preserve_mtimes: false,
+            keymap: Vec::new(),
+            context_lines: None,
+            theme: None,
+            emit_patch: Some(PathBuf::from("patch")),
+        },
+    )?;
+    select_all(&mut files);
+    for file in &mut files {
+        if file.path.as_ref() == Path::new("unselected") {
+            file.set_checked(false);
+        } else if file.path.as_ref() == Path::new("changed") {
+            // Only select the first change.
+            if let Some(Section::Changed { lines, .. }) = file.sections.last_mut() {
+                for line in lines {
+                    line.is_checked = false;
+                }
+            }
+        }
+    }
+
+    insta::assert_snapshot!(render_patch(&filesystem, &left_root, &files)?, @r###"
+    diff --git a/added b/added
+    new file mode 100644
+    --- /dev/null
+    +++ b/added
+    @@ -0,0 +1 @@
+    +added
+    diff --git a/changed b/changed
+    --- a/changed
+    +++ b/changed
+    @@ -1,3 +1,3 @@
+    -foo 1
+    +FOO 1
+     foo 2
+     foo 3
+    diff --git a/deleted b/deleted
+    deleted file mode 100644
+    --- a/deleted
+    +++ /dev/null
+    @@ -1 +0,0 @@
+    -deleted
+    diff --git a/executable b/executable
+    old mode 100644
+    new mode 100755
+    "###);
     Ok(())
 }
@@ -12,12 +12,16 @@ fn bench_record(c: &mut Criterion) {
         let before_line = SectionChangedLine {
             line: Cow::Borrowed("foo"),
             is_checked: false,
+            commit_idx: 0,
             change_type: ChangeType::Removed,
+            annotation: None,
         };
         let after_line = SectionChangedLine {
             line: Cow::Borrowed("foo"),
             is_checked: false,
+            commit_idx: 0,
             change_type: ChangeType::Added,
+            annotation: None,
         };
         let record_state = RecordState {
             is_read_only: false,
@@ -28,7 +32,11 @@ fn bench_record(c: &mut Criterion) {
                 file_mode: FileMode::FILE_DEFAULT,
                 sections: vec![Section::Changed {
                     lines: [vec![before_line; 1000], vec![after_line; 1000]].concat(),
+                    label: None,
+                    context: None,
                 }],
+                is_deferred: false,
+                is_lossy: false,
             }],
         };
         let mut input = TestingInput::new(
@@ -5,8 +5,8 @@ use std::borrow::Cow;
 use std::path::Path;
 
 use tug_record::{
-    helpers::CrosstermInput, ChangeType, File, FileMode, RecordError, RecordState, Recorder,
-    Section, SectionChangedLine, SelectedChanges, SelectedContents,
+    helpers::CrosstermInput, ChangeType, File, FileMode, LineAnnotation, RecordError, RecordState,
+    Recorder, Section, SectionChangedLine, SelectedChanges, SelectedContents,
 };
 
 fn main() {
@@ -25,31 +25,55 @@ fn main() {
                     lines: vec![
                         SectionChangedLine {
                             is_checked: true,
+                            commit_idx: 0,
                             change_type: ChangeType::Removed,
                             line: Cow::Borrowed("before text 1\n"),
+                            annotation: Some(LineAnnotation {
+                                author: Cow::Borrowed("Jane Doe"),
+                                age: Cow::Borrowed("3 weeks"),
+                            }),
                         },
                         SectionChangedLine {
                             is_checked: true,
+                            commit_idx: 0,
                             change_type: ChangeType::Removed,
                             line: Cow::Borrowed("before text 2\n"),
+                            annotation: Some(LineAnnotation {
+                                author: Cow::Borrowed("Jane Doe"),
+                                age: Cow::Borrowed("3 weeks"),
+                            }),
                         },
                         SectionChangedLine {
                             is_checked: true,
+                            commit_idx: 0,
                             change_type: ChangeType::Added,
 
                             line: Cow::Borrowed("after text 1\n"),
+                            annotation: Some(LineAnnotation {
+                                author: Cow::Borrowed("Jane Doe"),
+                                age: Cow::Borrowed("2 days"),
+                            }),
                         },
                         SectionChangedLine {
                             is_checked: false,
+                            commit_idx: 0,
                             change_type: ChangeType::Added,
                             line: Cow::Borrowed("after text 2\n"),
+                            annotation: Some(LineAnnotation {
+                                author: Cow::Borrowed("Jane Doe"),
+                                age: Cow::Borrowed("2 days"),
+                            }),
                         },
                     ],
+                    label: Some(Cow::Borrowed("Replace the before text")),
+                    context: None,
                 },
                 Section::Unchanged {
                     lines: vec![Cow::Borrowed("this is some trailing text\n")],
                 },
             ],
+            is_deferred: false,
+            is_lossy: false,
         },
         File {
             old_path: None,
@@ -66,30 +90,42 @@ fn main() {
                     lines: vec![
                         SectionChangedLine {
                             is_checked: true,
+                            commit_idx: 0,
                             change_type: ChangeType::Removed,
                             line: Cow::Borrowed("before text 1\n"),
+                            annotation: None,
                         },
                         SectionChangedLine {
                             is_checked: true,
+                            commit_idx: 0,
                             change_type: ChangeType::Removed,
                             line: Cow::Borrowed("before text 2\n"),
+                            annotation: None,
                         },
                         SectionChangedLine {
                             is_checked: true,
+                            commit_idx: 0,
                             change_type: ChangeType::Added,
                             line: Cow::Borrowed("after text 1\n"),
+                            annotation: None,
                         },
                         SectionChangedLine {
                             is_checked: true,
+                            commit_idx: 0,
                             change_type: ChangeType::Added,
                             line: Cow::Borrowed("after text 2\n"),
+                            annotation: None,
                         },
                     ],
+                    label: None,
+                    context: None,
                 },
                 Section::Unchanged {
                     lines: vec![Cow::Borrowed("this is some trailing text")],
                 },
             ],
+            is_deferred: false,
+            is_lossy: false,
         },
     ];
     let record_state = RecordState {
@@ -11,3 +11,10 @@ pub const DUMP_UI_STATE_FILENAME: &str = "tug_record_ui_state.json";
 /// Render a debug pane over the file. Only works if compiled with the `debug`
 /// feature.
 pub const ENV_VAR_DEBUG_UI: &str = "TUG_RECORD_DEBUG_UI";
+
+/// Append each transition of the UI to the file at the path in this variable,
+/// as a line of JSON with the event, how the UI responded to it, and the
+/// selection afterwards. The trace can be replayed with
+/// [`crate::TestingRecorder::replay_to`]. Only works if compiled with the
+/// `debug` feature.
+pub const ENV_VAR_TRACE_TRANSITIONS: &str = "TUG_RECORD_TRACE_TRANSITIONS";
@@ -1,9 +1,28 @@
 //! Helper functions for rendering UI components.
 
-use std::{collections::VecDeque, time::Duration};
+use std::borrow::Cow;
 
+use crate::BinaryMetadata;
+
+#[cfg(feature = "tui")]
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
+#[cfg(all(feature = "tui", not(target_family = "wasm")))]
+use std::{env, fs, process::Command, time::Duration};
+#[cfg(all(feature = "tui", not(target_family = "wasm")))]
+use web_time::Instant;
+
+#[cfg(all(feature = "tui", not(target_family = "wasm")))]
+use crate::ui::terminal::{self, TerminalCapabilities};
+#[cfg(feature = "tui")]
 use crate::{
-    ui::{event, input::RecordInput, terminal::TerminalKind},
+    ui::{
+        event,
+        input::{FileVersion, ImagePreview, RecordInput},
+        terminal::TerminalKind,
+    },
     RecordError,
 };
 
@@ -12,19 +31,112 @@ pub fn make_binary_description(hash: &str, num_bytes: u64) -> String {
     format!("{hash} ({num_bytes} bytes)")
 }
 
+/// Generate the metadata of a binary file from the same information as
+/// [`make_binary_description`], for display as a table.
+pub fn make_binary_metadata(hash: &str, num_bytes: u64) -> BinaryMetadata<'static> {
+    BinaryMetadata {
+        num_bytes: Some(num_bytes),
+        mime_type: None,
+        hash: Some(Cow::Owned(hash.to_owned())),
+    }
+}
+
+/// Decode the contents of a line which might not be valid UTF-8, such as one
+/// from a file with mixed encodings, for use in a [`crate::Section`]. Invalid
+/// sequences are replaced with [`char::REPLACEMENT_CHARACTER`], which is drawn
+/// as a placeholder.
+///
+/// Also returns whether any sequences were replaced. If so, the selected
+/// contents of the file won't reproduce its original bytes, so
+/// [`crate::File::is_lossy`] should be set.
+pub fn decode_line_lossy(line: &[u8]) -> (Cow<'_, str>, bool) {
+    let decoded = String::from_utf8_lossy(line);
+    let is_lossy = match decoded {
+        Cow::Borrowed(_) => false,
+        Cow::Owned(_) => true,
+    };
+    (decoded, is_lossy)
+}
+
 /// Reads input events from the terminal using `crossterm`.
 ///
-/// Its default implementation of `edit_commit_message` returns the provided
-/// message unchanged.
+/// Commit messages are edited by writing them to a temporary file and opening
+/// it in the editor named by `$VISUAL` or `$EDITOR` (falling back to `vi`).
+///
+/// This isn't available on WebAssembly, see the `web` module instead.
+#[cfg(all(feature = "tui", not(target_family = "wasm")))]
 pub struct CrosstermInput;
 
+#[cfg(all(feature = "tui", not(target_family = "wasm")))]
+impl CrosstermInput {
+    /// How often to check whether the process was asked to terminate while
+    /// waiting for input.
+    const TERMINATION_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Wait at most `timeout` for an event to become available. If the
+    /// process is asked to terminate in the meantime, the session is
+    /// cancelled.
+    fn poll(timeout: Duration) -> Result<bool, RecordError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if terminal::is_termination_requested() {
+                return Err(RecordError::Cancelled);
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if crossterm::event::poll(timeout.min(Self::TERMINATION_CHECK_INTERVAL))
+                .map_err(RecordError::ReadInput)?
+            {
+                return Ok(true);
+            }
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// The editor command to use to edit commit messages.
+    fn editor() -> String {
+        ["VISUAL", "EDITOR"]
+            .into_iter()
+            .filter_map(env::var_os)
+            .map(|editor| editor.to_string_lossy().trim().to_owned())
+            .find(|editor| !editor.is_empty())
+            .unwrap_or_else(|| "vi".to_owned())
+    }
+
+    /// Build the command to open `path` in `editor`. The editor may include
+    /// arguments, such as `code --wait`.
+    fn editor_command(editor: &str, path: &Path) -> Command {
+        if cfg!(unix) {
+            let mut command = Command::new("sh");
+            command
+                .arg("-c")
+                .arg(format!("{editor} \"$@\""))
+                .arg(editor)
+                .arg(path);
+            command
+        } else {
+            let mut args = editor.split_whitespace();
+            let mut command = Command::new(args.next().unwrap_or(editor));
+            command.args(args).arg(path);
+            command
+        }
+    }
+}
+
+#[cfg(all(feature = "tui", not(target_family = "wasm")))]
 impl RecordInput for CrosstermInput {
     fn terminal_kind(&self) -> TerminalKind {
         TerminalKind::Crossterm
     }
 
+    fn terminal_capabilities(&self) -> TerminalCapabilities {
+        TerminalCapabilities::detect()
+    }
+
     fn next_events(&mut self) -> Result<Vec<event::Event>, RecordError> {
         // Ensure we block for at least one event.
+        while !Self::poll(Self::TERMINATION_CHECK_INTERVAL)? {}
         let first_event = crossterm::event::read().map_err(RecordError::ReadInput)?;
         let mut events = vec![first_event.into()];
         // Some events, like scrolling, are generated more quickly than
@@ -37,12 +149,66 @@ impl RecordInput for CrosstermInput {
         Ok(events)
     }
 
+    fn poll_events(&mut self, timeout: Duration) -> Result<Vec<event::Event>, RecordError> {
+        if Self::poll(timeout)? {
+            self.next_events()
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
     fn edit_commit_message(&mut self, message: &str) -> Result<String, RecordError> {
-        Ok(message.to_owned())
+        let path =
+            env::temp_dir().join(format!("tug-record-COMMIT_EDITMSG-{}", std::process::id()));
+        fs::write(&path, message).map_err(|source| RecordError::WriteFile {
+            path: path.clone(),
+            source,
+        })?;
+
+        let editor = Self::editor();
+        let result = Self::editor_command(&editor, &path)
+            .status()
+            .map_err(|source| RecordError::RunEditor {
+                editor: editor.clone(),
+                source,
+            })
+            .and_then(|status| {
+                if status.success() {
+                    fs::read_to_string(&path).map_err(|source| RecordError::ReadFile {
+                        path: path.clone(),
+                        source,
+                    })
+                } else {
+                    Err(RecordError::EditorFailed {
+                        editor: editor.clone(),
+                        status,
+                    })
+                }
+            });
+        // Failing to clean up the temporary file shouldn't discard the edit.
+        let _ = fs::remove_file(&path);
+        result
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<(), RecordError> {
+        terminal::copy_to_clipboard(text)
+    }
+
+    #[cfg(feature = "image-preview")]
+    fn show_images(&mut self, path: &Path, images: &[ImagePreview]) -> Result<bool, RecordError> {
+        let protocol = match terminal::graphics_protocol() {
+            Some(protocol) => protocol,
+            None => return Ok(false),
+        };
+        terminal::clean_up_crossterm()?;
+        let result = terminal::show_images(protocol, path, images).map_err(RecordError::ShowImages);
+        terminal::set_up_crossterm()?;
+        result.map(|()| true)
     }
 }
 
 /// Reads events from the provided sequence of events.
+#[cfg(feature = "tui")]
 pub struct TestingInput {
     /// The width of the virtual terminal in columns.
     pub width: usize,
@@ -55,8 +221,23 @@ pub struct TestingInput {
 
     /// Commit messages to use when the commit editor is opened.
     pub commit_messages: VecDeque<String>,
+
+    /// The texts which were copied to the clipboard, in order.
+    pub clipboard: Vec<String>,
+
+    /// The full contents of each version of each file, for when the user
+    /// views a file. Versions which aren't in here don't exist.
+    pub file_contents: HashMap<(PathBuf, FileVersion), String>,
+
+    /// The raw contents of each version of each image file, for when the user
+    /// previews images. Versions which aren't in here aren't images.
+    pub image_contents: HashMap<(PathBuf, FileVersion), Vec<u8>>,
+
+    /// The path and versions of each image file which was shown, in order.
+    pub shown_images: Vec<(PathBuf, Vec<FileVersion>)>,
 }
 
+#[cfg(feature = "tui")]
 impl TestingInput {
     /// Helper function to construct a `TestingInput`.
     pub fn new(
@@ -69,10 +250,15 @@ impl TestingInput {
             height,
             events: Box::new(events.into_iter()),
             commit_messages: Default::default(),
+            clipboard: Default::default(),
+            file_contents: Default::default(),
+            image_contents: Default::default(),
+            shown_images: Default::default(),
         }
     }
 }
 
+#[cfg(feature = "tui")]
 impl RecordInput for TestingInput {
     fn terminal_kind(&self) -> TerminalKind {
         let Self {
@@ -80,6 +266,10 @@ impl RecordInput for TestingInput {
             height,
             events: _,
             commit_messages: _,
+            clipboard: _,
+            file_contents: _,
+            image_contents: _,
+            shown_images: _,
         } = self;
         TerminalKind::Testing {
             width: *width,
@@ -96,4 +286,56 @@ impl RecordInput for TestingInput {
             .pop_front()
             .ok_or_else(|| RecordError::Other("No more commit messages available".to_string()))
     }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<(), RecordError> {
+        self.clipboard.push(text.to_owned());
+        Ok(())
+    }
+
+    fn file_contents(
+        &mut self,
+        path: &Path,
+        version: FileVersion,
+    ) -> Result<Option<String>, RecordError> {
+        Ok(self.file_contents.get(&(path.to_owned(), version)).cloned())
+    }
+
+    fn image_contents(
+        &mut self,
+        path: &Path,
+        version: FileVersion,
+    ) -> Result<Option<Vec<u8>>, RecordError> {
+        Ok(self
+            .image_contents
+            .get(&(path.to_owned(), version))
+            .cloned())
+    }
+
+    fn show_images(&mut self, path: &Path, images: &[ImagePreview]) -> Result<bool, RecordError> {
+        let versions = images.iter().map(|image| image.version).collect();
+        self.shown_images.push((path.to_owned(), versions));
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_line_lossy() {
+        assert_eq!(
+            decode_line_lossy(b"caf\xc3\xa9\n"),
+            (Cow::Borrowed("caf\u{e9}\n"), false)
+        );
+        assert_eq!(
+            decode_line_lossy(b"caf\xe9\n"),
+            (Cow::Owned("caf\u{FFFD}\n".to_owned()), true)
+        );
+        // A truncated multi-byte sequence is replaced as a whole.
+        assert_eq!(
+            decode_line_lossy(b"\xe2\x82 and \xff\xfe"),
+            (Cow::Owned("\u{FFFD} and \u{FFFD}\u{FFFD}".to_owned()), true)
+        );
+    }
 }
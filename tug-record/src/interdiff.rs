@@ -0,0 +1,287 @@
+//! Comparison of a [`RecordState`] with a previous one for the same changes,
+//! such as after rebasing and running the diff editor again.
+
+use std::path::PathBuf;
+
+use crate::types::{RecordState, Section, SectionChangedLine};
+
+/// How the changes in a [`RecordState`] compare to those in a previous
+/// state. Pass it in [`crate::RecordOptions::interdiff`] to highlight what
+/// changed in the UI.
+///
+/// Files are matched by path. Sections are matched by their changes, and not
+/// by their position, since that typically shifts after a rebase.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Interdiff {
+    /// How each file compares to the previous state, in the same order as
+    /// [`RecordState::files`].
+    pub files: Vec<FileInterdiff>,
+
+    /// The paths of the files which were in the previous state, but no
+    /// longer are.
+    pub disappeared_files: Vec<PathBuf>,
+}
+
+/// How a file compares to the same file in a previous state.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct FileInterdiff {
+    /// Whether the file wasn't in the previous state.
+    pub is_new: bool,
+
+    /// How each section compares to the previous state, in the same order as
+    /// [`crate::File::sections`].
+    pub sections: Vec<SectionInterdiff>,
+
+    /// The number of sections with changes in the previous state which no
+    /// longer appear in the file.
+    pub num_disappeared_sections: usize,
+}
+
+/// How a section compares to the sections of the same file in a previous
+/// state.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum SectionInterdiff {
+    /// The section has the same changes as a section in the previous state,
+    /// with the same selections. Sections without changes are always
+    /// unchanged.
+    Unchanged,
+
+    /// The section has the same changes as a section in the previous state,
+    /// but different ones are selected.
+    SelectionChanged,
+
+    /// No section in the previous state had the same changes.
+    New,
+}
+
+impl Interdiff {
+    /// Compare `current` with `previous`.
+    pub fn new(previous: &RecordState, current: &RecordState) -> Self {
+        let files = current
+            .files
+            .iter()
+            .map(|file| {
+                let previous_file = previous
+                    .files
+                    .iter()
+                    .find(|previous_file| previous_file.path == file.path);
+                let previous_sections: &[Section] = match previous_file {
+                    Some(previous_file) => &previous_file.sections,
+                    None => &[],
+                };
+
+                // Each section of the previous file can only be matched once.
+                let mut is_matched = vec![false; previous_sections.len()];
+                let sections = file
+                    .sections
+                    .iter()
+                    .map(|section| {
+                        if !section.is_editable() {
+                            return SectionInterdiff::Unchanged;
+                        }
+                        let matching_idx = previous_sections.iter().zip(&is_matched).position(
+                            |(previous_section, is_matched)| {
+                                !is_matched && has_same_changes(previous_section, section)
+                            },
+                        );
+                        match matching_idx {
+                            Some(idx) => {
+                                is_matched[idx] = true;
+                                if has_same_selections(&previous_sections[idx], section) {
+                                    SectionInterdiff::Unchanged
+                                } else {
+                                    SectionInterdiff::SelectionChanged
+                                }
+                            }
+                            None => SectionInterdiff::New,
+                        }
+                    })
+                    .collect();
+                let num_disappeared_sections = previous_sections
+                    .iter()
+                    .zip(&is_matched)
+                    .filter(|(section, is_matched)| section.is_editable() && !**is_matched)
+                    .count();
+                FileInterdiff {
+                    is_new: previous_file.is_none(),
+                    sections,
+                    num_disappeared_sections,
+                }
+            })
+            .collect();
+
+        let disappeared_files = previous
+            .files
+            .iter()
+            .filter(|previous_file| {
+                !current
+                    .files
+                    .iter()
+                    .any(|file| file.path == previous_file.path)
+            })
+            .map(|previous_file| previous_file.path.to_path_buf())
+            .collect();
+
+        Self {
+            files,
+            disappeared_files,
+        }
+    }
+}
+
+impl FileInterdiff {
+    /// Whether anything about the file changed since the previous state, so
+    /// that the user should look at it again.
+    pub fn needs_attention(&self) -> bool {
+        let Self {
+            is_new,
+            sections,
+            num_disappeared_sections,
+        } = self;
+        *is_new
+            || *num_disappeared_sections > 0
+            || sections
+                .iter()
+                .any(|section| *section != SectionInterdiff::Unchanged)
+    }
+}
+
+/// Whether the sections make the same changes, regardless of which of them
+/// are selected.
+fn has_same_changes(old: &Section, new: &Section) -> bool {
+    match (old, new) {
+        (
+            Section::Changed {
+                lines: old_lines,
+                label: _,
+                context: _,
+            },
+            Section::Changed {
+                lines: new_lines,
+                label: _,
+                context: _,
+            },
+        ) => {
+            old_lines.len() == new_lines.len()
+                && old_lines.iter().zip(new_lines).all(|(old_line, new_line)| {
+                    let SectionChangedLine {
+                        is_checked: _,
+                        commit_idx: _,
+                        change_type: old_change_type,
+                        line: old_line,
+                        annotation: _,
+                    } = old_line;
+                    let SectionChangedLine {
+                        is_checked: _,
+                        commit_idx: _,
+                        change_type: new_change_type,
+                        line: new_line,
+                        annotation: _,
+                    } = new_line;
+                    old_change_type == new_change_type && old_line == new_line
+                })
+        }
+        (
+            Section::FileMode {
+                is_checked: _,
+                commit_idx: _,
+                mode: old_mode,
+            },
+            Section::FileMode {
+                is_checked: _,
+                commit_idx: _,
+                mode: new_mode,
+            },
+        ) => old_mode == new_mode,
+        (
+            Section::Binary {
+                is_checked: _,
+                commit_idx: _,
+                old_description: old_old_description,
+                new_description: old_new_description,
+                old_metadata: old_old_metadata,
+                new_metadata: old_new_metadata,
+            },
+            Section::Binary {
+                is_checked: _,
+                commit_idx: _,
+                old_description: new_old_description,
+                new_description: new_new_description,
+                old_metadata: new_old_metadata,
+                new_metadata: new_new_metadata,
+            },
+        ) => {
+            old_old_description == new_old_description
+                && old_new_description == new_new_description
+                && old_old_metadata == new_old_metadata
+                && old_new_metadata == new_new_metadata
+        }
+        (
+            Section::Unchanged { .. }
+            | Section::Changed { .. }
+            | Section::FileMode { .. }
+            | Section::Binary { .. },
+            _,
+        ) => false,
+    }
+}
+
+/// Whether the same changes are selected in both sections, which must make
+/// the same changes.
+fn has_same_selections(old: &Section, new: &Section) -> bool {
+    match (old, new) {
+        (
+            Section::Changed {
+                lines: old_lines,
+                label: _,
+                context: _,
+            },
+            Section::Changed {
+                lines: new_lines,
+                label: _,
+                context: _,
+            },
+        ) => old_lines
+            .iter()
+            .zip(new_lines)
+            .all(|(old_line, new_line)| old_line.is_checked == new_line.is_checked),
+        (
+            Section::FileMode {
+                is_checked: old_is_checked,
+                commit_idx: _,
+                mode: _,
+            }
+            | Section::Binary {
+                is_checked: old_is_checked,
+                commit_idx: _,
+                old_description: _,
+                new_description: _,
+                old_metadata: _,
+                new_metadata: _,
+            },
+            Section::FileMode {
+                is_checked: new_is_checked,
+                commit_idx: _,
+                mode: _,
+            }
+            | Section::Binary {
+                is_checked: new_is_checked,
+                commit_idx: _,
+                old_description: _,
+                new_description: _,
+                old_metadata: _,
+                new_metadata: _,
+            },
+        ) => old_is_checked == new_is_checked,
+        (
+            Section::Unchanged { .. }
+            | Section::Changed { .. }
+            | Section::FileMode { .. }
+            | Section::Binary { .. },
+            _,
+        ) => true,
+    }
+}
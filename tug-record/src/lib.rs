@@ -1,4 +1,27 @@
 //! Reusable change selector UI for source control systems.
+//!
+//! The terminal UI is behind the `tui` feature, which is enabled by default.
+//! Most tools only need [`record`], which asks the user to select among the
+//! given files in the terminal. Without the `tui` feature, the data model is
+//! still available, such as [`RecordState`] and the selection logic on
+//! [`File`], along with [`Selector`], which moves the selection and toggles
+//! items the same way as the terminal UI. This lets tools outside of a
+//! terminal reuse them with minimal dependencies.
+//!
+//! The `render` module, also behind `tui`, exposes the component and
+//! viewport layer that the recorder is drawn with, for frontends which want
+//! to draw their own components the same way.
+//!
+//! The experimental `wasm` feature adds the `web` module, which bridges the
+//! recorder to browser key events and an `xterm.js` terminal, so that web
+//! based review tools can embed the same UI. It builds for
+//! `wasm32-unknown-unknown`, where `record` and `CrosstermInput` aren't
+//! available since `crossterm` doesn't build there.
+//!
+//! The `serde` feature allows states to be (de)serialized, such as to save
+//! an interrupted session, and the `tracing` feature logs warnings about
+//! unexpected UI states. Both are enabled by default, along with `debug`,
+//! which adds environment variables to debug the UI.
 
 #![warn(missing_docs)]
 #![warn(
@@ -9,17 +32,50 @@
 )]
 #![allow(clippy::too_many_arguments)]
 
-mod render;
+/// Without the `tracing` feature, warnings aren't logged anywhere.
+#[cfg(all(feature = "tui", not(feature = "tracing")))]
+macro_rules! warn {
+    ($($arg:tt)*) => {};
+}
+
+mod interdiff;
+#[cfg(feature = "tui")]
+pub mod render;
+#[cfg(all(feature = "tui", not(target_family = "wasm")))]
+mod session;
 mod types;
 mod ui;
 mod util;
+#[cfg(feature = "wasm")]
+pub mod web;
 
 pub mod consts;
 pub mod helpers;
+pub use interdiff::{FileInterdiff, Interdiff, SectionInterdiff};
+#[cfg(all(feature = "tui", not(target_family = "wasm")))]
+pub use session::{record, RecordResult};
 pub use types::{
-    ChangeType, Commit, File, FileMode, RecordError, RecordState, Section, SectionChangedLine,
+    BinaryMetadata, ChangeType, Commit, DirtyState, File, FileMode, InitialExpansion,
+    LineAnnotation, RecordError, RecordOptions, RecordState, Section, SectionChangedLine,
     SelectedChanges, SelectedContents, Tristate,
 };
-pub use ui::{ recorder::Recorder };
+#[cfg(feature = "tui")]
+pub use ui::recorder::{DrawnComponent, DrawnLayout, EmbeddedRecorder, Recorder, TestingRecorder};
+pub use ui::state::{SelectedItem, Selector};
 
-pub use crate::ui::input::RecordInput;
+#[cfg(feature = "tui")]
+pub use crate::ui::announcer::Announcer;
+#[cfg(feature = "tui")]
+pub use crate::ui::event::Event;
+#[cfg(feature = "tui")]
+pub use crate::ui::input::{FileVersion, ImagePreview, RecordInput, TestingScreenshot};
+#[cfg(feature = "tui")]
+pub use crate::ui::keymap::{Key, KeyBinding, Keymap, KeymapError};
+#[cfg(feature = "tui")]
+pub use crate::ui::metrics::Metrics;
+#[cfg(feature = "tui")]
+pub use crate::ui::terminal::{ColorSupport, TerminalCapabilities, TerminalKind};
+#[cfg(feature = "tui")]
+pub use crate::ui::theme::Theme;
+#[cfg(feature = "debug")]
+pub use crate::ui::transition_log::TransitionTrace;
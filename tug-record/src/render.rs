@@ -1,3 +1,18 @@
+//! A small retained-mode layer on top of `ratatui` used to draw the recorder.
+//!
+//! Components implementing [`Component`] draw onto a virtual canvas through a
+//! [`Viewport`], which can be addressed with negative or off-screen
+//! coordinates and only renders the visible portion. Every component draw is
+//! traced, so that after rendering, [`DrawnRects`] reports where each
+//! component ended up; this is what the recorder uses for mouse hit-testing
+//! and scrolling.
+//!
+//! The items in this module are part of the public API and follow the crate's
+//! semver guarantees, so that other frontends can build their own components
+//! on top of them. Since [`Viewport`] draws into a [`ratatui::buffer::Buffer`],
+//! upgrading the `ratatui` dependency to an incompatible version is treated
+//! as a breaking change as well.
+
 use std::borrow::Cow;
 use std::cmp::{max, min};
 use std::collections::HashMap;
@@ -12,13 +27,18 @@ use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{StatefulWidget, Widget};
 use ratatui::Frame;
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+use crate::ui::theme::Theme;
 use crate::util::{IsizeExt, UsizeExt};
 
+/// The dimensions of a [`Rect`], without its position.
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub(crate) struct RectSize {
+pub struct RectSize {
+    /// The number of columns.
     pub width: usize,
+
+    /// The number of rows.
     pub height: usize,
 }
 
@@ -43,10 +63,17 @@ impl From<Rect> for RectSize {
 /// Like `ratatui::layout::Rect`, but supports addressing negative coordinates. (These
 /// coordinates shouldn't be rendered.)
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub(crate) struct Rect {
+pub struct Rect {
+    /// The column of the left edge.
     pub x: isize,
+
+    /// The row of the top edge.
     pub y: isize,
+
+    /// The number of columns.
     pub width: usize,
+
+    /// The number of rows.
     pub height: usize,
 }
 
@@ -68,14 +95,17 @@ impl From<ratatui::layout::Rect> for Rect {
 }
 
 impl Rect {
+    /// The column just past the right edge of this `Rect`.
     pub fn end_x(self) -> isize {
         self.x + self.width.unwrap_isize()
     }
 
+    /// The row just past the bottom edge of this `Rect`.
     pub fn end_y(self) -> isize {
         self.y + self.height.unwrap_isize()
     }
 
+    /// Iterate over the rows covered by this `Rect`, from top to bottom.
     pub fn iter_ys(self) -> impl Iterator<Item = isize> {
         self.y..self.end_y()
     }
@@ -147,7 +177,9 @@ impl Rect {
 
 /// Create a centered `Rect` of at least the given size and at most the provided
 /// percentages.
-pub(crate) fn centered_rect(
+///
+/// If `rect` is smaller than `min_size`, the result is clipped to `rect`.
+pub fn centered_rect(
     rect: Rect,
     min_size: RectSize,
     max_percent_width: usize,
@@ -184,6 +216,10 @@ pub(crate) fn centered_rect(
     } = min_size;
     let min_width: f64 = cast(min_width).unwrap();
     let min_height: f64 = cast(min_height).unwrap();
+    // If the rect is smaller than the minimum size, then the constraints
+    // can't be satisfied, so the result is clipped to the rect instead.
+    let min_width = min_width.min(max_width);
+    let min_height = min_height.min(max_height);
 
     let mut solver = Solver::new();
     let x = Variable::new();
@@ -225,8 +261,11 @@ pub(crate) fn centered_rect(
 
 /// A "half-open" `Rect` used to to restrict drawing to a certain portion of the screen.
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub(crate) struct Mask {
+pub struct Mask {
+    /// The column of the left edge.
     pub x: isize,
+
+    /// The row of the top edge.
     pub y: isize,
 
     /// If `None`, the mask is unrestricted on the x-axis past the `x` value.
@@ -252,10 +291,12 @@ impl Mask {
         mask_rect.intersect(rect)
     }
 
+    /// The column just past the right edge of this mask, if it is bounded.
     pub fn end_x(self) -> Option<isize> {
         self.width.map(|width| self.x + width.unwrap_isize())
     }
 
+    /// The row just past the bottom edge of this mask, if it is bounded.
     pub fn end_y(self) -> Option<isize> {
         self.height.map(|height| self.y + height.unwrap_isize())
     }
@@ -327,34 +368,50 @@ impl<ComponentId> Default for DrawTrace<ComponentId> {
     }
 }
 
+/// Where a component was drawn during a render.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub(crate) struct DrawnRect {
+pub struct DrawnRect {
+    /// The bounding box of everything drawn by the component and its
+    /// children, in virtual canvas coordinates.
     pub rect: Rect,
+
+    /// The order in which the component was drawn relative to the others in
+    /// the same render. Components drawn later have larger timestamps and
+    /// appear on top of those drawn earlier, e.g. dialogs over the commit
+    /// view.
     pub timestamp: usize,
 }
 
-pub(crate) type DrawnRects<C> = HashMap<C, DrawnRect>;
+/// The mapping from component ID to where that component was drawn, as
+/// returned by [`Viewport::render_top_level`].
+pub type DrawnRects<C> = HashMap<C, DrawnRect>;
 
 /// Accessor to draw on the virtual canvas. The caller can draw anywhere on the
 /// canvas, but the actual renering will be restricted to this viewport. All
 /// draw calls are also tracked so that we know where each component was drawn
 /// after the fact (see `DrawTrace`).
 #[derive(Debug)]
-pub(crate) struct Viewport<'a, ComponentId> {
+pub struct Viewport<'a, ComponentId> {
     buf: &'a mut Buffer,
     rect: Rect,
     mask: Option<Mask>,
+    theme: Theme,
     timestamp: usize,
     trace: Vec<DrawTrace<ComponentId>>,
     debug_messages: Vec<String>,
 }
 
 impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
+    /// Create a viewport which renders the `rect` portion of the virtual
+    /// canvas into `buf`. Most callers should use
+    /// [`Viewport::render_top_level`] instead, which also collects the
+    /// [`DrawnRects`].
     pub fn new(buf: &'a mut Buffer, rect: Rect) -> Self {
         Self {
             buf,
             rect,
             mask: Default::default(),
+            theme: Default::default(),
             timestamp: Default::default(),
             trace: vec![Default::default()],
             debug_messages: Default::default(),
@@ -385,6 +442,17 @@ impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
         self.mask().apply(self.rect())
     }
 
+    /// The styles which components should draw with. Defaults to
+    /// [`Theme::default`] until set with `Viewport::set_theme`.
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Set the styles used by the components drawn after this call.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
     /// Render the provided component using the given `Frame`. Returns a mapping
     /// indicating where each component was drawn on the screen.
     pub fn render_top_level<C: Component>(
@@ -480,26 +548,15 @@ impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
             None => draw_rect,
         };
         if !draw_rect.is_empty() {
-            let span_start_idx = (draw_rect.x - span_rect.x).unwrap_usize();
-            let span_start_byte_idx = content
-                .char_indices()
-                .nth(span_start_idx)
-                .map(|(i, _c)| i)
-                .unwrap_or(0);
-            let span_end_byte_idx = match content
-                .char_indices()
-                .nth(span_start_idx + draw_rect.width)
-                .map(|(i, _c)| i)
-            {
-                Some(span_end_byte_index) => span_end_byte_index,
-                None => content.len(),
-            };
+            let span_start_x = (draw_rect.x - span_rect.x).unwrap_usize();
+            let draw_content = slice_columns(content, span_start_x, draw_rect.width);
             let draw_span = Span {
-                content: Cow::Borrowed(&content.as_ref()[span_start_byte_idx..span_end_byte_idx]),
+                content: draw_content,
                 style: *style,
             };
 
             let buf_rect = self.translate_rect(draw_rect);
+            self.clear_wide_symbol_before(buf_rect);
             self.buf
                 .set_span(buf_rect.x, buf_rect.y, &draw_span, buf_rect.width);
         }
@@ -507,7 +564,32 @@ impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
         span_rect
     }
 
+    /// If a multi-width symbol drawn earlier extends into the start of
+    /// `buf_rect`, replace it with a space, so that it can't cover what is
+    /// about to be drawn there, such as a toggle box after CJK text.
+    fn clear_wide_symbol_before(&mut self, buf_rect: ratatui::layout::Rect) {
+        let row_start_x = self.buf.area.x;
+        for prev_x in (row_start_x..buf_rect.x).rev() {
+            let Some(cell) = self.buf.cell_mut((prev_x, buf_rect.y)) else {
+                break;
+            };
+            let symbol_width = cell.symbol().width();
+            if symbol_width == 0 {
+                // Hidden by a wide symbol further to the left.
+                continue;
+            }
+            if usize::from(prev_x) + symbol_width > usize::from(buf_rect.x) {
+                cell.set_symbol(" ");
+            }
+            break;
+        }
+    }
+
     /// Draw a [`Line`] directly to the screen at `(x, y)` location.
+    ///
+    /// Each span is drawn with [`Viewport::draw_span`], so that a line which
+    /// starts off-screen or outside of the mask is clipped by display
+    /// columns, accounting for multi-width symbols.
     pub fn draw_line(&mut self, x: isize, y: isize, line: &Line) -> Rect {
         let line_rect = Rect {
             x,
@@ -517,15 +599,14 @@ impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
         };
         self.current_trace_mut().merge_rect(line_rect);
 
-        let draw_rect = self.rect.intersect(line_rect);
-
-        let draw_rect = match self.mask {
-            Some(mask) => mask.apply(draw_rect),
-            None => draw_rect,
-        };
-        if !draw_rect.is_empty() {
-            let buf_rect = self.translate_rect(draw_rect);
-            line.render(buf_rect, self.buf);
+        let mut span_x = x;
+        for span in &line.spans {
+            let span = Span {
+                content: Cow::Borrowed(span.content.as_ref()),
+                style: line.style.patch(span.style),
+            };
+            let span_rect = self.draw_span(span_x, y, &span);
+            span_x = span_rect.end_x();
         }
 
         line_rect
@@ -543,11 +624,14 @@ impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
         line_rect
     }
 
+    /// Render a `ratatui` widget into the given on-screen area, such as one
+    /// returned by [`Viewport::translate_rect`].
     pub fn draw_widget(&mut self, rect: ratatui::layout::Rect, widget: impl Widget) {
         self.current_trace_mut().merge_rect(rect.into());
         widget.render(rect, self.buf);
     }
 
+    /// Clear the given area, resetting its styling.
     pub fn draw_blank(&mut self, rect: Rect) {
         for y in rect.iter_ys() {
             self.draw_span(
@@ -577,6 +661,61 @@ impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
     }
 }
 
+/// Get the part of `content` displayed in the `width` columns starting at
+/// column `start_x`. A multi-width symbol which only partly fits at either
+/// end is replaced with spaces, so that the result is exactly as wide as the
+/// columns which `content` covers.
+fn slice_columns(content: &str, start_x: usize, width: usize) -> Cow<'_, str> {
+    let end_x = start_x + width;
+    let mut x = 0;
+    let mut start_byte_idx = None;
+    let mut end_byte_idx = content.len();
+    let mut leading_padding = 0;
+    let mut trailing_padding = 0;
+    for (i, c) in content.char_indices() {
+        let char_width = c.width().unwrap_or(0);
+        if start_byte_idx.is_none() {
+            if x + char_width <= start_x {
+                x += char_width;
+                continue;
+            }
+            if x < start_x {
+                // Only the right part of the symbol is visible.
+                leading_padding = min(x + char_width, end_x) - start_x;
+                x += char_width;
+                start_byte_idx = Some(i + c.len_utf8());
+                continue;
+            }
+            start_byte_idx = Some(i);
+        }
+        if x >= end_x {
+            end_byte_idx = i;
+            break;
+        }
+        if x + char_width > end_x {
+            trailing_padding = end_x - x;
+            end_byte_idx = i;
+            break;
+        }
+        x += char_width;
+    }
+    let start_byte_idx = match start_byte_idx {
+        Some(start_byte_idx) => min(start_byte_idx, end_byte_idx),
+        None => return Cow::Borrowed(""),
+    };
+
+    let visible = &content[start_byte_idx..end_byte_idx];
+    if leading_padding == 0 && trailing_padding == 0 {
+        Cow::Borrowed(visible)
+    } else {
+        Cow::Owned(format!(
+            "{}{visible}{}",
+            " ".repeat(leading_padding),
+            " ".repeat(trailing_padding)
+        ))
+    }
+}
+
 /// Wrapper to render via `ratatui::Frame`.
 struct TopLevelWidget<'a, C> {
     component: &'a C,
@@ -640,7 +779,7 @@ impl<C: Component> StatefulWidget for TopLevelWidget<'_, C> {
 /// A component which can be rendered on the virtual canvas. All calls to draw
 /// components are traced so that it can be determined later where a given
 /// component was drawn.
-pub(crate) trait Component: Sized {
+pub trait Component: Sized {
     /// A unique identifier which identifies this component or one of its child
     /// components. This can be used with the return value of
     /// `Viewport::render_top_level` to find where the component with a given ID
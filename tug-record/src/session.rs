@@ -0,0 +1,71 @@
+//! A high-level entry point for tools which only need to ask the user which
+//! changes to select, without customizing the input or the terminal.
+
+use std::path::Path;
+
+use crate::helpers::CrosstermInput;
+use crate::{DirtyState, File, RecordError, RecordOptions, RecordState, Recorder, SelectedChanges};
+
+/// The outcome of a session started with [`record`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecordResult<'state> {
+    /// The final state, with the user's selections and commit messages.
+    pub state: RecordState<'state>,
+
+    /// Which parts of the state the user modified.
+    pub dirty: DirtyState,
+}
+
+impl<'state> RecordResult<'state> {
+    /// Whether the user modified anything at all.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.is_dirty()
+    }
+
+    /// The selected changes of each file, such as to stage or commit them,
+    /// along with its path.
+    pub fn selected_changes(&self) -> impl Iterator<Item = (&Path, SelectedChanges<'_>)> {
+        self.state.files.iter().map(|file| {
+            let (selected, _unselected) = file.get_selected_contents();
+            (file.path.as_ref(), selected)
+        })
+    }
+
+    /// The changes of each file which were left unselected, such as to
+    /// record them again, along with its path.
+    pub fn unselected_changes(&self) -> impl Iterator<Item = (&Path, SelectedChanges<'_>)> {
+        self.state.files.iter().map(|file| {
+            let (_selected, unselected) = file.get_selected_contents();
+            (file.path.as_ref(), unselected)
+        })
+    }
+
+    /// Take the final state.
+    pub fn into_state(self) -> RecordState<'state> {
+        self.state
+    }
+}
+
+/// Have the user interactively select changes among `files` in the terminal,
+/// reading input with [`CrosstermInput`].
+///
+/// This is a shortcut for constructing a [`RecordState`] and running a
+/// [`Recorder`] with it. Tools which need to edit commit messages, customize
+/// the input or draw to another terminal should use those directly.
+///
+/// Returns [`RecordError::Cancelled`] if the user quit without accepting the
+/// changes.
+pub fn record<'state>(
+    files: Vec<File<'state>>,
+    options: RecordOptions,
+) -> Result<RecordResult<'state>, RecordError> {
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files,
+    };
+    let mut input = CrosstermInput;
+    let recorder = Recorder::new_with_options(state, &mut input, options);
+    let (state, dirty) = recorder.run_with_dirty_state()?;
+    Ok(RecordResult { state, dirty })
+}
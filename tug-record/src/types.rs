@@ -4,13 +4,25 @@ use std::borrow::Cow;
 use std::fmt::Display;
 use std::io;
 use std::num::TryFromIntError;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::Duration;
 
 use thiserror::Error;
 
+use crate::interdiff::Interdiff;
+
+#[cfg(feature = "tui")]
+use crate::ui::keymap::Keymap;
+#[cfg(feature = "tui")]
+use crate::ui::theme::Theme;
+
 /// The state used to render the changes. This is passed into
 /// [`crate::Recorder::new`] and then updated and returned with
 /// [`crate::Recorder::run`].
+///
+/// The state is `Send` and `Sync`, so it can be built on another thread than
+/// the one running the UI.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct RecordState<'a> {
@@ -22,7 +34,10 @@ pub struct RecordState<'a> {
     /// assigned to exactly one commit.
     ///
     /// If there are fewer than two commits in this list, then it is padded to
-    /// two commits using `Commit::default` before being returned.
+    /// two commits using `Commit::default` before being returned. With more
+    /// than two commits, the changes are assigned to them with the
+    /// `commit_idx` of each line and section, and
+    /// [`File::get_commit_contents`] returns the contents for each commit.
     ///
     /// It's important to note that the `Commit`s do not literally contain the
     /// selected changes. They are stored out-of-band in the `files` field. It
@@ -40,6 +55,172 @@ pub struct RecordState<'a> {
     pub files: Vec<File<'a>>,
 }
 
+impl RecordState<'_> {
+    /// Check that the lines of every file are well-formed, as described in
+    /// [`File::validate`]. This is done by the recorder before showing the UI.
+    pub fn validate(&self) -> Result<(), RecordError> {
+        for file in &self.files {
+            file.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Options which customize the behavior of the UI. This is passed into
+/// [`crate::Recorder::new_with_options`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct RecordOptions {
+    /// Previously-used commit messages, most recent first. For example, these
+    /// could be read from `.git/COMMIT_EDITMSG` or from earlier descriptions.
+    /// If non-empty, the user can pick one of them to reuse as the message of
+    /// the focused commit.
+    pub message_history: Vec<String>,
+
+    /// The bindings of keys to actions. Defaults to the built-in bindings,
+    /// which embedding tools can change to let their users remap keys.
+    #[cfg(feature = "tui")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub keymap: Keymap,
+
+    /// If set, an [`crate::Event::Idle`] event is emitted once the user
+    /// hasn't provided any input for this long.
+    pub idle_timeout: Option<Duration>,
+
+    /// If set, the in-progress selections are saved to this file whenever the
+    /// user goes idle (see [`RecordOptions::idle_timeout`]), so that they
+    /// aren't lost if the terminal crashes. The file is removed once the
+    /// session ends normally, whether accepted or cancelled.
+    ///
+    /// Saving the file requires the `serde` feature.
+    pub recovery_path: Option<PathBuf>,
+
+    /// If set, toggling all changes at once asks for confirmation first when
+    /// there are more than this many files, since a stray key press could
+    /// otherwise throw away a lot of careful selections. Defaults to 50.
+    pub toggle_all_confirmation_threshold: Option<usize>,
+
+    /// How the changes compare to a previous state of the same changes, as
+    /// computed by [`Interdiff::new`]. If set, new sections and sections
+    /// whose selections changed are highlighted, and the user can hide the
+    /// files which didn't change.
+    pub interdiff: Option<Interdiff>,
+
+    /// How many unchanged lines are shown before and after each change. The
+    /// unchanged lines between them are collapsed. Defaults to 4.
+    pub num_context_lines: usize,
+
+    /// If set, [`crate::Recorder::run`] returns
+    /// [`RecordError::NothingToSelect`] right away when none of the files has
+    /// a change which could be selected, rather than showing an empty UI which
+    /// the user has to quit.
+    pub skip_if_nothing_to_select: bool,
+
+    /// If set, accepting asks for confirmation first when no change is
+    /// selected and no commit message was edited, since that usually means
+    /// the user forgot to select anything.
+    pub confirm_empty_selection: bool,
+
+    /// If set, quitting without accepting asks for confirmation first when
+    /// the user changed any selection or commit message, since the changes
+    /// would be lost. Only quitting with a key press is confirmed, not
+    /// [`crate::Event::QuitCancel`] events from the input. Defaults to `true`.
+    pub confirm_quit: bool,
+
+    /// Which files are expanded when the UI is first shown.
+    pub initial_expansion: InitialExpansion,
+
+    /// If set, toggling a change collapses its file once all of the file's
+    /// changes are selected, or all of them are unselected, so that the files
+    /// still needing attention stand out.
+    pub auto_collapse_decided_files: bool,
+
+    /// If set, the words which changed between a removed line and the added
+    /// line which replaced it are emphasized, so that small edits inside long
+    /// lines stand out. Defaults to `true`.
+    pub word_diff: bool,
+
+    /// The colors of the interface. Defaults to [`Theme::dark`]; use
+    /// [`Theme::light`] in terminals with a light background.
+    #[cfg(feature = "tui")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub theme: Theme,
+}
+
+impl Default for RecordOptions {
+    fn default() -> Self {
+        Self {
+            message_history: Default::default(),
+            #[cfg(feature = "tui")]
+            keymap: Default::default(),
+            idle_timeout: None,
+            recovery_path: None,
+            toggle_all_confirmation_threshold: Some(50),
+            interdiff: None,
+            num_context_lines: 4,
+            skip_if_nothing_to_select: false,
+            confirm_empty_selection: false,
+            confirm_quit: true,
+            initial_expansion: InitialExpansion::default(),
+            auto_collapse_decided_files: false,
+            word_diff: true,
+            #[cfg(feature = "tui")]
+            theme: Default::default(),
+        }
+    }
+}
+
+/// Which files are expanded when the UI is first shown. This is set with
+/// [`RecordOptions::initial_expansion`]. Directories and sections always
+/// start expanded, so that expanding a file shows all of its changes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum InitialExpansion {
+    /// Expand every file.
+    ExpandAll,
+
+    /// Collapse every file, so that only the list of files is shown.
+    #[default]
+    CollapseAll,
+
+    /// Expand only the first file.
+    ExpandFirstFile,
+
+    /// Expand the files with fewer than this many changed lines, so that
+    /// small changes can be reviewed at a glance without large ones taking
+    /// up the whole screen.
+    ExpandFilesUnderLines(usize),
+}
+
+/// Which parts of the [`RecordState`] the user modified during a session.
+/// This is returned by [`crate::Recorder::run_with_dirty_state`] so that
+/// callers can skip work when nothing changed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct DirtyState {
+    /// Whether the message of any commit was changed.
+    pub commit_messages: bool,
+
+    /// Whether any change was selected or deselected. This is set even if a
+    /// change was toggled back to its original state.
+    pub selections: bool,
+
+    /// Whether any file was marked or unmarked as deferred.
+    pub deferred_files: bool,
+}
+
+impl DirtyState {
+    /// Whether the user modified anything at all.
+    pub fn is_dirty(&self) -> bool {
+        let Self {
+            commit_messages,
+            selections,
+            deferred_files,
+        } = self;
+        *commit_messages || *selections || *deferred_files
+    }
+}
+
 /// An error which occurred when attempting to record changes.
 #[allow(missing_docs)]
 #[derive(Debug, Error)]
@@ -48,6 +229,11 @@ pub enum RecordError {
     #[error("cancelled by user")]
     Cancelled,
 
+    /// There were no changes to select, so the UI wasn't shown. See
+    /// [`RecordOptions::skip_if_nothing_to_select`].
+    #[error("no changes to select")]
+    NothingToSelect,
+
     #[error("failed to set up terminal: {0}")]
     SetUpTerminal(#[source] io::Error),
 
@@ -64,8 +250,37 @@ pub enum RecordError {
     #[error("failed to serialize JSON: {0}")]
     SerializeJson(#[source] serde_json::Error),
 
-    #[error("failed to wrote file: {0}")]
-    WriteFile(#[source] io::Error),
+    #[cfg(feature = "serde")]
+    #[error("failed to deserialize JSON from {path}: {source}")]
+    DeserializeJson {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[error("failed to read file {path}: {source}")]
+    ReadFile { path: PathBuf, source: io::Error },
+
+    #[error("failed to write file {path}: {source}")]
+    WriteFile { path: PathBuf, source: io::Error },
+
+    #[error("failed to remove file {path}: {source}")]
+    RemoveFile { path: PathBuf, source: io::Error },
+
+    #[error("failed to run editor {editor:?}: {source}")]
+    RunEditor { editor: String, source: io::Error },
+
+    #[error("editor {editor:?} exited with {status}")]
+    EditorFailed { editor: String, status: ExitStatus },
+
+    #[error("failed to copy to the clipboard: {source}")]
+    CopyToClipboard { source: io::Error },
+
+    #[cfg(feature = "image-preview")]
+    #[error("failed to show images: {0}")]
+    ShowImages(#[source] io::Error),
+
+    #[error("invalid input for file {path}: {reason}")]
+    InvalidInput { path: PathBuf, reason: String },
 
     #[error("{0}")]
     Other(String),
@@ -88,6 +303,9 @@ pub enum FileMode {
 impl FileMode {
     /// The default Unix permissions for files.
     pub const FILE_DEFAULT: FileMode = FileMode::Unix(0o100644);
+
+    /// The Unix mode of symbolic links, whose contents are their targets.
+    pub const SYMLINK: FileMode = FileMode::Unix(0o120000);
 }
 
 impl Display for FileMode {
@@ -155,6 +373,55 @@ pub struct Commit {
     pub message: Option<String>,
 }
 
+impl Commit {
+    /// Construct a commit whose message is assembled from the given subject
+    /// and body. See [`Commit::set_subject_and_body`].
+    pub fn from_subject_and_body(subject: &str, body: &str) -> Self {
+        let mut commit = Self::default();
+        commit.set_subject_and_body(subject, body);
+        commit
+    }
+
+    /// The first line of the commit message, with surrounding whitespace
+    /// removed. Returns `None` if the commit has no message.
+    pub fn subject(&self) -> Option<&str> {
+        let Self { message } = self;
+        message.as_deref().map(|message| split_message(message).0)
+    }
+
+    /// The remainder of the commit message after the subject, with leading
+    /// blank lines and trailing whitespace removed. Returns `None` if the
+    /// commit has no message.
+    pub fn body(&self) -> Option<&str> {
+        let Self { message } = self;
+        message.as_deref().map(|message| split_message(message).1)
+    }
+
+    /// Replace the commit message with the given subject and body. The result
+    /// is well-formed: the subject is a single line, and a non-empty body is
+    /// separated from it by exactly one blank line.
+    pub fn set_subject_and_body(&mut self, subject: &str, body: &str) {
+        let Self { message } = self;
+        let subject = subject.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+        let body = body.trim_start_matches(['\r', '\n']).trim_end();
+        *message = Some(if body.is_empty() {
+            subject
+        } else {
+            format!("{subject}\n\n{body}")
+        });
+    }
+}
+
+fn split_message(message: &str) -> (&str, &str) {
+    match message.split_once('\n') {
+        Some((subject, body)) => (
+            subject.trim(),
+            body.trim_start_matches(['\r', '\n']).trim_end(),
+        ),
+        None => (message.trim(), ""),
+    }
+}
+
 /// The state of a file to be recorded.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
@@ -176,6 +443,20 @@ pub struct File<'a> {
 
     /// The set of [`Section`]s inside the file.
     pub sections: Vec<Section<'a>>,
+
+    /// Whether the user deferred deciding on the changes in this file. The
+    /// selected changes are returned as usual; it's up to the caller to present
+    /// the file again, for example in a follow-up session.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub is_deferred: bool,
+
+    /// Whether the contents of the file weren't valid UTF-8, so that its lines
+    /// were decoded with [`crate::helpers::decode_line_lossy`]. The selected
+    /// contents of such a file have replacement characters in place of the
+    /// invalid bytes, so they mustn't be written back to it; the caller should
+    /// keep one of the original versions of the file instead.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub is_lossy: bool,
 }
 
 /// The changes for a particular file selected as part of the record operation.
@@ -235,6 +516,28 @@ impl File<'_> {
     /// example, the first value would be suitable for staging or committing,
     /// and the second value would be suitable for potentially recording again.
     pub fn get_selected_contents(&self) -> (SelectedChanges<'_>, SelectedChanges<'_>) {
+        self.split_contents(|is_checked, _commit_idx| is_checked)
+    }
+
+    /// Calculate the contents of the file in the commit at `commit_idx`, as
+    /// an index into [`RecordState::commits`], once the changes assigned to
+    /// it and to the commits before it are applied. With two commits, the
+    /// contents in the first commit are the selected contents given by
+    /// [`File::get_selected_contents`].
+    pub fn get_commit_contents(&self, commit_idx: usize) -> SelectedChanges<'_> {
+        let (contents, _rest) = self.split_contents(|is_checked, line_commit_idx| {
+            assigned_commit_idx(is_checked, line_commit_idx) <= commit_idx
+        });
+        contents
+    }
+
+    /// Calculate the contents of the file with the changes for which
+    /// `is_selected` returns true, and with the rest of the changes, given
+    /// whether each change is checked and the commit it's assigned to if so.
+    fn split_contents(
+        &self,
+        is_selected: impl Fn(bool, usize) -> bool,
+    ) -> (SelectedChanges<'_>, SelectedChanges<'_>) {
         let mut acc_selected = SelectedContents::Unchanged;
         let mut acc_unselected = SelectedContents::Unchanged;
 
@@ -243,25 +546,31 @@ impl File<'_> {
             path: _,
             file_mode,
             sections,
+            is_deferred: _,
+            is_lossy: _,
         } = self;
 
         let file_mode_section = sections.iter().find_map(|section| match section {
             Section::Unchanged { .. } | Section::Changed { .. } | Section::Binary { .. } => None,
 
-            Section::FileMode { is_checked, mode } => Some((mode, is_checked)),
+            Section::FileMode {
+                is_checked,
+                commit_idx,
+                mode,
+            } => Some((mode, is_selected(*is_checked, *commit_idx))),
         });
 
         // The file mode for the selected changes is the selected file mode, if one was selected,
         // or the original mode of the file, if not.
         let selected_file_mode = file_mode_section
-            .filter(|(_, is_checked)| **is_checked)
+            .filter(|(_, is_checked)| *is_checked)
             .map(|(change, _)| *change)
             .unwrap_or(*file_mode);
 
         // The file mode for the unselected changes is the unselected file mode, if one was provided,
         // or the original mode of the file, if not
         let unselected_file_mode = file_mode_section
-            .filter(|(_, is_checked)| !**is_checked)
+            .filter(|(_, is_checked)| !*is_checked)
             .map(|(change, _)| *change)
             .unwrap_or(*file_mode);
 
@@ -274,14 +583,16 @@ impl File<'_> {
                     }
                 }
 
-                Section::Changed { lines } => {
+                Section::Changed { lines, .. } => {
                     for line in lines {
                         let SectionChangedLine {
                             is_checked,
+                            commit_idx,
                             change_type,
                             line,
+                            annotation: _,
                         } = line;
-                        match (change_type, is_checked) {
+                        match (change_type, is_selected(*is_checked, *commit_idx)) {
                             (ChangeType::Added, true) | (ChangeType::Removed, false) => {
                                 acc_selected.push_str(line);
                             }
@@ -306,14 +617,17 @@ impl File<'_> {
 
                 Section::Binary {
                     is_checked,
+                    commit_idx,
                     old_description,
                     new_description,
+                    old_metadata: _,
+                    new_metadata: _,
                 } => {
                     let selected_contents = SelectedContents::Binary {
                         old_description: old_description.clone(),
                         new_description: new_description.clone(),
                     };
-                    if *is_checked {
+                    if is_selected(*is_checked, *commit_idx) {
                         acc_selected = selected_contents;
                         acc_unselected = SelectedContents::Unchanged;
                     } else {
@@ -355,48 +669,36 @@ impl File<'_> {
     /// Get the tristate value of the file. If there are no sections in this
     /// file, returns `Tristate::False`.
     pub fn tristate(&self) -> Tristate {
+        self.tristate_by(|section| section.tristate())
+    }
+
+    /// Get whether the changes of the file are assigned to the commit at
+    /// `commit_idx`, as a tristate. If there are no sections in this file,
+    /// returns `Tristate::False`.
+    pub fn commit_tristate(&self, commit_idx: usize) -> Tristate {
+        self.tristate_by(|section| section.commit_tristate(commit_idx))
+    }
+
+    fn tristate_by(&self, section_tristate: impl Fn(&Section) -> Tristate) -> Tristate {
         let Self {
             old_path: _,
             path: _,
             file_mode: _,
             sections,
+            is_deferred: _,
+            is_lossy: _,
         } = self;
         let mut seen_value = None;
-        for section in sections {
-            match section {
-                Section::Unchanged { .. } => {}
-                Section::Changed { lines } => {
-                    for line in lines {
-                        seen_value = match (seen_value, line.is_checked) {
-                            (None, is_checked) => Some(is_checked),
-                            (Some(true), true) => Some(true),
-                            (Some(false), false) => Some(false),
-                            (Some(true), false) | (Some(false), true) => return Tristate::Partial,
-                        };
-                    }
-                }
-                Section::FileMode {
-                    is_checked,
-                    mode: _,
-                }
-                | Section::Binary {
-                    is_checked,
-                    old_description: _,
-                    new_description: _,
-                } => {
-                    seen_value = match (seen_value, is_checked) {
-                        (None, is_checked) => Some(*is_checked),
-                        (Some(true), true) => Some(true),
-                        (Some(false), false) => Some(false),
-                        (Some(true), false) | (Some(false), true) => return Tristate::Partial,
-                    }
-                }
-            }
-        }
-        match seen_value {
-            Some(true) => Tristate::True,
-            None | Some(false) => Tristate::False,
+        for section in sections.iter().filter(|section| section.is_editable()) {
+            seen_value = match (seen_value, section_tristate(section)) {
+                (_, Tristate::Partial) => return Tristate::Partial,
+                (None, tristate) => Some(tristate),
+                (Some(Tristate::True), Tristate::True) => Some(Tristate::True),
+                (Some(Tristate::False), Tristate::False) => Some(Tristate::False),
+                (Some(_), _) => return Tristate::Partial,
+            };
         }
+        seen_value.unwrap_or(Tristate::False)
     }
 
     /// Set the selection of all sections and lines in this file.
@@ -406,24 +708,125 @@ impl File<'_> {
             path: _,
             file_mode: _,
             sections,
+            is_deferred: _,
+            is_lossy: _,
         } = self;
         for section in sections {
             section.set_checked(checked);
         }
     }
 
-    /// Toggle the selection of all sections in this file.
-    pub fn toggle_all(&mut self) {
+    /// Assign all sections and lines in this file to the commit at
+    /// `commit_idx`, out of `num_commits` commits.
+    pub fn assign_to_commit(&mut self, commit_idx: usize, num_commits: usize) {
+        for section in &mut self.sections {
+            section.assign_to_commit(commit_idx, num_commits);
+        }
+    }
+
+    /// Toggle all sections of this file in the commit at `commit_idx`, out of
+    /// `num_commits` commits: changes assigned to that commit are moved out of
+    /// it, and the others are moved into it. See [`Section::toggle_all`].
+    pub fn toggle_all(&mut self, commit_idx: usize, num_commits: usize) {
         let Self {
             old_path: _,
             path: _,
             file_mode: _,
             sections,
+            is_deferred: _,
+            is_lossy: _,
         } = self;
         for section in sections {
-            section.toggle_all();
+            section.toggle_all(commit_idx, num_commits);
         }
     }
+
+    /// Check that each line contains at most one line break, at its end, and
+    /// that only the last line of the old or new contents of the file is
+    /// missing one. Otherwise, the lines would be numbered and drawn
+    /// incorrectly, and the selected contents of the file would be corrupted.
+    pub fn validate(&self) -> Result<(), RecordError> {
+        let Self {
+            old_path: _,
+            path,
+            file_mode: _,
+            sections,
+            is_deferred: _,
+            is_lossy: _,
+        } = self;
+        let invalid_input = |reason: String| RecordError::InvalidInput {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        // The section and line numbers of the line without a trailing line
+        // break in the old and new contents, if any.
+        let mut old_unterminated_line: Option<(usize, usize)> = None;
+        let mut new_unterminated_line: Option<(usize, usize)> = None;
+        for (section_idx, section) in sections.iter().enumerate() {
+            // The lines, and whether they're part of the old and new contents.
+            let lines: Vec<(&str, bool, bool)> = match section {
+                Section::Unchanged { lines } => lines
+                    .iter()
+                    .map(|line| (line.as_ref(), true, true))
+                    .collect(),
+                Section::Changed { lines, .. } => lines
+                    .iter()
+                    .map(|line| {
+                        let SectionChangedLine {
+                            is_checked: _,
+                            commit_idx: _,
+                            change_type,
+                            line,
+                            annotation: _,
+                        } = line;
+                        match change_type {
+                            ChangeType::Removed => (line.as_ref(), true, false),
+                            ChangeType::Added => (line.as_ref(), false, true),
+                        }
+                    })
+                    .collect(),
+                Section::FileMode { .. } | Section::Binary { .. } => Vec::new(),
+            };
+
+            for (line_idx, (line, is_old, is_new)) in lines.into_iter().enumerate() {
+                let (section_num, line_num) = (section_idx + 1, line_idx + 1);
+                for (is_in_contents, unterminated_line, contents) in [
+                    (is_old, old_unterminated_line, "old"),
+                    (is_new, new_unterminated_line, "new"),
+                ] {
+                    if !is_in_contents {
+                        continue;
+                    }
+                    if let Some((section_num, line_num)) = unterminated_line {
+                        return Err(invalid_input(format!(
+                            "line {line_num} of section {section_num} has no trailing line \
+                            break, but isn't the last line of the {contents} contents"
+                        )));
+                    }
+                }
+
+                match line.find('\n') {
+                    Some(idx) if idx + 1 < line.len() => {
+                        return Err(invalid_input(format!(
+                            "line {line_num} of section {section_num} contains a line break \
+                            before its end"
+                        )));
+                    }
+                    Some(_) => {}
+                    None => {
+                        if is_old {
+                            old_unterminated_line = Some((section_num, line_num));
+                        }
+                        if is_new {
+                            new_unterminated_line = Some((section_num, line_num));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 /// A section of a file to be rendered and recorded.
@@ -447,6 +850,17 @@ pub enum Section<'a> {
         /// The contents of the lines, including their trailing newline
         /// character(s), if any.
         lines: Vec<SectionChangedLine<'a>>,
+
+        /// A human-readable description of the changes. If set, this is shown
+        /// in the section header instead of the section number.
+        #[cfg_attr(feature = "serde", serde(default))]
+        label: Option<Cow<'a, str>>,
+
+        /// The line giving context for where the changes are, typically the
+        /// declaration of the enclosing function, as in the `@@` hunk headers
+        /// of `git diff`. This is shown dimmed in the section header.
+        #[cfg_attr(feature = "serde", serde(default))]
+        context: Option<Cow<'a, str>>,
     },
 
     /// This indicates that the Unix file mode of the file changed, and that the
@@ -457,6 +871,11 @@ pub enum Section<'a> {
         /// the UI.
         is_checked: bool,
 
+        /// The commit which the change is assigned to if it's checked, as
+        /// for [`SectionChangedLine::commit_idx`].
+        #[cfg_attr(feature = "serde", serde(default))]
+        commit_idx: usize,
+
         /// The mode of the file after these changes.
         mode: FileMode,
     },
@@ -467,14 +886,48 @@ pub enum Section<'a> {
         /// in the UI.
         is_checked: bool,
 
+        /// The commit which the change is assigned to if it's checked, as
+        /// for [`SectionChangedLine::commit_idx`].
+        #[cfg_attr(feature = "serde", serde(default))]
+        commit_idx: usize,
+
         /// The description of the old binary contents, for use in the UI only.
         old_description: Option<Cow<'a, str>>,
 
         /// The description of the new binary contents, for use in the UI only.
         new_description: Option<Cow<'a, str>>,
+
+        /// Information about the old binary contents. If set for either
+        /// version, it's shown as a table instead of the descriptions.
+        #[cfg_attr(feature = "serde", serde(default))]
+        old_metadata: Option<BinaryMetadata<'a>>,
+
+        /// Information about the new binary contents. If set for either
+        /// version, it's shown as a table instead of the descriptions.
+        #[cfg_attr(feature = "serde", serde(default))]
+        new_metadata: Option<BinaryMetadata<'a>>,
     },
 }
 
+/// Information about one version of a binary file, for use in the UI only.
+/// Any of the fields can be left unset if it isn't known or is too expensive to
+/// compute.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct BinaryMetadata<'a> {
+    /// The size of the contents, in bytes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub num_bytes: Option<u64>,
+
+    /// The media type of the contents, such as `image/png`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mime_type: Option<Cow<'a, str>>,
+
+    /// A hash of the contents, to tell apart versions of the same size.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hash: Option<Cow<'a, str>>,
+}
+
 impl Section<'_> {
     /// Whether or not this section contains user-editable content (as opposed
     /// to simply contextual content).
@@ -488,35 +941,49 @@ impl Section<'_> {
     /// Get the tristate value of this section. If there are no items in this
     /// section, returns `Tristate::False`.
     pub fn tristate(&self) -> Tristate {
+        self.tristate_by(|is_checked, _commit_idx| is_checked)
+    }
+
+    /// Get whether the items of this section are assigned to the commit at
+    /// `commit_idx`, as a tristate. If there are no items in this section,
+    /// returns `Tristate::False`.
+    pub fn commit_tristate(&self, commit_idx: usize) -> Tristate {
+        self.tristate_by(|is_checked, item_commit_idx| {
+            assigned_commit_idx(is_checked, item_commit_idx) == commit_idx
+        })
+    }
+
+    /// Get the tristate value of whether `is_on` holds for the items of this
+    /// section, given whether each item is checked and its commit index.
+    fn tristate_by(&self, is_on: impl Fn(bool, usize) -> bool) -> Tristate {
         let mut seen_value = None;
-        match self {
-            Section::Unchanged { .. } => {}
-            Section::Changed { lines } => {
-                for line in lines {
-                    seen_value = match (seen_value, line.is_checked) {
-                        (None, is_checked) => Some(is_checked),
-                        (Some(true), true) => Some(true),
-                        (Some(false), false) => Some(false),
-                        (Some(true), false) | (Some(false), true) => return Tristate::Partial,
-                    };
-                }
-            }
+        let items: Vec<(bool, usize)> = match self {
+            Section::Unchanged { .. } => Vec::new(),
+            Section::Changed { lines, .. } => lines
+                .iter()
+                .map(|line| (line.is_checked, line.commit_idx))
+                .collect(),
             Section::FileMode {
                 is_checked,
+                commit_idx,
                 mode: _,
             }
             | Section::Binary {
                 is_checked,
+                commit_idx,
                 old_description: _,
                 new_description: _,
-            } => {
-                seen_value = match (seen_value, is_checked) {
-                    (None, is_checked) => Some(*is_checked),
-                    (Some(true), true) => Some(true),
-                    (Some(false), false) => Some(false),
-                    (Some(true), false) | (Some(false), true) => return Tristate::Partial,
-                }
-            }
+                old_metadata: _,
+                new_metadata: _,
+            } => vec![(*is_checked, *commit_idx)],
+        };
+        for (is_checked, commit_idx) in items {
+            seen_value = match (seen_value, is_on(is_checked, commit_idx)) {
+                (None, value) => Some(value),
+                (Some(true), true) => Some(true),
+                (Some(false), false) => Some(false),
+                (Some(true), false) | (Some(false), true) => return Tristate::Partial,
+            };
         }
         match seen_value {
             Some(true) => Tristate::True,
@@ -528,15 +995,12 @@ impl Section<'_> {
     pub fn set_checked(&mut self, checked: bool) {
         match self {
             Section::Unchanged { .. } => {}
-            Section::Changed { lines } => {
+            Section::Changed { lines, .. } => {
                 for line in lines {
                     line.is_checked = checked;
                 }
             }
-            Section::FileMode {
-                is_checked,
-                mode: _,
-            } => {
+            Section::FileMode { is_checked, .. } => {
                 *is_checked = checked;
             }
             Section::Binary { is_checked, .. } => {
@@ -545,20 +1009,60 @@ impl Section<'_> {
         }
     }
 
-    /// Toggle the selection of this section.
-    pub fn toggle_all(&mut self) {
+    /// Assign all items in this section to the commit at `commit_idx`, out of
+    /// `num_commits` commits.
+    pub fn assign_to_commit(&mut self, commit_idx: usize, num_commits: usize) {
         match self {
             Section::Unchanged { .. } => {}
-            Section::Changed { lines } => {
+            Section::Changed { lines, .. } => {
                 for line in lines {
-                    line.is_checked = !line.is_checked;
+                    line.assign_to_commit(commit_idx, num_commits);
                 }
             }
-            Section::FileMode { is_checked, .. } => {
-                *is_checked = !*is_checked;
+            Section::FileMode {
+                is_checked,
+                commit_idx: assigned_commit_idx,
+                ..
             }
-            Section::Binary { is_checked, .. } => {
-                *is_checked = !*is_checked;
+            | Section::Binary {
+                is_checked,
+                commit_idx: assigned_commit_idx,
+                ..
+            } => {
+                (*is_checked, *assigned_commit_idx) = commit_assignment(commit_idx, num_commits);
+            }
+        }
+    }
+
+    /// Toggle each item of this section in the commit at `commit_idx`, out of
+    /// `num_commits` commits: items assigned to that commit are moved into the
+    /// next commit if there is one, or otherwise into the previous one, and
+    /// the others are moved into it. With two commits, this inverts the
+    /// selection of each item.
+    pub fn toggle_all(&mut self, commit_idx: usize, num_commits: usize) {
+        let toggle = |is_checked: &mut bool, item_commit_idx: &mut usize| {
+            let is_in_commit = assigned_commit_idx(*is_checked, *item_commit_idx) == commit_idx;
+            let new_commit_idx = toggled_commit_idx(commit_idx, is_in_commit, num_commits);
+            (*is_checked, *item_commit_idx) = commit_assignment(new_commit_idx, num_commits);
+        };
+        match self {
+            Section::Unchanged { .. } => {}
+            Section::Changed { lines, .. } => {
+                for line in lines {
+                    toggle(&mut line.is_checked, &mut line.commit_idx);
+                }
+            }
+            Section::FileMode {
+                is_checked,
+                commit_idx,
+                ..
+            }
+            | Section::Binary {
+                is_checked,
+                commit_idx,
+                ..
+            } => {
+                toggle(is_checked, commit_idx);
             }
         }
     }
@@ -582,10 +1086,338 @@ pub struct SectionChangedLine<'a> {
     /// Whether or not this line was selected to be recorded.
     pub is_checked: bool,
 
+    /// The commit which the line is assigned to if it's checked, as an index
+    /// into [`RecordState::commits`]. Unchecked lines are assigned to the
+    /// commit after it. This only needs to be set when splitting changes
+    /// into more than two commits, and is the first commit by default.
+    ///
+    /// For example, with three commits, a line in the second commit is
+    /// checked with a `commit_idx` of 1, and a line in the third commit is
+    /// unchecked with a `commit_idx` of 1. Use
+    /// [`SectionChangedLine::assigned_commit_idx`] and
+    /// [`SectionChangedLine::assign_to_commit`] rather than decoding and
+    /// encoding the commit by hand.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub commit_idx: usize,
+
     /// The type of change this line was.
     pub change_type: ChangeType,
 
     /// The contents of the line, including its trailing newline character(s),
     /// if any.
     pub line: Cow<'a, str>,
+
+    /// Where the line came from, for display purposes. This is shown in a
+    /// gutter which the user can toggle.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub annotation: Option<LineAnnotation<'a>>,
+}
+
+impl SectionChangedLine<'_> {
+    /// The index of the commit which the line is assigned to.
+    pub fn assigned_commit_idx(&self) -> usize {
+        assigned_commit_idx(self.is_checked, self.commit_idx)
+    }
+
+    /// Assign the line to the commit at `commit_idx`, out of `num_commits`
+    /// commits.
+    pub fn assign_to_commit(&mut self, commit_idx: usize, num_commits: usize) {
+        (self.is_checked, self.commit_idx) = commit_assignment(commit_idx, num_commits);
+    }
+}
+
+/// The index of the commit which a change is assigned to, given the values of
+/// its `is_checked` and `commit_idx` fields: checked changes are in the commit
+/// at `commit_idx`, and unchecked ones are in the commit after it. This is the
+/// inverse of [`commit_assignment`].
+fn assigned_commit_idx(is_checked: bool, commit_idx: usize) -> usize {
+    commit_idx + usize::from(!is_checked)
+}
+
+/// The commit to move a change to when it's toggled in the commit at
+/// `commit_idx`, out of `num_commits` commits, given whether it's assigned to
+/// that commit: changes outside of it are moved into it, and changes in it are
+/// moved into the next commit if there is one, or otherwise into the previous
+/// one.
+pub(crate) fn toggled_commit_idx(
+    commit_idx: usize,
+    is_in_commit: bool,
+    num_commits: usize,
+) -> usize {
+    if !is_in_commit {
+        commit_idx
+    } else if commit_idx + 1 < num_commits {
+        commit_idx + 1
+    } else {
+        commit_idx.saturating_sub(1)
+    }
+}
+
+/// The values of `is_checked` and `commit_idx` which assign a change to the
+/// commit at `commit_idx`. Changes are checked unless they're assigned to the
+/// last commit, so that with two commits, the changes in the first commit are
+/// the checked ones.
+fn commit_assignment(commit_idx: usize, num_commits: usize) -> (bool, usize) {
+    if commit_idx + 1 < num_commits {
+        (true, commit_idx)
+    } else {
+        (false, commit_idx.saturating_sub(1))
+    }
+}
+
+/// Metadata about the origin of a changed line, such as from `git blame`. When
+/// splitting a commit, this helps to tell which lines came from which original
+/// change.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct LineAnnotation<'a> {
+    /// The author of the change which introduced the line.
+    pub author: Cow<'a, str>,
+
+    /// How long ago the line was changed, already formatted for display (e.g.
+    /// `3 days`).
+    pub age: Cow<'a, str>,
+}
+
+// Applications build the state on worker threads and move it to the thread
+// running the UI, so the data model must stay `Send` and `Sync`.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<RecordState<'static>>();
+    assert_send_sync::<RecordOptions>();
+    assert_send_sync::<RecordError>();
+    assert_send_sync::<DirtyState>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_subject_and_body() {
+        let commit = Commit {
+            message: Some("  Subject  \n\n\nFirst line\n\nSecond line\n\n".to_owned()),
+        };
+        assert_eq!(commit.subject(), Some("Subject"));
+        assert_eq!(commit.body(), Some("First line\n\nSecond line"));
+
+        let commit = Commit {
+            message: Some("Subject only".to_owned()),
+        };
+        assert_eq!(commit.subject(), Some("Subject only"));
+        assert_eq!(commit.body(), Some(""));
+
+        assert_eq!(Commit::default().subject(), None);
+        assert_eq!(Commit::default().body(), None);
+    }
+
+    #[test]
+    fn test_commit_set_subject_and_body() {
+        let commit = Commit::from_subject_and_body(" Split\n subject ", "\n\nBody\n\n");
+        assert_eq!(commit.message.as_deref(), Some("Split subject\n\nBody"));
+
+        let commit = Commit::from_subject_and_body("Subject", "\n  \n");
+        assert_eq!(commit.message.as_deref(), Some("Subject"));
+    }
+
+    fn changed_line(change_type: ChangeType, line: &str) -> SectionChangedLine<'_> {
+        SectionChangedLine {
+            is_checked: false,
+            commit_idx: 0,
+            change_type,
+            line: Cow::Borrowed(line),
+            annotation: None,
+        }
+    }
+
+    fn file<'a>(sections: Vec<Section<'a>>) -> File<'a> {
+        File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new("foo")),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections,
+            is_deferred: false,
+            is_lossy: false,
+        }
+    }
+
+    fn validation_error(file: &File) -> Option<String> {
+        file.validate().err().map(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_file_validate() {
+        // Only the last line of the old and of the new contents may be
+        // missing a line break, and they can differ.
+        let valid = file(vec![
+            Section::Unchanged {
+                lines: vec![Cow::Borrowed("a\n")],
+            },
+            Section::Changed {
+                label: None,
+                context: None,
+                lines: vec![
+                    changed_line(ChangeType::Removed, "b"),
+                    changed_line(ChangeType::Added, "b\n"),
+                    changed_line(ChangeType::Added, "c"),
+                ],
+            },
+        ]);
+        assert_eq!(validation_error(&valid), None);
+
+        let unterminated_before_end = file(vec![
+            Section::Unchanged {
+                lines: vec![Cow::Borrowed("a")],
+            },
+            Section::Changed {
+                label: None,
+                context: None,
+                lines: vec![changed_line(ChangeType::Added, "b\n")],
+            },
+        ]);
+        assert_eq!(
+            validation_error(&unterminated_before_end).as_deref(),
+            Some(
+                "invalid input for file foo: line 1 of section 1 has no trailing line break, \
+                but isn't the last line of the new contents"
+            )
+        );
+
+        let line_break_before_end = file(vec![Section::Changed {
+            label: None,
+            context: None,
+            lines: vec![
+                changed_line(ChangeType::Removed, "a\n"),
+                changed_line(ChangeType::Removed, "b\nc\n"),
+            ],
+        }]);
+        assert_eq!(
+            validation_error(&line_break_before_end).as_deref(),
+            Some(
+                "invalid input for file foo: line 2 of section 1 contains a line break \
+                before its end"
+            )
+        );
+    }
+
+    #[test]
+    fn test_assign_to_commit() {
+        let mut line = changed_line(ChangeType::Added, "a\n");
+        for num_commits in 2..=4 {
+            for commit_idx in 0..num_commits {
+                line.assign_to_commit(commit_idx, num_commits);
+                assert_eq!(line.assigned_commit_idx(), commit_idx);
+                // With two commits, the changes of the first one are checked.
+                assert_eq!(line.is_checked, commit_idx + 1 < num_commits);
+            }
+        }
+    }
+
+    /// A file with changes assigned to each of three commits.
+    fn three_commit_file() -> File<'static> {
+        let line = |change_type, line, commit_idx| {
+            let mut line = changed_line(change_type, line);
+            line.assign_to_commit(commit_idx, 3);
+            line
+        };
+        file(vec![
+            Section::Changed {
+                label: None,
+                context: None,
+                lines: vec![
+                    line(ChangeType::Removed, "a\n", 0),
+                    line(ChangeType::Added, "b\n", 1),
+                ],
+            },
+            Section::Unchanged {
+                lines: vec![Cow::Borrowed("c\n")],
+            },
+            Section::Changed {
+                label: None,
+                context: None,
+                lines: vec![line(ChangeType::Added, "d\n", 2)],
+            },
+        ])
+    }
+
+    #[test]
+    fn test_get_commit_contents_with_three_commits() {
+        let file = three_commit_file();
+        let contents: Vec<SelectedContents> = (0..3)
+            .map(|commit_idx| file.get_commit_contents(commit_idx).contents)
+            .collect();
+        let text = |contents: &str| SelectedContents::Text {
+            contents: contents.to_owned(),
+        };
+        assert_eq!(contents, [text("c\n"), text("b\nc\n"), text("b\nc\nd\n")]);
+
+        // The last commit has all of the changes, whether or not they're
+        // checked.
+        let (selected, unselected) = file.get_selected_contents();
+        assert_eq!(selected.contents, text("b\nc\n"));
+        assert_eq!(unselected.contents, text("a\nc\nd\n"));
+    }
+
+    #[test]
+    fn test_commit_tristate_with_three_commits() {
+        let file = three_commit_file();
+        let section_tristates: Vec<Vec<Tristate>> = file
+            .sections
+            .iter()
+            .map(|section| {
+                (0..3)
+                    .map(|commit_idx| section.commit_tristate(commit_idx))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            section_tristates,
+            [
+                [Tristate::Partial, Tristate::Partial, Tristate::False],
+                [Tristate::False, Tristate::False, Tristate::False],
+                [Tristate::False, Tristate::False, Tristate::True],
+            ]
+        );
+        assert_eq!(
+            (0..4)
+                .map(|commit_idx| file.commit_tristate(commit_idx))
+                .collect::<Vec<_>>(),
+            [
+                Tristate::Partial,
+                Tristate::Partial,
+                Tristate::Partial,
+                Tristate::False
+            ]
+        );
+
+        let mut file = file;
+        file.assign_to_commit(1, 3);
+        assert_eq!(
+            (0..3)
+                .map(|commit_idx| file.commit_tristate(commit_idx))
+                .collect::<Vec<_>>(),
+            [Tristate::False, Tristate::True, Tristate::False]
+        );
+    }
+
+    #[test]
+    fn test_dirty_state_is_dirty() {
+        assert!(!DirtyState::default().is_dirty());
+        for dirty in [
+            DirtyState {
+                commit_messages: true,
+                ..Default::default()
+            },
+            DirtyState {
+                selections: true,
+                ..Default::default()
+            },
+            DirtyState {
+                deferred_files: true,
+                ..Default::default()
+            },
+        ] {
+            assert!(dirty.is_dirty(), "{dirty:?}");
+        }
+    }
 }
@@ -0,0 +1,17 @@
+/// Receives human-readable descriptions of what changed in the UI, such as
+/// `selected src/lib.rs section 3 of 5` or `toggled on`, so that the host can
+/// forward them to a screen reader or a log. Set it with
+/// [`crate::Recorder::with_announcer`].
+///
+/// Announcements are made after each event is handled, before the next
+/// frame is drawn.
+pub trait Announcer {
+    /// Announce a change to the user.
+    fn announce(&mut self, announcement: &str);
+}
+
+impl<F: FnMut(&str)> Announcer for F {
+    fn announce(&mut self, announcement: &str) {
+        self(announcement)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::render::{Component, Rect, Viewport};
+use crate::ui::components::ComponentId;
+use crate::util::UsizeExt;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use std::fmt::Debug;
+
+/// Lists the most recent actions which changed the user's choices, so that
+/// they can check what they did during a long session. Drawn over the bottom
+/// rows of the screen while it is open.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ActionHistoryPanel {
+    /// The description of each action, and whether it was undone, most
+    /// recent first.
+    pub actions: Vec<(String, bool)>,
+}
+
+impl ActionHistoryPanel {
+    /// The most rows which the panel takes up, including its title, so that
+    /// most of the screen stays available for the changes.
+    pub fn max_height(term_height: usize) -> usize {
+        (term_height / 3).max(2)
+    }
+
+    /// The number of rows which the panel takes up on a terminal of the
+    /// given height.
+    pub fn height(&self, term_height: usize) -> usize {
+        let Self { actions } = self;
+        (actions.len().max(1) + 1).min(Self::max_height(term_height))
+    }
+}
+
+impl Component for ActionHistoryPanel {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::ActionHistoryPanel
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _x: isize, _y: isize) {
+        let Self { actions } = self;
+        let viewport_rect = viewport.rect();
+        let num_rows = self.height(viewport_rect.height);
+        let rect = Rect {
+            y: viewport_rect.end_y() - num_rows.unwrap_isize(),
+            height: num_rows,
+            ..viewport_rect
+        };
+        viewport.draw_blank(rect);
+        viewport.set_style(rect, viewport.theme().bar);
+
+        let title = if actions.is_empty() {
+            "Recent actions (L to close)".to_string()
+        } else {
+            format!(
+                "Recent actions (showing {} of {}, L to close)",
+                actions.len().min(num_rows - 1),
+                actions.len()
+            )
+        };
+        viewport.draw_span(
+            rect.x,
+            rect.y,
+            &Span::styled(title, viewport.theme().accent.add_modifier(Modifier::BOLD)),
+        );
+        if actions.is_empty() {
+            viewport.draw_span(
+                rect.x,
+                rect.y + 1,
+                &Span::styled(
+                    "  Nothing has been changed yet",
+                    Style::default().add_modifier(Modifier::DIM),
+                ),
+            );
+        }
+        for ((description, is_undone), y) in actions.iter().zip(rect.iter_ys().skip(1)) {
+            let line = if *is_undone {
+                Line::from(vec![
+                    Span::styled(
+                        format!("  {description}"),
+                        Style::default().add_modifier(Modifier::CROSSED_OUT | Modifier::DIM),
+                    ),
+                    Span::styled(" (undone)", Style::default().add_modifier(Modifier::DIM)),
+                ])
+            } else {
+                Line::from(format!("  {description}"))
+            };
+            viewport.draw_text(rect.x, y, line);
+        }
+    }
+}
@@ -1,29 +1,32 @@
 use crate::render::{Component, DrawnRect, Mask, Viewport};
+use crate::ui::components::action_history_panel::ActionHistoryPanel;
 use crate::ui::components::commit_message_view::CommitViewMode;
 use crate::ui::components::commit_view::CommitView;
-use crate::ui::components::file::FileKey;
+use crate::ui::components::empty_selection_dialog::EmptySelectionDialog;
+use crate::ui::components::error_dialog::ErrorDialog;
+use crate::ui::components::file_contents_dialog::FileContentsDialog;
+use crate::ui::components::file_list_pane::FileListPane;
 use crate::ui::components::help_dialog::HelpDialog;
-use crate::ui::components::line::LineKey;
-use crate::ui::components::section::SectionKey;
+use crate::ui::components::line_diff_dialog::LineDiffDialog;
+use crate::ui::components::message_diff_dialog::MessageDiffDialog;
+use crate::ui::components::message_editor_dialog::MessageEditorDialog;
+use crate::ui::components::message_history_dialog::MessageHistoryDialog;
+use crate::ui::components::preview_dialog::PreviewDialog;
+use crate::ui::components::quit_dialog::QuitDialog;
+use crate::ui::components::recovery_dialog::RecoveryDialog;
+use crate::ui::components::stats_bar::StatsBar;
+use crate::ui::components::terminal_too_small::TerminalTooSmall;
+use crate::ui::components::text_prompt::TextPrompt;
+use crate::ui::components::toggle_all_dialog::ToggleAllDialog;
+use crate::ui::components::view_indicators::ViewIndicators;
+use crate::ui::components::which_key_popup::WhichKeyPopup;
 use crate::ui::components::ComponentId;
+use crate::ui::state::{SelectionKey, StatusMessage};
+use crate::ui::theme::Theme;
 use crate::util::UsizeExt;
 use std::collections::BTreeMap;
 use std::fmt::Debug;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
-pub enum SelectionKey {
-    None,
-    File(FileKey),
-    Section(SectionKey),
-    Line(LineKey),
-}
-
-impl Default for SelectionKey {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct AppDebugInfo {
@@ -37,9 +40,34 @@ pub struct AppDebugInfo {
 #[derive(Clone, Debug)]
 pub struct AppView<'a> {
     pub debug_info: Option<AppDebugInfo>,
+    /// The styles to draw every component with.
+    pub theme: Theme,
     pub commit_view_mode: CommitViewMode,
     pub commit_views: Vec<CommitView<'a>>,
+    /// The index in `commit_views` of the commit being edited. If not all of
+    /// them fit side by side, they're scrolled horizontally to show it.
+    pub focused_commit_idx: usize,
+    pub stats_bar: Option<StatsBar>,
+    pub view_indicators: Option<ViewIndicators>,
+    pub text_prompt: Option<TextPrompt>,
+    pub status_message: Option<StatusMessage>,
+    pub which_key_popup: Option<WhichKeyPopup>,
+    pub action_history_panel: Option<ActionHistoryPanel>,
+    /// Only set in the two-pane layout, in which case the commit views are
+    /// drawn to its right.
+    pub file_list_pane: Option<FileListPane>,
     pub help_dialog: Option<HelpDialog>,
+    pub message_history_dialog: Option<MessageHistoryDialog<'a>>,
+    pub message_editor_dialog: Option<MessageEditorDialog>,
+    pub message_diff_dialog: Option<MessageDiffDialog>,
+    pub line_diff_dialog: Option<LineDiffDialog>,
+    pub preview_dialog: Option<PreviewDialog>,
+    pub file_contents_dialog: Option<FileContentsDialog>,
+    pub recovery_dialog: Option<RecoveryDialog>,
+    pub error_dialog: Option<ErrorDialog>,
+    pub toggle_all_dialog: Option<ToggleAllDialog>,
+    pub empty_selection_dialog: Option<EmptySelectionDialog>,
+    pub quit_dialog: Option<QuitDialog>,
 }
 
 impl Component for AppView<'_> {
@@ -52,34 +80,88 @@ impl Component for AppView<'_> {
     fn draw(&self, viewport: &mut Viewport<Self::Id>, _x: isize, _y: isize) {
         let Self {
             debug_info,
+            theme,
             commit_view_mode,
             commit_views,
+            focused_commit_idx,
+            stats_bar,
+            view_indicators,
+            text_prompt,
+            status_message,
+            which_key_popup,
+            action_history_panel,
+            file_list_pane,
             help_dialog,
+            message_history_dialog,
+            message_editor_dialog,
+            message_diff_dialog,
+            line_diff_dialog,
+            preview_dialog,
+            file_contents_dialog,
+            recovery_dialog,
+            error_dialog,
+            toggle_all_dialog,
+            empty_selection_dialog,
+            quit_dialog,
         } = self;
 
         if let Some(debug_info) = debug_info {
             viewport.debug(format!("app debug info: {debug_info:#?}"));
         }
+        viewport.set_theme(*theme);
 
         let viewport_rect = viewport.mask_rect();
+        if TerminalTooSmall::is_too_small(viewport_rect.width, viewport_rect.height) {
+            viewport.draw_component(0, 0, &TerminalTooSmall);
+            return;
+        }
 
+        // The file list pane and the separator after it take up the left of
+        // the screen.
+        let commit_views_x = match file_list_pane {
+            Some(_) => (FileListPane::width(viewport_rect.width) + 1).unwrap_isize(),
+            None => 0,
+        };
+        let commit_views_width =
+            viewport_rect.width - usize::try_from(commit_views_x).unwrap_or_default();
         let commit_view_width = match commit_view_mode {
-            CommitViewMode::Inline => viewport.rect().width,
+            CommitViewMode::Inline => commit_views_width,
             CommitViewMode::Adjacent => {
                 const MAX_COMMIT_VIEW_WIDTH: usize = 120;
-                MAX_COMMIT_VIEW_WIDTH
-                    .min(viewport.rect().width.saturating_sub(CommitView::MARGIN) / 2)
+                MAX_COMMIT_VIEW_WIDTH.min(commit_views_width.saturating_sub(CommitView::MARGIN) / 2)
             }
         };
         let commit_views_mask = Mask {
-            x: viewport_rect.x,
+            x: viewport_rect.x + commit_views_x,
             y: viewport_rect.y,
-            width: Some(viewport_rect.width),
+            width: Some(commit_views_width),
             height: None,
         };
         viewport.with_mask(commit_views_mask, |viewport| {
+            let mut commit_views_y = 0;
+            if let Some(stats_bar) = stats_bar {
+                commit_views_y += viewport
+                    .draw_component(commit_views_x, commit_views_y, stats_bar)
+                    .height
+                    .unwrap_isize();
+            }
+            if let Some(view_indicators) = view_indicators {
+                commit_views_y += viewport
+                    .draw_component(commit_views_x, commit_views_y, view_indicators)
+                    .height
+                    .unwrap_isize();
+            }
+
+            // Skip the commits to the left of the focused one if it wouldn't
+            // fit on the screen otherwise.
+            let num_visible_commit_views = ((commit_views_width + CommitView::MARGIN)
+                / (commit_view_width + CommitView::MARGIN))
+                .max(1);
+            let num_skipped_commit_views =
+                (focused_commit_idx + 1).saturating_sub(num_visible_commit_views);
+
             let mut commit_view_x = 0;
-            for commit_view in commit_views {
+            for commit_view in commit_views.iter().skip(num_skipped_commit_views) {
                 let commit_view_mask = Mask {
                     x: commit_views_mask.x + commit_view_x,
                     y: commit_views_mask.y,
@@ -87,7 +169,11 @@ impl Component for AppView<'_> {
                     height: None,
                 };
                 let commit_view_rect = viewport.with_mask(commit_view_mask, |viewport| {
-                    viewport.draw_component(commit_view_x, 0, commit_view)
+                    viewport.draw_component(
+                        commit_views_x + commit_view_x,
+                        commit_views_y,
+                        commit_view,
+                    )
                 });
                 commit_view_x += (CommitView::MARGIN
                     + commit_view_mask.apply(commit_view_rect).width)
@@ -95,6 +181,54 @@ impl Component for AppView<'_> {
             }
         });
 
+        if let Some(file_list_pane) = file_list_pane {
+            viewport.draw_component(0, 0, file_list_pane);
+        }
+        if let Some(action_history_panel) = action_history_panel {
+            viewport.draw_component(0, 0, action_history_panel);
+        }
+        if let Some(status_message) = status_message {
+            viewport.draw_component(0, 0, status_message);
+        }
+        if let Some(which_key_popup) = which_key_popup {
+            viewport.draw_component(0, 0, which_key_popup);
+        }
+        if let Some(text_prompt) = text_prompt {
+            viewport.draw_component(0, 0, text_prompt);
+        }
+        if let Some(message_history_dialog) = message_history_dialog {
+            viewport.draw_component(0, 0, message_history_dialog);
+        }
+        if let Some(message_editor_dialog) = message_editor_dialog {
+            viewport.draw_component(0, 0, message_editor_dialog);
+        }
+        if let Some(message_diff_dialog) = message_diff_dialog {
+            viewport.draw_component(0, 0, message_diff_dialog);
+        }
+        if let Some(line_diff_dialog) = line_diff_dialog {
+            viewport.draw_component(0, 0, line_diff_dialog);
+        }
+        if let Some(preview_dialog) = preview_dialog {
+            viewport.draw_component(0, 0, preview_dialog);
+        }
+        if let Some(file_contents_dialog) = file_contents_dialog {
+            viewport.draw_component(0, 0, file_contents_dialog);
+        }
+        if let Some(recovery_dialog) = recovery_dialog {
+            viewport.draw_component(0, 0, recovery_dialog);
+        }
+        if let Some(error_dialog) = error_dialog {
+            viewport.draw_component(0, 0, error_dialog);
+        }
+        if let Some(toggle_all_dialog) = toggle_all_dialog {
+            viewport.draw_component(0, 0, toggle_all_dialog);
+        }
+        if let Some(empty_selection_dialog) = empty_selection_dialog {
+            viewport.draw_component(0, 0, empty_selection_dialog);
+        }
+        if let Some(quit_dialog) = quit_dialog {
+            viewport.draw_component(0, 0, quit_dialog);
+        }
         if let Some(help_dialog) = help_dialog {
             viewport.draw_component(0, 0, help_dialog);
         }
@@ -1,11 +1,23 @@
 use crate::render::{Component, Rect, Viewport};
 use crate::types::Commit;
+use crate::ui::components::line::wrap_spans;
 use crate::ui::components::widgets::Button;
 use crate::ui::components::ComponentId;
+use crate::ui::theme::Theme;
+use crate::util::{IsizeExt, UsizeExt};
 use ratatui::style::{Modifier, Style};
-use ratatui::text::Span;
+use ratatui::text::{Line, Span};
 use std::borrow::Cow;
 use std::fmt::Debug;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The conventional maximum width of a commit subject line. Characters past
+/// this column are rendered in a warning color.
+pub const SUBJECT_SOFT_LIMIT: usize = 50;
+
+/// The conventional column at which the body of a commit message is wrapped.
+/// Characters of the subject past this column are rendered in an error color.
+pub const BODY_WRAP_COLUMN: usize = 72;
 
 #[derive(Clone, Copy, Debug)]
 pub enum CommitViewMode {
@@ -17,6 +29,7 @@ pub enum CommitViewMode {
 pub struct CommitMessageView<'a> {
     pub commit_idx: usize,
     pub commit: &'a Commit,
+    pub is_expanded: bool,
 }
 
 impl Component for CommitMessageView<'_> {
@@ -27,12 +40,14 @@ impl Component for CommitMessageView<'_> {
     }
 
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
-        let Self { commit_idx, commit } = self;
-        match commit {
-            Commit { message: None } => {}
-            Commit {
-                message: Some(message),
-            } => {
+        let Self {
+            commit_idx,
+            commit,
+            is_expanded,
+        } = self;
+        match (commit.subject(), commit.body()) {
+            (None, _) | (_, None) => {}
+            (Some(subject), Some(body)) => {
                 viewport.draw_blank(Rect {
                     x,
                     y,
@@ -41,39 +56,28 @@ impl Component for CommitMessageView<'_> {
                 });
                 let y = y + 1;
 
-                let style = Style::default();
-                let button_rect = viewport.draw_component(
-                    x,
-                    y,
-                    &Button {
-                        id: ComponentId::CommitEditMessageButton(*commit_idx),
-                        label: Cow::Borrowed("Edit message"),
-                        style,
-                        is_focused: false,
-                    },
-                );
-                let divider_rect =
-                    viewport.draw_span(button_rect.end_x() + 1, y, &Span::raw(" • "));
-                viewport.draw_text(
-                    divider_rect.end_x() + 1,
-                    y,
-                    Span::styled(
-                        Cow::Borrowed({
-                            let first_line = match message.split_once('\n') {
-                                Some((before, _after)) => before,
-                                None => message,
-                            };
-                            let first_line = first_line.trim();
-                            if first_line.is_empty() {
-                                "(no message)"
-                            } else {
-                                first_line
-                            }
-                        }),
-                        style.add_modifier(Modifier::UNDERLINED),
-                    ),
-                );
-                let y = y + 1;
+                let subject_x =
+                    draw_header_row(viewport, x, y, *commit_idx, subject, body, *is_expanded);
+                let body_lines: Vec<&str> = body.lines().collect();
+                let mut y = y + 1;
+
+                let mut exceeds_limits = subject.width() > SUBJECT_SOFT_LIMIT;
+                if *is_expanded {
+                    let width = (viewport.mask_rect().end_x() - subject_x).clamp_into_usize();
+                    for body_line in body_lines {
+                        exceeds_limits |= body_line.width() > BODY_WRAP_COLUMN;
+                        for row in wrap_body_line(body_line, width) {
+                            viewport.draw_blank(Rect {
+                                x,
+                                y,
+                                width: viewport.mask_rect().width,
+                                height: 1,
+                            });
+                            viewport.draw_text(subject_x, y, Line::from(row));
+                            y += 1;
+                        }
+                    }
+                }
 
                 viewport.draw_blank(Rect {
                     x,
@@ -81,7 +85,217 @@ impl Component for CommitMessageView<'_> {
                     width: viewport.mask_rect().width,
                     height: 1,
                 });
+                if exceeds_limits {
+                    draw_column_guides(viewport, subject_x, y);
+                }
             }
         }
     }
 }
+
+impl CommitMessageView<'_> {
+    /// The row of the "Edit message" button and the subject is drawn this
+    /// many rows below the top of the view.
+    pub const HEADER_ROW_OFFSET: isize = 1;
+
+    /// Whether anything is drawn for the message.
+    pub fn is_visible(&self) -> bool {
+        let Self {
+            commit_idx: _,
+            commit,
+            is_expanded: _,
+        } = self;
+        commit.subject().is_some() && commit.body().is_some()
+    }
+
+    /// Draw only the row of the "Edit message" button and the subject, such
+    /// as to keep it pinned to the top of the screen while scrolling.
+    pub fn draw_header(&self, viewport: &mut Viewport<ComponentId>, x: isize, y: isize) {
+        let Self {
+            commit_idx,
+            commit,
+            is_expanded,
+        } = self;
+        if let (Some(subject), Some(body)) = (commit.subject(), commit.body()) {
+            viewport.draw_blank(Rect {
+                x,
+                y,
+                width: viewport.mask_rect().width,
+                height: 1,
+            });
+            draw_header_row(viewport, x, y, *commit_idx, subject, body, *is_expanded);
+        }
+    }
+}
+
+/// Draw the "Edit message" button followed by the subject, and return the
+/// column at which the subject starts.
+fn draw_header_row(
+    viewport: &mut Viewport<ComponentId>,
+    x: isize,
+    y: isize,
+    commit_idx: usize,
+    subject: &str,
+    body: &str,
+    is_expanded: bool,
+) -> isize {
+    let style = Style::default();
+    let button_rect = viewport.draw_component(
+        x,
+        y,
+        &Button {
+            id: ComponentId::CommitEditMessageButton(commit_idx),
+            label: Cow::Borrowed("Edit message"),
+            style,
+            is_focused: false,
+        },
+    );
+    let divider_rect = viewport.draw_span(button_rect.end_x() + 1, y, &Span::raw(" • "));
+    let subject_x = divider_rect.end_x() + 1;
+    let mut subject_text = if subject.is_empty() {
+        Line::from(Span::styled(
+            "(no message)",
+            style.add_modifier(Modifier::UNDERLINED),
+        ))
+    } else {
+        subject_line(viewport.theme(), subject, style)
+    };
+    let num_body_lines = body.lines().count();
+    if !is_expanded && num_body_lines > 0 {
+        subject_text.push_span(Span::styled(
+            format!(" (+{num_body_lines} lines)"),
+            style.add_modifier(Modifier::DIM),
+        ));
+    }
+    viewport.draw_text(subject_x, y, subject_text);
+    subject_x
+}
+
+/// Wrap a line of the commit body onto rows of at most `width` columns. Rows
+/// after the first are indented to line up with the text after the line's
+/// leading whitespace and list marker, if any, so that wrapped paragraphs and
+/// bullet points stay readable.
+fn wrap_body_line(body_line: &str, width: usize) -> Vec<Vec<Span<'_>>> {
+    let style = Style::default().add_modifier(Modifier::DIM);
+    let indent = hanging_indent(body_line);
+    // Don't indent when that would leave too little room for the text.
+    let indent = if indent * 2 < width { indent } else { 0 };
+    let mut rows = wrap_spans(vec![Span::styled(body_line, style)], width);
+    if rows.len() > 1 {
+        let first_row_len: usize = rows[0].iter().map(|span| span.content.len()).sum();
+        let rest = &body_line[first_row_len..];
+        rows.truncate(1);
+        rows.extend(
+            wrap_spans(vec![Span::styled(rest, style)], width - indent)
+                .into_iter()
+                .map(|mut row| {
+                    row.insert(0, Span::raw(" ".repeat(indent)));
+                    row
+                }),
+        );
+    }
+    rows
+}
+
+/// The number of columns taken up by the leading whitespace of the line and
+/// by the list marker after it, such as `-`, `*` or `1.`, if any.
+fn hanging_indent(body_line: &str) -> usize {
+    let text = body_line.trim_start();
+    let whitespace_len = body_line.len() - text.len();
+    let marker_len = match text.split_once(' ') {
+        Some((marker, _))
+            if matches!(marker, "-" | "*" | "+")
+                || (marker.len() > 1
+                    && marker.ends_with(['.', ')'])
+                    && marker[..marker.len() - 1]
+                        .chars()
+                        .all(|c| c.is_ascii_digit())) =>
+        {
+            marker.len() + 1
+        }
+        Some(_) | None => 0,
+    };
+    whitespace_len + marker_len
+}
+
+/// Split the subject into spans so that the portions past
+/// [`SUBJECT_SOFT_LIMIT`] and [`BODY_WRAP_COLUMN`] are highlighted.
+fn subject_line<'a>(theme: &Theme, subject: &'a str, style: Style) -> Line<'a> {
+    let style = style.add_modifier(Modifier::UNDERLINED);
+    let soft_idx = byte_idx_of_column(subject, SUBJECT_SOFT_LIMIT);
+    let hard_idx = byte_idx_of_column(subject, BODY_WRAP_COLUMN);
+    Line::from(vec![
+        Span::styled(&subject[..soft_idx], style),
+        Span::styled(&subject[soft_idx..hard_idx], style.patch(theme.warning)),
+        Span::styled(&subject[hard_idx..], style.patch(theme.error)),
+    ])
+}
+
+/// The byte offset of the first character of the text which doesn't fit in
+/// `column` columns, or the length of the text if all of it fits. Wide
+/// characters take up two columns.
+fn byte_idx_of_column(text: &str, column: usize) -> usize {
+    let mut width = 0;
+    for (idx, c) in text.char_indices() {
+        width += c.width().unwrap_or(0);
+        if width > column {
+            return idx;
+        }
+    }
+    text.len()
+}
+
+/// Draw markers underneath the subject at the [`SUBJECT_SOFT_LIMIT`] and
+/// [`BODY_WRAP_COLUMN`] columns.
+fn draw_column_guides(viewport: &mut Viewport<ComponentId>, subject_x: isize, y: isize) {
+    let guide_style = Style::default().add_modifier(Modifier::DIM);
+    let theme = *viewport.theme();
+    for (limit, style) in [
+        (SUBJECT_SOFT_LIMIT, theme.warning),
+        (BODY_WRAP_COLUMN, theme.error),
+    ] {
+        let guide = format!("╵{limit}");
+        viewport.draw_span(
+            subject_x + limit.unwrap_isize(),
+            y,
+            &Span::styled(guide, guide_style.patch(style)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_idx_of_column() {
+        assert_eq!(byte_idx_of_column("abcdef", 4), 4);
+        assert_eq!(byte_idx_of_column("abc", 4), 3);
+        assert_eq!(byte_idx_of_column("", 4), 0);
+        // Each of these characters is 3 bytes long and 2 columns wide.
+        assert_eq!(byte_idx_of_column("日本語です", 4), 6);
+        // A wide character which would straddle the column doesn't fit.
+        assert_eq!(byte_idx_of_column("a日本", 2), 1);
+        assert_eq!(byte_idx_of_column("é日", 1), "é".len());
+    }
+
+    #[test]
+    fn test_subject_line_highlights_by_display_width() {
+        let theme = Theme::default();
+        // 30 wide characters take up 60 columns, so the last 5 are past the
+        // soft limit but none are past the wrap column.
+        let subject = "字".repeat(30);
+        let line = subject_line(&theme, &subject, Style::default());
+        let contents: Vec<&str> = line
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect();
+        assert_eq!(contents, ["字".repeat(25), "字".repeat(5), String::new()]);
+
+        let subject = "x".repeat(80);
+        let line = subject_line(&theme, &subject, Style::default());
+        let widths: Vec<usize> = line.spans.iter().map(|span| span.content.width()).collect();
+        assert_eq!(widths, [50, 22, 8]);
+    }
+}
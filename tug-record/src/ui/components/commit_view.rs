@@ -1,6 +1,7 @@
 use crate::render::{centered_rect, Component, Mask, Rect, RectSize, Viewport};
 use crate::ui::components::app::AppDebugInfo;
 use crate::ui::components::commit_message_view::CommitMessageView;
+use crate::ui::components::directory::DirectoryView;
 use crate::ui::components::file::{FileView, FileViewHeader};
 use crate::ui::components::ComponentId;
 use crate::util::UsizeExt;
@@ -10,12 +11,27 @@ use std::fmt::Debug;
 #[derive(Clone, Debug)]
 pub struct CommitView<'a> {
     pub debug_info: Option<&'a AppDebugInfo>,
-    pub commit_message_view: CommitMessageView<'a>,
-    pub file_views: Vec<FileView<'a>>,
+    /// Not shown when a single file is zoomed into.
+    pub commit_message_view: Option<CommitMessageView<'a>>,
+    pub file_list_items: Vec<FileListItem<'a>>,
+    /// What to show instead of the files when there are none.
+    pub empty_message: &'static str,
 }
 
 impl CommitView<'_> {
     pub const MARGIN: usize = 1;
+
+    /// The number of columns by which each level of the file tree is
+    /// indented.
+    pub const TREE_INDENT: usize = 2;
+}
+
+/// An entry in the list of files. Directories only appear when the files are
+/// displayed as a tree.
+#[derive(Clone, Debug)]
+pub enum FileListItem<'a> {
+    Directory(DirectoryView),
+    File(FileView<'a>),
 }
 
 impl Component for CommitView<'_> {
@@ -29,12 +45,16 @@ impl Component for CommitView<'_> {
         let Self {
             debug_info,
             commit_message_view,
-            file_views,
+            file_list_items,
+            empty_message,
         } = self;
 
-        let commit_message_view_rect = viewport.draw_component(x, y, commit_message_view);
-        if file_views.is_empty() {
-            let message = "There are no changes to view.";
+        let commit_message_view_height = match commit_message_view {
+            Some(commit_message_view) => viewport.draw_component(x, y, commit_message_view).height,
+            None => 0,
+        };
+        if file_list_items.is_empty() {
+            let message = *empty_message;
             let message_rect = centered_rect(
                 Rect {
                     x,
@@ -53,9 +73,32 @@ impl Component for CommitView<'_> {
             return;
         }
 
+        // Once the row of the "Edit message" button is scrolled past, it's
+        // pinned to the top, and the sticky file headers go below it.
+        let pinned_commit_message_view =
+            commit_message_view.as_ref().filter(|commit_message_view| {
+                commit_message_view.is_visible()
+                    && y + CommitMessageView::HEADER_ROW_OFFSET < viewport.mask().y
+            });
+        let sticky_header_y = match pinned_commit_message_view {
+            Some(_) => viewport.mask().y + 1,
+            None => viewport.mask().y,
+        };
+
         let mut y = y;
-        y += commit_message_view_rect.height.unwrap_isize();
-        for file_view in file_views {
+        y += commit_message_view_height.unwrap_isize();
+        for file_list_item in file_list_items {
+            let file_view = match file_list_item {
+                FileListItem::Directory(directory_view) => {
+                    let indent = (directory_view.depth * Self::TREE_INDENT).unwrap_isize();
+                    let directory_view_rect =
+                        viewport.draw_component(x + indent, y, directory_view);
+                    y += directory_view_rect.height.unwrap_isize();
+                    continue;
+                }
+                FileListItem::File(file_view) => file_view,
+            };
+            let file_x = x + (file_view.depth * Self::TREE_INDENT).unwrap_isize();
             let file_view_rect = {
                 let file_view_mask = Mask {
                     x,
@@ -64,30 +107,33 @@ impl Component for CommitView<'_> {
                     height: None,
                 };
                 viewport.with_mask(file_view_mask, |viewport| {
-                    viewport.draw_component(x, y, file_view)
+                    viewport.draw_component(file_x, y, file_view)
                 })
             };
 
             // Render a sticky header if necessary.
-            let mask = viewport.mask();
-            if file_view_rect.y < mask.y
-                && mask.y < file_view_rect.y + file_view_rect.height.unwrap_isize()
+            if file_view_rect.y < sticky_header_y
+                && sticky_header_y < file_view_rect.y + file_view_rect.height.unwrap_isize()
             {
                 viewport.with_mask(
                     Mask {
                         x,
-                        y: mask.y,
+                        y: sticky_header_y,
                         width: Some(viewport.mask_rect().width),
                         height: Some(1),
                     },
                     |viewport| {
                         viewport.draw_component(
-                            x,
-                            mask.y,
+                            file_x,
+                            sticky_header_y,
                             &FileViewHeader {
                                 file_key: file_view.file_key,
                                 path: file_view.path,
                                 old_path: file_view.old_path,
+                                stats: file_view.stats,
+                                is_deferred: file_view.is_deferred,
+                                is_bookmarked: file_view.is_bookmarked,
+                                interdiff: file_view.interdiff,
                                 is_selected: file_view.is_header_selected,
                                 toggle_box: file_view.toggle_box.clone(),
                                 expand_box: file_view.expand_box.clone(),
@@ -106,5 +152,18 @@ impl Component for CommitView<'_> {
                 ));
             }
         }
+
+        if let Some(commit_message_view) = pinned_commit_message_view {
+            let mask_y = viewport.mask().y;
+            viewport.with_mask(
+                Mask {
+                    x,
+                    y: mask_y,
+                    width: Some(viewport.mask_rect().width),
+                    height: Some(1),
+                },
+                |viewport| commit_message_view.draw_header(viewport, x, mask_y),
+            );
+        }
     }
 }
@@ -43,11 +43,13 @@ impl<Id: Clone + Debug + Eq + Hash> Component for Dialog<'_, Id> {
                 20,
             );
 
-            let paragraph = Paragraph::new((*body.as_ref()).clone()).block(
-                Block::default()
-                    .title(title.as_ref())
-                    .borders(Borders::all()),
-            );
+            let paragraph = Paragraph::new((*body.as_ref()).clone())
+                .style(viewport.theme().dialog)
+                .block(
+                    Block::default()
+                        .title(title.as_ref())
+                        .borders(Borders::all()),
+                );
             let tui_rect = viewport.translate_rect(rect);
             viewport.draw_widget(tui_rect, Clear);
             viewport.draw_widget(tui_rect, paragraph);
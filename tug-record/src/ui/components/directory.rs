@@ -0,0 +1,68 @@
+use crate::render::{Component, Rect, Viewport};
+use crate::ui::components::file::draw_file_stats;
+use crate::ui::components::widgets::{highlight_rect, TristateBox};
+use crate::ui::components::ComponentId;
+use crate::ui::state::{DirectoryKey, FileStats, SelectionKey};
+use crate::util::UsizeExt;
+use ratatui::text::Span;
+use std::fmt::Debug;
+
+/// The header of a directory in the file tree. The files and directories
+/// inside it are drawn separately, indented below it.
+#[derive(Clone, Debug)]
+pub struct DirectoryView {
+    pub directory_key: DirectoryKey,
+    /// The path of the directory, relative to its parent node in the tree.
+    pub label: String,
+    pub depth: usize,
+    /// The combined statistics of the files inside the directory.
+    pub stats: FileStats,
+    pub is_selected: bool,
+    pub toggle_box: TristateBox<ComponentId>,
+    pub expand_box: TristateBox<ComponentId>,
+}
+
+impl Component for DirectoryView {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::SelectableItem(SelectionKey::Directory(self.directory_key))
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
+        let Self {
+            directory_key: _,
+            label,
+            depth: _,
+            stats,
+            is_selected,
+            toggle_box,
+            expand_box,
+        } = self;
+
+        let mut cursor_x = x;
+        let expand_box_rect = viewport.draw_component(cursor_x, y, expand_box);
+        cursor_x += expand_box_rect.width.unwrap_isize() + 1;
+        let toggle_box_rect = viewport.draw_component(cursor_x, y, toggle_box);
+        cursor_x += toggle_box_rect.width.unwrap_isize() + 1;
+
+        let label_rect = viewport.draw_span(
+            cursor_x,
+            y,
+            &Span::styled(format!("{label}/"), viewport.theme().directory_header),
+        );
+        draw_file_stats(viewport, label_rect.end_x() + 1, y, *stats);
+
+        if *is_selected {
+            highlight_rect(
+                viewport,
+                Rect {
+                    x: viewport.mask_rect().x,
+                    y,
+                    width: viewport.mask_rect().width,
+                    height: 1,
+                },
+            );
+        }
+    }
+}
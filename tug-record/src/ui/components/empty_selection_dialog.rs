@@ -0,0 +1,55 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// Asks for confirmation before accepting when nothing was selected and no
+/// commit message was edited, since that usually means the user forgot to
+/// toggle the changes they wanted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EmptySelectionDialog;
+
+impl Component for EmptySelectionDialog {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::EmptySelectionDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let body = Text::from(vec![
+            Line::from("No changes are selected. Accept anyway?"),
+            Line::from(""),
+            Line::from(Span::styled(
+                "c: accept  q: go back",
+                Style::default().add_modifier(Modifier::DIM),
+            )),
+        ]);
+
+        let buttons = [
+            Button {
+                id: ComponentId::EmptySelectionDialogAcceptButton,
+                label: Cow::Borrowed("Accept"),
+                style: Style::default(),
+                is_focused: false,
+            },
+            Button {
+                id: ComponentId::EmptySelectionDialogCancelButton,
+                label: Cow::Borrowed("Go back"),
+                style: Style::default(),
+                is_focused: true,
+            },
+        ];
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed("Nothing selected"),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// Reports an operation which failed without ending the session, such as
+/// running the external editor.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ErrorDialog {
+    pub title: String,
+    pub message: String,
+}
+
+impl Component for ErrorDialog {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::ErrorDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let Self { title, message } = self;
+
+        let mut lines: Vec<Line> = message.lines().map(Line::from).collect();
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "Press any key to return.",
+            Style::default().add_modifier(Modifier::DIM),
+        )));
+        let body = Text::from(lines);
+
+        let buttons = [Button {
+            id: ComponentId::ErrorDialogCloseButton,
+            label: Cow::Borrowed("Close"),
+            style: Style::default(),
+            is_focused: true,
+        }];
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed(title),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
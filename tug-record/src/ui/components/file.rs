@@ -1,21 +1,16 @@
 use crate::render::{Component, Rect, Viewport};
 use crate::types::Tristate;
-use crate::ui::components::app::SelectionKey;
-use crate::ui::components::widgets::{highlight_rect, TristateBox};
+use crate::ui::components::widgets::{bookmark_span, highlight_rect, TristateBox};
 use crate::ui::components::{section, ComponentId};
+use crate::ui::state::{FileKey, FileStats, SelectionKey};
 use crate::util::UsizeExt;
-use ratatui::style::{Color, Modifier, Style};
+use crate::FileInterdiff;
+use ratatui::style::Modifier;
 use ratatui::text::Span;
 use std::collections::HashSet;
 use std::fmt::Debug;
 use std::path::Path;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
-pub struct FileKey {
-    pub commit_idx: usize,
-    pub file_idx: usize,
-}
-
 #[derive(Clone, Debug)]
 pub struct FileView<'a> {
     pub debug: bool,
@@ -25,6 +20,14 @@ pub struct FileView<'a> {
     pub is_header_selected: bool,
     pub old_path: Option<&'a Path>,
     pub path: &'a Path,
+    pub stats: FileStats,
+    pub is_deferred: bool,
+    /// Whether the user bookmarked this file to come back to it.
+    pub is_bookmarked: bool,
+    /// How the file compares to the previous state, if one was given.
+    pub interdiff: Option<&'a FileInterdiff>,
+    /// How deeply the file is nested in the file tree.
+    pub depth: usize,
     pub section_views: Vec<section::SectionView<'a>>,
 }
 
@@ -52,6 +55,11 @@ impl Component for FileView<'_> {
             expand_box,
             old_path,
             path,
+            stats,
+            is_deferred,
+            is_bookmarked,
+            interdiff,
+            depth: _,
             section_views,
             is_header_selected,
         } = self;
@@ -63,6 +71,10 @@ impl Component for FileView<'_> {
                 file_key: *file_key,
                 path,
                 old_path: *old_path,
+                stats: *stats,
+                is_deferred: *is_deferred,
+                is_bookmarked: *is_bookmarked,
+                interdiff: *interdiff,
                 is_selected: *is_header_selected,
                 toggle_box: toggle_box.clone(),
                 expand_box: expand_box.clone(),
@@ -106,6 +118,10 @@ pub struct FileViewHeader<'a> {
     pub file_key: FileKey,
     pub path: &'a Path,
     pub old_path: Option<&'a Path>,
+    pub stats: FileStats,
+    pub is_deferred: bool,
+    pub is_bookmarked: bool,
+    pub interdiff: Option<&'a FileInterdiff>,
     pub is_selected: bool,
     pub toggle_box: TristateBox<ComponentId>,
     pub expand_box: TristateBox<ComponentId>,
@@ -119,6 +135,10 @@ impl Component for FileViewHeader<'_> {
             file_key,
             path: _,
             old_path: _,
+            stats: _,
+            is_deferred: _,
+            is_bookmarked: _,
+            interdiff: _,
             is_selected: _,
             toggle_box: _,
             expand_box: _,
@@ -132,6 +152,10 @@ impl Component for FileViewHeader<'_> {
             file_key: _,
             path,
             old_path,
+            stats,
+            is_deferred,
+            is_bookmarked,
+            interdiff,
             is_selected,
             toggle_box,
             expand_box,
@@ -146,7 +170,7 @@ impl Component for FileViewHeader<'_> {
         let toggle_box_rect = viewport.draw_component(cursor_x, y, toggle_box);
         cursor_x += toggle_box_rect.width.unwrap_isize() + 1; // Add 1 for spacing
 
-        viewport.draw_text(
+        let path_rect = viewport.draw_text(
             cursor_x,
             y,
             Span::styled(
@@ -158,11 +182,45 @@ impl Component for FileViewHeader<'_> {
                     },
                     path.to_string_lossy(),
                 ),
-                Style::default()
-                    .fg(Color::Magenta)
-                    .add_modifier(Modifier::BOLD),
+                viewport.theme().file_header,
             ),
         );
+        let mut cursor_x = path_rect.end_x() + 1;
+        if *is_deferred {
+            let deferred_rect = viewport.draw_span(
+                cursor_x,
+                y,
+                &Span::styled(
+                    "(deferred)",
+                    viewport.theme().warning.add_modifier(Modifier::ITALIC),
+                ),
+            );
+            cursor_x = deferred_rect.end_x() + 1;
+        }
+        if *is_bookmarked {
+            let bookmark_rect = viewport.draw_span(cursor_x, y, &bookmark_span(viewport.theme()));
+            cursor_x = bookmark_rect.end_x() + 1;
+        }
+        if let Some(interdiff) = interdiff {
+            let interdiff_style = viewport.theme().warning.add_modifier(Modifier::BOLD);
+            let mut badges = Vec::new();
+            if interdiff.is_new {
+                badges.push("(new)".to_owned());
+            } else if interdiff.needs_attention() {
+                badges.push("(changed)".to_owned());
+            }
+            match interdiff.num_disappeared_sections {
+                0 => {}
+                1 => badges.push("(1 section gone)".to_owned()),
+                n => badges.push(format!("({n} sections gone)")),
+            }
+            for badge in badges {
+                let badge_rect =
+                    viewport.draw_span(cursor_x, y, &Span::styled(badge, interdiff_style));
+                cursor_x = badge_rect.end_x() + 1;
+            }
+        }
+        draw_file_stats(viewport, cursor_x, y, *stats);
 
         // 4. Highlight the entire line if it's selected.
         if *is_selected {
@@ -179,3 +237,34 @@ impl Component for FileViewHeader<'_> {
     }
     // ANCHOR_END: updated_fileviewheader_draw
 }
+
+/// Draw the `+N -M` line counts and badges for binary and mode changes.
+pub fn draw_file_stats(viewport: &mut Viewport<ComponentId>, x: isize, y: isize, stats: FileStats) {
+    let FileStats {
+        num_added,
+        num_removed,
+        is_binary,
+        has_mode_change,
+    } = stats;
+    let theme = viewport.theme();
+    let mut spans = Vec::new();
+    if num_added > 0 {
+        spans.push(Span::styled(format!("+{num_added}"), theme.added));
+    }
+    if num_removed > 0 {
+        spans.push(Span::styled(format!("-{num_removed}"), theme.removed));
+    }
+    let badge_style = theme.accent.add_modifier(Modifier::DIM);
+    if is_binary {
+        spans.push(Span::styled("bin", badge_style));
+    }
+    if has_mode_change {
+        spans.push(Span::styled("mode", badge_style));
+    }
+
+    let mut x = x;
+    for span in spans {
+        let rect = viewport.draw_span(x, y, &span);
+        x = rect.end_x() + 1;
+    }
+}
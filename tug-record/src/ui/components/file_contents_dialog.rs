@@ -0,0 +1,108 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::line::push_spans_from_line;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use crate::ui::input::FileVersion;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// Shows the full contents of the old or new version of a file, for when the
+/// context around the changes isn't enough to decide on them. The contents
+/// can be scrolled, but not changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileContentsDialog {
+    pub path: String,
+    pub version: FileVersion,
+    pub lines: Vec<String>,
+    /// The index of the first line to show.
+    pub scroll_offset: usize,
+}
+
+impl FileContentsDialog {
+    /// The number of rows around the file's lines: the borders of the
+    /// dialog, and the summary line and the blank line after it.
+    const NUM_CHROME_ROWS: usize = 4;
+
+    /// The number of lines of the file which fit in a terminal of the given
+    /// height, leaving a row free above and below the dialog.
+    pub fn num_visible_lines(term_height: usize) -> usize {
+        term_height.saturating_sub(Self::NUM_CHROME_ROWS + 2).max(1)
+    }
+
+    /// The largest scroll offset which still fills the dialog.
+    pub fn max_scroll_offset(&self, term_height: usize) -> usize {
+        self.lines
+            .len()
+            .saturating_sub(Self::num_visible_lines(term_height))
+    }
+}
+
+impl Component for FileContentsDialog {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::FileContentsDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let Self {
+            path,
+            version,
+            lines: file_lines,
+            scroll_offset,
+        } = self;
+
+        let term_height = viewport.rect().height;
+        let num_visible_lines = Self::num_visible_lines(term_height);
+        let scroll_offset = (*scroll_offset).min(self.max_scroll_offset(term_height));
+        let mut summary = format!("{path}, {} lines", file_lines.len());
+        if file_lines.len() > num_visible_lines {
+            let last_line = (scroll_offset + num_visible_lines).min(file_lines.len());
+            summary.push_str(&format!(
+                ": lines {}-{last_line}, j/k to scroll",
+                scroll_offset + 1
+            ));
+        }
+
+        let line_num_width = file_lines.len().to_string().len();
+        let line_num_style = Style::default().add_modifier(Modifier::DIM);
+        let mut lines = vec![Line::from(summary), Line::from("")];
+        lines.extend(
+            file_lines
+                .iter()
+                .enumerate()
+                .skip(scroll_offset)
+                .take(num_visible_lines)
+                .map(|(line_idx, line)| {
+                    let mut spans = vec![Span::styled(
+                        format!("{:>line_num_width$} ", line_idx + 1),
+                        line_num_style,
+                    )];
+                    push_spans_from_line(line, &mut spans);
+                    Line::from(spans)
+                }),
+        );
+        let body = Text::from(lines);
+
+        let buttons = [Button {
+            id: ComponentId::FileContentsDialogCloseButton,
+            label: Cow::Borrowed("Close"),
+            style: Style::default(),
+            is_focused: true,
+        }];
+        let title = match version {
+            FileVersion::Old => "Old version",
+            FileVersion::New => "New version",
+        };
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed(title),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::render::{Component, Rect, Viewport};
+use crate::types::Tristate;
+use crate::ui::components::file::draw_file_stats;
+use crate::ui::components::widgets::{
+    bookmark_span, highlight_rect, TristateBox, TristateIconStyle,
+};
+use crate::ui::components::ComponentId;
+use crate::ui::state::{FileStats, SelectionKey};
+use crate::util::UsizeExt;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Span;
+use std::fmt::Debug;
+
+/// An entry in the file list pane.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileListPaneItem {
+    /// The file or directory.
+    pub selection_key: SelectionKey,
+    /// The path of the file or directory, relative to its parent node when
+    /// the files are displayed as a tree.
+    pub label: String,
+    pub depth: usize,
+    /// Whether the directory is expanded, or `None` for files, whose changes
+    /// are shown in the other pane instead.
+    pub is_expanded: Option<bool>,
+    pub tristate: Tristate,
+    pub stats: FileStats,
+    pub is_deferred: bool,
+    pub is_bookmarked: bool,
+}
+
+impl Component for FileListPaneItem {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::FileListPaneItem(self.selection_key)
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
+        let Self {
+            selection_key,
+            label,
+            depth,
+            is_expanded,
+            tristate,
+            stats,
+            is_deferred,
+            is_bookmarked,
+        } = self;
+
+        // Only the text and color of the boxes are needed: the boxes can't be
+        // clicked, and their IDs would clash with those in the diff pane.
+        let toggle_box = TristateBox {
+            id: (),
+            tristate: tristate.clone(),
+            icon_style: TristateIconStyle::Check,
+            is_read_only: false,
+        };
+        let mut cursor_x = x + (depth * FileListPane::TREE_INDENT).unwrap_isize();
+        if let Some(is_expanded) = is_expanded {
+            let expand_box = TristateBox {
+                id: (),
+                tristate: Tristate::from(*is_expanded),
+                icon_style: TristateIconStyle::Expand,
+                is_read_only: false,
+            };
+            let expand_box_rect = viewport.draw_span(
+                cursor_x,
+                y,
+                &Span::styled(expand_box.text(), expand_box.style(viewport.theme())),
+            );
+            cursor_x = expand_box_rect.end_x() + 1;
+        }
+        let toggle_box_rect = viewport.draw_span(
+            cursor_x,
+            y,
+            &Span::styled(toggle_box.text(), toggle_box.style(viewport.theme())),
+        );
+        cursor_x = toggle_box_rect.end_x() + 1;
+
+        let label_style = match selection_key {
+            SelectionKey::Directory(_) => viewport.theme().directory_header,
+            SelectionKey::None
+            | SelectionKey::File(_)
+            | SelectionKey::Section(_)
+            | SelectionKey::Line(_) => Style::default(),
+        };
+        let label = match selection_key {
+            SelectionKey::Directory(_) => format!("{label}/"),
+            SelectionKey::None
+            | SelectionKey::File(_)
+            | SelectionKey::Section(_)
+            | SelectionKey::Line(_) => label.clone(),
+        };
+        let label_rect = viewport.draw_span(cursor_x, y, &Span::styled(label, label_style));
+        cursor_x = label_rect.end_x() + 1;
+        if *is_deferred {
+            let deferred_rect = viewport.draw_span(
+                cursor_x,
+                y,
+                &Span::styled("(deferred)", Style::default().add_modifier(Modifier::DIM)),
+            );
+            cursor_x = deferred_rect.end_x() + 1;
+        }
+        if *is_bookmarked {
+            let bookmark_rect = viewport.draw_span(cursor_x, y, &bookmark_span(viewport.theme()));
+            cursor_x = bookmark_rect.end_x() + 1;
+        }
+        draw_file_stats(viewport, cursor_x, y, *stats);
+    }
+}
+
+/// The list of files shown on the left of the two-pane layout, next to the
+/// changes of the selected file. It stays in place when the changes are
+/// scrolled, and scrolls on its own to keep the selected file in view.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileListPane {
+    pub items: Vec<FileListPaneItem>,
+    /// The index of the item which is selected or which contains the
+    /// selection, if any.
+    pub selected_idx: Option<usize>,
+}
+
+impl FileListPane {
+    /// The number of columns by which each level of the file tree is
+    /// indented.
+    pub const TREE_INDENT: usize = 2;
+
+    /// The widest the pane gets, so that wide terminals leave most of the
+    /// width for the changes.
+    const MAX_WIDTH: usize = 40;
+
+    /// The number of columns which the pane takes up on a terminal of the
+    /// given width, not including the separator to its right.
+    pub fn width(term_width: usize) -> usize {
+        Self::MAX_WIDTH.min(term_width / 3)
+    }
+}
+
+impl Component for FileListPane {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::FileListPane
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _x: isize, _y: isize) {
+        let Self {
+            items,
+            selected_idx,
+        } = self;
+        let viewport_rect = viewport.rect();
+        let rect = Rect {
+            width: Self::width(viewport_rect.width),
+            ..viewport_rect
+        };
+        viewport.draw_blank(Rect {
+            width: rect.width + 1,
+            ..rect
+        });
+        for y in rect.iter_ys() {
+            viewport.draw_span(
+                rect.end_x(),
+                y,
+                &Span::styled("│", viewport.theme().separator),
+            );
+        }
+
+        // Keep the selected item in the middle of the pane, once there are
+        // enough items above it.
+        let max_scroll_offset = items.len().saturating_sub(rect.height);
+        let scroll_offset = selected_idx
+            .unwrap_or_default()
+            .saturating_sub(rect.height / 2)
+            .min(max_scroll_offset);
+        viewport.with_mask(rect.into(), |viewport| {
+            for ((item_idx, item), y) in items
+                .iter()
+                .enumerate()
+                .skip(scroll_offset)
+                .zip(rect.iter_ys())
+            {
+                viewport.draw_component(rect.x, y, item);
+                if Some(item_idx) == *selected_idx {
+                    highlight_rect(
+                        viewport,
+                        Rect {
+                            x: rect.x,
+                            y,
+                            width: rect.width,
+                            height: 1,
+                        },
+                    );
+                }
+            }
+        });
+    }
+}
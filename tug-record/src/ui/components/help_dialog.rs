@@ -34,31 +34,51 @@ impl Component for HelpDialog {
             Line::from("    Confirm changes         c           Next/Prev of same type  PgDn/PgUp"),
             Line::from("    Force quit              ^c          Move out & fold         h or ←"),
             Line::from(
-                "                                        Move out & don't fold   H or Shift-←    ",
+                "    Record/replay macro     Q/@         Move out & don't fold   H or Shift-←    ",
             ),
             Line::from(vec![
                 Span::raw("    "),
                 Span::styled("View controls", Style::new().bold().underlined()),
                 Span::raw("                       Move in & unfold        l or →"),
             ]),
-            Line::from("    Expand/Collapse         f"),
+            Line::from("    Expand/Collapse (all)   f/F         Next/Prev file          Alt-↓/↑"),
             Line::from(vec![
-                Span::raw("    Expand/Collapse all     F           "),
+                Span::raw("    Char diff/wrap line     D/w         "),
                 Span::styled("Scrolling", Style::new().bold().underlined()),
             ]),
-            Line::from("    Edit commit message     e           Scroll up/down          ^y/^e"),
-            Line::from("                                                             or ^↑/^↓"),
+            Line::from("    Edit/expand message     e/E         Scroll up/down          ^y/^e"),
+            Line::from("    Blame gutter            b                                or ^↑/^↓"),
+            Line::from("    Reuse old message       r           Page up/down            ^b/^f"),
+            Line::from(
+                "    Show diff statistics    i                                or ^PgUp/^PgDn",
+            ),
+            Line::from("    Directory tree          T           Previous/Next page      ^u/^d"),
+            Line::from("    Zoom into file          Z           First/Last, center      gg/G/zz"),
+            Line::from("    Outline (files only)    O           Bookmark/next/prev      m/'/\""),
+            Line::from("    Recent actions          L"),
+            Line::from("    Two-pane layout         V"),
+            Line::from("    Hide unchanged lines    C"),
+            Line::from("    Prev/next commit        </>"),
+            Line::from("    Move item prev/next     [/]"),
+            Line::from("    Edit message externally Alt-e"),
             Line::from(vec![
                 Span::raw("    "),
                 Span::styled("Selection", Style::new().bold().underlined()),
-                Span::raw("                           Page up/down            ^b/^f"),
+            ]),
+            Line::from(vec![
+                Span::raw("    Toggle current/file     Space/Alt-a "),
+                Span::styled("Filtering", Style::new().bold().underlined()),
             ]),
             Line::from(
-                "    Toggle current          Space                            or ^PgUp/^PgDn",
+                "    Toggle and advance      Enter       Filter files/clear      \\/Backspace",
             ),
-            Line::from("    Toggle and advance      Enter       Previous/Next page      ^u/^d"),
-            Line::from("    Invert all              a"),
-            Line::from("    Invert all uniformly    A"),
+            Line::from("    Invert all (uniformly)  a/A         Hide unselected files   x"),
+            Line::from("    Defer file              d           Hide selected files     X"),
+            Line::from("    Copy path/lines/diff    y/Y/P       Cycle file sort order   o"),
+            Line::from("    Undo/redo               u/^r        Hide unchanged files    I"),
+            Line::from("    Preview selected diff   p           Preview images          Alt-p"),
+            Line::from("    View new/old file       v/Alt-v     Search/next/prev        //n/N"),
+            Line::from("    Toggle line's section   Alt-s"),
         ]);
 
         let quit_button = Button {
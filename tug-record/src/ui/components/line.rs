@@ -1,35 +1,38 @@
-use crate::render::{Component, Rect, Viewport};
-use crate::types::ChangeType;
-use crate::ui::components::app::SelectionKey;
+use crate::render::{Component, Mask, Rect, Viewport};
+use crate::types::{ChangeType, LineAnnotation};
 use crate::ui::components::widgets::TristateBox;
 use crate::ui::components::ComponentId;
+use crate::ui::state::{LineKey, SelectionKey};
+use crate::util::{IsizeExt, UsizeExt};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
 use std::fmt::Debug;
-use unicode_width::UnicodeWidthChar;
-
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
-pub struct LineKey {
-    pub commit_idx: usize,
-    pub file_idx: usize,
-    pub section_idx: usize,
-    pub line_idx: usize,
-}
+use std::ops::Range;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The styles of parts of a line, such as its syntax highlighting, as byte
+/// ranges into the line, in order.
+pub type LineHighlights = Vec<(Range<usize>, Style)>;
 
 #[derive(Clone, Debug)]
 pub enum SectionLineViewInner<'a> {
     Unchanged {
         line: &'a str,
-        line_num: usize,
+        old_line_num: usize,
+        new_line_num: usize,
     },
     Changed {
         toggle_box: TristateBox<ComponentId>,
         change_type: ChangeType,
         line: &'a str,
+        /// The line number in the old file for removed lines, or in the new
+        /// file for added lines.
+        line_num: usize,
+        annotation: Option<&'a LineAnnotation<'a>>,
     },
 }
 
-fn replace_control_character(character: char) -> Option<&'static str> {
+pub fn replace_control_character(character: char) -> Option<&'static str> {
     match character {
         // Characters end up writing over each-other and end up
         // displaying incorrectly if ignored. Replacing tabs
@@ -102,10 +105,136 @@ pub fn push_spans_from_line<'line>(line: &'line str, spans: &mut Vec<Span<'line>
     }
 }
 
+/// Like [`push_spans_from_line`], but with the parts of the line covered by
+/// `highlights` styled accordingly. Control characters keep their own style.
+pub fn push_highlighted_spans_from_line<'line>(
+    line: &'line str,
+    highlights: &[(Range<usize>, Style)],
+    spans: &mut Vec<Span<'line>>,
+) {
+    let mut last_index = 0;
+    for (range, style) in highlights {
+        // The line may have been truncated to the part which is visible.
+        let start = range.start.clamp(last_index, line.len());
+        let end = range.end.clamp(start, line.len());
+        push_spans_from_line(&line[last_index..start], spans);
+        let highlighted_spans_start = spans.len();
+        push_spans_from_line(&line[start..end], spans);
+        for span in &mut spans[highlighted_spans_start..] {
+            span.style = style.patch(span.style);
+        }
+        last_index = end;
+    }
+    push_spans_from_line(&line[last_index..], spans);
+}
+
+/// Combine two sets of highlights of the same line, patching the styles of
+/// `overlay` onto those of `base` where they overlap.
+pub fn merge_highlights(
+    base: &[(Range<usize>, Style)],
+    overlay: &[(Range<usize>, Style)],
+) -> LineHighlights {
+    if overlay.is_empty() {
+        return base.to_vec();
+    }
+    if base.is_empty() {
+        return overlay.to_vec();
+    }
+    let mut boundaries: Vec<usize> = base
+        .iter()
+        .chain(overlay)
+        .flat_map(|(range, _)| [range.start, range.end])
+        .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    let style_at = |highlights: &[(Range<usize>, Style)], idx: usize| {
+        highlights
+            .iter()
+            .find(|(range, _)| range.contains(&idx))
+            .map(|(_, style)| *style)
+    };
+    boundaries
+        .windows(2)
+        .filter_map(|window| {
+            let range = window[0]..window[1];
+            match (style_at(base, range.start), style_at(overlay, range.start)) {
+                (None, None) => None,
+                (base_style, overlay_style) => Some((
+                    range,
+                    base_style
+                        .unwrap_or_default()
+                        .patch(overlay_style.unwrap_or_default()),
+                )),
+            }
+        })
+        .collect()
+}
+
+/// How many characters past the edge of the screen are processed when drawing
+/// a line. The rest of the line can't be visible, and processing all of it
+/// would slow down every frame for files with very long lines, such as
+/// minified code.
+const LINE_RENDER_MARGIN: usize = 16;
+
+/// Get the prefix of the line which is enough to fill `width` columns. Every
+/// character takes up at least one column once control characters have been
+/// replaced, so it's enough to count characters.
+fn visible_prefix(line: &str, width: usize) -> &str {
+    match line
+        .char_indices()
+        .nth(width.saturating_add(LINE_RENDER_MARGIN))
+    {
+        Some((idx, _)) => &line[..idx],
+        None => line,
+    }
+}
+
+/// Split the spans into rows of at most `width` columns.
+pub(crate) fn wrap_spans(spans: Vec<Span<'_>>, width: usize) -> Vec<Vec<Span<'_>>> {
+    let mut rows = vec![Vec::new()];
+    let mut row_width = 0;
+    for span in spans {
+        let Span { content, style } = span;
+        let mut piece = String::new();
+        for c in content.chars() {
+            let char_width = c.width().unwrap_or_default();
+            if row_width + char_width > width && row_width > 0 {
+                if !piece.is_empty() {
+                    rows.last_mut()
+                        .unwrap()
+                        .push(Span::styled(std::mem::take(&mut piece), style));
+                }
+                rows.push(Vec::new());
+                row_width = 0;
+            }
+            piece.push(c);
+            row_width += char_width;
+        }
+        if !piece.is_empty() {
+            rows.last_mut().unwrap().push(Span::styled(piece, style));
+        }
+    }
+    rows
+}
+
 #[derive(Clone, Debug)]
 pub struct SectionLineView<'a> {
     pub line_key: LineKey,
     pub inner: SectionLineViewInner<'a>,
+    /// Wrap the line onto as many rows as necessary instead of truncating it.
+    /// Only applies to changed lines.
+    pub is_wrapped: bool,
+    /// Reserve a gutter on the right for the annotation of the line. Only
+    /// applies to changed lines.
+    pub show_annotation: bool,
+    /// The syntax highlighting of the line, if any.
+    pub highlights: &'a [(Range<usize>, Style)],
+}
+
+impl SectionLineView<'_> {
+    /// The width of the gutter for annotations, including a space to separate
+    /// it from the line.
+    const ANNOTATION_GUTTER_WIDTH: usize = 24;
 }
 
 impl Component for SectionLineView<'_> {
@@ -124,35 +253,144 @@ impl Component for SectionLineView<'_> {
         });
 
         match &self.inner {
-            SectionLineViewInner::Unchanged { line, line_num } => {
-                // Pad the number in 5 columns because that will align the
-                // beginning of the actual text with the `+`/`-` of the changed
-                // lines.
-                let line_number = Span::raw(format!("{line_num:5} "));
-                let mut spans = vec![line_number];
-                push_spans_from_line(line, &mut spans);
-
-                const UI_UNCHANGED_STYLE: Style = Style::new().fg(Color::Gray).add_modifier(Modifier::DIM);
-                viewport.draw_text(x, y, Line::from(spans).style(UI_UNCHANGED_STYLE));
+            SectionLineViewInner::Unchanged {
+                line,
+                old_line_num,
+                new_line_num,
+            } => {
+                // Leave room for the toggle box of the changed lines, and pad
+                // the numbers in 5 columns each so that they line up with the
+                // numbers of the changed lines.
+                let line_numbers = Span::raw(format!("    {old_line_num:5} {new_line_num:5}   "));
+                let width = (viewport.mask_rect().end_x() - x).clamp_into_usize();
+                let mut spans = vec![line_numbers];
+                push_highlighted_spans_from_line(
+                    visible_prefix(line, width),
+                    self.highlights,
+                    &mut spans,
+                );
+
+                let unchanged_style = viewport.theme().unchanged;
+                viewport.draw_text(x, y, Line::from(spans).style(unchanged_style));
             }
 
             SectionLineViewInner::Changed {
                 toggle_box,
                 change_type,
                 line,
+                line_num,
+                annotation,
             } => {
-                let toggle_box_rect = viewport.draw_component(x, y, toggle_box);
-                let x = toggle_box_rect.end_x() + 1;
-
-                let (change_type_text, changed_line_style) = match change_type {
-                    ChangeType::Added => ("+ ", Style::default().fg(Color::Green)),
-                    ChangeType::Removed => ("- ", Style::default().fg(Color::Red)),
+                let mask = viewport.mask();
+                let mask_rect = viewport.mask_rect();
+                let line_mask = if self.show_annotation
+                    && mask_rect.width > Self::ANNOTATION_GUTTER_WIDTH
+                {
+                    let gutter_x = mask_rect.end_x() - Self::ANNOTATION_GUTTER_WIDTH.unwrap_isize();
+                    if let Some(LineAnnotation { author, age }) = annotation {
+                        viewport.draw_span(
+                            gutter_x,
+                            y,
+                            &Span::styled(
+                                format!(" {author:<14.14} {age:>8.8}"),
+                                Style::default().add_modifier(Modifier::DIM),
+                            ),
+                        );
+                    }
+                    Mask {
+                        width: Some((gutter_x - mask.x).clamp_into_usize()),
+                        ..mask
+                    }
+                } else {
+                    mask
                 };
 
-                let mut spans = vec![Span::raw(change_type_text)];
-                push_spans_from_line(line, &mut spans);
+                viewport.with_mask(line_mask, |viewport| {
+                    let toggle_box_rect = viewport.draw_component(x, y, toggle_box);
+                    let x = toggle_box_rect.end_x() + 1;
+
+                    let (line_numbers, change_type_text, changed_line_style) = match change_type {
+                        ChangeType::Added => (
+                            format!("{:5} {line_num:5} ", ""),
+                            "+ ",
+                            viewport.theme().added,
+                        ),
+                        ChangeType::Removed => (
+                            format!("{line_num:5} {:5} ", ""),
+                            "- ",
+                            viewport.theme().removed,
+                        ),
+                    };
+
+                    let gutter = vec![
+                        Span::styled(line_numbers, Style::default().add_modifier(Modifier::DIM)),
+                        Span::raw(change_type_text),
+                    ];
+                    let content_x = x + gutter
+                        .iter()
+                        .map(|span| span.content.width())
+                        .sum::<usize>()
+                        .unwrap_isize();
+                    let mask_rect = viewport.mask_rect();
+                    let width = (mask_rect.end_x() - content_x).clamp_into_usize();
+                    // Only process as much of a wrapped line as can fit down to
+                    // the bottom of the screen. More of it is processed as it
+                    // is scrolled into view.
+                    let num_rows = if self.is_wrapped {
+                        (viewport.rect().end_y() - y).clamp_into_usize()
+                    } else {
+                        1
+                    };
+                    let mut spans = Vec::new();
+                    push_highlighted_spans_from_line(
+                        visible_prefix(line, width.saturating_mul(num_rows)),
+                        self.highlights,
+                        &mut spans,
+                    );
 
-                viewport.draw_text(x, y, Line::from(spans).style(changed_line_style));
+                    if self.is_wrapped && width > 0 {
+                        for (dy, row) in wrap_spans(spans, width).into_iter().enumerate() {
+                            let y = y + dy.unwrap_isize();
+                            if dy == 0 {
+                                viewport.draw_line(
+                                    x,
+                                    y,
+                                    &Line::from(gutter.clone()).style(changed_line_style),
+                                );
+                            } else {
+                                viewport.draw_blank(Rect {
+                                    x: mask_rect.x,
+                                    y,
+                                    width: mask_rect.width,
+                                    height: 1,
+                                });
+                            }
+                            viewport.draw_line(
+                                content_x,
+                                y,
+                                &Line::from(row).style(changed_line_style),
+                            );
+                        }
+                    } else {
+                        let line_rect = viewport.draw_line(
+                            x,
+                            y,
+                            &Line::from([gutter, spans].concat()).style(changed_line_style),
+                        );
+                        // Hint that the rest of the line can be shown by
+                        // wrapping it.
+                        if line_rect.end_x() > mask_rect.end_x() {
+                            viewport.draw_span(
+                                mask_rect.end_x() - 1,
+                                y,
+                                &Span::styled(
+                                    "▶",
+                                    viewport.theme().warning.add_modifier(Modifier::BOLD),
+                                ),
+                            );
+                        }
+                    }
+                });
             }
         }
     }
@@ -0,0 +1,167 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::line::replace_control_character;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+use unicode_width::UnicodeWidthStr;
+
+/// Shows the character-level changes between a removed line and the added line
+/// which replaced it, so that small edits inside long lines are easy to spot.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LineDiffDialog {
+    pub old_line: String,
+    pub new_line: String,
+}
+
+impl LineDiffDialog {
+    /// The maximum size of the table used to compute the diff. Past this, only
+    /// the common prefix and suffix of the lines are matched.
+    const MAX_LCS_CELLS: usize = 1 << 22;
+}
+
+/// Mark which items of `old` were removed and which items of `new` were added,
+/// using the longest common subsequence of the items between their common
+/// prefix and suffix. The items are usually the characters or the words of a
+/// line.
+pub(crate) fn diff_items<T: PartialEq>(old: &[T], new: &[T]) -> (Vec<bool>, Vec<bool>) {
+    let prefix_len = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(old_item, new_item)| old_item == new_item)
+        .count();
+    let suffix_len = old[prefix_len..]
+        .iter()
+        .rev()
+        .zip(new[prefix_len..].iter().rev())
+        .take_while(|(old_item, new_item)| old_item == new_item)
+        .count();
+    let old_middle = &old[prefix_len..old.len() - suffix_len];
+    let new_middle = &new[prefix_len..new.len() - suffix_len];
+
+    let mut is_removed = vec![false; old.len()];
+    let mut is_added = vec![false; new.len()];
+    if (old_middle.len() + 1) * (new_middle.len() + 1) > LineDiffDialog::MAX_LCS_CELLS {
+        is_removed[prefix_len..old.len() - suffix_len].fill(true);
+        is_added[prefix_len..new.len() - suffix_len].fill(true);
+        return (is_removed, is_added);
+    }
+
+    let mut lcs = vec![vec![0_usize; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lcs[i][j] = if old_middle[i] == new_middle[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() && j < new_middle.len() {
+        if old_middle[i] == new_middle[j] {
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            is_removed[prefix_len + i] = true;
+            i += 1;
+        } else {
+            is_added[prefix_len + j] = true;
+            j += 1;
+        }
+    }
+    is_removed[prefix_len + i..old.len() - suffix_len].fill(true);
+    is_added[prefix_len + j..new.len() - suffix_len].fill(true);
+    (is_removed, is_added)
+}
+
+/// Render the characters of a line, highlighting the changed ones and wrapping
+/// the result at `width` columns.
+fn wrap_line<'a>(
+    prefix: &'static str,
+    chars: &[char],
+    is_changed: &[bool],
+    style: Style,
+    width: usize,
+) -> Vec<Line<'a>> {
+    let changed_style = style.add_modifier(Modifier::REVERSED);
+    let mut lines = Vec::new();
+    let mut spans = vec![Span::styled(prefix, style)];
+    let mut line_width = 0;
+    for (c, is_changed) in chars.iter().zip(is_changed.iter()) {
+        let text = match replace_control_character(*c) {
+            Some(replacement) => replacement.to_owned(),
+            None => c.to_string(),
+        };
+        let text_width = text.width();
+        if line_width + text_width > width && line_width > 0 {
+            lines.push(Line::from(std::mem::take(&mut spans)));
+            spans.push(Span::raw("  "));
+            line_width = 0;
+        }
+        line_width += text_width;
+        spans.push(Span::styled(
+            text,
+            if *is_changed { changed_style } else { style },
+        ));
+    }
+    lines.push(Line::from(spans));
+    lines
+}
+
+impl Component for LineDiffDialog {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::LineDiffDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let Self { old_line, new_line } = self;
+
+        let old_chars: Vec<char> = old_line.chars().collect();
+        let new_chars: Vec<char> = new_line.chars().collect();
+        let (is_removed, is_added) = diff_items(&old_chars, &new_chars);
+        let num_removed = is_removed.iter().filter(|is_removed| **is_removed).count();
+        let num_added = is_added.iter().filter(|is_added| **is_added).count();
+
+        // Leave room for the dialog borders and the `-`/`+` prefixes.
+        let width = viewport.rect().width.saturating_sub(6).max(1);
+        let mut lines = vec![
+            Line::from(format!("{num_removed} removed, {num_added} added:")),
+            Line::from(""),
+        ];
+        lines.extend(wrap_line(
+            "- ",
+            &old_chars,
+            &is_removed,
+            viewport.theme().removed,
+            width,
+        ));
+        lines.extend(wrap_line(
+            "+ ",
+            &new_chars,
+            &is_added,
+            viewport.theme().added,
+            width,
+        ));
+        let body = Text::from(lines);
+
+        let buttons = [Button {
+            id: ComponentId::LineDiffDialogCloseButton,
+            label: Cow::Borrowed("Close"),
+            style: Style::default(),
+            is_focused: true,
+        }];
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed("Character changes"),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// Shows the lines that were added to or removed from a commit message by the
+/// external editor, so that accidental truncation is noticed before the
+/// changes are accepted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MessageDiffDialog {
+    pub old_message: String,
+    pub new_message: String,
+}
+
+impl MessageDiffDialog {
+    /// The maximum number of diff lines to show.
+    const MAX_LINES: usize = 16;
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum DiffLine<'a> {
+    Unchanged(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Compute a line-based diff using the longest common subsequence. Commit
+/// messages are short, so the quadratic cost is acceptable.
+fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut lcs = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            result.push(DiffLine::Unchanged(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            result.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            result.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    result.extend(old[i..].iter().map(|line| DiffLine::Removed(line)));
+    result.extend(new[j..].iter().map(|line| DiffLine::Added(line)));
+    result
+}
+
+impl Component for MessageDiffDialog {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::MessageDiffDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let Self {
+            old_message,
+            new_message,
+        } = self;
+
+        let diff = diff_lines(old_message, new_message);
+        let (num_added, num_removed) =
+            diff.iter()
+                .fold((0, 0), |(added, removed), line| match line {
+                    DiffLine::Unchanged(_) => (added, removed),
+                    DiffLine::Removed(_) => (added, removed + 1),
+                    DiffLine::Added(_) => (added + 1, removed),
+                });
+
+        let mut lines = vec![
+            Line::from(format!(
+                "The commit message was changed ({num_added} added, {num_removed} removed):"
+            )),
+            Line::from(""),
+        ];
+        let theme = viewport.theme();
+        lines.extend(diff.iter().take(Self::MAX_LINES).map(|line| match line {
+            DiffLine::Unchanged(line) => Line::from(Span::styled(
+                format!("  {line}"),
+                Style::default().add_modifier(Modifier::DIM),
+            )),
+            DiffLine::Removed(line) => Line::from(Span::styled(format!("- {line}"), theme.removed)),
+            DiffLine::Added(line) => Line::from(Span::styled(format!("+ {line}"), theme.added)),
+        }));
+        if diff.len() > Self::MAX_LINES {
+            lines.push(Line::from(Span::styled(
+                format!("  ({} more lines)", diff.len() - Self::MAX_LINES),
+                Style::default().add_modifier(Modifier::DIM),
+            )));
+        }
+        let body = Text::from(lines);
+
+        let buttons = [Button {
+            id: ComponentId::MessageDiffDialogCloseButton,
+            label: Cow::Borrowed("Close"),
+            style: Style::default(),
+            is_focused: true,
+        }];
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed("Message changed"),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
@@ -0,0 +1,432 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use crate::ui::key_event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+use std::ops::Range;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The result of passing a key press to a [`MessageEditorDialog`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MessageEditorAction {
+    /// The message was edited, the cursor moved, or the key was ignored.
+    Edit,
+    /// The user accepted the message.
+    Save,
+    /// The user dismissed the editor, discarding the changes.
+    Cancel,
+    /// The user asked to continue editing the message in the external
+    /// editor.
+    EditExternally,
+}
+
+/// Edits a commit message in a dialog, without suspending the terminal to run
+/// an external editor. Lines which don't fit are wrapped onto several rows,
+/// but the message itself only has the line breaks typed by the user.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MessageEditorDialog {
+    pub commit_idx: usize,
+    pub text: String,
+    /// The byte offset of the cursor in `text`.
+    pub cursor: usize,
+}
+
+impl MessageEditorDialog {
+    /// Commit messages are conventionally wrapped at 72 columns, so the rows
+    /// are no wider than that, even in wide terminals.
+    const MAX_WRAP_WIDTH: usize = 72;
+
+    /// The number of rows around the message: the borders of the dialog, and
+    /// the blank line and the key hints after the message.
+    const NUM_CHROME_ROWS: usize = 4;
+
+    /// The number of rows of the message to show, at least, so that the
+    /// dialog looks like an editor even for a one-line message.
+    const MIN_VISIBLE_ROWS: usize = 8;
+
+    /// Edit the message, starting with the cursor at its end.
+    pub fn new(commit_idx: usize, text: impl Into<String>) -> Self {
+        let text = text.into();
+        Self {
+            commit_idx,
+            cursor: text.len(),
+            text,
+        }
+    }
+
+    /// The width at which the lines are wrapped in a terminal of the given
+    /// width, leaving room for the borders of the dialog and the cursor at
+    /// the end of a row.
+    pub fn wrap_width(term_width: usize) -> usize {
+        term_width.saturating_sub(6).clamp(1, Self::MAX_WRAP_WIDTH)
+    }
+
+    /// The number of rows of the message which fit in a terminal of the
+    /// given height, leaving a row free above and below the dialog.
+    fn num_visible_rows(term_height: usize) -> usize {
+        term_height.saturating_sub(Self::NUM_CHROME_ROWS + 2).max(1)
+    }
+
+    /// Update the message in response to a key press. `term_width` is needed
+    /// to move the cursor up and down by rows rather than by lines.
+    pub fn handle_key(&mut self, key_event: KeyEvent, term_width: usize) -> MessageEditorAction {
+        let KeyEvent {
+            code,
+            modifiers,
+            kind,
+            state: _,
+        } = key_event;
+        if kind != KeyEventKind::Press {
+            return MessageEditorAction::Edit;
+        }
+        let wrap_width = Self::wrap_width(term_width);
+        match (code, modifiers) {
+            (KeyCode::Char('s'), KeyModifiers::CONTROL) => return MessageEditorAction::Save,
+            (KeyCode::Esc, _) => return MessageEditorAction::Cancel,
+            (KeyCode::Char('e'), KeyModifiers::ALT) => return MessageEditorAction::EditExternally,
+
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => self.insert(c),
+            (KeyCode::Enter, _) => self.insert('\n'),
+            (KeyCode::Backspace, _) => {
+                if let Some(c) = self.text[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                    self.text.remove(self.cursor);
+                }
+            }
+            (KeyCode::Delete, _) if self.cursor < self.text.len() => {
+                self.text.remove(self.cursor);
+            }
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
+                let line_start = self.line_start();
+                self.text.replace_range(line_start..self.cursor, "");
+                self.cursor = line_start;
+            }
+
+            (KeyCode::Left, _) => {
+                if let Some(c) = self.text[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                }
+            }
+            (KeyCode::Right, _) => {
+                if let Some(c) = self.text[self.cursor..].chars().next() {
+                    self.cursor += c.len_utf8();
+                }
+            }
+            (KeyCode::Up, _) => self.move_to_row(wrap_width, /*move_down=*/ false),
+            (KeyCode::Down, _) => self.move_to_row(wrap_width, /*move_down=*/ true),
+            (KeyCode::Home, _) | (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
+                let rows = rows(&self.text, wrap_width);
+                self.cursor = rows[cursor_row(&rows, self.cursor)].start;
+            }
+            (KeyCode::End, _) | (KeyCode::Char('e'), KeyModifiers::CONTROL) => {
+                let rows = rows(&self.text, wrap_width);
+                let row_idx = cursor_row(&rows, self.cursor);
+                self.cursor = self.offset_in_row(&rows, row_idx, usize::MAX);
+            }
+            _ => {}
+        }
+        MessageEditorAction::Edit
+    }
+
+    fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// The byte offset of the start of the line containing the cursor.
+    fn line_start(&self) -> usize {
+        self.text[..self.cursor]
+            .rfind('\n')
+            .map_or(0, |newline_idx| newline_idx + 1)
+    }
+
+    /// Move the cursor to the previous or next row, keeping its column if the
+    /// row is long enough.
+    fn move_to_row(&mut self, wrap_width: usize, move_down: bool) {
+        let rows = rows(&self.text, wrap_width);
+        let row_idx = cursor_row(&rows, self.cursor);
+        let column = self.text[rows[row_idx].start..self.cursor].width();
+        let target_row_idx = if move_down {
+            row_idx + 1
+        } else {
+            match row_idx.checked_sub(1) {
+                Some(row_idx) => row_idx,
+                None => {
+                    self.cursor = 0;
+                    return;
+                }
+            }
+        };
+        if target_row_idx < rows.len() {
+            self.cursor = self.offset_in_row(&rows, target_row_idx, column);
+        } else {
+            self.cursor = self.text.len();
+        }
+    }
+
+    /// The byte offset of the given column of the row, or of the end of the
+    /// row if it's shorter. The end of a row which continues on the next one
+    /// is the start of that row, so the last character of the row is used
+    /// instead, to keep the cursor on the row.
+    fn offset_in_row(&self, rows: &[Range<usize>], row_idx: usize, column: usize) -> usize {
+        let row = rows[row_idx].clone();
+        let mut width = 0;
+        for (idx, c) in self.text[row.clone()].char_indices() {
+            width += c.width().unwrap_or(0);
+            if width > column {
+                return row.start + idx;
+            }
+        }
+        let is_wrapped = rows
+            .get(row_idx + 1)
+            .is_some_and(|next_row| next_row.start == row.end);
+        match self.text[row.clone()].chars().next_back() {
+            Some(c) if is_wrapped => row.end - c.len_utf8(),
+            Some(_) | None => row.end,
+        }
+    }
+}
+
+/// Split the text into the rows which it's shown on, as byte ranges without
+/// the line breaks. Lines which are wider than `wrap_width` are wrapped after
+/// the last space which fits, or after the last character which fits if
+/// there's no such space. A space which doesn't fit is kept at the end of the
+/// row, in the column left free for the cursor, rather than wrapping the word
+/// before it.
+fn rows(text: &str, wrap_width: usize) -> Vec<Range<usize>> {
+    let mut rows = Vec::new();
+    let mut line_start = 0;
+    for line in text.split('\n') {
+        let mut row_start = line_start;
+        let mut row_width = 0;
+        let mut last_break = None;
+        for (idx, c) in line.char_indices() {
+            let idx = line_start + idx;
+            let char_width = c.width().unwrap_or(0);
+            if row_width + char_width > wrap_width && idx > row_start {
+                if c == ' ' {
+                    let row_end = idx + c.len_utf8();
+                    rows.push(row_start..row_end);
+                    row_start = row_end;
+                    row_width = 0;
+                    continue;
+                }
+                let row_end = last_break
+                    .filter(|last_break| *last_break > row_start)
+                    .unwrap_or(idx);
+                rows.push(row_start..row_end);
+                row_start = row_end;
+                row_width = text[row_start..idx].width();
+            }
+            row_width += char_width;
+            if c == ' ' {
+                last_break = Some(idx + c.len_utf8());
+            }
+        }
+        rows.push(row_start..line_start + line.len());
+        line_start += line.len() + '\n'.len_utf8();
+    }
+    rows
+}
+
+/// The index of the row containing the cursor. At the boundary of a wrapped
+/// line, the cursor is at the start of the next row.
+fn cursor_row(rows: &[Range<usize>], cursor: usize) -> usize {
+    rows.iter()
+        .rposition(|row| row.start <= cursor)
+        .unwrap_or_default()
+}
+
+impl Component for MessageEditorDialog {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::MessageEditorDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let Self {
+            commit_idx: _,
+            text,
+            cursor,
+        } = self;
+
+        let wrap_width = Self::wrap_width(viewport.rect().width);
+        let num_visible_rows = Self::num_visible_rows(viewport.rect().height);
+        let rows = rows(text, wrap_width);
+        let cursor_row_idx = cursor_row(&rows, *cursor);
+        // Scroll just far enough to show the cursor.
+        let scroll_offset = (cursor_row_idx + 1).saturating_sub(num_visible_rows);
+
+        let cursor_style = Style::default().add_modifier(Modifier::REVERSED);
+        let mut lines: Vec<Line> = rows
+            .iter()
+            .enumerate()
+            .skip(scroll_offset)
+            .take(num_visible_rows)
+            .map(|(row_idx, row)| {
+                let mut spans = Vec::new();
+                if row_idx == cursor_row_idx {
+                    let (before, after) = text[row.clone()].split_at(*cursor - row.start);
+                    let mut after = after.chars();
+                    let under_cursor = after.next().map_or(" ".to_string(), String::from);
+                    spans.push(Span::raw(before));
+                    spans.push(Span::styled(under_cursor, cursor_style));
+                    spans.push(Span::raw(after.as_str()));
+                } else {
+                    spans.push(Span::raw(&text[row.clone()]));
+                }
+                Line::from(spans)
+            })
+            .collect();
+        // Keep the size of the dialog the same while typing.
+        let num_rows = num_visible_rows.min(Self::MIN_VISIBLE_ROWS.max(rows.len()));
+        lines.resize(num_rows, Line::default());
+        lines.push(Line::from(" ".repeat(wrap_width + 1)));
+        lines.push(Line::from(Span::styled(
+            "^s to save, Esc to cancel, Alt-e for the external editor",
+            Style::default().add_modifier(Modifier::DIM),
+        )));
+        let body = Text::from(lines);
+
+        let buttons = [Button {
+            id: ComponentId::MessageEditorDialogSaveButton,
+            label: Cow::Borrowed("Save"),
+            style: Style::default(),
+            is_focused: true,
+        }];
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed("Edit commit message"),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row_texts(text: &str, wrap_width: usize) -> Vec<&str> {
+        rows(text, wrap_width)
+            .into_iter()
+            .map(|row| &text[row])
+            .collect()
+    }
+
+    fn press(dialog: &mut MessageEditorDialog, code: KeyCode, term_width: usize) {
+        let action = dialog.handle_key(KeyEvent::new(code, KeyModifiers::NONE), term_width);
+        assert_eq!(action, MessageEditorAction::Edit);
+    }
+
+    #[test]
+    fn test_rows_wrap_at_space() {
+        assert_eq!(row_texts("hello world foo", 8), ["hello ", "world ", "foo"]);
+        // The space after a word which fits exactly stays on its row.
+        assert_eq!(row_texts("hello world foo", 11), ["hello world ", "foo"]);
+        assert_eq!(row_texts("ab  cd", 3), ["ab  ", "cd"]);
+        // Line breaks always start a new row.
+        assert_eq!(row_texts("a\n\nb c", 2), ["a", "", "b ", "c"]);
+        assert_eq!(row_texts("", 2), [""]);
+    }
+
+    #[test]
+    fn test_rows_break_words_longer_than_width() {
+        assert_eq!(row_texts("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        assert_eq!(row_texts("ab cdefghij", 4), ["ab ", "cdef", "ghij"]);
+        // A row always has at least one character, even if it doesn't fit.
+        assert_eq!(row_texts("abc", 0), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_rows_wide_characters() {
+        // Each of these characters is 2 columns wide.
+        assert_eq!(row_texts("日本語です", 5), ["日本", "語で", "す"]);
+        assert_eq!(row_texts("a日本", 2), ["a", "日", "本"]);
+        assert_eq!(row_texts("日本 語", 5), ["日本 ", "語"]);
+    }
+
+    #[test]
+    fn test_cursor_row() {
+        let rows = rows("hello world foo\n\nbar", 8);
+        assert_eq!(rows, [0..6, 6..12, 12..15, 16..16, 17..20]);
+        assert_eq!(cursor_row(&rows, 0), 0);
+        assert_eq!(cursor_row(&rows, 5), 0);
+        // At the boundary of a wrapped line, the cursor is on the next row.
+        assert_eq!(cursor_row(&rows, 6), 1);
+        assert_eq!(cursor_row(&rows, 15), 2);
+        assert_eq!(cursor_row(&rows, 16), 3);
+        assert_eq!(cursor_row(&rows, 20), 4);
+    }
+
+    #[test]
+    fn test_delete_multi_byte_characters() {
+        let mut dialog = MessageEditorDialog::new(0, "aé日b");
+        press(&mut dialog, KeyCode::Backspace, 80);
+        assert_eq!((dialog.text.as_str(), dialog.cursor), ("aé日", 6));
+        press(&mut dialog, KeyCode::Backspace, 80);
+        assert_eq!((dialog.text.as_str(), dialog.cursor), ("aé", 3));
+        press(&mut dialog, KeyCode::Left, 80);
+        assert_eq!(dialog.cursor, 1);
+        press(&mut dialog, KeyCode::Delete, 80);
+        assert_eq!((dialog.text.as_str(), dialog.cursor), ("a", 1));
+        // Nothing is deleted past either end.
+        press(&mut dialog, KeyCode::Delete, 80);
+        assert_eq!((dialog.text.as_str(), dialog.cursor), ("a", 1));
+        press(&mut dialog, KeyCode::Backspace, 80);
+        press(&mut dialog, KeyCode::Backspace, 80);
+        assert_eq!((dialog.text.as_str(), dialog.cursor), ("", 0));
+
+        press(&mut dialog, KeyCode::Char('é'), 80);
+        press(&mut dialog, KeyCode::Enter, 80);
+        press(&mut dialog, KeyCode::Char('日'), 80);
+        assert_eq!((dialog.text.as_str(), dialog.cursor), ("é\n日", 6));
+    }
+
+    #[test]
+    fn test_move_up_and_down_across_wrapped_rows() {
+        // Leaves a wrap width of 8 columns.
+        let term_width = 14;
+        let mut dialog = MessageEditorDialog::new(0, "hello world foo");
+        let mut cursors = Vec::new();
+        for code in [KeyCode::Up, KeyCode::Up, KeyCode::Up] {
+            press(&mut dialog, code, term_width);
+            cursors.push(dialog.cursor);
+        }
+        // The column is kept, until moving up from the first row.
+        assert_eq!(cursors, [9, 3, 0]);
+
+        cursors.clear();
+        for code in [KeyCode::Down, KeyCode::Down, KeyCode::Down] {
+            press(&mut dialog, code, term_width);
+            cursors.push(dialog.cursor);
+        }
+        // Moving down from the last row goes to the end.
+        assert_eq!(cursors, [6, 12, 15]);
+
+        // At the end of a wrapped row, the cursor stays on the row, before
+        // its last character. This leaves a wrap width of 4 columns.
+        let term_width = 10;
+        let mut dialog = MessageEditorDialog::new(0, "ab cdefgh");
+        assert_eq!(row_texts(&dialog.text, 4), ["ab ", "cdef", "gh"]);
+        dialog.cursor = 3;
+        press(&mut dialog, KeyCode::End, term_width);
+        assert_eq!(dialog.cursor, 6);
+        press(&mut dialog, KeyCode::Up, term_width);
+        assert_eq!(dialog.cursor, 2);
+
+        // Wide characters take up two columns each.
+        let mut dialog = MessageEditorDialog::new(0, "日本語\nabcdef");
+        dialog.cursor = "日本語\nabcd".len();
+        press(&mut dialog, KeyCode::Up, 80);
+        assert_eq!(dialog.cursor, "日本".len());
+        press(&mut dialog, KeyCode::Down, 80);
+        assert_eq!(dialog.cursor, "日本語\nabcd".len());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// The state of the open message history picker.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MessageHistoryPicker {
+    pub commit_idx: usize,
+    pub selected_idx: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct MessageHistoryDialog<'a> {
+    pub picker: MessageHistoryPicker,
+    pub messages: &'a [String],
+}
+
+impl MessageHistoryDialog<'_> {
+    /// The maximum number of messages listed at once.
+    const MAX_VISIBLE_MESSAGES: usize = 10;
+
+    /// The maximum number of characters of each subject to display.
+    const MAX_SUBJECT_WIDTH: usize = 72;
+}
+
+impl Component for MessageHistoryDialog<'_> {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::MessageHistoryDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let Self {
+            picker:
+                MessageHistoryPicker {
+                    commit_idx: _,
+                    selected_idx,
+                },
+            messages,
+        } = self;
+
+        // Scroll the list so that the selected message is always visible.
+        let start_idx = selected_idx.saturating_sub(Self::MAX_VISIBLE_MESSAGES - 1);
+        let mut lines = vec![
+            Line::from("Reuse a previous commit message:"),
+            Line::from(""),
+        ];
+        lines.extend(
+            messages
+                .iter()
+                .enumerate()
+                .skip(start_idx)
+                .take(Self::MAX_VISIBLE_MESSAGES)
+                .map(|(idx, message)| {
+                    let subject = message.lines().next().unwrap_or_default().trim();
+                    let subject: String = if subject.is_empty() {
+                        "(no message)".to_owned()
+                    } else {
+                        subject.chars().take(Self::MAX_SUBJECT_WIDTH).collect()
+                    };
+                    if idx == *selected_idx {
+                        Line::from(Span::styled(
+                            format!("  > {subject}"),
+                            Style::default().add_modifier(Modifier::REVERSED),
+                        ))
+                    } else {
+                        Line::from(format!("    {subject}"))
+                    }
+                }),
+        );
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "j/k: move  space/c: use  e: use and edit  q: cancel",
+            Style::default().add_modifier(Modifier::DIM),
+        )));
+        let body = Text::from(lines);
+
+        let buttons = [Button {
+            id: ComponentId::MessageHistoryDialogCancelButton,
+            label: Cow::Borrowed("Cancel"),
+            style: Style::default(),
+            is_focused: false,
+        }];
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed("Message history"),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
@@ -1,13 +1,33 @@
-use crate::ui::components::{app::SelectionKey, file::FileKey};
+use crate::ui::state::{FileKey, SelectionKey};
 
+pub mod action_history_panel;
 pub mod app;
 pub mod commit_message_view;
 pub mod commit_view;
 pub mod dialog;
+pub mod directory;
+pub mod empty_selection_dialog;
+pub mod error_dialog;
 pub mod file;
+pub mod file_contents_dialog;
+pub mod file_list_pane;
 pub mod help_dialog;
 pub mod line;
+pub mod line_diff_dialog;
+pub mod message_diff_dialog;
+pub mod message_editor_dialog;
+pub mod message_history_dialog;
+pub mod preview_dialog;
+pub mod quit_dialog;
+pub mod recovery_dialog;
 pub mod section;
+pub mod stats_bar;
+pub mod status_message;
+pub mod terminal_too_small;
+pub mod text_prompt;
+pub mod toggle_all_dialog;
+pub mod view_indicators;
+pub mod which_key_popup;
 pub mod widgets;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
@@ -20,6 +40,41 @@ pub enum ComponentId {
     SelectableItem(SelectionKey),
     ToggleBox(SelectionKey),
     ExpandBox(SelectionKey),
+    StatsBar,
+    ViewIndicators,
+    FileListPane,
+    FileListPaneItem(SelectionKey),
+    TextPrompt,
+    StatusMessage,
     HelpDialog,
     HelpDialogQuitButton,
+    MessageDiffDialog,
+    MessageDiffDialogCloseButton,
+    MessageEditorDialog,
+    MessageEditorDialogSaveButton,
+    LineDiffDialog,
+    LineDiffDialogCloseButton,
+    PreviewDialog,
+    PreviewDialogCloseButton,
+    FileContentsDialog,
+    FileContentsDialogCloseButton,
+    MessageHistoryDialog,
+    MessageHistoryDialogCancelButton,
+    WhichKeyPopup,
+    ActionHistoryPanel,
+    RecoveryDialog,
+    RecoveryDialogRestoreButton,
+    RecoveryDialogDiscardButton,
+    ErrorDialog,
+    ErrorDialogCloseButton,
+    TerminalTooSmall,
+    ToggleAllDialog,
+    ToggleAllDialogToggleButton,
+    ToggleAllDialogCancelButton,
+    EmptySelectionDialog,
+    EmptySelectionDialogAcceptButton,
+    EmptySelectionDialogCancelButton,
+    QuitDialog,
+    QuitDialogQuitButton,
+    QuitDialogGoBackButton,
 }
@@ -0,0 +1,120 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// The kind of a line of the preview, which determines its style.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PreviewLineKind {
+    FileHeader,
+    SectionHeader,
+    /// A change which isn't made of lines, such as a mode change.
+    Metadata,
+    Removed,
+    Added,
+}
+
+/// Shows the diff which will result from the current selections, i.e. only
+/// the checked changes, so that the user can double-check it before
+/// confirming. The diff can be scrolled, but not changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PreviewDialog {
+    pub lines: Vec<(PreviewLineKind, String)>,
+    pub num_files: usize,
+    pub num_added: usize,
+    pub num_removed: usize,
+    /// The index of the first line to show.
+    pub scroll_offset: usize,
+}
+
+impl PreviewDialog {
+    /// The number of rows around the diff lines: the borders of the dialog,
+    /// and the summary line and the blank line after it.
+    const NUM_CHROME_ROWS: usize = 4;
+
+    /// The number of diff lines which fit in a terminal of the given height,
+    /// leaving a row free above and below the dialog.
+    pub fn num_visible_lines(term_height: usize) -> usize {
+        term_height.saturating_sub(Self::NUM_CHROME_ROWS + 2).max(1)
+    }
+
+    /// The largest scroll offset which still fills the dialog.
+    pub fn max_scroll_offset(&self, term_height: usize) -> usize {
+        self.lines
+            .len()
+            .saturating_sub(Self::num_visible_lines(term_height))
+    }
+}
+
+impl Component for PreviewDialog {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::PreviewDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let Self {
+            lines: preview_lines,
+            num_files,
+            num_added,
+            num_removed,
+            scroll_offset,
+        } = self;
+
+        let term_height = viewport.rect().height;
+        let num_visible_lines = Self::num_visible_lines(term_height);
+        let scroll_offset = (*scroll_offset).min(self.max_scroll_offset(term_height));
+        let files = if *num_files == 1 { "file" } else { "files" };
+        let mut summary =
+            format!("The selected changes to {num_files} {files} (+{num_added} -{num_removed}):");
+        if preview_lines.len() > num_visible_lines {
+            let last_line = (scroll_offset + num_visible_lines).min(preview_lines.len());
+            summary.push_str(&format!(
+                " lines {}-{last_line} of {}, j/k to scroll",
+                scroll_offset + 1,
+                preview_lines.len()
+            ));
+        }
+
+        let theme = viewport.theme();
+        let mut lines = vec![Line::from(summary), Line::from("")];
+        lines.extend(
+            preview_lines
+                .iter()
+                .skip(scroll_offset)
+                .take(num_visible_lines)
+                .map(|(kind, text)| {
+                    let style = match kind {
+                        PreviewLineKind::FileHeader => {
+                            Style::default().add_modifier(Modifier::BOLD)
+                        }
+                        PreviewLineKind::SectionHeader => theme.accent,
+                        PreviewLineKind::Metadata => theme.metadata,
+                        PreviewLineKind::Removed => theme.removed,
+                        PreviewLineKind::Added => theme.added,
+                    };
+                    Line::from(Span::styled(text.as_str(), style))
+                }),
+        );
+        let body = Text::from(lines);
+
+        let buttons = [Button {
+            id: ComponentId::PreviewDialogCloseButton,
+            label: Cow::Borrowed("Close"),
+            style: Style::default(),
+            is_focused: true,
+        }];
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed("Preview"),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::render::{Component, Viewport};
+use crate::types::DirtyState;
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// A button of the [`QuitDialog`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum QuitDialogButton {
+    Quit,
+    /// Focused by default, so that pressing Enter by accident doesn't throw
+    /// away the user's work.
+    #[default]
+    GoBack,
+}
+
+impl QuitDialogButton {
+    /// The other button, to move the focus to.
+    pub fn other(self) -> Self {
+        match self {
+            Self::Quit => Self::GoBack,
+            Self::GoBack => Self::Quit,
+        }
+    }
+}
+
+/// Asks for confirmation before quitting without accepting, when the user
+/// changed the selections or the commit messages, since they would be lost.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QuitDialog {
+    /// What the user changed, to say what would be lost.
+    pub dirty: DirtyState,
+    pub focused_button: QuitDialogButton,
+}
+
+impl QuitDialog {
+    pub fn new(dirty: DirtyState) -> Self {
+        Self {
+            dirty,
+            focused_button: Default::default(),
+        }
+    }
+
+    /// A description of the changes which would be lost, like "the selected
+    /// changes and the commit messages".
+    fn describe_changes(&self) -> String {
+        let DirtyState {
+            commit_messages,
+            selections,
+            deferred_files,
+        } = self.dirty;
+        let changes: Vec<&str> = [
+            (selections, "the selected changes"),
+            (commit_messages, "the commit messages"),
+            (deferred_files, "the deferred files"),
+        ]
+        .into_iter()
+        .filter(|(is_dirty, _)| *is_dirty)
+        .map(|(_, description)| description)
+        .collect();
+        match changes.as_slice() {
+            [] => "your changes".to_string(),
+            [change] => change.to_string(),
+            [changes @ .., last] => format!("{} and {last}", changes.join(", ")),
+        }
+    }
+}
+
+impl Component for QuitDialog {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::QuitDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let Self {
+            dirty: _,
+            focused_button,
+        } = self;
+
+        let body = Text::from(vec![
+            Line::from(format!("Quit and discard {}?", self.describe_changes())),
+            Line::from(""),
+            Line::from(Span::styled(
+                "tab/←/→: switch  enter: choose  q: quit  esc: go back",
+                Style::default().add_modifier(Modifier::DIM),
+            )),
+        ]);
+
+        let buttons = [
+            Button {
+                id: ComponentId::QuitDialogQuitButton,
+                label: Cow::Borrowed("Quit"),
+                style: Style::default(),
+                is_focused: *focused_button == QuitDialogButton::Quit,
+            },
+            Button {
+                id: ComponentId::QuitDialogGoBackButton,
+                label: Cow::Borrowed("Go back"),
+                style: Style::default(),
+                is_focused: *focused_button == QuitDialogButton::GoBack,
+            },
+        ];
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed("Discard changes?"),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// Offers to restore the selections of a session which was interrupted
+/// before the user finished, as saved in the recovery file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecoveryDialog {
+    /// The number of changed lines, sections and files which were selected
+    /// in the interrupted session.
+    pub num_checked: usize,
+    /// The number of files which were deferred in the interrupted session.
+    pub num_deferred_files: usize,
+}
+
+impl Component for RecoveryDialog {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::RecoveryDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let Self {
+            num_checked,
+            num_deferred_files,
+        } = self;
+
+        let mut lines = vec![
+            Line::from("A previous session for these changes was interrupted."),
+            Line::from("Restore its selections and expanded items?"),
+            Line::from(""),
+            Line::from(format!("  {num_checked} changes selected")),
+        ];
+        if *num_deferred_files > 0 {
+            lines.push(Line::from(format!("  {num_deferred_files} files deferred")));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "space/c: restore  q: discard",
+            Style::default().add_modifier(Modifier::DIM),
+        )));
+        let body = Text::from(lines);
+
+        let buttons = [
+            Button {
+                id: ComponentId::RecoveryDialogRestoreButton,
+                label: Cow::Borrowed("Restore"),
+                style: Style::default(),
+                is_focused: true,
+            },
+            Button {
+                id: ComponentId::RecoveryDialogDiscardButton,
+                label: Cow::Borrowed("Discard"),
+                style: Style::default(),
+                is_focused: false,
+            },
+        ];
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed("Recover session"),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
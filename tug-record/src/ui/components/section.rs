@@ -1,37 +1,30 @@
 use std::cmp::min;
 
 use ratatui::{
-    style::{Color, Modifier, Style},
-    text::Span,
+    style::{Modifier, Style},
+    text::{Line, Span},
 };
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
     render::{Component, Rect, Viewport},
     ui::components::{
-        app::SelectionKey,
-        line::{LineKey, SectionLineView, SectionLineViewInner},
-        widgets::{highlight_rect, TristateBox, TristateIconStyle},
+        line::{LineHighlights, SectionLineView, SectionLineViewInner},
+        widgets::{bookmark_span, highlight_rect, TristateBox, TristateIconStyle},
         ComponentId,
     },
+    ui::state::{LineKey, SectionKey, SelectionKey},
+    ui::theme::Theme,
     util::UsizeExt,
-    FileMode, Section, SectionChangedLine, Tristate,
+    BinaryMetadata, ChangeType, FileMode, Section, SectionChangedLine, SectionInterdiff, Tristate,
 };
 
-pub const NUM_CONTEXT_LINES: usize = 4;
-
 #[derive(Clone, Debug)]
 pub enum SectionSelection {
     SectionHeader,
     ChangedLine(usize),
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
-pub struct SectionKey {
-    pub commit_idx: usize,
-    pub file_idx: usize,
-    pub section_idx: usize,
-}
-
 #[derive(Clone, Debug)]
 pub struct SectionView<'a> {
     pub is_read_only: bool,
@@ -43,7 +36,22 @@ pub struct SectionView<'a> {
     pub editable_section_num: usize,
     pub total_num_editable_sections: usize,
     pub section: &'a Section<'a>,
-    pub line_start_num: usize,
+    /// The line number of the first line of this section in the old file.
+    pub old_line_start_num: usize,
+    /// The line number of the first line of this section in the new file.
+    pub new_line_start_num: usize,
+    /// The index of the changed line to wrap instead of truncating, if any.
+    pub wrapped_line_idx: Option<usize>,
+    /// Whether to show the annotations of the changed lines.
+    pub show_annotations: bool,
+    /// How the section compares to the previous state, if one was given.
+    pub interdiff: Option<SectionInterdiff>,
+    /// How many unchanged lines to show around the changed sections.
+    pub num_context_lines: usize,
+    /// Whether the user bookmarked this section to come back to it.
+    pub is_bookmarked: bool,
+    /// The syntax highlighting of each line of the section, if any.
+    pub line_highlights: &'a [LineHighlights],
 }
 
 impl SectionView<'_> {
@@ -80,7 +88,14 @@ impl Component for SectionView<'_> {
             editable_section_num,
             total_num_editable_sections,
             section,
-            line_start_num,
+            old_line_start_num,
+            new_line_start_num,
+            wrapped_line_idx,
+            show_annotations,
+            interdiff,
+            num_context_lines,
+            is_bookmarked,
+            line_highlights,
         } = self;
         viewport.draw_blank(Rect {
             x,
@@ -104,8 +119,8 @@ impl Component for SectionView<'_> {
                 let lines: Vec<_> = lines.iter().enumerate().collect();
                 let is_first_section = section_idx == 0;
                 let is_last_section = section_idx + 1 == *total_num_sections;
-                let before_ellipsis_lines = &lines[..min(NUM_CONTEXT_LINES, lines.len())];
-                let after_ellipsis_lines = &lines[lines.len().saturating_sub(NUM_CONTEXT_LINES)..];
+                let before_ellipsis_lines = &lines[..min(*num_context_lines, lines.len())];
+                let after_ellipsis_lines = &lines[lines.len().saturating_sub(*num_context_lines)..];
 
                 match (before_ellipsis_lines, after_ellipsis_lines) {
                     ([.., (last_before_idx, _)], [(first_after_idx, _), ..])
@@ -118,9 +133,9 @@ impl Component for SectionView<'_> {
                         let overlapped_lines = &lines[first_before_idx..=last_after_idx];
                         let overlapped_lines = if is_first_section {
                             &overlapped_lines
-                                [overlapped_lines.len().saturating_sub(NUM_CONTEXT_LINES)..]
+                                [overlapped_lines.len().saturating_sub(*num_context_lines)..]
                         } else if is_last_section {
-                            &overlapped_lines[..lines.len().min(NUM_CONTEXT_LINES)]
+                            &overlapped_lines[..lines.len().min(*num_context_lines)]
                         } else {
                             overlapped_lines
                         };
@@ -134,8 +149,14 @@ impl Component for SectionView<'_> {
                                 },
                                 inner: SectionLineViewInner::Unchanged {
                                     line: line.as_ref(),
-                                    line_num: line_start_num + line_idx,
+                                    old_line_num: old_line_start_num + line_idx,
+                                    new_line_num: new_line_start_num + line_idx,
                                 },
+                                is_wrapped: false,
+                                show_annotation: false,
+                                highlights: line_highlights
+                                    .get(*line_idx)
+                                    .map_or(&[], Vec::as_slice),
                             };
                             viewport.draw_component(x + 2, y + dy.unwrap_isize(), &line_view);
                         }
@@ -156,19 +177,23 @@ impl Component for SectionView<'_> {
                             },
                             inner: SectionLineViewInner::Unchanged {
                                 line: line.as_ref(),
-                                line_num: line_start_num + line_idx,
+                                old_line_num: old_line_start_num + line_idx,
+                                new_line_num: new_line_start_num + line_idx,
                             },
+                            is_wrapped: false,
+                            show_annotation: false,
+                            highlights: line_highlights.get(*line_idx).map_or(&[], Vec::as_slice),
                         };
                         viewport.draw_component(x + 2, y + dy, &line_view);
                         dy += 1;
                     }
                 }
 
-                let should_render_ellipsis = lines.len() > NUM_CONTEXT_LINES;
+                let should_render_ellipsis = lines.len() > *num_context_lines;
                 if should_render_ellipsis {
                     let ellipsis = "\u{22EE}";
                     viewport.draw_span(
-                        x + 6, // align with line numbering
+                        x + 10, // align with line numbering
                         y + dy,
                         &Span::styled(ellipsis, Style::default().add_modifier(Modifier::DIM)),
                     );
@@ -186,8 +211,12 @@ impl Component for SectionView<'_> {
                             },
                             inner: SectionLineViewInner::Unchanged {
                                 line: line.as_ref(),
-                                line_num: line_start_num + line_idx,
+                                old_line_num: old_line_start_num + line_idx,
+                                new_line_num: new_line_start_num + line_idx,
                             },
+                            is_wrapped: false,
+                            show_annotation: false,
+                            highlights: line_highlights.get(*line_idx).map_or(&[], Vec::as_slice),
                         };
                         viewport.draw_component(x + 2, y + dy, &line_view);
                         dy += 1;
@@ -195,7 +224,11 @@ impl Component for SectionView<'_> {
                 }
             }
 
-            Section::Changed { lines } => {
+            Section::Changed {
+                lines,
+                label,
+                context,
+            } => {
                 // Draw section header from left to right.
                 let mut cursor_x = x;
 
@@ -207,16 +240,66 @@ impl Component for SectionView<'_> {
                 let toggle_box_rect = viewport.draw_component(cursor_x, y, toggle_box);
                 cursor_x += toggle_box_rect.width.unwrap_isize() + 1;
 
-                // 3. Draw the section description text.
-                viewport.draw_text(
-                    cursor_x,
-                    y,
-                    Span::styled(
-                        format!("Section {editable_section_num}/{total_num_editable_sections}"),
-                        // Use a distinct color for hunk headers.
-                        Style::default().fg(Color::LightMagenta),
-                    ),
-                );
+                // 3. Draw the section description text, followed by the
+                // number of changed lines and the context of the changes, if
+                // any.
+                let mut spans = vec![Span::styled(
+                    match label {
+                        Some(label) => label.to_string(),
+                        None => {
+                            format!("Section {editable_section_num}/{total_num_editable_sections}")
+                        }
+                    },
+                    viewport.theme().section_header,
+                )];
+                let (mut num_added, mut num_removed, mut num_checked) = (0, 0, 0);
+                for line in lines {
+                    match line.change_type {
+                        ChangeType::Added => num_added += 1,
+                        ChangeType::Removed => num_removed += 1,
+                    }
+                    if line.assigned_commit_idx() == commit_idx {
+                        num_checked += 1;
+                    }
+                }
+                if num_added > 0 {
+                    spans.push(Span::styled(
+                        format!(" +{num_added}"),
+                        viewport.theme().added,
+                    ));
+                }
+                if num_removed > 0 {
+                    spans.push(Span::styled(
+                        format!(" -{num_removed}"),
+                        viewport.theme().removed,
+                    ));
+                }
+                spans.push(Span::styled(
+                    format!(" ({num_checked}/{} checked)", lines.len()),
+                    Style::default().add_modifier(Modifier::DIM),
+                ));
+                let interdiff_badge = match interdiff {
+                    None | Some(SectionInterdiff::Unchanged) => None,
+                    Some(SectionInterdiff::SelectionChanged) => Some(" (selection changed)"),
+                    Some(SectionInterdiff::New) => Some(" (new)"),
+                };
+                if let Some(interdiff_badge) = interdiff_badge {
+                    spans.push(Span::styled(
+                        interdiff_badge,
+                        viewport.theme().warning.add_modifier(Modifier::BOLD),
+                    ));
+                }
+                if *is_bookmarked {
+                    spans.push(Span::raw(" "));
+                    spans.push(bookmark_span(viewport.theme()));
+                }
+                if let Some(context) = context {
+                    spans.push(Span::styled(
+                        format!(" @@ {}", context.trim_end()),
+                        Style::default().add_modifier(Modifier::DIM),
+                    ));
+                }
+                viewport.draw_text(cursor_x, y, Line::from(spans));
 
                 match selection {
                     Some(SectionSelection::SectionHeader) => {
@@ -235,12 +318,17 @@ impl Component for SectionView<'_> {
 
                 if self.is_expanded() {
                     // Draw changed lines.
-                    let y = y + 1;
+                    let mut line_y = y + 1;
+                    let mut old_line_num = *old_line_start_num;
+                    let mut new_line_num = *new_line_start_num;
                     for (line_idx, line) in lines.iter().enumerate() {
+                        let is_checked = line.assigned_commit_idx() == commit_idx;
                         let SectionChangedLine {
-                            is_checked,
+                            is_checked: _,
+                            commit_idx: _,
                             change_type,
                             line,
+                            annotation,
                         } = line;
                         let is_focused = match selection {
                             Some(SectionSelection::ChangedLine(selected_line_idx)) => {
@@ -248,6 +336,12 @@ impl Component for SectionView<'_> {
                             }
                             Some(SectionSelection::SectionHeader) | None => false,
                         };
+                        let next_line_num = match change_type {
+                            ChangeType::Added => &mut new_line_num,
+                            ChangeType::Removed => &mut old_line_num,
+                        };
+                        let line_num = *next_line_num;
+                        *next_line_num += 1;
                         let line_key = LineKey {
                             commit_idx,
                             file_idx,
@@ -257,7 +351,7 @@ impl Component for SectionView<'_> {
                         let toggle_box = TristateBox {
                             id: ComponentId::ToggleBox(SelectionKey::Line(line_key)),
                             icon_style: TristateIconStyle::Check,
-                            tristate: Tristate::from(*is_checked),
+                            tristate: Tristate::from(is_checked),
                             is_read_only: *is_read_only,
                         };
                         let line_view = SectionLineView {
@@ -266,27 +360,36 @@ impl Component for SectionView<'_> {
                                 toggle_box,
                                 change_type: *change_type,
                                 line: line.as_ref(),
+                                line_num,
+                                annotation: annotation.as_ref(),
                             },
+                            is_wrapped: *wrapped_line_idx == Some(line_idx),
+                            show_annotation: *show_annotations,
+                            highlights: line_highlights.get(line_idx).map_or(&[], Vec::as_slice),
                         };
-                        let y = y + line_idx.unwrap_isize();
-                        viewport.draw_component(x + 2, y, &line_view);
+                        let line_rect = viewport.draw_component(x + 2, line_y, &line_view);
                         if is_focused {
                             highlight_rect(
                                 viewport,
                                 Rect {
                                     x: viewport.mask_rect().x,
-                                    y,
+                                    y: line_y,
                                     width: viewport.mask_rect().width,
-                                    height: 1,
+                                    height: line_rect.height,
                                 },
                             );
                         }
+                        line_y += line_rect.height.unwrap_isize();
                     }
                 }
             }
 
             // ... (Section::FileMode and Section::Binary remain unchanged) ...
-            Section::FileMode { is_checked, mode } => {
+            Section::FileMode {
+                is_checked: _,
+                commit_idx: _,
+                mode,
+            } => {
                 let is_focused = match selection {
                     Some(SectionSelection::SectionHeader) => true,
                     Some(SectionSelection::ChangedLine(_)) | None => false,
@@ -300,7 +403,7 @@ impl Component for SectionView<'_> {
                 let toggle_box = TristateBox {
                     id: ComponentId::ToggleBox(selection_key),
                     icon_style: TristateIconStyle::Check,
-                    tristate: Tristate::from(*is_checked),
+                    tristate: section.commit_tristate(commit_idx),
                     is_read_only: *is_read_only,
                 };
                 let toggle_box_rect = viewport.draw_component(x, y, &toggle_box);
@@ -313,7 +416,7 @@ impl Component for SectionView<'_> {
                     FileMode::Absent => "File deleted".to_owned(),
                 };
 
-                viewport.draw_text(x, y, Span::styled(text, Style::default().fg(Color::Magenta)));
+                viewport.draw_text(x, y, header_line(viewport.theme(), text, *is_bookmarked));
                 if is_focused {
                     highlight_rect(
                         viewport,
@@ -328,9 +431,12 @@ impl Component for SectionView<'_> {
             }
 
             Section::Binary {
-                is_checked,
+                is_checked: _,
+                commit_idx: _,
                 old_description,
                 new_description,
+                old_metadata,
+                new_metadata,
             } => {
                 let is_focused = match selection {
                     Some(SectionSelection::SectionHeader) => true,
@@ -344,13 +450,16 @@ impl Component for SectionView<'_> {
                 let toggle_box = TristateBox {
                     id: ComponentId::ToggleBox(SelectionKey::Section(section_key)),
                     icon_style: TristateIconStyle::Check,
-                    tristate: Tristate::from(*is_checked),
+                    tristate: section.commit_tristate(commit_idx),
                     is_read_only: *is_read_only,
                 };
                 let toggle_box_rect = viewport.draw_component(x, y, &toggle_box);
                 let x = x + toggle_box_rect.width.unwrap_isize() + 1;
 
-                let text = {
+                let has_metadata = old_metadata.is_some() || new_metadata.is_some();
+                let text = if has_metadata {
+                    "(binary contents)".to_owned()
+                } else {
                     let mut result =
                         vec![if old_description.is_some() || new_description.is_some() {
                             "binary contents:"
@@ -367,7 +476,16 @@ impl Component for SectionView<'_> {
                     result.push(description.join(" -> "));
                     format!("({})", result.join(" "))
                 };
-                viewport.draw_text(x, y, Span::styled(text, Style::default().fg(Color::Magenta)));
+                viewport.draw_text(x, y, header_line(viewport.theme(), text, *is_bookmarked));
+                if has_metadata {
+                    draw_binary_metadata_table(
+                        viewport,
+                        x,
+                        y + 1,
+                        old_metadata.as_ref(),
+                        new_metadata.as_ref(),
+                    );
+                }
 
                 if is_focused {
                     highlight_rect(
@@ -384,3 +502,96 @@ impl Component for SectionView<'_> {
         }
     }
 }
+
+/// The header of a file mode or binary section, with its bookmark if any.
+fn header_line(theme: &Theme, text: String, is_bookmarked: bool) -> Line<'static> {
+    let mut spans = vec![Span::styled(text, theme.metadata)];
+    if is_bookmarked {
+        spans.push(Span::raw(" "));
+        spans.push(bookmark_span(theme));
+    }
+    Line::from(spans)
+}
+
+/// Draw the metadata of the old and new versions of a binary file side by
+/// side, with one row per kind of metadata known for either version. Values
+/// which differ between the versions are highlighted.
+fn draw_binary_metadata_table(
+    viewport: &mut Viewport<ComponentId>,
+    x: isize,
+    y: isize,
+    old_metadata: Option<&BinaryMetadata>,
+    new_metadata: Option<&BinaryMetadata>,
+) {
+    let values = |metadata: Option<&BinaryMetadata>| -> [Option<String>; 3] {
+        match metadata {
+            Some(BinaryMetadata {
+                num_bytes,
+                mime_type,
+                hash,
+            }) => [
+                num_bytes.map(|num_bytes| format!("{num_bytes} bytes")),
+                mime_type.as_ref().map(|mime_type| mime_type.to_string()),
+                hash.as_ref().map(|hash| hash.to_string()),
+            ],
+            None => [None, None, None],
+        }
+    };
+    let rows: Vec<(&str, Option<String>, Option<String>)> = ["Size", "Type", "Hash"]
+        .into_iter()
+        .zip(values(old_metadata).into_iter().zip(values(new_metadata)))
+        .filter(|(_, (old_value, new_value))| old_value.is_some() || new_value.is_some())
+        .map(|(label, (old_value, new_value))| (label, old_value, new_value))
+        .collect();
+
+    let missing = |metadata: Option<&BinaryMetadata>| match metadata {
+        Some(_) => "?",
+        None => "(absent)",
+    };
+    let label_width = rows
+        .iter()
+        .map(|(label, _, _)| label.len())
+        .max()
+        .unwrap_or_default();
+    let old_width = rows
+        .iter()
+        .map(|(_, old_value, _)| {
+            old_value
+                .as_deref()
+                .unwrap_or(missing(old_metadata))
+                .width()
+        })
+        .chain(["Old".len()])
+        .max()
+        .unwrap_or_default();
+    let dim_style = Style::default().add_modifier(Modifier::DIM);
+    viewport.draw_text(
+        x,
+        y,
+        Line::from(vec![
+            Span::raw(" ".repeat(label_width + 2)),
+            Span::styled(format!("{:old_width$}  ", "Old"), dim_style),
+            Span::styled("New", dim_style),
+        ]),
+    );
+    for ((label, old_value, new_value), y) in rows.iter().zip(y + 1..) {
+        let value_style = if old_value == new_value {
+            viewport.theme().metadata
+        } else {
+            viewport.theme().warning
+        };
+        let old_value = old_value.as_deref().unwrap_or(missing(old_metadata));
+        let new_value = new_value.as_deref().unwrap_or(missing(new_metadata));
+        let old_padding = " ".repeat(old_width.saturating_sub(old_value.width()));
+        viewport.draw_text(
+            x,
+            y,
+            Line::from(vec![
+                Span::styled(format!("{label:label_width$}  "), dim_style),
+                Span::styled(old_value, value_style),
+                Span::raw(format!("{old_padding}  ")),
+                Span::styled(new_value, value_style),
+            ]),
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use crate::render::{Component, Rect, Viewport};
+use crate::ui::components::ComponentId;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use std::fmt::Debug;
+
+/// A one-line summary of the whole diff and of how much of it is currently
+/// selected.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StatsBar {
+    pub num_files: usize,
+    pub num_selected_files: usize,
+    pub num_added: usize,
+    pub num_removed: usize,
+    pub num_selected_lines: usize,
+}
+
+fn pluralize(count: usize, singular: &str, plural: &str) -> String {
+    if count == 1 {
+        format!("{count} {singular}")
+    } else {
+        format!("{count} {plural}")
+    }
+}
+
+impl Component for StatsBar {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::StatsBar
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
+        let Self {
+            num_files,
+            num_selected_files,
+            num_added,
+            num_removed,
+            num_selected_lines,
+        } = *self;
+
+        viewport.draw_blank(Rect {
+            x: viewport.mask_rect().x,
+            y,
+            width: viewport.mask_rect().width,
+            height: 1,
+        });
+        let dim = Style::default().add_modifier(Modifier::DIM);
+        let line = Line::from(vec![
+            Span::raw(pluralize(num_files, "file", "files")),
+            Span::styled(" changed, ", dim),
+            Span::styled(
+                pluralize(num_added, "insertion(+)", "insertions(+)"),
+                viewport.theme().added,
+            ),
+            Span::styled(", ", dim),
+            Span::styled(
+                pluralize(num_removed, "deletion(-)", "deletions(-)"),
+                viewport.theme().removed,
+            ),
+            Span::styled(" • selected: ", dim),
+            Span::raw(format!("{num_selected_files}/{num_files} files, ")),
+            Span::raw(format!(
+                "{num_selected_lines}/{} lines",
+                num_added + num_removed
+            )),
+        ]);
+        viewport.draw_text(x, y, line);
+    }
+}
@@ -0,0 +1,32 @@
+use crate::render::{Component, Rect, Viewport};
+use crate::ui::components::ComponentId;
+use crate::ui::state::StatusMessage;
+use ratatui::text::Span;
+
+impl Component for StatusMessage {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::StatusMessage
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _x: isize, _y: isize) {
+        let Self {
+            message,
+            is_error,
+            expires_at: _,
+        } = self;
+        let rect = Rect {
+            height: 1,
+            ..viewport.rect()
+        };
+        viewport.draw_blank(rect);
+        viewport.set_style(rect, viewport.theme().bar);
+        let style = if *is_error {
+            viewport.theme().error
+        } else {
+            viewport.theme().success
+        };
+        viewport.draw_span(rect.x, rect.y, &Span::styled(message.as_str(), style));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::ComponentId;
+use crate::util::UsizeExt;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Span;
+use std::fmt::Debug;
+
+/// Drawn instead of the app when the terminal is too small to lay it out, so
+/// that the user knows to resize it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TerminalTooSmall;
+
+impl TerminalTooSmall {
+    /// The smallest terminal width at which the app is drawn.
+    pub const MIN_WIDTH: usize = 40;
+
+    /// The smallest terminal height at which the app is drawn.
+    pub const MIN_HEIGHT: usize = 10;
+
+    /// Whether a terminal with the given dimensions is too small.
+    pub fn is_too_small(width: usize, height: usize) -> bool {
+        width < Self::MIN_WIDTH || height < Self::MIN_HEIGHT
+    }
+}
+
+impl Component for TerminalTooSmall {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::TerminalTooSmall
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _x: isize, _y: isize) {
+        let Self = self;
+        let rect = viewport.rect();
+        viewport.draw_blank(rect);
+        let y = rect.y + (rect.height / 2).unwrap_isize();
+        viewport.draw_span(
+            rect.x,
+            y,
+            &Span::raw(format!(
+                "terminal too small: need {}x{}",
+                Self::MIN_WIDTH,
+                Self::MIN_HEIGHT
+            )),
+        );
+        viewport.draw_span(
+            rect.x,
+            y + 1,
+            &Span::styled(
+                format!("(currently {}x{}, q: quit)", rect.width, rect.height),
+                Style::default().add_modifier(Modifier::DIM),
+            ),
+        );
+    }
+}
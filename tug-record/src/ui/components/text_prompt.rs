@@ -0,0 +1,93 @@
+use crate::render::{Component, Rect, Viewport};
+use crate::ui::components::ComponentId;
+use crate::ui::key_event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use std::fmt::Debug;
+
+/// The result of passing a key press to a [`TextPrompt`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TextPromptAction {
+    /// The text was edited, or the key was ignored.
+    Edit,
+    /// The user accepted the text.
+    Submit,
+    /// The user dismissed the prompt.
+    Cancel,
+}
+
+/// A single-line text input, drawn over the top row of the screen.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TextPrompt {
+    pub label: &'static str,
+    pub text: String,
+}
+
+impl TextPrompt {
+    pub fn new(label: &'static str, text: impl Into<String>) -> Self {
+        Self {
+            label,
+            text: text.into(),
+        }
+    }
+
+    /// Update the text in response to a key press.
+    pub fn handle_key(&mut self, key_event: KeyEvent) -> TextPromptAction {
+        let KeyEvent {
+            code,
+            modifiers,
+            kind,
+            state: _,
+        } = key_event;
+        if kind != KeyEventKind::Press {
+            return TextPromptAction::Edit;
+        }
+        match (code, modifiers) {
+            (KeyCode::Enter, _) => TextPromptAction::Submit,
+            (KeyCode::Esc, _) => TextPromptAction::Cancel,
+            (KeyCode::Backspace, _) => {
+                self.text.pop();
+                TextPromptAction::Edit
+            }
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
+                self.text.clear();
+                TextPromptAction::Edit
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.text.push(c);
+                TextPromptAction::Edit
+            }
+            _ => TextPromptAction::Edit,
+        }
+    }
+}
+
+impl Component for TextPrompt {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::TextPrompt
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _x: isize, _y: isize) {
+        let Self { label, text } = self;
+        let rect = Rect {
+            height: 1,
+            ..viewport.rect()
+        };
+        viewport.draw_blank(rect);
+        viewport.set_style(rect, viewport.theme().bar);
+        viewport.draw_text(
+            rect.x,
+            rect.y,
+            Line::from(vec![
+                Span::styled(
+                    format!("{label}: "),
+                    viewport.theme().accent.add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(text.as_str()),
+                Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)),
+            ]),
+        );
+    }
+}
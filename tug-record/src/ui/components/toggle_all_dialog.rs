@@ -0,0 +1,71 @@
+use crate::render::{Component, Viewport};
+use crate::ui::components::dialog::Dialog;
+use crate::ui::components::widgets::Button;
+use crate::ui::components::ComponentId;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// Asks for confirmation before toggling every change at once, since doing so
+/// by accident on a large diff would throw away a lot of careful selections.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ToggleAllDialog {
+    /// The number of files whose changes would be toggled.
+    pub num_files: usize,
+
+    /// Whether all changes would be set to the same state, as with
+    /// [`crate::Event::ToggleAllUniform`], rather than each being inverted.
+    pub is_uniform: bool,
+}
+
+impl Component for ToggleAllDialog {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::ToggleAllDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
+        let Self {
+            num_files,
+            is_uniform,
+        } = self;
+
+        let description = if *is_uniform {
+            format!("Select or deselect all changes in {num_files} files?")
+        } else {
+            format!("Invert the selection of all changes in {num_files} files?")
+        };
+        let body = Text::from(vec![
+            Line::from(description),
+            Line::from(""),
+            Line::from(Span::styled(
+                "space/c: toggle  q: cancel",
+                Style::default().add_modifier(Modifier::DIM),
+            )),
+        ]);
+
+        let buttons = [
+            Button {
+                id: ComponentId::ToggleAllDialogToggleButton,
+                label: Cow::Borrowed("Toggle"),
+                style: Style::default(),
+                is_focused: false,
+            },
+            Button {
+                id: ComponentId::ToggleAllDialogCancelButton,
+                label: Cow::Borrowed("Cancel"),
+                style: Style::default(),
+                is_focused: true,
+            },
+        ];
+        let dialog = Dialog {
+            id: self.id(),
+            title: Cow::Borrowed("Toggle all changes"),
+            body: Cow::Borrowed(&body),
+            buttons: &buttons,
+        };
+        viewport.draw_component(0, 0, &dialog);
+    }
+}
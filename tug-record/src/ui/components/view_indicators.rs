@@ -0,0 +1,164 @@
+use crate::render::{Component, Rect, Viewport};
+use crate::ui::components::ComponentId;
+use crate::ui::state::FileSortOrder;
+use ratatui::style::Modifier;
+use ratatui::text::{Line, Span};
+use std::fmt::Debug;
+
+/// A one-line summary of the view settings which affect which files are
+/// shown, and in which order. Only drawn when some setting differs from its
+/// default, so that the user knows why the file list looks different. Also
+/// indicates when the changes are read-only, when a macro is being recorded,
+/// and how the changes compare to a previous state.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ViewIndicators {
+    pub is_read_only: bool,
+    pub file_sort_order: FileSortOrder,
+    pub file_filter: Option<String>,
+    pub hide_unselected_files: bool,
+    pub hide_selected_files: bool,
+    /// How the changes compare to the previous state, if one was given.
+    pub interdiff: Option<InterdiffIndicator>,
+    /// Only the files are listed, without their changes.
+    pub is_outline: bool,
+    /// The path of the file which is zoomed into, if any.
+    pub zoomed_file: Option<String>,
+    pub num_visible_files: usize,
+    pub num_files: usize,
+    pub recording_macro: Option<char>,
+}
+
+/// A summary of the interdiff with the previous state.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct InterdiffIndicator {
+    pub num_changed_files: usize,
+    pub num_disappeared_files: usize,
+    pub hide_unchanged_files: bool,
+}
+
+impl ViewIndicators {
+    pub fn is_empty(&self) -> bool {
+        let Self {
+            is_read_only,
+            file_sort_order,
+            file_filter,
+            hide_unselected_files,
+            hide_selected_files,
+            interdiff,
+            is_outline,
+            zoomed_file,
+            num_visible_files: _,
+            num_files: _,
+            recording_macro,
+        } = self;
+        !is_read_only
+            && *file_sort_order == FileSortOrder::Input
+            && file_filter.is_none()
+            && !hide_unselected_files
+            && !hide_selected_files
+            && interdiff.is_none()
+            && !is_outline
+            && zoomed_file.is_none()
+            && recording_macro.is_none()
+    }
+}
+
+impl Component for ViewIndicators {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::ViewIndicators
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
+        let Self {
+            is_read_only,
+            file_sort_order,
+            file_filter,
+            hide_unselected_files,
+            hide_selected_files,
+            interdiff,
+            is_outline,
+            zoomed_file,
+            num_visible_files,
+            num_files,
+            recording_macro,
+        } = self;
+
+        let mut indicators = Vec::new();
+        if *is_read_only {
+            indicators.push("read-only (Space to page down)".to_string());
+        }
+        if let Some(register) = recording_macro {
+            indicators.push(format!("recording macro @{register} (Q to stop)"));
+        }
+        if *is_outline {
+            indicators.push("outline (O to show changes)".to_string());
+        }
+        // The filters and the sort order don't apply to a zoomed file.
+        if let Some(zoomed_file) = zoomed_file {
+            indicators.push(format!("zoomed into {zoomed_file} (Z or Esc to exit)"));
+        } else {
+            if let Some(file_filter) = file_filter {
+                indicators.push(format!(
+                    "filter: {file_filter:?} ({num_visible_files}/{num_files} files, Backspace to clear)"
+                ));
+            }
+            match (hide_unselected_files, hide_selected_files) {
+                (false, false) => {}
+                (true, false) => indicators.push(format!(
+                    "hiding unselected files ({num_visible_files}/{num_files} shown)"
+                )),
+                (false, true) => indicators.push(format!(
+                    "hiding selected files ({num_visible_files}/{num_files} shown)"
+                )),
+                (true, true) => indicators.push(format!(
+                    "only partially selected files ({num_visible_files}/{num_files} shown)"
+                )),
+            }
+            if let Some(InterdiffIndicator {
+                num_changed_files,
+                num_disappeared_files,
+                hide_unchanged_files,
+            }) = interdiff
+            {
+                let mut indicator =
+                    format!("{num_changed_files}/{num_files} files changed since last time");
+                if *num_disappeared_files > 0 {
+                    indicator.push_str(&format!(", {num_disappeared_files} gone"));
+                }
+                indicator.push_str(if *hide_unchanged_files {
+                    " (I to show unchanged)"
+                } else {
+                    " (I to hide unchanged)"
+                });
+                indicators.push(indicator);
+            }
+            match file_sort_order {
+                FileSortOrder::Input => {}
+                FileSortOrder::Path | FileSortOrder::Size | FileSortOrder::Status => {
+                    indicators.push(format!("sorted by {}", file_sort_order.label()));
+                }
+            }
+        }
+
+        viewport.draw_blank(Rect {
+            x: viewport.mask_rect().x,
+            y,
+            width: viewport.mask_rect().width,
+            height: 1,
+        });
+        let style = viewport.theme().accent.add_modifier(Modifier::DIM);
+        let mut spans = Vec::new();
+        for (i, indicator) in indicators.into_iter().enumerate() {
+            if i > 0 {
+                spans.push(Span::styled(" • ", style));
+            }
+            spans.push(Span::styled(
+                indicator,
+                style.remove_modifier(Modifier::DIM),
+            ));
+        }
+        viewport.draw_text(x, y, Line::from(spans));
+    }
+}
@@ -0,0 +1,70 @@
+use crate::render::{Component, Rect, Viewport};
+use crate::ui::components::ComponentId;
+use crate::util::UsizeExt;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use std::fmt::Debug;
+use unicode_width::UnicodeWidthStr;
+
+/// Lists the keys which can follow the keys pressed so far, along with what
+/// they do. Drawn over the bottom rows of the screen.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WhichKeyPopup {
+    /// The keys pressed so far.
+    pub pending: String,
+    /// The remaining keys of each binding, and its description.
+    pub continuations: Vec<(String, String)>,
+}
+
+impl Component for WhichKeyPopup {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::WhichKeyPopup
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _x: isize, _y: isize) {
+        let Self {
+            pending,
+            continuations,
+        } = self;
+        let viewport_rect = viewport.rect();
+        // Leave the top half of the screen visible.
+        let max_rows = (viewport_rect.height / 2).max(1);
+        let num_rows = (continuations.len() + 1).min(max_rows);
+        let rect = Rect {
+            y: viewport_rect.end_y() - num_rows.unwrap_isize(),
+            height: num_rows,
+            ..viewport_rect
+        };
+        viewport.draw_blank(rect);
+        viewport.set_style(rect, viewport.theme().bar);
+
+        viewport.draw_text(
+            rect.x,
+            rect.y,
+            Line::from(vec![
+                Span::styled(
+                    format!("{pending} "),
+                    viewport.theme().accent.add_modifier(Modifier::BOLD),
+                ),
+                Span::styled("…", Style::default().add_modifier(Modifier::DIM)),
+            ]),
+        );
+        let keys_width = continuations
+            .iter()
+            .map(|(keys, _)| keys.width())
+            .max()
+            .unwrap_or_default();
+        for ((keys, description), y) in continuations.iter().zip(rect.iter_ys().skip(1)) {
+            viewport.draw_text(
+                rect.x,
+                y,
+                Line::from(vec![
+                    Span::styled(format!("  {keys:keys_width$}  "), viewport.theme().warning),
+                    Span::raw(description.as_str()),
+                ]),
+            );
+        }
+    }
+}
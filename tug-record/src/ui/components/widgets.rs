@@ -1,6 +1,7 @@
 use crate::render::{Component, Rect, Viewport};
+use crate::ui::theme::Theme;
 use crate::Tristate;
-use ratatui::style::{Color, Modifier, Style};
+use ratatui::style::{Modifier, Style};
 use ratatui::text::Span;
 use std::borrow::Cow;
 use std::fmt::Debug;
@@ -45,7 +46,7 @@ impl<Id> TristateBox<Id> {
         }
     }
 
-    pub fn color(&self) -> Color {
+    pub fn style(&self, theme: &Theme) -> Style {
         let Self {
             tristate,
             icon_style,
@@ -53,12 +54,12 @@ impl<Id> TristateBox<Id> {
         } = self;
 
         match icon_style {
-            TristateIconStyle::Expand => Color::Magenta,
+            TristateIconStyle::Expand => theme.expand_box,
             // Render selection state icons.
             TristateIconStyle::Check => match tristate {
-                Tristate::False => Color::DarkGray,
-                Tristate::True => Color::Blue,
-                Tristate::Partial => Color::Yellow,
+                Tristate::False => theme.toggle_box_unchecked,
+                Tristate::True => theme.toggle_box_checked,
+                Tristate::Partial => theme.toggle_box_partial,
             },
         }
     }
@@ -72,12 +73,16 @@ impl<Id: Clone + Debug + Eq + Hash> Component for TristateBox<Id> {
     }
 
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
-        let style = if self.is_read_only {
-            Style::default().fg(Color::Gray).add_modifier(Modifier::DIM)
+        // Boxes which can't be toggled are left blank, but still take up the
+        // same space, so that the rest of the line lines up.
+        let span = if self.is_read_only {
+            Span::raw(" ".repeat(Span::raw(self.text()).width()))
         } else {
-            Style::default().fg(self.color()).add_modifier(Modifier::BOLD)
+            Span::styled(
+                self.text(),
+                self.style(viewport.theme()).add_modifier(Modifier::BOLD),
+            )
         };
-        let span = Span::styled(self.text(), style);
         viewport.draw_span(x, y, &span);
     }
 }
@@ -122,6 +127,11 @@ impl<Id: Clone + Debug + Eq + Hash> Component for Button<'_, Id> {
     }
 }
 
+/// The badge drawn after a bookmarked file or section.
+pub fn bookmark_span(theme: &Theme) -> Span<'static> {
+    Span::styled("(bookmarked)", theme.accent.add_modifier(Modifier::ITALIC))
+}
+
 pub fn highlight_rect<Id: Clone + Debug + Eq + Hash>(viewport: &mut Viewport<Id>, rect: Rect) {
-    viewport.set_style(rect, Style::default().bg(Color::Rgb(38, 38, 38)));
+    viewport.set_style(rect, viewport.theme().selected);
 }
@@ -1,8 +1,10 @@
-use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use super::input::TestingScreenshot;
+use super::input::{FileVersion, TestingScreenshot};
+use super::key_event::{KeyEvent, KeyEventKind};
+use super::keymap::{default_key_bindings, Key};
 
 #[allow(missing_docs)]
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub enum Event {
     None,
     QuitAccept,
@@ -11,6 +13,8 @@ pub enum Event {
     QuitEscape,
     TakeScreenshot(TestingScreenshot),
     Redraw,
+    /// The terminal was resized.
+    Resize,
     EnsureSelectionInViewport,
     ScrollUp,
     ScrollDown,
@@ -24,6 +28,16 @@ pub enum Event {
     /// Move focus to the next item of the same kind.
     FocusNextSameKind,
     FocusNextPage,
+    /// Move focus to the previous file.
+    FocusPrevFile,
+    /// Move focus to the next file.
+    FocusNextFile,
+    /// Move focus to the first item.
+    FocusFirst,
+    /// Move focus to the last item.
+    FocusLast,
+    /// Scroll so that the selected item is in the middle of the screen.
+    ScrollSelectionToCenter,
     FocusInner,
     /// If `fold_section` is true, and the current section is expanded, the
     /// section should be collapsed without moving focus. Otherwise, move the
@@ -33,190 +47,156 @@ pub enum Event {
     },
     ToggleItem,
     ToggleItemAndAdvance,
+    /// Toggle the whole file containing the selected item.
+    ToggleFile,
+    /// Toggle the whole section containing the selected line, without moving
+    /// the selection.
+    ToggleSection,
     ToggleAll,
     ToggleAllUniform,
     ExpandItem,
     ExpandAll,
+    /// A key press which has not yet been resolved to an action. Keys are
+    /// resolved by the UI, since their meaning depends on its state; for
+    /// example, typing into a text prompt.
+    Key(KeyEvent),
     ToggleCommitViewMode, // no key binding currently
+    /// Move focus to the previous commit, to assign changes to it.
+    FocusPrevCommit,
+    /// Move focus to the next commit, to assign changes to it.
+    FocusNextCommit,
+    /// Move the selected item out of the focused commit into the previous
+    /// one.
+    MoveItemToPrevCommit,
+    /// Move the selected item out of the focused commit into the next one.
+    MoveItemToNextCommit,
+    /// Edit the message of the focused commit in a dialog.
     EditCommitMessage,
+    /// Edit the message of the focused commit with the input, such as in the
+    /// user's editor.
+    EditCommitMessageExternally,
+    /// Show or hide the full body of the commit message.
+    ToggleCommitMessageExpanded,
+    /// Open a picker to reuse a message from the commit message history.
+    OpenMessageHistory,
+    /// Show or hide the summary of the whole diff.
+    ToggleStatsBar,
+    /// Show or hide the list of the most recent actions.
+    ToggleActionHistory,
+    /// Cycle through the orders in which files can be displayed.
+    CycleFileSortOrder,
+    /// Open a prompt to only show files whose paths match a filter.
+    FilterFiles,
+    /// Open a prompt to search the paths of the files and their changed
+    /// lines, selecting the first match as the text is typed.
+    Search,
+    /// Select the next match of the last search.
+    SearchNext,
+    /// Select the previous match of the last search.
+    SearchPrev,
+    /// Show all files again.
+    ClearFileFilter,
+    /// Show or hide the files which have no changes selected.
+    ToggleHideUnselectedFiles,
+    /// Show or hide the files which have all changes selected.
+    ToggleHideSelectedFiles,
+    /// Show or hide the files which are the same as in the previous state
+    /// given for the interdiff.
+    ToggleHideUnchangedFiles,
+    /// Switch between a flat list of files and a tree grouped by directory.
+    ToggleFileTree,
+    /// Switch between a single list of files and their changes, and a layout
+    /// with the list of files on the left and the changes of the selected
+    /// file on the right.
+    ToggleTwoPane,
+    /// List only the files, with their selection state and statistics, or
+    /// show their changes again.
+    ToggleOutline,
+    /// Show the diff which will result from the current selections.
+    PreviewSelection,
+    /// Show only the file of the selected item, using the whole screen, or
+    /// show all files again.
+    ToggleZoomFile,
+    /// Copy the path of the selected file to the clipboard.
+    CopyPath,
+    /// Copy the checked lines of the selected section or file to the
+    /// clipboard, either as plain text or as a diff.
+    CopySelectedLines {
+        as_diff: bool,
+    },
+    /// Show the full contents of the old or new version of the selected
+    /// file.
+    ViewFileContents {
+        version: FileVersion,
+    },
+    /// Show the old and new versions of the selected image file, if the
+    /// terminal can display images.
+    PreviewImages,
+    /// Mark the file of the selected item as deferred, to be decided on
+    /// later.
+    ToggleDeferItem,
+    /// Bookmark the selected file or section, to come back to it later, or
+    /// remove its bookmark. A selected line bookmarks its section.
+    ToggleBookmark,
+    /// Move focus to the previous bookmarked file or section, wrapping
+    /// around to the last one.
+    FocusPrevBookmark,
+    /// Move focus to the next bookmarked file or section, wrapping around to
+    /// the first one.
+    FocusNextBookmark,
+    /// Show the character-level changes between the selected changed line
+    /// and its counterpart.
+    ShowLineDiff,
+    /// Show the selected changed line in full by wrapping it, or truncate it
+    /// again.
+    ToggleWrapLine,
+    /// Show or hide the author and age of the changed lines, if known.
+    ToggleAnnotations,
+    /// Hide the unchanged lines around the changes, leaving only a separator
+    /// between them, or show them again.
+    ToggleContext,
+    /// The user stopped typing partway through a key binding with several
+    /// keys.
+    KeySequenceTimeout,
+    /// Start recording a macro into the register named by the next key, or
+    /// stop recording if a macro is being recorded.
+    RecordMacro,
+    /// Replay the macro in the register named by the next key. If a count
+    /// was typed first, the macro is replayed that many times.
+    ReplayMacro,
+    /// Revert the last change to the selections or commit messages.
+    Undo,
+    /// Apply the last undone change again.
+    Redo,
+    /// The user hasn't provided any input for
+    /// [`crate::RecordOptions::idle_timeout`].
+    Idle,
     Help,
 }
 
+#[cfg(not(target_family = "wasm"))]
 impl From<crossterm::event::Event> for Event {
     fn from(event: crossterm::event::Event) -> Self {
         use crossterm::event::Event;
         match event {
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('q'),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::QuitCancel,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Esc,
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::QuitEscape,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('c'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::QuitInterrupt,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('c'),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::QuitAccept,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('?'),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::Help,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Up | KeyCode::Char('y'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::ScrollUp,
-            Event::Key(KeyEvent {
-                code: KeyCode::Down | KeyCode::Char('e'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::ScrollDown,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::PageUp | KeyCode::Char('b'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::PageUp,
-            Event::Key(KeyEvent {
-                code: KeyCode::PageDown | KeyCode::Char('f'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::PageDown,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Up | KeyCode::Char('k'),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::FocusPrev,
-            Event::Key(KeyEvent {
-                code: KeyCode::Down | KeyCode::Char('j'),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::FocusNext,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::PageUp,
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::FocusPrevSameKind,
-            Event::Key(KeyEvent {
-                code: KeyCode::PageDown,
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::FocusNextSameKind,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Left | KeyCode::Char('h'),
-                modifiers: KeyModifiers::SHIFT,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::FocusOuter {
-                fold_section: false,
-            },
-            Event::Key(KeyEvent {
-                code: KeyCode::Left | KeyCode::Char('h'),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::FocusOuter { fold_section: true },
-            Event::Key(KeyEvent {
-                code: KeyCode::Right | KeyCode::Char('l'),
-                // The shift modifier is accepted for continuity with FocusOuter.
-                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::FocusInner,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('u'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::FocusPrevPage,
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('d'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::FocusNextPage,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Char(' '),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::ToggleItem,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Enter,
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) =>  Self::QuitInterrupt,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('a'),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::ToggleAll,
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('A'),
-                modifiers: KeyModifiers::SHIFT,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::ToggleAllUniform,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('f'),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::ExpandItem,
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('F'),
-                modifiers: KeyModifiers::SHIFT,
-                kind: KeyEventKind::Press,
-                state: _,
-            }) => Self::ExpandAll,
-
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('e'),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: _event,
-            }) => Self::EditCommitMessage,
-
+            Event::Key(key_event) => Self::Key(key_event),
+            Event::Resize(_, _) => Self::Resize,
             _event => Self::None,
         }
     }
 }
 
+impl From<KeyEvent> for Event {
+    /// Resolve a key press to the action which it is bound to by default. Use
+    /// a [`crate::Keymap`] to bind keys differently.
+    fn from(event: KeyEvent) -> Self {
+        if event.kind != KeyEventKind::Press {
+            return Self::None;
+        }
+        let key = Key::from(event);
+        default_key_bindings()
+            .iter()
+            .find_map(|(bound_key, bound_event)| (*bound_key == key).then(|| bound_event.clone()))
+            .unwrap_or(Self::None)
+    }
+}
@@ -0,0 +1,564 @@
+use std::ffi::OsStr;
+use std::path::Path;
+
+use ratatui::style::Style;
+
+use crate::types::{File, Section};
+use crate::ui::components::line::LineHighlights;
+use crate::ui::theme::Theme;
+
+/// The syntax of a language, as far as it's needed to highlight it one line
+/// at a time. This only recognizes keywords, literals and comments, which is
+/// enough to make the changes easier to read without a full parser.
+struct Language {
+    keywords: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    string_quotes: &'static [char],
+}
+
+const RUST: Language = Language {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    // Single quotes also start lifetimes, so character literals aren't
+    // highlighted.
+    string_quotes: &['"'],
+};
+
+const C: Language = Language {
+    keywords: &[
+        "auto",
+        "break",
+        "case",
+        "char",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "double",
+        "else",
+        "enum",
+        "extern",
+        "false",
+        "float",
+        "for",
+        "goto",
+        "if",
+        "inline",
+        "int",
+        "long",
+        "namespace",
+        "new",
+        "nullptr",
+        "private",
+        "protected",
+        "public",
+        "register",
+        "return",
+        "short",
+        "signed",
+        "sizeof",
+        "static",
+        "struct",
+        "switch",
+        "template",
+        "this",
+        "true",
+        "typedef",
+        "union",
+        "unsigned",
+        "using",
+        "virtual",
+        "void",
+        "volatile",
+        "while",
+    ],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    string_quotes: &['"', '\''],
+};
+
+const GO: Language = Language {
+    keywords: &[
+        "break",
+        "case",
+        "chan",
+        "const",
+        "continue",
+        "default",
+        "defer",
+        "else",
+        "fallthrough",
+        "false",
+        "for",
+        "func",
+        "go",
+        "goto",
+        "if",
+        "import",
+        "interface",
+        "map",
+        "nil",
+        "package",
+        "range",
+        "return",
+        "select",
+        "struct",
+        "switch",
+        "true",
+        "type",
+        "var",
+    ],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    string_quotes: &['"', '\'', '`'],
+};
+
+const JAVA: Language = Language {
+    keywords: &[
+        "abstract",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "do",
+        "else",
+        "enum",
+        "extends",
+        "false",
+        "final",
+        "finally",
+        "for",
+        "fun",
+        "if",
+        "implements",
+        "import",
+        "instanceof",
+        "interface",
+        "new",
+        "null",
+        "object",
+        "override",
+        "package",
+        "private",
+        "protected",
+        "public",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "throws",
+        "true",
+        "try",
+        "val",
+        "var",
+        "void",
+        "when",
+        "while",
+    ],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    string_quotes: &['"', '\''],
+};
+
+const JAVASCRIPT: Language = Language {
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "null",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "type",
+        "typeof",
+        "undefined",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    string_quotes: &['"', '\'', '`'],
+};
+
+const PYTHON: Language = Language {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+        "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True",
+        "try", "while", "with", "yield",
+    ],
+    line_comment: Some("#"),
+    block_comment: None,
+    string_quotes: &['"', '\''],
+};
+
+const SHELL: Language = Language {
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+        "in", "local", "return", "then", "until", "while",
+    ],
+    line_comment: Some("#"),
+    block_comment: None,
+    string_quotes: &['"', '\''],
+};
+
+impl Language {
+    /// The language of the file, guessed from the extension of its path.
+    fn from_path(path: &Path) -> Option<&'static Self> {
+        let language = match path.extension().and_then(OsStr::to_str)? {
+            "rs" => &RUST,
+            "c" | "cc" | "cpp" | "cxx" | "h" | "hh" | "hpp" => &C,
+            "go" => &GO,
+            "java" | "kt" | "kts" | "scala" => &JAVA,
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => &JAVASCRIPT,
+            "py" | "pyi" => &PYTHON,
+            "sh" | "bash" | "zsh" => &SHELL,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    /// Highlight the line. `in_block_comment` is whether the line starts
+    /// inside a block comment, and is updated to whether the next one does.
+    fn highlight_line(
+        &self,
+        theme: &Theme,
+        line: &str,
+        in_block_comment: &mut bool,
+    ) -> LineHighlights {
+        let mut highlights = Vec::new();
+        let mut idx = 0;
+        while idx < line.len() {
+            let (len, style) = self.token_at(theme, &line[idx..], in_block_comment);
+            if let Some(style) = style {
+                highlights.push((idx..idx + len, style));
+            }
+            idx += len;
+        }
+        highlights
+    }
+
+    /// The length and style of the token at the start of `rest`, which must
+    /// not be empty.
+    fn token_at(
+        &self,
+        theme: &Theme,
+        rest: &str,
+        in_block_comment: &mut bool,
+    ) -> (usize, Option<Style>) {
+        if let Some((start, end)) = self.block_comment {
+            if *in_block_comment || rest.starts_with(start) {
+                let search_from = if *in_block_comment { 0 } else { start.len() };
+                return match rest[search_from..].find(end) {
+                    Some(end_idx) => {
+                        *in_block_comment = false;
+                        (
+                            search_from + end_idx + end.len(),
+                            Some(theme.syntax_comment),
+                        )
+                    }
+                    None => {
+                        *in_block_comment = true;
+                        (rest.len(), Some(theme.syntax_comment))
+                    }
+                };
+            }
+        }
+        if let Some(line_comment) = self.line_comment {
+            if rest.starts_with(line_comment) {
+                return (rest.len(), Some(theme.syntax_comment));
+            }
+        }
+
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => return (0, None),
+        };
+        if self.string_quotes.contains(&c) {
+            // Strings which aren't closed on the same line run to its end.
+            let mut is_escaped = false;
+            for (quote_idx, quote) in rest.char_indices().skip(1) {
+                if is_escaped {
+                    is_escaped = false;
+                } else if quote == '\\' {
+                    is_escaped = true;
+                } else if quote == c {
+                    return (quote_idx + quote.len_utf8(), Some(theme.syntax_string));
+                }
+            }
+            return (rest.len(), Some(theme.syntax_string));
+        }
+        if c.is_alphanumeric() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let style = if c.is_ascii_digit() {
+                Some(theme.syntax_number)
+            } else if self.keywords.contains(&&rest[..len]) {
+                Some(theme.syntax_keyword)
+            } else {
+                None
+            };
+            return (len, style);
+        }
+        (c.len_utf8(), None)
+    }
+}
+
+/// The syntax highlighting of the lines of each section of the file, indexed
+/// like `File::sections`, in the styles of the theme. Empty if the language
+/// of the file isn't known.
+pub(crate) fn highlight_file(file: &File, theme: &Theme) -> Vec<Vec<LineHighlights>> {
+    let language = match Language::from_path(&file.path) {
+        Some(language) => language,
+        None => return Vec::new(),
+    };
+    file.sections
+        .iter()
+        .map(|section| {
+            // Start each section outside of a block comment, so that a
+            // comment which is only opened in one version of the file doesn't
+            // spill over into the rest of it.
+            let mut in_block_comment = false;
+            match section {
+                Section::Unchanged { lines } => lines
+                    .iter()
+                    .map(|line| language.highlight_line(theme, line, &mut in_block_comment))
+                    .collect(),
+                Section::Changed { lines, .. } => lines
+                    .iter()
+                    .map(|line| language.highlight_line(theme, &line.line, &mut in_block_comment))
+                    .collect(),
+                Section::FileMode { .. } | Section::Binary { .. } => Vec::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::types::{FileMode, RecordOptions, RecordState};
+    use crate::ui::state::App;
+
+    fn file(path: &'static str, sections: &[&[&'static str]]) -> File<'static> {
+        File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new(path)),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections: sections
+                .iter()
+                .map(|lines| Section::Unchanged {
+                    lines: lines.iter().copied().map(Cow::Borrowed).collect(),
+                })
+                .collect(),
+            is_deferred: false,
+            is_lossy: false,
+        }
+    }
+
+    /// The highlighted tokens of each line of each section, with the names
+    /// of their styles.
+    fn tokens(
+        path: &'static str,
+        sections: &[&[&'static str]],
+    ) -> Vec<Vec<Vec<(String, &'static str)>>> {
+        let theme = Theme::default();
+        let style_name = |style: Style| {
+            [
+                (theme.syntax_keyword, "keyword"),
+                (theme.syntax_string, "string"),
+                (theme.syntax_number, "number"),
+                (theme.syntax_comment, "comment"),
+            ]
+            .into_iter()
+            .find_map(|(theme_style, name)| (theme_style == style).then_some(name))
+            .unwrap()
+        };
+        let file = file(path, sections);
+        highlight_file(&file, &theme)
+            .into_iter()
+            .zip(sections)
+            .map(|(section_highlights, lines)| {
+                section_highlights
+                    .into_iter()
+                    .zip(lines.iter())
+                    .map(|(highlights, line)| {
+                        highlights
+                            .into_iter()
+                            .map(|(range, style)| (line[range].to_owned(), style_name(style)))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn token(text: &str, style_name: &'static str) -> (String, &'static str) {
+        (text.to_owned(), style_name)
+    }
+
+    #[test]
+    fn test_block_comment_spanning_lines() {
+        assert_eq!(
+            tokens(
+                "foo.rs",
+                &[
+                    &[
+                        "let x = 1; /* start\n",
+                        "fn in a comment\n",
+                        "end */ let y\n",
+                    ],
+                    // Each section starts outside of a comment.
+                    &["/* unclosed\n"],
+                    &["let z\n"],
+                ],
+            ),
+            [
+                vec![
+                    vec![
+                        token("let", "keyword"),
+                        token("1", "number"),
+                        token("/* start\n", "comment"),
+                    ],
+                    vec![token("fn in a comment\n", "comment")],
+                    vec![token("end */", "comment"), token("let", "keyword")],
+                ],
+                vec![vec![token("/* unclosed\n", "comment")]],
+                vec![vec![token("let", "keyword")]],
+            ]
+        );
+
+        // Languages without block comments only have line comments.
+        assert_eq!(
+            tokens("foo.py", &[&["x = 1 /* # not */ if\n", "if\n"]]),
+            [vec![
+                vec![token("1", "number"), token("# not */ if\n", "comment")],
+                vec![token("if", "keyword")],
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_string_spanning_lines() {
+        // Strings are only recognized within a line, so the closing quote on
+        // the next line starts another string.
+        assert_eq!(
+            tokens("foo.rs", &[&["let s = \"abc\n", "def\" fn\n"]]),
+            [vec![
+                vec![token("let", "keyword"), token("\"abc\n", "string")],
+                vec![token("\" fn\n", "string")],
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_keywords_next_to_identifiers() {
+        assert_eq!(
+            tokens(
+                "foo.rs",
+                &[&["letter let_x fn(self.iffy) match_ x.fn 1fn\n"]],
+            ),
+            [vec![vec![
+                token("fn", "keyword"),
+                token("self", "keyword"),
+                token("fn", "keyword"),
+                token("1fn", "number"),
+            ]]]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_string() {
+        assert_eq!(
+            tokens(
+                "foo.js",
+                &[&["f(\"a \\\" b\", 'c') + `d\n", "g(\"oops, if\n",]],
+            ),
+            [vec![
+                vec![
+                    token("\"a \\\" b\"", "string"),
+                    token("'c'", "string"),
+                    token("`d\n", "string"),
+                ],
+                vec![token("\"oops, if\n", "string")],
+            ]]
+        );
+
+        // Single quotes start lifetimes in Rust, so they aren't strings.
+        assert_eq!(
+            tokens("foo.rs", &[&["fn f<'a>(x: &'a str)\n"]]),
+            [vec![vec![token("fn", "keyword")]]]
+        );
+    }
+
+    #[test]
+    fn test_line_highlights_are_cached_per_file() {
+        let state = RecordState {
+            is_read_only: false,
+            commits: Vec::new(),
+            files: vec![
+                file("foo.rs", &[&["let x\n"], &["fn f\n"]]),
+                file("bar.rs", &[&["let y\n"]]),
+            ],
+        };
+        let app = App::new(state, RecordOptions::default());
+        assert!(app.line_highlights.iter().all(|cell| cell.get().is_none()));
+
+        let highlights = app.line_highlights(0, 1);
+        assert_eq!(highlights, [vec![(0..2, Theme::default().syntax_keyword)]]);
+        // The whole file was highlighted, but not the other files.
+        assert_eq!(app.line_highlights[0].get().map(Vec::len), Some(2));
+        assert!(app.line_highlights[1].get().is_none());
+        // Later calls return the cached highlights.
+        assert!(std::ptr::eq(highlights, app.line_highlights(0, 1)));
+        // Sections past the end have no highlights.
+        assert!(app.line_highlights(0, 2).is_empty());
+    }
+}
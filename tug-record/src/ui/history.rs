@@ -0,0 +1,420 @@
+use crate::types::{DirtyState, RecordState, Section};
+use crate::ui::state::SelectionKey;
+
+/// An action which changed the user's choices, i.e. which changes are
+/// selected, which files are deferred, or the commit messages.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Command {
+    ToggleItem(SelectionKey),
+    #[cfg(feature = "tui")]
+    ToggleDeferItem(SelectionKey),
+    #[cfg(feature = "tui")]
+    MoveItemToCommit {
+        selection_key: SelectionKey,
+        commit_idx: usize,
+    },
+    ToggleAll,
+    #[cfg(feature = "tui")]
+    ToggleAllUniform,
+    SetCommitMessage {
+        commit_idx: usize,
+    },
+    #[cfg(feature = "tui")]
+    RestoreRecovery,
+}
+
+impl Command {
+    /// A short description of the command, for status messages.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Command::ToggleItem(_) => "toggle",
+            #[cfg(feature = "tui")]
+            Command::ToggleDeferItem(_) => "deferral",
+            #[cfg(feature = "tui")]
+            Command::MoveItemToCommit { .. } => "move to another commit",
+            Command::ToggleAll => "invert all",
+            #[cfg(feature = "tui")]
+            Command::ToggleAllUniform => "toggle all",
+            Command::SetCommitMessage { commit_idx: _ } => "commit message change",
+            #[cfg(feature = "tui")]
+            Command::RestoreRecovery => "session restore",
+        }
+    }
+}
+
+/// The parts of the state which the user can change, as captured before a
+/// command runs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Choices {
+    files: Vec<FileChoices>,
+    commit_messages: Vec<Option<String>>,
+}
+
+impl Choices {
+    pub fn new(state: &RecordState) -> Self {
+        let RecordState {
+            is_read_only: _,
+            commits,
+            files,
+        } = state;
+        Self {
+            files: files
+                .iter()
+                .map(|file| FileChoices {
+                    assignments: file.sections.iter().flat_map(section_assignments).collect(),
+                    is_deferred: file.is_deferred,
+                })
+                .collect(),
+            commit_messages: commits
+                .iter()
+                .map(|commit| commit.message.clone())
+                .collect(),
+        }
+    }
+}
+
+/// The choices for a single file. The checkboxes of all its sections, along
+/// with the commits they're assigned to, are flattened into one list, in
+/// order.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct FileChoices {
+    assignments: Vec<(bool, usize)>,
+    is_deferred: bool,
+}
+
+fn section_assignments(section: &Section) -> Vec<(bool, usize)> {
+    match section {
+        Section::Unchanged { lines: _ } => Vec::new(),
+        Section::Changed {
+            lines,
+            label: _,
+            context: _,
+        } => lines
+            .iter()
+            .map(|line| (line.is_checked, line.commit_idx))
+            .collect(),
+        Section::FileMode {
+            is_checked,
+            commit_idx,
+            mode: _,
+        }
+        | Section::Binary {
+            is_checked,
+            commit_idx,
+            old_description: _,
+            new_description: _,
+            old_metadata: _,
+            new_metadata: _,
+        } => vec![(*is_checked, *commit_idx)],
+    }
+}
+
+fn set_file_choices(state: &mut RecordState, file_idx: usize, choices: &FileChoices) {
+    let FileChoices {
+        assignments,
+        is_deferred,
+    } = choices;
+    let file = &mut state.files[file_idx];
+    file.is_deferred = *is_deferred;
+    let mut assignments = assignments.iter().copied();
+    for section in &mut file.sections {
+        match section {
+            Section::Unchanged { lines: _ } => {}
+            Section::Changed {
+                lines,
+                label: _,
+                context: _,
+            } => {
+                for (line, (is_checked, commit_idx)) in lines.iter_mut().zip(&mut assignments) {
+                    line.is_checked = is_checked;
+                    line.commit_idx = commit_idx;
+                }
+            }
+            Section::FileMode {
+                is_checked: section_is_checked,
+                commit_idx: section_commit_idx,
+                mode: _,
+            }
+            | Section::Binary {
+                is_checked: section_is_checked,
+                commit_idx: section_commit_idx,
+                old_description: _,
+                new_description: _,
+                old_metadata: _,
+                new_metadata: _,
+            } => {
+                if let Some((is_checked, commit_idx)) = assignments.next() {
+                    *section_is_checked = is_checked;
+                    *section_commit_idx = commit_idx;
+                }
+            }
+        }
+    }
+}
+
+/// A command which was run, along with the parts of the state which it
+/// changed, so that it can be reverted and applied again.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct HistoryEntry {
+    command: Command,
+
+    /// The index, old choices, and new choices of each file which changed.
+    files: Vec<(usize, FileChoices, FileChoices)>,
+
+    /// The index, old message, and new message of each commit whose message
+    /// changed.
+    commit_messages: Vec<(usize, Option<String>, Option<String>)>,
+}
+
+impl HistoryEntry {
+    fn new(command: Command, before: Choices, after: Choices) -> Self {
+        let Choices {
+            files: old_files,
+            commit_messages: old_commit_messages,
+        } = before;
+        let Choices {
+            files: new_files,
+            commit_messages: new_commit_messages,
+        } = after;
+        Self {
+            command,
+            files: old_files
+                .into_iter()
+                .zip(new_files)
+                .enumerate()
+                .filter(|(_, (old, new))| old != new)
+                .map(|(file_idx, (old, new))| (file_idx, old, new))
+                .collect(),
+            commit_messages: old_commit_messages
+                .into_iter()
+                .zip(new_commit_messages)
+                .enumerate()
+                .filter(|(_, (old, new))| old != new)
+                .map(|(commit_idx, (old, new))| (commit_idx, old, new))
+                .collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        let Self {
+            command: _,
+            files,
+            commit_messages,
+        } = self;
+        files.is_empty() && commit_messages.is_empty()
+    }
+
+    /// Mark the parts of the state which this entry changes as dirty. Like
+    /// the original command, undoing or redoing it counts as a modification,
+    /// even if the state ends up as it was at the start.
+    fn mark_dirty(&self, dirty: &mut DirtyState) {
+        let Self {
+            command: _,
+            files,
+            commit_messages,
+        } = self;
+        for (_file_idx, old, new) in files {
+            dirty.selections |= old.assignments != new.assignments;
+            dirty.deferred_files |= old.is_deferred != new.is_deferred;
+        }
+        dirty.commit_messages |= !commit_messages.is_empty();
+    }
+
+    /// Change the state back to how it was before the command ran.
+    fn revert(&self, state: &mut RecordState) {
+        for (file_idx, old, _new) in &self.files {
+            set_file_choices(state, *file_idx, old);
+        }
+        for (commit_idx, old, _new) in &self.commit_messages {
+            state.commits[*commit_idx].message = old.clone();
+        }
+    }
+
+    /// Change the state to how it was after the command ran.
+    fn apply(&self, state: &mut RecordState) {
+        for (file_idx, _old, new) in &self.files {
+            set_file_choices(state, *file_idx, new);
+        }
+        for (commit_idx, _old, new) in &self.commit_messages {
+            state.commits[*commit_idx].message = new.clone();
+        }
+    }
+}
+
+/// The commands which the user ran, so that they can be undone and redone.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct History {
+    undo_stack: Vec<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Record that `command` changed the user's choices from `before` to
+    /// those in `state`. Commands which didn't change anything aren't
+    /// recorded. This discards the commands which were undone.
+    pub fn record(&mut self, command: Command, before: Choices, state: &RecordState) {
+        let entry = HistoryEntry::new(command, before, Choices::new(state));
+        if !entry.is_empty() {
+            self.undo_stack.push(entry);
+            self.redo_stack.clear();
+        }
+    }
+
+    /// The commands which were run, oldest first, along with whether they
+    /// were undone.
+    #[cfg(feature = "tui")]
+    pub fn commands(&self) -> impl DoubleEndedIterator<Item = (Command, bool)> + '_ {
+        let Self {
+            undo_stack,
+            redo_stack,
+        } = self;
+        // The most recently undone command is on top of the redo stack.
+        undo_stack
+            .iter()
+            .map(|entry| (entry.command, false))
+            .chain(redo_stack.iter().rev().map(|entry| (entry.command, true)))
+    }
+
+    /// Revert the last command, returning it, if any.
+    pub fn undo(&mut self, state: &mut RecordState, dirty: &mut DirtyState) -> Option<Command> {
+        let entry = self.undo_stack.pop()?;
+        entry.revert(state);
+        entry.mark_dirty(dirty);
+        let command = entry.command;
+        self.redo_stack.push(entry);
+        Some(command)
+    }
+
+    /// Apply the last undone command again, returning it, if any.
+    pub fn redo(&mut self, state: &mut RecordState, dirty: &mut DirtyState) -> Option<Command> {
+        let entry = self.redo_stack.pop()?;
+        entry.apply(state);
+        entry.mark_dirty(dirty);
+        let command = entry.command;
+        self.undo_stack.push(entry);
+        Some(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::path::Path;
+
+    use super::*;
+    use crate::types::{ChangeType, Commit, File, FileMode, SectionChangedLine, Tristate};
+    use crate::ui::state::FileKey;
+
+    fn state() -> RecordState<'static> {
+        let line = |change_type, line| SectionChangedLine {
+            is_checked: false,
+            commit_idx: 0,
+            change_type,
+            line: Cow::Borrowed(line),
+            annotation: None,
+        };
+        RecordState {
+            is_read_only: false,
+            commits: vec![Commit::default(), Commit::default()],
+            files: vec![File {
+                old_path: None,
+                path: Cow::Borrowed(Path::new("foo")),
+                file_mode: FileMode::FILE_DEFAULT,
+                sections: vec![Section::Changed {
+                    label: None,
+                    context: None,
+                    lines: vec![
+                        line(ChangeType::Removed, "old\n"),
+                        line(ChangeType::Added, "new\n"),
+                    ],
+                }],
+                is_deferred: false,
+                is_lossy: false,
+            }],
+        }
+    }
+
+    fn toggle_file() -> Command {
+        Command::ToggleItem(SelectionKey::File(FileKey {
+            commit_idx: 0,
+            file_idx: 0,
+        }))
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut state = state();
+        let mut dirty = DirtyState::default();
+        let mut history = History::default();
+        assert_eq!(history.undo(&mut state, &mut dirty), None);
+
+        let before = Choices::new(&state);
+        state.files[0].set_checked(true);
+        history.record(toggle_file(), before, &state);
+
+        let before = Choices::new(&state);
+        state.commits[0].message = Some("Message".to_owned());
+        history.record(Command::SetCommitMessage { commit_idx: 0 }, before, &state);
+        let after = state.clone();
+
+        assert_eq!(
+            history.undo(&mut state, &mut dirty),
+            Some(Command::SetCommitMessage { commit_idx: 0 })
+        );
+        assert_eq!(state.commits[0].message, None);
+        assert_eq!(state.files[0].tristate(), Tristate::True);
+        assert_eq!(
+            dirty,
+            DirtyState {
+                commit_messages: true,
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(history.undo(&mut state, &mut dirty), Some(toggle_file()));
+        assert_eq!(state, self::state());
+        assert_eq!(history.undo(&mut state, &mut dirty), None);
+        // Undoing back to the original state still counts as a change.
+        assert!(dirty.selections);
+
+        assert_eq!(history.redo(&mut state, &mut dirty), Some(toggle_file()));
+        assert_eq!(
+            history.redo(&mut state, &mut dirty),
+            Some(Command::SetCommitMessage { commit_idx: 0 })
+        );
+        assert_eq!(state, after);
+        assert_eq!(history.redo(&mut state, &mut dirty), None);
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_record_discards_undone_commands() {
+        let mut state = state();
+        let mut dirty = DirtyState::default();
+        let mut history = History::default();
+
+        let before = Choices::new(&state);
+        state.files[0].set_checked(true);
+        history.record(toggle_file(), before, &state);
+        history.undo(&mut state, &mut dirty);
+
+        // Commands which don't change anything aren't recorded, and don't
+        // discard the undone commands.
+        let before = Choices::new(&state);
+        history.record(Command::ToggleAll, before, &state);
+        assert_eq!(history.undo(&mut state, &mut dirty), None);
+        assert_eq!(history.redo(&mut state, &mut dirty), Some(toggle_file()));
+        history.undo(&mut state, &mut dirty);
+
+        let before = Choices::new(&state);
+        state.files[0].is_deferred = true;
+        history.record(Command::ToggleDeferItem(SelectionKey::None), before, &state);
+        assert_eq!(history.redo(&mut state, &mut dirty), None);
+        assert_eq!(
+            history.undo(&mut state, &mut dirty),
+            Some(Command::ToggleDeferItem(SelectionKey::None))
+        );
+        assert!(!state.files[0].is_deferred);
+        assert!(dirty.deferred_files);
+    }
+}
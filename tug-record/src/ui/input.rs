@@ -1,21 +1,48 @@
 use crate::RecordError;
 
 use super::{event, terminal};
-use std::cell::RefCell;
 use std::fmt::Display;
-use std::rc::Rc;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
 
 ///
 /// A copy of the contents of the screen at a certain point in time.
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+///
+/// The contents are shared between clones, which can be sent to other
+/// threads along with the [`event::Event`] which records them.
+#[derive(Clone, Debug, Default)]
 pub struct TestingScreenshot {
-    contents: Rc<RefCell<Option<String>>>,
+    contents: Arc<Mutex<Option<String>>>,
+}
+
+// The mutex is locked briefly and never while calling back into the UI, so a
+// poisoned lock still holds consistent contents.
+fn lock(contents: &Mutex<Option<String>>) -> std::sync::MutexGuard<'_, Option<String>> {
+    contents.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl PartialEq for TestingScreenshot {
+    fn eq(&self, other: &Self) -> bool {
+        // Clones share the same contents, which can't be locked twice.
+        Arc::ptr_eq(&self.contents, &other.contents)
+            || *lock(&self.contents) == *lock(&other.contents)
+    }
 }
 
+impl Eq for TestingScreenshot {}
+
+// Events are sent from input threads in async applications.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<event::Event>();
+};
+
 impl TestingScreenshot {
+    /// Replace the recorded contents of the screen.
     pub fn set(&self, new_contents: String) {
         let Self { contents } = self;
-        *contents.borrow_mut() = Some(new_contents);
+        *lock(contents) = Some(new_contents);
     }
 
     /// Produce an `Event` which will record the screenshot when it's handled.
@@ -27,25 +54,141 @@ impl TestingScreenshot {
 impl Display for TestingScreenshot {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Self { contents } = self;
-        match contents.borrow().as_ref() {
+        match lock(contents).as_ref() {
             Some(contents) => write!(f, "{contents}"),
             None => write!(f, "<this screenshot was never assigned>"),
         }
     }
 }
 
+// Screenshots are serialized without their contents, such as in a trace of
+// the events, so that replaying the events takes new screenshots.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TestingScreenshot {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TestingScreenshot {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <()>::deserialize(deserializer)?;
+        Ok(Self::default())
+    }
+}
+
+/// A version of a file: from before or after the changes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum FileVersion {
+    /// The file before the changes, at its old path if it was renamed.
+    Old,
+    /// The file after all the changes, whether or not they are selected.
+    New,
+}
+
+/// The contents of a version of an image file, to preview it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImagePreview {
+    /// The version of the file.
+    pub version: FileVersion,
+    /// The raw contents of the file, in its image format.
+    pub contents: Vec<u8>,
+}
+
 /// Get user input.
 pub trait RecordInput {
     /// Return the kind of terminal to use.
     fn terminal_kind(&self) -> terminal::TerminalKind;
 
+    /// What the terminal can display, so that the UI can fall back to fewer
+    /// colors and ASCII glyphs on limited terminals.
+    ///
+    /// By default, the terminal is assumed to display anything.
+    /// [`crate::helpers::CrosstermInput`] guesses from the environment with
+    /// [`crate::TerminalCapabilities::detect`].
+    fn terminal_capabilities(&self) -> terminal::TerminalCapabilities {
+        terminal::TerminalCapabilities::default()
+    }
+
     /// Get all available user events. This should block until there is at least
     /// one available event.
     fn next_events(&mut self) -> Result<Vec<event::Event>, RecordError>;
 
+    /// Get all available user events, waiting at most `timeout` for one to
+    /// become available. Returns no events if the timeout expired.
+    ///
+    /// This lets the recorder wake up to handle timers, such as for pending
+    /// key sequences, expiring status messages, and autosaving while the user
+    /// is idle (see [`crate::RecordOptions::idle_timeout`]).
+    ///
+    /// By default, this waits for events with `next_events` regardless of the
+    /// timeout, so timers only fire once the next event arrives.
+    fn poll_events(&mut self, timeout: Duration) -> Result<Vec<event::Event>, RecordError> {
+        let _ = timeout;
+        self.next_events()
+    }
+
     /// Open a commit editor and interactively edit the given message.
     ///
     /// This function will only be invoked if one of the provided `Commit`s had
     /// a non-`None` commit message.
     fn edit_commit_message(&mut self, message: &str) -> Result<String, RecordError>;
+
+    /// Copy the given text to the system clipboard.
+    ///
+    /// By default, copying is not supported, and an error is returned.
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<(), RecordError> {
+        let _ = text;
+        Err(RecordError::Other(
+            "copying to the clipboard is not supported".to_string(),
+        ))
+    }
+
+    /// Get the full contents of the given version of the file at `path`, so
+    /// that the user can read more than the context around the changes.
+    /// Returns `None` if the file doesn't exist in that version, such as the
+    /// old version of an added file.
+    ///
+    /// This is only called when the user asks to view a file, so the
+    /// contents don't need to be loaded ahead of time. By default, viewing
+    /// files is not supported, and an error is returned.
+    fn file_contents(
+        &mut self,
+        path: &Path,
+        version: FileVersion,
+    ) -> Result<Option<String>, RecordError> {
+        let _ = (path, version);
+        Err(RecordError::Other(
+            "viewing the full contents of files is not supported".to_string(),
+        ))
+    }
+
+    /// Get the raw contents of the given version of the image file at `path`,
+    /// to preview it. Returns `None` if that version doesn't exist or isn't an
+    /// image.
+    ///
+    /// By default, no images are available, and only the metadata of binary
+    /// files is shown.
+    fn image_contents(
+        &mut self,
+        path: &Path,
+        version: FileVersion,
+    ) -> Result<Option<Vec<u8>>, RecordError> {
+        let _ = (path, version);
+        Ok(None)
+    }
+
+    /// Show the given versions of the image file at `path`, and wait until
+    /// the user is done looking at them. Returns `false` without showing
+    /// anything if the terminal can't display images.
+    ///
+    /// By default, images can't be displayed. With the `image-preview`
+    /// feature, [`crate::helpers::CrosstermInput`] displays them with the
+    /// terminal's graphics protocol, if it has one.
+    fn show_images(&mut self, path: &Path, images: &[ImagePreview]) -> Result<bool, RecordError> {
+        let _ = (path, images);
+        Ok(false)
+    }
 }
@@ -0,0 +1,206 @@
+//! The types of key events, such as in [`crate::Event::Key`].
+//!
+//! These are `crossterm`'s, except on WebAssembly. `crossterm` doesn't build
+//! there, so they're replaced by stand-ins with the same names and the parts
+//! of the same API which the recorder uses. Pages create them from browser
+//! events with the `web` module.
+
+#[cfg(not(target_family = "wasm"))]
+pub use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+#[cfg(target_family = "wasm")]
+pub use self::wasm::{
+    KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers, ModifierKeyCode,
+};
+
+#[cfg(target_family = "wasm")]
+mod wasm {
+    use std::fmt;
+    use std::ops::{BitOr, BitOrAssign};
+
+    /// A key event, like `crossterm::event::KeyEvent`.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+    pub struct KeyEvent {
+        /// The key itself.
+        pub code: KeyCode,
+
+        /// The modifier keys which were held down.
+        pub modifiers: KeyModifiers,
+
+        /// Whether the key was pressed or released.
+        pub kind: KeyEventKind,
+
+        /// The state of the keyboard, which is never set in a browser.
+        pub state: KeyEventState,
+    }
+
+    impl KeyEvent {
+        /// Create a key press.
+        pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+            Self {
+                code,
+                modifiers,
+                kind: KeyEventKind::Press,
+                state: KeyEventState::NONE,
+            }
+        }
+    }
+
+    /// A key, like `crossterm::event::KeyCode`.
+    #[allow(missing_docs)]
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+    pub enum KeyCode {
+        Backspace,
+        Enter,
+        Left,
+        Right,
+        Up,
+        Down,
+        Home,
+        End,
+        PageUp,
+        PageDown,
+        Tab,
+        BackTab,
+        Delete,
+        Insert,
+        F(u8),
+        Char(char),
+        Null,
+        Esc,
+        Modifier(ModifierKeyCode),
+    }
+
+    impl fmt::Display for KeyCode {
+        /// Format the key the same way as `crossterm` does on platforms other
+        /// than macOS.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                KeyCode::Backspace => write!(f, "Backspace"),
+                KeyCode::Enter => write!(f, "Enter"),
+                KeyCode::Left => write!(f, "Left"),
+                KeyCode::Right => write!(f, "Right"),
+                KeyCode::Up => write!(f, "Up"),
+                KeyCode::Down => write!(f, "Down"),
+                KeyCode::Home => write!(f, "Home"),
+                KeyCode::End => write!(f, "End"),
+                KeyCode::PageUp => write!(f, "Page Up"),
+                KeyCode::PageDown => write!(f, "Page Down"),
+                KeyCode::Tab => write!(f, "Tab"),
+                KeyCode::BackTab => write!(f, "Back Tab"),
+                KeyCode::Delete => write!(f, "Del"),
+                KeyCode::Insert => write!(f, "Insert"),
+                KeyCode::F(n) => write!(f, "F{n}"),
+                KeyCode::Char(' ') => write!(f, "Space"),
+                KeyCode::Char(c) => write!(f, "{c}"),
+                KeyCode::Null => write!(f, "Null"),
+                KeyCode::Esc => write!(f, "Esc"),
+                KeyCode::Modifier(modifier) => write!(f, "{modifier:?}"),
+            }
+        }
+    }
+
+    /// A modifier key pressed on its own, like
+    /// `crossterm::event::ModifierKeyCode`.
+    #[allow(missing_docs)]
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+    pub enum ModifierKeyCode {
+        LeftShift,
+        LeftControl,
+        LeftAlt,
+        LeftSuper,
+        RightShift,
+        RightControl,
+        RightAlt,
+        RightSuper,
+    }
+
+    /// Whether a key was pressed or released, like
+    /// `crossterm::event::KeyEventKind`.
+    #[allow(missing_docs)]
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+    pub enum KeyEventKind {
+        Press,
+        Repeat,
+        Release,
+    }
+
+    /// A set of modifier keys, like `crossterm::event::KeyModifiers`.
+    #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Deserialize, serde::Serialize),
+        serde(transparent)
+    )]
+    pub struct KeyModifiers(u8);
+
+    #[allow(missing_docs)]
+    impl KeyModifiers {
+        pub const NONE: Self = Self(0);
+        pub const SHIFT: Self = Self(1 << 0);
+        pub const CONTROL: Self = Self(1 << 1);
+        pub const ALT: Self = Self(1 << 2);
+        pub const SUPER: Self = Self(1 << 3);
+
+        /// Whether all of the modifiers in `other` are in this set.
+        pub const fn contains(self, other: Self) -> bool {
+            self.0 & other.0 == other.0
+        }
+
+        /// Whether there are no modifiers in this set.
+        pub const fn is_empty(self) -> bool {
+            self.0 == 0
+        }
+
+        /// Add the modifiers in `other` to this set.
+        pub fn insert(&mut self, other: Self) {
+            self.0 |= other.0;
+        }
+
+        /// Remove the modifiers in `other` from this set.
+        pub fn remove(&mut self, other: Self) {
+            self.0 &= !other.0;
+        }
+
+        /// Add or remove the modifiers in `other`, depending on `value`.
+        pub fn set(&mut self, other: Self, value: bool) {
+            if value {
+                self.insert(other);
+            } else {
+                self.remove(other);
+            }
+        }
+    }
+
+    impl BitOr for KeyModifiers {
+        type Output = Self;
+
+        fn bitor(self, other: Self) -> Self {
+            Self(self.0 | other.0)
+        }
+    }
+
+    impl BitOrAssign for KeyModifiers {
+        fn bitor_assign(&mut self, other: Self) {
+            self.insert(other);
+        }
+    }
+
+    /// The state of the keyboard, like `crossterm::event::KeyEventState`.
+    #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Deserialize, serde::Serialize),
+        serde(transparent)
+    )]
+    pub struct KeyEventState(u8);
+
+    #[allow(missing_docs)]
+    impl KeyEventState {
+        pub const NONE: Self = Self(0);
+    }
+}
@@ -0,0 +1,768 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::ui::event::Event;
+use crate::ui::input::FileVersion;
+use crate::ui::key_event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+/// An error parsing a [`Key`] or a [`KeyBinding`].
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum KeymapError {
+    /// The key binding wasn't of the form `KEYS=ACTION`.
+    #[error("invalid key binding, expected KEYS=ACTION: {0}")]
+    InvalidBinding(String),
+
+    /// The key wasn't recognized.
+    #[error("unknown key: {0}")]
+    UnknownKey(String),
+
+    /// The action wasn't recognized.
+    #[error("unknown action: {0}")]
+    UnknownAction(String),
+}
+
+/// A key combination which can be bound to an action, such as `q` or `^c`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Key {
+    /// The key which was pressed.
+    pub code: KeyCode,
+
+    /// The modifiers which were held down.
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Constructor.
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+}
+
+impl From<char> for Key {
+    /// A key without modifiers, except for `Shift` if the character is
+    /// uppercase.
+    fn from(c: char) -> Self {
+        let modifiers = if c.is_uppercase() {
+            KeyModifiers::SHIFT
+        } else {
+            KeyModifiers::NONE
+        };
+        Self::new(KeyCode::Char(c), modifiers)
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(key_event: KeyEvent) -> Self {
+        Self::new(key_event.code, key_event.modifiers)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { code, modifiers } = self;
+        let mut modifiers = *modifiers;
+        if modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "^")?;
+        }
+        if modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        // The shift modifier is already implied by an uppercase character.
+        if let KeyCode::Char(c) = code {
+            if c.is_uppercase() {
+                modifiers.remove(KeyModifiers::SHIFT);
+            }
+        }
+        if modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift-")?;
+        }
+        match code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Tab => write!(f, "Tab"),
+            code => write!(f, "{code}"),
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = KeymapError;
+
+    /// Parse a key in the format it's displayed in, such as `q`, `^c`,
+    /// `Alt-v`, `Space` or `PgDn`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            if let Some(after) = rest.strip_prefix('^').filter(|after| !after.is_empty()) {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("Alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("Shift-") {
+                modifiers |= KeyModifiers::SHIFT;
+                rest = after;
+            } else {
+                break;
+            }
+        }
+        let code = match rest {
+            "Space" => KeyCode::Char(' '),
+            "Up" | "↑" => KeyCode::Up,
+            "Down" | "↓" => KeyCode::Down,
+            "Left" | "←" => KeyCode::Left,
+            "Right" | "→" => KeyCode::Right,
+            "PgUp" => KeyCode::PageUp,
+            "PgDn" => KeyCode::PageDown,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "Enter" => KeyCode::Enter,
+            "Esc" => KeyCode::Esc,
+            "Backspace" => KeyCode::Backspace,
+            "Tab" => KeyCode::Tab,
+            rest => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => {
+                        if c.is_uppercase() {
+                            modifiers |= KeyModifiers::SHIFT;
+                        }
+                        KeyCode::Char(c)
+                    }
+                    _ => match rest.strip_prefix('F').and_then(|num| num.parse().ok()) {
+                        Some(num) => KeyCode::F(num),
+                        None => return Err(KeymapError::UnknownKey(s.to_owned())),
+                    },
+                }
+            }
+        };
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+/// The names of the events which can be bound to keys, as used in the
+/// `KEYS=ACTION` format of [`KeyBinding`].
+fn named_events() -> Vec<(&'static str, Event)> {
+    vec![
+        ("none", Event::None),
+        ("quit-accept", Event::QuitAccept),
+        ("quit-cancel", Event::QuitCancel),
+        ("quit-interrupt", Event::QuitInterrupt),
+        ("quit-escape", Event::QuitEscape),
+        ("scroll-up", Event::ScrollUp),
+        ("scroll-down", Event::ScrollDown),
+        ("page-up", Event::PageUp),
+        ("page-down", Event::PageDown),
+        ("focus-prev", Event::FocusPrev),
+        ("focus-prev-same-kind", Event::FocusPrevSameKind),
+        ("focus-prev-page", Event::FocusPrevPage),
+        ("focus-next", Event::FocusNext),
+        ("focus-next-same-kind", Event::FocusNextSameKind),
+        ("focus-next-page", Event::FocusNextPage),
+        ("focus-prev-file", Event::FocusPrevFile),
+        ("focus-next-file", Event::FocusNextFile),
+        ("focus-first", Event::FocusFirst),
+        ("focus-last", Event::FocusLast),
+        ("scroll-selection-to-center", Event::ScrollSelectionToCenter),
+        ("focus-inner", Event::FocusInner),
+        (
+            "focus-outer",
+            Event::FocusOuter {
+                fold_section: false,
+            },
+        ),
+        ("fold-section", Event::FocusOuter { fold_section: true }),
+        ("toggle-item", Event::ToggleItem),
+        ("toggle-item-and-advance", Event::ToggleItemAndAdvance),
+        ("toggle-file", Event::ToggleFile),
+        ("toggle-section", Event::ToggleSection),
+        ("toggle-all", Event::ToggleAll),
+        ("toggle-all-uniform", Event::ToggleAllUniform),
+        ("expand-item", Event::ExpandItem),
+        ("expand-all", Event::ExpandAll),
+        ("toggle-commit-view-mode", Event::ToggleCommitViewMode),
+        ("focus-prev-commit", Event::FocusPrevCommit),
+        ("focus-next-commit", Event::FocusNextCommit),
+        ("move-item-to-prev-commit", Event::MoveItemToPrevCommit),
+        ("move-item-to-next-commit", Event::MoveItemToNextCommit),
+        ("edit-commit-message", Event::EditCommitMessage),
+        (
+            "edit-commit-message-externally",
+            Event::EditCommitMessageExternally,
+        ),
+        (
+            "toggle-commit-message-expanded",
+            Event::ToggleCommitMessageExpanded,
+        ),
+        ("open-message-history", Event::OpenMessageHistory),
+        ("toggle-stats-bar", Event::ToggleStatsBar),
+        ("toggle-action-history", Event::ToggleActionHistory),
+        ("cycle-file-sort-order", Event::CycleFileSortOrder),
+        ("filter-files", Event::FilterFiles),
+        ("clear-file-filter", Event::ClearFileFilter),
+        ("search", Event::Search),
+        ("search-next", Event::SearchNext),
+        ("search-prev", Event::SearchPrev),
+        (
+            "toggle-hide-unselected-files",
+            Event::ToggleHideUnselectedFiles,
+        ),
+        ("toggle-hide-selected-files", Event::ToggleHideSelectedFiles),
+        (
+            "toggle-hide-unchanged-files",
+            Event::ToggleHideUnchangedFiles,
+        ),
+        ("toggle-file-tree", Event::ToggleFileTree),
+        ("toggle-two-pane", Event::ToggleTwoPane),
+        ("toggle-outline", Event::ToggleOutline),
+        ("preview-selection", Event::PreviewSelection),
+        ("toggle-zoom-file", Event::ToggleZoomFile),
+        ("copy-path", Event::CopyPath),
+        (
+            "copy-selected-lines",
+            Event::CopySelectedLines { as_diff: false },
+        ),
+        (
+            "copy-selected-lines-as-diff",
+            Event::CopySelectedLines { as_diff: true },
+        ),
+        (
+            "view-old-contents",
+            Event::ViewFileContents {
+                version: FileVersion::Old,
+            },
+        ),
+        (
+            "view-new-contents",
+            Event::ViewFileContents {
+                version: FileVersion::New,
+            },
+        ),
+        ("preview-images", Event::PreviewImages),
+        ("toggle-defer-item", Event::ToggleDeferItem),
+        ("toggle-bookmark", Event::ToggleBookmark),
+        ("focus-prev-bookmark", Event::FocusPrevBookmark),
+        ("focus-next-bookmark", Event::FocusNextBookmark),
+        ("show-line-diff", Event::ShowLineDiff),
+        ("toggle-wrap-line", Event::ToggleWrapLine),
+        ("toggle-annotations", Event::ToggleAnnotations),
+        ("toggle-context", Event::ToggleContext),
+        ("record-macro", Event::RecordMacro),
+        ("replay-macro", Event::ReplayMacro),
+        ("undo", Event::Undo),
+        ("redo", Event::Redo),
+        ("help", Event::Help),
+    ]
+}
+
+/// The built-in bindings of single keys, in the format of [`Key`]'s
+/// [`FromStr`] implementation. Keys which some terminals report along with
+/// the shift key needed to type them are bound both with and without it.
+const DEFAULT_KEYS: &[(&str, Event)] = &[
+    ("q", Event::QuitCancel),
+    ("Esc", Event::QuitEscape),
+    ("^c", Event::QuitInterrupt),
+    ("c", Event::QuitAccept),
+    ("?", Event::Help),
+    ("^Up", Event::ScrollUp),
+    ("^y", Event::ScrollUp),
+    ("^Down", Event::ScrollDown),
+    ("^e", Event::ScrollDown),
+    ("^PgUp", Event::PageUp),
+    ("^b", Event::PageUp),
+    ("^PgDn", Event::PageDown),
+    ("^f", Event::PageDown),
+    ("Up", Event::FocusPrev),
+    ("k", Event::FocusPrev),
+    ("Down", Event::FocusNext),
+    ("j", Event::FocusNext),
+    ("PgUp", Event::FocusPrevSameKind),
+    ("PgDn", Event::FocusNextSameKind),
+    (
+        "Shift-Left",
+        Event::FocusOuter {
+            fold_section: false,
+        },
+    ),
+    (
+        "Shift-h",
+        Event::FocusOuter {
+            fold_section: false,
+        },
+    ),
+    ("Left", Event::FocusOuter { fold_section: true }),
+    ("h", Event::FocusOuter { fold_section: true }),
+    // The shift modifier is accepted for continuity with `FocusOuter`.
+    ("Right", Event::FocusInner),
+    ("Shift-Right", Event::FocusInner),
+    ("l", Event::FocusInner),
+    ("Shift-l", Event::FocusInner),
+    ("^u", Event::FocusPrevPage),
+    ("^d", Event::FocusNextPage),
+    ("G", Event::FocusLast),
+    ("Alt-Up", Event::FocusPrevFile),
+    ("Alt-Down", Event::FocusNextFile),
+    ("Space", Event::ToggleItem),
+    ("Enter", Event::QuitInterrupt),
+    ("Alt-a", Event::ToggleFile),
+    ("Alt-s", Event::ToggleSection),
+    ("a", Event::ToggleAll),
+    ("A", Event::ToggleAllUniform),
+    ("f", Event::ExpandItem),
+    ("F", Event::ExpandAll),
+    ("e", Event::EditCommitMessage),
+    ("Alt-e", Event::EditCommitMessageExternally),
+    ("E", Event::ToggleCommitMessageExpanded),
+    ("r", Event::OpenMessageHistory),
+    ("i", Event::ToggleStatsBar),
+    ("L", Event::ToggleActionHistory),
+    ("o", Event::CycleFileSortOrder),
+    ("\\", Event::FilterFiles),
+    ("/", Event::Search),
+    ("n", Event::SearchNext),
+    ("N", Event::SearchPrev),
+    ("Backspace", Event::ClearFileFilter),
+    ("x", Event::ToggleHideUnselectedFiles),
+    ("X", Event::ToggleHideSelectedFiles),
+    ("I", Event::ToggleHideUnchangedFiles),
+    ("T", Event::ToggleFileTree),
+    ("V", Event::ToggleTwoPane),
+    ("Z", Event::ToggleZoomFile),
+    ("p", Event::PreviewSelection),
+    ("O", Event::ToggleOutline),
+    ("y", Event::CopyPath),
+    ("Y", Event::CopySelectedLines { as_diff: false }),
+    ("P", Event::CopySelectedLines { as_diff: true }),
+    (
+        "v",
+        Event::ViewFileContents {
+            version: FileVersion::New,
+        },
+    ),
+    (
+        "Alt-v",
+        Event::ViewFileContents {
+            version: FileVersion::Old,
+        },
+    ),
+    ("Alt-p", Event::PreviewImages),
+    ("d", Event::ToggleDeferItem),
+    ("m", Event::ToggleBookmark),
+    ("'", Event::FocusNextBookmark),
+    ("\"", Event::FocusPrevBookmark),
+    ("Shift-\"", Event::FocusPrevBookmark),
+    ("<", Event::FocusPrevCommit),
+    ("Shift-<", Event::FocusPrevCommit),
+    (">", Event::FocusNextCommit),
+    ("Shift->", Event::FocusNextCommit),
+    ("[", Event::MoveItemToPrevCommit),
+    ("]", Event::MoveItemToNextCommit),
+    ("D", Event::ShowLineDiff),
+    ("w", Event::ToggleWrapLine),
+    ("b", Event::ToggleAnnotations),
+    ("C", Event::ToggleContext),
+    ("Q", Event::RecordMacro),
+    ("@", Event::ReplayMacro),
+    ("Shift-@", Event::ReplayMacro),
+    ("u", Event::Undo),
+    ("^r", Event::Redo),
+];
+
+/// The built-in bindings of single keys, which the default [`Keymap`] starts
+/// with. They're parsed once, since key presses are looked up in them when
+/// they're converted to events with [`Event::from`].
+pub(crate) fn default_key_bindings() -> &'static [(Key, Event)] {
+    static DEFAULT_KEY_BINDINGS: LazyLock<Vec<(Key, Event)>> = LazyLock::new(|| {
+        DEFAULT_KEYS
+            .iter()
+            .map(|(key, event)| {
+                let key = key
+                    .parse()
+                    .unwrap_or_else(|err| panic!("invalid built-in key {key:?}: {err}"));
+                (key, event.clone())
+            })
+            .collect()
+    });
+    &DEFAULT_KEY_BINDINGS
+}
+
+/// A sequence of keys which triggers an [`Event`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeyBinding {
+    /// The keys to press, in order.
+    pub keys: Vec<Key>,
+
+    /// The event to trigger once all the keys have been pressed.
+    pub event: Event,
+
+    /// A short description of the action, shown when the user has pressed
+    /// some of the keys and is being prompted for the rest.
+    pub description: String,
+}
+
+impl FromStr for KeyBinding {
+    type Err = KeymapError;
+
+    /// Parse a binding of the form `KEYS=ACTION`, where `KEYS` are separated
+    /// by spaces and `ACTION` is the name of the event in kebab case, such as
+    /// `g g=focus-first`. The action name is used as the description.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (keys, action) = s
+            .rsplit_once('=')
+            .filter(|(keys, _)| !keys.trim().is_empty())
+            .ok_or_else(|| KeymapError::InvalidBinding(s.to_owned()))?;
+        let keys = keys
+            .split_whitespace()
+            .map(Key::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        let action = action.trim();
+        let event = named_events()
+            .into_iter()
+            .find_map(|(name, event)| (name == action).then_some(event))
+            .ok_or_else(|| KeymapError::UnknownAction(action.to_owned()))?;
+        Ok(Self {
+            keys,
+            event,
+            description: action.to_owned(),
+        })
+    }
+}
+
+/// The bindings of keys to the actions which they trigger. The default keymap
+/// has the built-in bindings, which can be replaced one at a time with
+/// [`Keymap::bind`], or all at once by starting from [`Keymap::clear`].
+///
+/// Bindings can consist of several keys in a row; when the user presses the
+/// first keys of such a binding, a popup lists the keys which can follow.
+///
+/// If the keys of a binding are also the start of a longer binding, then the
+/// shorter binding is triggered once the user stops typing for
+/// [`Keymap::sequence_timeout`], or presses a key which doesn't continue the
+/// longer binding.
+///
+/// Bind a key to [`Event::None`] to disable it without the user being told
+/// that the key isn't bound.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<KeyBinding>,
+
+    /// How long to wait for the next key of a binding with several keys.
+    pub sequence_timeout: Duration,
+}
+
+impl Default for Keymap {
+    /// The built-in bindings of single keys, and a few bindings with several
+    /// keys, in the style of Vim. Single keys are described by the names of
+    /// their actions, as in the `KEYS=ACTION` format of [`KeyBinding`].
+    fn default() -> Self {
+        let names = named_events();
+        let bindings = default_key_bindings()
+            .iter()
+            .map(|(key, event)| {
+                let description = names
+                    .iter()
+                    .find_map(|(name, named_event)| (named_event == event).then_some(*name))
+                    .unwrap_or_default();
+                KeyBinding {
+                    keys: vec![*key],
+                    event: event.clone(),
+                    description: description.to_owned(),
+                }
+            })
+            .collect();
+        let mut keymap = Self {
+            bindings,
+            sequence_timeout: Duration::from_secs(1),
+        };
+        keymap
+            .bind(
+                [Key::from('g'), Key::from('g')],
+                Event::FocusFirst,
+                "Go to first item",
+            )
+            .bind(
+                [Key::from('z'), Key::from('z')],
+                Event::ScrollSelectionToCenter,
+                "Center selection on screen",
+            );
+        keymap
+    }
+}
+
+/// The result of resolving a key press with a [`Keymap`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum KeymapMatch {
+    /// The keys are bound to this event.
+    Event(Event),
+
+    /// The keys are the start of at least one binding, so more keys are
+    /// needed.
+    Pending(Vec<Key>),
+
+    /// The keys started a binding, but the last one didn't continue it. If
+    /// the keys before it were bound by themselves, then this is their event.
+    Interrupted(Option<Event>),
+
+    /// The key isn't bound to anything and doesn't start a binding.
+    Unbound(Key),
+}
+
+impl Keymap {
+    /// Bind the sequence of keys to the event, replacing any existing binding
+    /// for the same keys.
+    pub fn bind(
+        &mut self,
+        keys: impl IntoIterator<Item = Key>,
+        event: Event,
+        description: impl Into<String>,
+    ) -> &mut Self {
+        let keys: Vec<Key> = keys.into_iter().collect();
+        self.unbind(&keys);
+        self.bindings.push(KeyBinding {
+            keys,
+            event,
+            description: description.into(),
+        });
+        self
+    }
+
+    /// Remove all bindings, including the built-in ones, so that only the
+    /// keys bound afterwards do anything.
+    pub fn clear(&mut self) -> &mut Self {
+        self.bindings.clear();
+        self
+    }
+
+    /// Remove the binding for the sequence of keys, if any, including a
+    /// built-in one.
+    pub fn unbind(&mut self, keys: &[Key]) -> &mut Self {
+        self.bindings.retain(|binding| binding.keys != keys);
+        self
+    }
+
+    /// All the bindings, in the order in which they were added.
+    pub fn bindings(&self) -> &[KeyBinding] {
+        &self.bindings
+    }
+
+    /// The bindings which start with the `pending` keys and have more keys
+    /// after them.
+    pub(crate) fn continuations<'a>(
+        &'a self,
+        pending: &'a [Key],
+    ) -> impl Iterator<Item = &'a KeyBinding> + 'a {
+        self.bindings
+            .iter()
+            .filter(move |binding| binding.keys.len() > pending.len())
+            .filter(move |binding| binding.keys.starts_with(pending))
+    }
+
+    /// The event bound to exactly these keys, if any.
+    pub(crate) fn lookup(&self, keys: &[Key]) -> Option<&Event> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.keys == keys)
+            .map(|binding| &binding.event)
+    }
+
+    /// Resolve the key press which follows the `pending` keys.
+    pub(crate) fn resolve(&self, pending: &[Key], key_event: KeyEvent) -> KeymapMatch {
+        if key_event.kind != KeyEventKind::Press {
+            return KeymapMatch::Event(Event::None);
+        }
+        let keys = [pending, &[Key::from(key_event)]].concat();
+        if self.continuations(&keys).next().is_some() {
+            return KeymapMatch::Pending(keys);
+        }
+        match self.lookup(&keys) {
+            Some(event) => KeymapMatch::Event(event.clone()),
+            // Pressing a modifier by itself is never a mistake.
+            None if pending.is_empty() && matches!(key_event.code, KeyCode::Modifier(_)) => {
+                KeymapMatch::Event(Event::None)
+            }
+            None if pending.is_empty() => KeymapMatch::Unbound(Key::from(key_event)),
+            None => KeymapMatch::Interrupted(self.lookup(pending).cloned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(key: Key) -> KeyEvent {
+        KeyEvent::new(key.code, key.modifiers)
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!("q".parse(), Ok(Key::from('q')));
+        assert_eq!(
+            "Q".parse(),
+            Ok(Key::new(KeyCode::Char('Q'), KeyModifiers::SHIFT))
+        );
+        assert_eq!(
+            "^c".parse(),
+            Ok(Key::new(KeyCode::Char('c'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            "^Alt-Shift-Up".parse(),
+            Ok(Key::new(
+                KeyCode::Up,
+                KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT
+            ))
+        );
+        assert_eq!("↓".parse(), Ok(Key::new(KeyCode::Down, KeyModifiers::NONE)));
+        assert_eq!("Space".parse(), Ok(Key::from(' ')));
+        assert_eq!(
+            "F5".parse(),
+            Ok(Key::new(KeyCode::F(5), KeyModifiers::NONE))
+        );
+        // A caret by itself is the key, not the control modifier.
+        assert_eq!("^".parse(), Ok(Key::from('^')));
+        assert_eq!(
+            "^^".parse(),
+            Ok(Key::new(KeyCode::Char('^'), KeyModifiers::CONTROL))
+        );
+
+        assert_eq!(
+            "Foo".parse::<Key>(),
+            Err(KeymapError::UnknownKey("Foo".to_owned()))
+        );
+        assert_eq!(
+            "Alt-".parse::<Key>(),
+            Err(KeymapError::UnknownKey("Alt-".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_display_key() {
+        let keys = [
+            Key::from('q'),
+            Key::from('Q'),
+            Key::from(' '),
+            Key::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+            Key::new(KeyCode::Left, KeyModifiers::ALT | KeyModifiers::SHIFT),
+            Key::new(KeyCode::PageDown, KeyModifiers::NONE),
+        ];
+        let displayed: Vec<String> = keys.iter().map(Key::to_string).collect();
+        assert_eq!(displayed, ["q", "Q", "Space", "^c", "Alt-Shift-←", "PgDn"]);
+        for (key, displayed) in keys.iter().zip(&displayed) {
+            assert_eq!(displayed.parse(), Ok(*key));
+        }
+    }
+
+    #[test]
+    fn test_default_keys_round_trip() {
+        for (key, _) in DEFAULT_KEYS {
+            let parsed: Key = key
+                .parse()
+                .unwrap_or_else(|err| panic!("invalid built-in key {key:?}: {err}"));
+            assert_eq!(parsed.to_string().parse(), Ok(parsed), "{key:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_key_binding() {
+        assert_eq!(
+            "g g=focus-first".parse(),
+            Ok(KeyBinding {
+                keys: vec![Key::from('g'), Key::from('g')],
+                event: Event::FocusFirst,
+                description: "focus-first".to_owned(),
+            })
+        );
+        // The last `=` separates the action, so `=` itself can be bound.
+        assert_eq!(
+            "= = toggle-item"
+                .parse::<KeyBinding>()
+                .map(|binding| binding.keys),
+            Ok(vec![Key::from('=')])
+        );
+
+        assert_eq!(
+            "focus-first".parse::<KeyBinding>(),
+            Err(KeymapError::InvalidBinding("focus-first".to_owned()))
+        );
+        assert_eq!(
+            " =focus-first".parse::<KeyBinding>(),
+            Err(KeymapError::InvalidBinding(" =focus-first".to_owned()))
+        );
+        assert_eq!(
+            "g Foo=focus-first".parse::<KeyBinding>(),
+            Err(KeymapError::UnknownKey("Foo".to_owned()))
+        );
+        assert_eq!(
+            "g=focus-nowhere".parse::<KeyBinding>(),
+            Err(KeymapError::UnknownAction("focus-nowhere".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_keymap_resolve() {
+        let mut keymap = Keymap::default();
+        let g = Key::from('g');
+        assert_eq!(keymap.resolve(&[], press(g)), KeymapMatch::Pending(vec![g]));
+        assert_eq!(
+            keymap.resolve(&[g], press(g)),
+            KeymapMatch::Event(Event::FocusFirst)
+        );
+        assert_eq!(
+            keymap.resolve(&[g], press(Key::from('x'))),
+            KeymapMatch::Interrupted(None)
+        );
+        assert_eq!(
+            keymap.resolve(&[], press(Key::from('j'))),
+            KeymapMatch::Event(Event::FocusNext)
+        );
+        let unbound = Key::new(KeyCode::F(12), KeyModifiers::NONE);
+        assert_eq!(
+            keymap.resolve(&[], press(unbound)),
+            KeymapMatch::Unbound(unbound)
+        );
+
+        // Once `g` is bound by itself, it's triggered if the sequence is
+        // interrupted.
+        keymap.bind([g], Event::FocusLast, "Go to last item");
+        assert_eq!(keymap.resolve(&[], press(g)), KeymapMatch::Pending(vec![g]));
+        assert_eq!(
+            keymap.resolve(&[g], press(Key::from('x'))),
+            KeymapMatch::Interrupted(Some(Event::FocusLast))
+        );
+
+        // Rebinding replaces the built-in binding instead of adding another.
+        keymap.bind([Key::from('j')], Event::FocusPrev, "Up");
+        assert_eq!(
+            keymap.resolve(&[], press(Key::from('j'))),
+            KeymapMatch::Event(Event::FocusPrev)
+        );
+        assert_eq!(
+            keymap
+                .bindings()
+                .iter()
+                .filter(|binding| binding.keys == [Key::from('j')])
+                .count(),
+            1
+        );
+
+        keymap.clear();
+        assert_eq!(
+            keymap.resolve(&[], press(Key::from('j'))),
+            KeymapMatch::Unbound(Key::from('j'))
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use crate::ui::key_event::KeyEvent;
+
+/// What to do with the register whose name the user types next.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum MacroPrompt {
+    /// Start recording a macro into the register.
+    Record,
+
+    /// Replay the macro in the register this many times.
+    Replay { count: usize },
+}
+
+/// Sequences of key presses recorded by the user, which can be replayed. A
+/// macro is stored in a register named by a lowercase letter, in the style of
+/// Vim.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Macros {
+    registers: HashMap<char, Vec<KeyEvent>>,
+
+    /// The register being recorded into, and the keys pressed so far.
+    recording: Option<(char, Vec<KeyEvent>)>,
+
+    /// The register which was replayed last, for `@@`.
+    last_replayed: Option<char>,
+}
+
+impl Macros {
+    /// Whether the character names a register.
+    pub fn is_register(c: char) -> bool {
+        c.is_ascii_lowercase()
+    }
+
+    /// The register being recorded into, if any.
+    pub fn recording_register(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _keys)| *register)
+    }
+
+    /// The register which was replayed last, if any.
+    pub fn last_replayed(&self) -> Option<char> {
+        self.last_replayed
+    }
+
+    /// Start recording into the register, discarding the macro stored there.
+    pub fn start_recording(&mut self, register: char) {
+        self.recording = Some((register, Vec::new()));
+    }
+
+    /// Stop recording and store the keys pressed since recording started.
+    pub fn stop_recording(&mut self) {
+        if let Some((register, keys)) = self.recording.take() {
+            self.registers.insert(register, keys);
+        }
+    }
+
+    /// Add the key press to the macro being recorded, if any.
+    pub fn record(&mut self, key_event: KeyEvent) {
+        if let Some((_register, keys)) = &mut self.recording {
+            keys.push(key_event);
+        }
+    }
+
+    /// The keys of the macro stored in the register, and remember it as the
+    /// last one replayed.
+    pub fn replay(&mut self, register: char) -> Option<&[KeyEvent]> {
+        let keys = self.registers.get(&register)?;
+        self.last_replayed = Some(register);
+        Some(keys)
+    }
+}
@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+/// Receives counters and timings from the recorder, so that hosts can
+/// understand how it is used and how it performs. Set it with
+/// [`crate::Recorder::with_metrics`].
+///
+/// Every method does nothing by default, so implementors only need to
+/// override the ones they collect.
+pub trait Metrics {
+    /// A single item was toggled, such as a file, section or line.
+    fn item_toggled(&mut self) {}
+
+    /// All of the changes were toggled at once.
+    fn all_toggled(&mut self) {}
+
+    /// An item was expanded or collapsed, as given by `is_expanded`.
+    fn item_expanded(&mut self, is_expanded: bool) {
+        let _ = is_expanded;
+    }
+
+    /// A frame was drawn, which took `render_time`.
+    fn frame_rendered(&mut self, render_time: Duration) {
+        let _ = render_time;
+    }
+
+    /// The session ended after `duration`, either by the user accepting the
+    /// changes or by cancelling, as given by `is_accepted`.
+    fn session_ended(&mut self, duration: Duration, is_accepted: bool) {
+        let _ = (duration, is_accepted);
+    }
+}
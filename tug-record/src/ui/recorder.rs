@@ -1,15 +1,42 @@
 use crate::consts::ENV_VAR_DEBUG_UI;
-use crate::render::{DrawnRect, DrawnRects, Viewport};
-use crate::types::{RecordError, RecordState};
+use crate::render::{DrawnRect, DrawnRects, Rect, Viewport};
+use crate::types::{DirtyState, FileMode, RecordError, RecordOptions, RecordState, Section};
+use crate::ui::announcer::Announcer;
 use crate::ui::components::app::{AppDebugInfo, AppView};
 use crate::ui::components::commit_message_view::CommitViewMode;
+use crate::ui::components::error_dialog::ErrorDialog;
+use crate::ui::components::file_contents_dialog::FileContentsDialog;
+use crate::ui::components::message_diff_dialog::MessageDiffDialog;
+use crate::ui::components::message_editor_dialog::MessageEditorDialog;
 use crate::ui::components::ComponentId;
-use crate::ui::{event, input, terminal, App, StateUpdate};
+use crate::ui::history::Command;
+use crate::ui::key_event::{KeyEvent, KeyEventKind};
+use crate::ui::metrics::Metrics;
+use crate::ui::recovery;
+use crate::ui::state::{
+    selected_item, App, FileKey, SelectedItem, SelectionKey, StateUpdate, StatusMessage,
+};
+#[cfg(feature = "debug")]
+use crate::ui::transition_log::{Transition, TransitionLog, TransitionTrace};
+use crate::ui::{event, input, terminal};
 use crate::util::UsizeExt;
+#[cfg(not(target_family = "wasm"))]
+use ratatui::backend::CrosstermBackend;
 use ratatui::backend::{Backend, TestBackend};
-use ratatui::{backend::CrosstermBackend, Terminal};
+use ratatui::{Frame, Terminal};
 use std::any::Any;
-use std::{io, mem};
+use std::collections::VecDeque;
+#[cfg(not(target_family = "wasm"))]
+use std::io;
+use std::iter;
+use std::mem;
+use std::time::Duration;
+use web_time::Instant;
+
+/// How long the terminal size must stay the same before redrawing after it's
+/// resized, so that continuously resizing the window doesn't re-layout the UI
+/// for every intermediate size.
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(50);
 
 /// UI component to record the user's changes.
 /// This struct is the main driver for the UI, handling the event loop,
@@ -19,49 +46,205 @@ pub struct Recorder<'state, 'input> {
     app: App<'state>,
     input: &'input mut dyn input::RecordInput,
     pending_events: Vec<event::Event>,
+    /// Whether to draw debugging information over the UI.
+    debug: bool,
+    /// When to stop waiting for the rest of a key sequence.
+    key_sequence_deadline: Option<Instant>,
+    last_input_time: Instant,
+    /// Only report that the user is idle once until they do something.
+    is_idle: bool,
+    /// Where each component was drawn in the last frame.
+    drawn_rects: DrawnRects<ComponentId>,
+    /// How far the last frame was scrolled.
+    drawn_scroll_offset_y: isize,
+    announcer: Option<Box<dyn Announcer>>,
+    metrics: Option<Box<dyn Metrics>>,
+    /// When the session started, to report how long it took.
+    created_at: Instant,
+    /// What the terminal can display, as reported by the input.
+    capabilities: terminal::TerminalCapabilities,
+    /// Where to log each transition, if anywhere.
+    #[cfg(feature = "debug")]
+    transition_log: Option<TransitionLog>,
 }
 
 impl<'state, 'input> Recorder<'state, 'input> {
     /// Constructor.
     pub fn new(state: RecordState<'state>, input: &'input mut dyn input::RecordInput) -> Self {
+        Self::new_with_options(state, input, RecordOptions::default())
+    }
+
+    /// Construct a recorder whose behavior is customized by `options`.
+    pub fn new_with_options(
+        state: RecordState<'state>,
+        input: &'input mut dyn input::RecordInput,
+        options: RecordOptions,
+    ) -> Self {
+        let mut app = App::new(state, options);
+        if let Some(recovery_path) = &app.options.recovery_path {
+            match recovery::read_recovery_file(recovery_path) {
+                Ok(Some(recovery)) => app.offer_recovery(recovery),
+                Ok(None) => {}
+                Err(err) => {
+                    app.ui.status_message = Some(StatusMessage::error(format!(
+                        "Failed to read recovery file: {err}"
+                    )));
+                }
+            }
+        }
+        let debug = if cfg!(feature = "debug") {
+            std::env::var_os(ENV_VAR_DEBUG_UI).is_some()
+        } else {
+            false
+        };
+        #[cfg(feature = "debug")]
+        let transition_log =
+            std::env::var_os(crate::consts::ENV_VAR_TRACE_TRANSITIONS).and_then(|path| {
+                match TransitionLog::create(path.as_ref()) {
+                    Ok(transition_log) => Some(transition_log),
+                    Err(err) => {
+                        app.ui.status_message = Some(StatusMessage::error(format!(
+                            "Failed to trace transitions: {err}"
+                        )));
+                        None
+                    }
+                }
+            });
+        let capabilities = input.terminal_capabilities();
         Self {
-            app: App::new(state),
+            app,
             input,
             pending_events: Default::default(),
+            debug,
+            key_sequence_deadline: None,
+            last_input_time: Instant::now(),
+            is_idle: false,
+            drawn_rects: Default::default(),
+            drawn_scroll_offset_y: 0,
+            announcer: None,
+            metrics: None,
+            created_at: Instant::now(),
+            capabilities,
+            #[cfg(feature = "debug")]
+            transition_log,
         }
     }
 
+    /// Describe what changes in the UI to `announcer`, such as which item is
+    /// selected and whether it was toggled. The announcer may outlive the
+    /// recorder, so it should send the announcements elsewhere, such as over
+    /// a channel, rather than borrow where they are stored.
+    pub fn with_announcer(mut self, announcer: impl Announcer + 'static) -> Self {
+        self.announcer = Some(Box::new(announcer));
+        self
+    }
+
+    /// Report how the recorder is used and how it performs to `metrics`. Like
+    /// an announcer, it may outlive the recorder.
+    pub fn with_metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+        self.metrics = Some(Box::new(metrics));
+        self
+    }
+
     /// Run the terminal user interface and have the user interactively select
     /// changes.
     pub fn run(self) -> Result<RecordState<'state>, RecordError> {
+        let (state, _dirty) = self.run_with_dirty_state()?;
+        Ok(state)
+    }
+
+    /// Like [`Recorder::run`], but also report which parts of the state the
+    /// user modified.
+    pub fn run_with_dirty_state(self) -> Result<(RecordState<'state>, DirtyState), RecordError> {
+        self.run_session(|recorder| match recorder.input.terminal_kind() {
+            #[cfg(not(target_family = "wasm"))]
+            terminal::TerminalKind::Crossterm => recorder.run_crossterm(),
+            terminal::TerminalKind::Testing { width, height } => {
+                recorder.run_testing(width, height)
+            }
+        })
+    }
+
+    /// Like [`Recorder::run_with_dirty_state`], but draw the UI with the given
+    /// terminal instead of the one chosen by the input's
+    /// [`crate::TerminalKind`], such as a terminal on stderr or one with a
+    /// custom backend. Events are still read from the input.
+    ///
+    /// The caller is responsible for setting up the terminal, such as
+    /// enabling raw mode and switching to the alternate screen, and for
+    /// restoring it afterwards.
+    pub fn run_with_terminal<B: Backend + Any>(
+        self,
+        term: &mut Terminal<B>,
+    ) -> Result<(RecordState<'state>, DirtyState), RecordError> {
+        self.run_session(|recorder| recorder.run_inner(term))
+    }
+
+    /// Validate the state, run the UI with `run`, and clean up afterwards.
+    fn run_session(
+        self,
+        run: impl FnOnce(Self) -> Result<(RecordState<'state>, DirtyState), RecordError>,
+    ) -> Result<(RecordState<'state>, DirtyState), RecordError> {
+        self.app.state.validate()?;
+        if self.app.options.skip_if_nothing_to_select
+            && !self
+                .app
+                .state
+                .files
+                .iter()
+                .any(|file| file.sections.iter().any(|section| section.is_editable()))
+        {
+            return Err(RecordError::NothingToSelect);
+        }
+
         #[cfg(feature = "debug")]
         if std::env::var_os(crate::consts::ENV_VAR_DUMP_UI_STATE).is_some() {
             let ui_state = serde_json::to_string_pretty(&self.app.state)
                 .map_err(RecordError::SerializeJson)?;
-            std::fs::write(crate::consts::DUMP_UI_STATE_FILENAME, ui_state)
-                .map_err(RecordError::WriteFile)?;
+            std::fs::write(crate::consts::DUMP_UI_STATE_FILENAME, ui_state).map_err(|source| {
+                RecordError::WriteFile {
+                    path: crate::consts::DUMP_UI_STATE_FILENAME.into(),
+                    source,
+                }
+            })?;
         }
 
-        match self.input.terminal_kind() {
-            terminal::TerminalKind::Crossterm => self.run_crossterm(),
-            terminal::TerminalKind::Testing { width, height } => self.run_testing(width, height),
+        let recovery_path = self.app.options.recovery_path.clone();
+        let result = run(self);
+        // Keep the recovery file around only if the session was interrupted
+        // by an error.
+        if let (Ok(_) | Err(RecordError::Cancelled), Some(recovery_path)) = (&result, recovery_path)
+        {
+            recovery::remove_recovery_file(&recovery_path)?;
         }
+        result
     }
 
     /// Run the recorder UI using `crossterm` as the backend connected to stdout.
-    fn run_crossterm(self) -> Result<RecordState<'state>, RecordError> {
-        terminal::set_up_crossterm()?;
+    #[cfg(not(target_family = "wasm"))]
+    fn run_crossterm(self) -> Result<(RecordState<'state>, DirtyState), RecordError> {
+        let _termination_signal_guard = terminal::TerminationSignalGuard::new()?;
+        let crossterm_guard = terminal::CrosstermGuard::new()?;
         terminal::install_panic_hook();
         let backend = CrosstermBackend::new(io::stdout());
         let mut term = Terminal::new(backend).map_err(RecordError::SetUpTerminal)?;
         term.clear().map_err(RecordError::RenderFrame)?;
         let result = self.run_inner(&mut term);
-        terminal::clean_up_crossterm()?;
-        result
+        // If the terminal was closed, it can't be cleaned up, but the session
+        // should still be reported as cancelled.
+        let clean_up_result = crossterm_guard.clean_up();
+        let result = result?;
+        clean_up_result?;
+        Ok(result)
     }
 
-    fn run_testing(self, width: usize, height: usize) -> Result<RecordState<'state>, RecordError> {
-        let backend = TestBackend::new(width.clamp_into_u16(), height.clamp_into_u16());
+    fn run_testing(
+        self,
+        width: usize,
+        height: usize,
+    ) -> Result<(RecordState<'state>, DirtyState), RecordError> {
+        let (width, height) = terminal::validate_terminal_size(width, height);
+        let backend = TestBackend::new(width, height);
         let mut term = Terminal::new(backend).map_err(RecordError::SetUpTerminal)?;
         self.run_inner(&mut term)
     }
@@ -69,18 +252,108 @@ impl<'state, 'input> Recorder<'state, 'input> {
     fn run_inner(
         mut self,
         term: &mut Terminal<impl Backend + Any>,
-    ) -> Result<RecordState<'state>, RecordError> {
-        let debug = if cfg!(feature = "debug") {
-            std::env::var_os(ENV_VAR_DEBUG_UI).is_some()
-        } else {
-            false
-        };
+    ) -> Result<(RecordState<'state>, DirtyState), RecordError> {
+        self.last_input_time = Instant::now();
+        loop {
+            let drawn_rects = self.draw(term)?;
+            let are_pending = !self.pending_events.is_empty();
+            let mut events: VecDeque<event::Event> = if !are_pending {
+                // Wait for input, but wake up in time for the earliest timer.
+                let events = match self.next_deadline() {
+                    Some(deadline) => self
+                        .input
+                        .poll_events(deadline.saturating_duration_since(Instant::now()))?,
+                    None => self.input.next_events()?,
+                };
+                let now = Instant::now();
+                if events.is_empty() {
+                    self.timer_events(now)
+                } else {
+                    self.last_input_time = now;
+                    events
+                }
+            } else {
+                // FIXME: the pending events should be applied without redrawing
+                // the screen, as otherwise there may be a flash of content
+                // containing the screen contents before the event is applied.
+                mem::take(&mut self.pending_events)
+            }
+            .into();
+            while events.back() == Some(&event::Event::Resize) {
+                let more_events = self.input.poll_events(RESIZE_DEBOUNCE)?;
+                if more_events.is_empty() {
+                    break;
+                }
+                events.extend(more_events);
+            }
+            if self.handle_events(term, events, are_pending, &drawn_rects)? {
+                break;
+            }
+        }
+
+        Ok((self.app.state, self.app.dirty))
+    }
+
+    /// When to report that the user is idle, if they haven't been already.
+    fn idle_deadline(&self) -> Option<Instant> {
+        match self.app.options.idle_timeout {
+            Some(idle_timeout) if !self.is_idle => Some(self.last_input_time + idle_timeout),
+            Some(_) | None => None,
+        }
+    }
+
+    /// The earliest time at which a timer fires, if any.
+    fn next_deadline(&self) -> Option<Instant> {
+        let status_message_deadline = self
+            .app
+            .ui
+            .status_message
+            .as_ref()
+            .and_then(|status_message| status_message.expires_at);
+        [
+            self.key_sequence_deadline,
+            self.idle_deadline(),
+            status_message_deadline,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
 
-        'outer: loop {
+    /// The events for the timers which fired by `now`.
+    fn timer_events(&self, now: Instant) -> Vec<event::Event> {
+        let mut events = Vec::new();
+        // Stop waiting for the rest of a key sequence after a while.
+        if self
+            .key_sequence_deadline
+            .is_some_and(|deadline| deadline <= now)
+        {
+            events.push(event::Event::KeySequenceTimeout);
+        }
+        if self.idle_deadline().is_some_and(|deadline| deadline <= now) {
+            events.push(event::Event::Idle);
+        }
+        // An expired status message is cleared before the next frame is
+        // drawn.
+        events
+    }
+
+    /// Render the app, returning where each component was drawn.
+    fn draw(
+        &mut self,
+        term: &mut Terminal<impl Backend>,
+    ) -> Result<DrawnRects<ComponentId>, RecordError> {
+        loop {
+            if let Some(status_message) = &self.app.ui.status_message {
+                if status_message.is_expired(Instant::now()) {
+                    self.app.ui.status_message = None;
+                }
+            }
             let app_view = self.app.view(None);
             let term_height = usize::from(term.get_frame().area().height);
 
             let mut drawn_rects: Option<DrawnRects<ComponentId>> = None;
+            let render_start = Instant::now();
             term.draw(|frame| {
                 drawn_rects = Some(Viewport::<ComponentId>::render_top_level(
                     frame,
@@ -88,13 +361,24 @@ impl<'state, 'input> Recorder<'state, 'input> {
                     self.app.ui.scroll_offset_y,
                     &app_view,
                 ));
+                terminal::apply_capabilities(frame.buffer_mut(), self.capabilities);
             })
             .map_err(RecordError::RenderFrame)?;
             let drawn_rects = drawn_rects.unwrap();
+            if let Some(metrics) = &mut self.metrics {
+                metrics.frame_rendered(render_start.elapsed());
+            }
+
+            // The selection should always have been drawn, but rather than
+            // failing later on, select something which was and draw again.
+            if let Some(selection_key) = self.app.repair_selection(&drawn_rects) {
+                self.app.ui.selection_key = selection_key;
+                continue;
+            }
 
             // Dump debug info. We may need to use information about the
             // rendered app, so we perform a re-render here.
-            if debug {
+            if self.debug {
                 let debug_info = AppDebugInfo {
                     term_height,
                     scroll_offset_y: self.app.ui.scroll_offset_y,
@@ -115,124 +399,665 @@ impl<'state, 'input> Recorder<'state, 'input> {
                         self.app.ui.scroll_offset_y,
                         &debug_app,
                     );
+                    terminal::apply_capabilities(frame.buffer_mut(), self.capabilities);
                 })
                 .map_err(RecordError::RenderFrame)?;
             }
 
-            let events = if self.pending_events.is_empty() {
-                self.input.next_events()?
-            } else {
-                // FIXME: the pending events should be applied without redrawing
-                // the screen, as otherwise there may be a flash of content
-                // containing the screen contents before the event is applied.
-                mem::take(&mut self.pending_events)
-            };
-            for event in events {
-                match self.app.handle_event(event, term_height, &drawn_rects)? {
-                    StateUpdate::None => {}
-                    StateUpdate::SetHelpDialog(help_dialog) => {
-                        self.app.ui.help_dialog = help_dialog;
-                    }
-                    StateUpdate::QuitAccept => {
-                        if self.app.ui.help_dialog.is_some() {
-                            self.app.ui.help_dialog = None;
-                        } else {
-                            break 'outer;
-                        }
-                    }
-                    StateUpdate::QuitCancel => return Err(RecordError::Cancelled),
-                    StateUpdate::TakeScreenshot(screenshot) => {
-                        let backend: &dyn Any = term.backend();
-                        let test_backend = backend
-                            .downcast_ref::<TestBackend>()
-                            .expect("TakeScreenshot event generated for non-testing backend");
-                        screenshot.set(terminal::buffer_view(test_backend.buffer()));
+            self.drawn_rects = drawn_rects.clone();
+            self.drawn_scroll_offset_y = self.app.ui.scroll_offset_y;
+            return Ok(drawn_rects);
+        }
+    }
+
+    /// Where the components were drawn in the last frame.
+    fn layout(&self) -> DrawnLayout {
+        let mut components: Vec<(usize, DrawnComponent, Rect)> = self
+            .drawn_rects
+            .iter()
+            .map(|(id, DrawnRect { rect, timestamp })| {
+                let component = match id {
+                    ComponentId::SelectableItem(selection_key) => {
+                        DrawnComponent::Item(selected_item(&self.app, *selection_key))
                     }
-                    StateUpdate::Redraw => {
-                        term.clear().map_err(RecordError::RenderFrame)?;
+                    ComponentId::ToggleBox(selection_key) => {
+                        DrawnComponent::ToggleBox(selected_item(&self.app, *selection_key))
                     }
-                    StateUpdate::EnsureSelectionInViewport => {
-                        if let Some(scroll_offset_y) = self.app.ensure_in_viewport(
-                            term_height,
-                            &drawn_rects,
-                            self.app.ui.selection_key,
-                        ) {
-                            self.app.ui.scroll_offset_y = scroll_offset_y;
-                        }
+                    ComponentId::ExpandBox(selection_key) => {
+                        DrawnComponent::ExpandBox(selected_item(&self.app, *selection_key))
                     }
-                    StateUpdate::ScrollTo(scroll_offset_y) => {
-                        self.app.ui.scroll_offset_y = scroll_offset_y.clamp(0, {
-                            let DrawnRect { rect, timestamp: _ } = drawn_rects[&ComponentId::App];
-                            rect.height.unwrap_isize() - 1
+                    id => DrawnComponent::Other(format!("{id:?}")),
+                };
+                // The components were drawn on a canvas which was scrolled.
+                let rect = Rect {
+                    y: rect.y - self.drawn_scroll_offset_y,
+                    ..*rect
+                };
+                (*timestamp, component, rect)
+            })
+            .collect();
+        components.sort_by_key(|(timestamp, _, _)| *timestamp);
+        DrawnLayout {
+            components: components
+                .into_iter()
+                .map(|(_, component, rect)| (component, rect))
+                .collect(),
+        }
+    }
+
+    /// Handle the events and the events which they cause, drawing in between
+    /// and afterwards, for callers which own the event loop. Returns whether
+    /// the user accepted the changes and the session is over.
+    fn process_events(
+        &mut self,
+        term: &mut Terminal<impl Backend + Any>,
+        events: Vec<event::Event>,
+    ) -> Result<bool, RecordError> {
+        let mut events: VecDeque<event::Event> = events.into();
+        let mut are_pending = false;
+        loop {
+            let drawn_rects = self.draw(term)?;
+            if events.is_empty() {
+                return Ok(false);
+            }
+            if self.handle_events(term, events, are_pending, &drawn_rects)? {
+                return Ok(true);
+            }
+            events = mem::take(&mut self.pending_events).into();
+            are_pending = true;
+        }
+    }
+
+    /// Handle the events, along with any events which they queue up.
+    /// `are_pending` is whether the events were queued by the recorder
+    /// rather than read from the input. Returns whether the user accepted
+    /// the changes and the session is over.
+    fn handle_events(
+        &mut self,
+        term: &mut Terminal<impl Backend + Any>,
+        mut events: VecDeque<event::Event>,
+        are_pending: bool,
+        drawn_rects: &DrawnRects<ComponentId>,
+    ) -> Result<bool, RecordError> {
+        // Only the transition log tells pending events apart.
+        #[cfg(not(feature = "debug"))]
+        let _ = are_pending;
+        let mut term_width = usize::from(term.get_frame().area().width);
+        let mut term_height = usize::from(term.get_frame().area().height);
+        // Events generated while handling other events, such as the keys
+        // of a replayed macro, which are handled before the next event
+        // from the input.
+        let mut queued_events: VecDeque<event::Event> = VecDeque::new();
+        while let Some((event, is_from_input)) = queued_events
+            .pop_front()
+            .map(|event| (event, false))
+            .or_else(|| events.pop_front().map(|event| (event, true)))
+        {
+            // Status messages are shown until the next user action.
+            match event {
+                event::Event::None
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::TakeScreenshot(_) => {}
+                event::Event::Idle => self.is_idle = true,
+                _ => {
+                    self.app.ui.status_message = None;
+                    self.is_idle = false;
+                }
+            }
+
+            // A key press either continues or ends the pending key
+            // sequence.
+            let ends_key_sequence = matches!(
+                event,
+                event::Event::Key(KeyEvent {
+                    kind: KeyEventKind::Press,
+                    ..
+                }) | event::Event::KeySequenceTimeout
+            );
+            let recorded_key_event = match event {
+                event::Event::Key(
+                    key_event @ KeyEvent {
+                        kind: KeyEventKind::Press,
+                        ..
+                    },
+                ) if is_from_input => Some(key_event),
+                _ => None,
+            };
+            let was_recording_macro = self.app.ui.macros.recording_register().is_some();
+            let old_selection_key = self.app.ui.selection_key;
+            let old_tristate = self.app.item_tristate(old_selection_key);
+            #[cfg(feature = "debug")]
+            let traced_event = self.transition_log.as_ref().map(|_| event.clone());
+            let state_update =
+                self.app
+                    .handle_event(event, term_width, term_height, drawn_rects)?;
+            #[cfg(feature = "debug")]
+            let traced_transition = traced_event.map(|event| {
+                (
+                    event,
+                    is_from_input && !are_pending,
+                    format!("{state_update:?}"),
+                )
+            });
+            if ends_key_sequence {
+                self.app.ui.pending_keys.clear();
+                self.app.ui.count = None;
+                self.app.ui.macro_prompt = None;
+                self.key_sequence_deadline = None;
+            }
+
+            match state_update {
+                StateUpdate::SetHelpDialog(help_dialog) => {
+                    self.app.ui.help_dialog = help_dialog;
+                }
+                StateUpdate::QuitAccept => {
+                    if self.app.ui.help_dialog.is_some() {
+                        self.app.ui.help_dialog = None;
+                    } else {
+                        self.record_metric(|metrics, duration| {
+                            metrics.session_ended(duration, /*is_accepted=*/ true)
                         });
+                        return Ok(true);
                     }
-                    StateUpdate::SelectItem {
-                        selection_key,
-                        ensure_in_viewport,
-                    } => {
-                        self.app.ui.selection_key = selection_key;
-                        self.app.expand_item_ancestors(selection_key);
-                        if ensure_in_viewport {
-                            self.pending_events
-                                .push(event::Event::EnsureSelectionInViewport);
-                        }
-                    }
-                    StateUpdate::ToggleItem(selection_key) => {
-                        self.app.toggle_item(selection_key)?;
+                }
+                StateUpdate::QuitCancel => {
+                    self.record_metric(|metrics, duration| {
+                        metrics.session_ended(duration, /*is_accepted=*/ false)
+                    });
+                    return Err(RecordError::Cancelled);
+                }
+                StateUpdate::TakeScreenshot(screenshot) => {
+                    let backend: &dyn Any = term.backend();
+                    let test_backend = backend
+                        .downcast_ref::<TestBackend>()
+                        .expect("TakeScreenshot event generated for non-testing backend");
+                    screenshot.set(terminal::buffer_view(test_backend.buffer()));
+                }
+                StateUpdate::Redraw => {
+                    term.clear().map_err(RecordError::RenderFrame)?;
+                }
+                StateUpdate::Resize => {
+                    term.autoresize().map_err(RecordError::RenderFrame)?;
+                    term.clear().map_err(RecordError::RenderFrame)?;
+                    let area = term.get_frame().area();
+                    term_width = usize::from(area.width);
+                    term_height = usize::from(area.height);
+                    // Don't leave the screen scrolled past the end of the
+                    // contents when the terminal gets taller. The contents
+                    // are only laid out for the new size by the next render,
+                    // after which the selection is scrolled back into view.
+                    let DrawnRect { rect, timestamp: _ } = drawn_rects[&ComponentId::App];
+                    let max_scroll_offset_y = (rect.end_y() - term_height.unwrap_isize()).max(0);
+                    self.app.ui.scroll_offset_y =
+                        self.app.ui.scroll_offset_y.clamp(0, max_scroll_offset_y);
+                    self.pending_events
+                        .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::EnsureSelectionInViewport => {
+                    if let Some(scroll_offset_y) = self.app.ensure_in_viewport(
+                        term_height,
+                        drawn_rects,
+                        self.app.ui.selection_key,
+                    ) {
+                        self.app.ui.scroll_offset_y = scroll_offset_y;
                     }
-                    StateUpdate::ToggleItemAndAdvance(selection_key, new_key) => {
-                        self.app.toggle_item(selection_key)?;
-                        self.app.ui.selection_key = new_key;
-                        self.pending_events
-                            .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::ScrollTo(scroll_offset_y) => {
+                    self.app.ui.scroll_offset_y = scroll_offset_y.clamp(0, {
+                        let DrawnRect { rect, timestamp: _ } = drawn_rects[&ComponentId::App];
+                        (rect.height.unwrap_isize() - 1).max(0)
+                    });
+                }
+                state_update @ (StateUpdate::ToggleItem(_)
+                | StateUpdate::ToggleItemAndAdvance(..)) => {
+                    self.apply_state_update(state_update)?;
+                    self.record_metric(|metrics, _| metrics.item_toggled());
+                }
+                state_update @ (StateUpdate::ToggleAll | StateUpdate::ToggleAllUniform) => {
+                    self.apply_state_update(state_update)?;
+                    self.record_metric(|metrics, _| metrics.all_toggled());
+                }
+                StateUpdate::SetExpandItem(selection_key, is_expanded) => {
+                    self.app
+                        .set_expand_item_and_position(drawn_rects, selection_key, is_expanded);
+                    self.record_metric(|metrics, _| metrics.item_expanded(is_expanded));
+                    self.pending_events
+                        .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::ToggleExpandItem(selection_key @ SelectionKey::File(_)) => {
+                    let is_expanded = self.app.ui.expanded_items.contains(&selection_key);
+                    self.app
+                        .set_expand_item_and_position(drawn_rects, selection_key, !is_expanded);
+                    self.record_metric(|metrics, _| metrics.item_expanded(!is_expanded));
+                    self.pending_events
+                        .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::ToggleExpandItem(selection_key) => {
+                    self.apply_state_update(StateUpdate::ToggleExpandItem(selection_key))?;
+                    let is_expanded = self.app.ui.expanded_items.contains(&selection_key);
+                    self.record_metric(|metrics, _| metrics.item_expanded(is_expanded));
+                }
+                StateUpdate::ToggleExpandAll => {
+                    self.app.toggle_expand_all()?;
+                    self.app.ensure_selection_visible();
+                    self.pending_events
+                        .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::ToggleCommitViewMode => {
+                    self.app.ui.commit_view_mode = match self.app.ui.commit_view_mode {
+                        CommitViewMode::Inline => CommitViewMode::Adjacent,
+                        CommitViewMode::Adjacent => CommitViewMode::Inline,
+                    };
+                }
+                StateUpdate::SetFileFilterPrompt(prompt) => {
+                    self.app.ui.file_filter_prompt = prompt;
+                    self.app.ensure_selection_visible();
+                }
+                StateUpdate::SetFileFilter(file_filter) => {
+                    self.app.ui.file_filter_prompt = None;
+                    self.app.ui.file_filter =
+                        file_filter.filter(|file_filter| !file_filter.is_empty());
+                    self.app.ensure_selection_visible();
+                    self.pending_events
+                        .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::SetSearchPrompt(search_prompt, selection_key) => {
+                    self.app.ui.search_prompt = Some(search_prompt);
+                    self.app.select_item(selection_key);
+                    self.pending_events
+                        .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::SetSearchQuery(search_query) => {
+                    self.app.ui.search_prompt = None;
+                    if search_query.is_some() {
+                        self.app.ui.search_query = search_query;
                     }
-                    StateUpdate::ToggleAll => {
-                        self.app.toggle_all();
+                }
+                StateUpdate::CancelSearch(selection_key) => {
+                    self.app.ui.search_prompt = None;
+                    self.app.select_item(selection_key);
+                    self.pending_events
+                        .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::CopyToClipboard { text, description } => {
+                    self.app.ui.status_message = Some(match self.input.copy_to_clipboard(&text) {
+                        Ok(()) => {
+                            StatusMessage::info(format!("Copied {description} to the clipboard"))
+                        }
+                        Err(err) => StatusMessage::error(format!(
+                            "Failed to copy {description} to the clipboard: {err}"
+                        )),
+                    });
+                }
+                StateUpdate::SetPendingKeys(keys) => {
+                    self.app.ui.pending_keys = keys;
+                    self.key_sequence_deadline =
+                        Some(Instant::now() + self.app.options.keymap.sequence_timeout);
+                }
+                StateUpdate::Autosave => {
+                    if let Err(err) = self.autosave() {
+                        self.app.ui.status_message = Some(StatusMessage::error(format!(
+                            "Failed to save recovery file: {err}"
+                        )));
                     }
-                    StateUpdate::ToggleAllUniform => {
-                        self.app.toggle_all_uniform();
+                }
+                StateUpdate::SetCount(count) => {
+                    self.app.ui.count = count;
+                }
+                StateUpdate::SetMacroPrompt(macro_prompt) => {
+                    self.app.ui.macro_prompt = macro_prompt;
+                }
+                StateUpdate::StartRecordingMacro(register) => {
+                    self.app.ui.macros.start_recording(register);
+                }
+                StateUpdate::StopRecordingMacro => {
+                    self.app.ui.macros.stop_recording();
+                }
+                StateUpdate::ReplayMacro { register, count } => {
+                    match self.app.ui.macros.replay(register) {
+                        Some(key_events) => {
+                            let replayed_events: Vec<event::Event> =
+                                iter::repeat_n(key_events, count)
+                                    .flatten()
+                                    .copied()
+                                    .map(event::Event::Key)
+                                    .collect();
+                            for event in replayed_events.into_iter().rev() {
+                                queued_events.push_front(event);
+                            }
+                        }
+                        None => {
+                            self.app.ui.status_message = Some(StatusMessage::error(format!(
+                                "No macro recorded in register @{register}"
+                            )));
+                        }
                     }
-                    StateUpdate::SetExpandItem(selection_key, is_expanded) => {
-                        self.app.set_expand_item(selection_key, is_expanded);
-                        self.pending_events
-                            .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::QueueEvents(events) => {
+                    for event in events.into_iter().rev() {
+                        queued_events.push_front(event);
                     }
-                    StateUpdate::ToggleExpandItem(selection_key) => {
-                        self.app.toggle_expand_item(selection_key)?;
-                        self.pending_events
-                            .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::EditCommitMessageExternally {
+                    commit_idx,
+                    message,
+                } => {
+                    self.app.ui.message_editor_dialog = None;
+                    self.pending_events.push(event::Event::Redraw);
+                    self.edit_commit_message_externally(commit_idx, message)?;
+                }
+                StateUpdate::SetMessageEditorDialog(message_editor_dialog) => {
+                    self.app.ui.message_editor_dialog = message_editor_dialog;
+                }
+                StateUpdate::SaveCommitMessage {
+                    commit_idx,
+                    message,
+                } => {
+                    self.app.ui.message_editor_dialog = None;
+                    self.app
+                        .record_command(Command::SetCommitMessage { commit_idx }, |app| {
+                            app.set_commit_message(commit_idx, message)
+                        });
+                }
+                StateUpdate::SetMessageDiffDialog(message_diff_dialog) => {
+                    self.app.ui.message_diff_dialog = message_diff_dialog;
+                }
+                StateUpdate::SetLineDiffDialog(line_diff_dialog) => {
+                    self.app.ui.line_diff_dialog = line_diff_dialog;
+                }
+                StateUpdate::SetPreviewDialog(preview_dialog) => {
+                    self.app.ui.preview_dialog = preview_dialog;
+                }
+                StateUpdate::ScrollPreviewDialog(scroll_offset) => {
+                    if let Some(preview_dialog) = &mut self.app.ui.preview_dialog {
+                        preview_dialog.scroll_offset = scroll_offset;
                     }
-                    StateUpdate::ToggleExpandAll => {
-                        self.app.toggle_expand_all()?;
-                        self.pending_events
-                            .push(event::Event::EnsureSelectionInViewport);
+                }
+                StateUpdate::ViewFileContents {
+                    file_idx,
+                    version,
+                    line_idx,
+                } => {
+                    self.view_file_contents(file_idx, version, line_idx);
+                }
+                StateUpdate::SetFileContentsDialog(file_contents_dialog) => {
+                    self.app.ui.file_contents_dialog = file_contents_dialog;
+                }
+                StateUpdate::ScrollFileContentsDialog(scroll_offset) => {
+                    if let Some(file_contents_dialog) = &mut self.app.ui.file_contents_dialog {
+                        file_contents_dialog.scroll_offset = scroll_offset;
                     }
-                    StateUpdate::ToggleCommitViewMode => {
-                        self.app.ui.commit_view_mode = match self.app.ui.commit_view_mode {
-                            CommitViewMode::Inline => CommitViewMode::Adjacent,
-                            CommitViewMode::Adjacent => CommitViewMode::Inline,
-                        };
+                }
+                StateUpdate::PreviewImages { file_idx } => {
+                    self.preview_images(file_idx)?;
+                }
+                StateUpdate::SetErrorDialog(error_dialog) => {
+                    self.app.ui.error_dialog = error_dialog;
+                }
+                StateUpdate::SetToggleAllDialog(toggle_all_dialog) => {
+                    self.app.ui.toggle_all_dialog = toggle_all_dialog;
+                }
+                StateUpdate::ConfirmToggleAll => {
+                    if let Some(toggle_all_dialog) = self.app.ui.toggle_all_dialog.take() {
+                        self.apply_state_update(if toggle_all_dialog.is_uniform {
+                            StateUpdate::ToggleAllUniform
+                        } else {
+                            StateUpdate::ToggleAll
+                        })?;
+                        self.record_metric(|metrics, _| metrics.all_toggled());
                     }
-                    StateUpdate::EditCommitMessage { commit_idx } => {
-                        self.pending_events.push(event::Event::Redraw);
-                        self.edit_commit_message(commit_idx)?;
+                }
+                StateUpdate::SetEmptySelectionDialog(empty_selection_dialog) => {
+                    self.app.ui.empty_selection_dialog = empty_selection_dialog;
+                }
+                StateUpdate::SetQuitDialog(quit_dialog) => {
+                    self.app.ui.quit_dialog = quit_dialog;
+                }
+                StateUpdate::SetMessageHistoryPicker(picker) => {
+                    self.app.ui.message_history_picker = picker;
+                }
+                StateUpdate::UseHistoryMessage {
+                    commit_idx,
+                    history_idx,
+                    edit,
+                } => {
+                    self.app.ui.message_history_picker = None;
+                    let message = self.app.options.message_history[history_idx].clone();
+                    if edit {
+                        self.app.ui.message_editor_dialog =
+                            Some(MessageEditorDialog::new(commit_idx, message));
+                    } else {
+                        self.app
+                            .record_command(Command::SetCommitMessage { commit_idx }, |app| {
+                                app.set_commit_message(commit_idx, message)
+                            });
                     }
                 }
+                state_update => self.apply_state_update(state_update)?,
+            }
+
+            #[cfg(feature = "debug")]
+            if let Some((event, is_from_input, state_update)) = traced_transition {
+                let term_size = term.get_frame().area().as_size();
+                self.log_transition(event, is_from_input, state_update, term_size);
+            }
+
+            if let Some(announcer) = &mut self.announcer {
+                for announcement in self.app.announcements(old_selection_key, old_tristate) {
+                    announcer.announce(&announcement);
+                }
+            }
+
+            // Record the key press if a macro was being recorded both
+            // before and after it, so that the keys which start and stop
+            // the recording aren't part of the macro.
+            if let Some(key_event) = recorded_key_event {
+                if was_recording_macro {
+                    self.app.ui.macros.record(key_event);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Log the transition with the resulting selection. The log is only for
+    /// debugging, so the session goes on without it if it can't be written.
+    #[cfg(feature = "debug")]
+    fn log_transition(
+        &mut self,
+        event: event::Event,
+        is_from_input: bool,
+        state_update: String,
+        term_size: ratatui::layout::Size,
+    ) {
+        let Some(transition_log) = &mut self.transition_log else {
+            return;
+        };
+        let transition = Transition {
+            step: transition_log.next_step(),
+            event,
+            is_from_input,
+            state_update,
+            selection_key: self.app.ui.selection_key,
+            scroll_offset_y: self.app.ui.scroll_offset_y,
+            term_width: usize::from(term_size.width),
+            term_height: usize::from(term_size.height),
+        };
+        if let Err(err) = transition_log.write(&transition) {
+            self.transition_log = None;
+            self.app.ui.status_message = Some(StatusMessage::error(format!(
+                "Failed to trace transitions: {err}"
+            )));
+        }
+    }
+
+    /// Apply the update to the state of the app, scrolling the selected item
+    /// back into view afterwards if it may have moved.
+    fn apply_state_update(&mut self, state_update: StateUpdate) -> Result<(), RecordError> {
+        if self.app.apply_state_update(state_update)? {
+            self.pending_events
+                .push(event::Event::EnsureSelectionInViewport);
+        }
+        Ok(())
+    }
+
+    /// Report to the metrics, if any, along with how long the session has
+    /// lasted so far.
+    fn record_metric(&mut self, f: impl FnOnce(&mut dyn Metrics, Duration)) {
+        if let Some(metrics) = &mut self.metrics {
+            f(metrics.as_mut(), self.created_at.elapsed());
+        }
+    }
+
+    /// Save the in-progress selections to the recovery file, if any.
+    fn autosave(&self) -> Result<(), RecordError> {
+        match &self.app.options.recovery_path {
+            Some(recovery_path) => {
+                recovery::write_recovery_file(recovery_path, &self.app.recovery())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Show the contents of the given version of the file, scrolled so that
+    /// the line at `line_idx` is near the top, or report why they can't be
+    /// shown.
+    fn view_file_contents(
+        &mut self,
+        file_idx: usize,
+        version: input::FileVersion,
+        line_idx: Option<usize>,
+    ) {
+        let file = &self.app.state.files[file_idx];
+        let path = match version {
+            input::FileVersion::Old => file.old_path.as_deref().unwrap_or(&file.path),
+            input::FileVersion::New => &file.path,
+        };
+        let display_path = path.to_string_lossy().into_owned();
+        // Added files have no old version, and deleted files no new one.
+        let (version_name, is_absent) = match version {
+            input::FileVersion::Old => ("old", file.file_mode == FileMode::Absent),
+            input::FileVersion::New => (
+                "new",
+                file.sections.iter().any(|section| {
+                    matches!(
+                        section,
+                        Section::FileMode {
+                            is_checked: _,
+                            commit_idx: _,
+                            mode: FileMode::Absent,
+                        }
+                    )
+                }),
+            ),
+        };
+        let result = if is_absent {
+            Ok(None)
+        } else {
+            self.input.file_contents(path, version)
+        };
+        match result {
+            Ok(Some(contents)) => {
+                // Show a few lines before the selected line for context.
+                const NUM_CONTEXT_LINES: usize = 3;
+                self.app.ui.file_contents_dialog = Some(FileContentsDialog {
+                    path: display_path,
+                    version,
+                    lines: contents.lines().map(str::to_owned).collect(),
+                    scroll_offset: line_idx
+                        .unwrap_or_default()
+                        .saturating_sub(NUM_CONTEXT_LINES),
+                });
+            }
+            Ok(None) => {
+                self.app.ui.status_message = Some(StatusMessage::error(format!(
+                    "There is no {version_name} version of {display_path}"
+                )));
+            }
+            Err(err) => {
+                self.app.ui.status_message = Some(StatusMessage::error(format!(
+                    "Failed to read the {version_name} version of {display_path}: {err}"
+                )));
+            }
+        }
+    }
+
+    fn preview_images(&mut self, file_idx: usize) -> Result<(), RecordError> {
+        let file = &self.app.state.files[file_idx];
+        let display_path = file.path.to_string_lossy().into_owned();
+        let mut images = Vec::new();
+        for version in [input::FileVersion::Old, input::FileVersion::New] {
+            let path = match version {
+                input::FileVersion::Old => file.old_path.as_deref().unwrap_or(&file.path),
+                input::FileVersion::New => &file.path,
+            };
+            match self.input.image_contents(path, version) {
+                Ok(Some(contents)) => images.push(input::ImagePreview { version, contents }),
+                Ok(None) => {}
+                Err(err) => {
+                    self.app.ui.status_message = Some(StatusMessage::error(format!(
+                        "Failed to read the images of {display_path}: {err}"
+                    )));
+                    return Ok(());
+                }
             }
         }
+        if images.is_empty() {
+            self.app.ui.status_message = Some(StatusMessage::error(format!(
+                "There are no images to preview for {display_path}"
+            )));
+            return Ok(());
+        }
 
-        Ok(self.app.state)
+        let path = file.path.clone();
+        match self.input.show_images(&path, &images) {
+            Ok(true) => {
+                self.pending_events.push(event::Event::Redraw);
+            }
+            // Fall back to the metadata of the file, which is shown with its
+            // binary section.
+            Ok(false) => {
+                let selection_key = SelectionKey::File(FileKey {
+                    commit_idx: self.app.ui.focused_commit_idx,
+                    file_idx,
+                });
+                self.app.set_expand_item(selection_key, true);
+                self.app.ui.status_message = Some(StatusMessage::info(format!(
+                    "This terminal can't show images, so only the metadata of {display_path} is shown"
+                )));
+            }
+            Err(RecordError::Cancelled) => return Err(RecordError::Cancelled),
+            Err(err) => {
+                self.pending_events.push(event::Event::Redraw);
+                self.app.ui.status_message = Some(StatusMessage::error(format!(
+                    "Failed to show the images of {display_path}: {err}"
+                )));
+            }
+        }
+        Ok(())
     }
 
-    fn edit_commit_message(&mut self, commit_idx: usize) -> Result<(), RecordError> {
-        let message = &mut self.app.state.commits[commit_idx].message;
-        let message_str = match message.as_ref() {
+    /// Edit the commit message with the input, starting from `message` if
+    /// it's set, such as the text typed into the message editor so far.
+    fn edit_commit_message_externally(
+        &mut self,
+        commit_idx: usize,
+        message: Option<String>,
+    ) -> Result<(), RecordError> {
+        let message_str = match message
+            .as_deref()
+            .or(self.app.state.commits[commit_idx].message.as_deref())
+        {
             Some(message) => message,
             None => return Ok(()),
         };
         let new_message = {
             match self.input.terminal_kind() {
                 terminal::TerminalKind::Testing { .. } => {}
+                #[cfg(not(target_family = "wasm"))]
                 terminal::TerminalKind::Crossterm => {
                     terminal::clean_up_crossterm()?;
                 }
@@ -240,13 +1065,690 @@ impl<'state, 'input> Recorder<'state, 'input> {
             let result = self.input.edit_commit_message(message_str);
             match self.input.terminal_kind() {
                 terminal::TerminalKind::Testing { .. } => {}
+                #[cfg(not(target_family = "wasm"))]
                 terminal::TerminalKind::Crossterm => {
                     terminal::set_up_crossterm()?;
                 }
             }
-            result?
+            match result {
+                Ok(new_message) => new_message,
+                Err(RecordError::Cancelled) => return Err(RecordError::Cancelled),
+                // Keep the old message and the user's selections rather than
+                // ending the session.
+                Err(err) => {
+                    self.app.ui.error_dialog = Some(ErrorDialog {
+                        title: "Failed to edit commit message".to_string(),
+                        message: err.to_string(),
+                    });
+                    return Ok(());
+                }
+            }
+        };
+        if let Some(old_message) = self
+            .app
+            .record_command(Command::SetCommitMessage { commit_idx }, |app| {
+                app.set_commit_message(commit_idx, new_message)
+            })
+        {
+            self.app.ui.message_diff_dialog = Some(MessageDiffDialog {
+                old_message,
+                new_message: self.app.state.commits[commit_idx]
+                    .message
+                    .clone()
+                    .unwrap_or_default(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A component of the UI, as reported by [`DrawnLayout`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DrawnComponent {
+    /// A directory, file, section or changed line.
+    Item(SelectedItem),
+
+    /// The checkbox which toggles the item.
+    ToggleBox(SelectedItem),
+
+    /// The arrow which expands or collapses the item.
+    ExpandBox(SelectedItem),
+
+    /// Any other component, such as a dialog or a button, named after its
+    /// internal identifier. These names aren't stable across versions.
+    Other(String),
+}
+
+/// Where the components of the UI were drawn in the last frame, so that
+/// callers can draw overlays on top of the recorder or route mouse clicks to
+/// the right item. The positions are relative to the top-left corner of the
+/// recorder's area, and may extend past its edges for components which were
+/// only partly visible.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DrawnLayout {
+    /// In the order in which they were drawn.
+    components: Vec<(DrawnComponent, Rect)>,
+}
+
+impl DrawnLayout {
+    /// The components which were drawn, in the order in which they were
+    /// drawn. Components drawn later appear on top of the ones before them,
+    /// and come after their parent components.
+    pub fn components(&self) -> impl Iterator<Item = (&DrawnComponent, Rect)> {
+        self.components
+            .iter()
+            .map(|(component, rect)| (component, *rect))
+    }
+
+    /// Where the component was drawn, if it was.
+    pub fn rect(&self, component: &DrawnComponent) -> Option<Rect> {
+        self.components()
+            .find(|(drawn_component, _)| *drawn_component == component)
+            .map(|(_, rect)| rect)
+    }
+
+    /// The topmost component drawn at the given column and row.
+    pub fn component_at(&self, x: isize, y: isize) -> Option<&DrawnComponent> {
+        self.components
+            .iter()
+            .rev()
+            .find(|(_, rect)| {
+                (rect.x..rect.end_x()).contains(&x) && (rect.y..rect.end_y()).contains(&y)
+            })
+            .map(|(component, _)| component)
+    }
+}
+
+/// Drives a [`Recorder`] one event at a time, without a terminal, so that
+/// tests can inspect the state and selection between events instead of
+/// taking screenshots.
+///
+/// The app is drawn to an in-memory buffer of the given size after each
+/// event, since the outcome of some events, such as paging, depends on the
+/// layout.
+pub struct TestingRecorder<'state, 'input> {
+    recorder: Recorder<'state, 'input>,
+    term: Terminal<TestBackend>,
+    is_finished: bool,
+}
+
+impl<'state, 'input> TestingRecorder<'state, 'input> {
+    /// Constructor. Fails if the state of the recorder is invalid. The
+    /// events of the recorder's input aren't used; pass events to
+    /// [`TestingRecorder::handle_event`] instead.
+    pub fn new(
+        recorder: Recorder<'state, 'input>,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, RecordError> {
+        recorder.app.state.validate()?;
+        let (width, height) = terminal::validate_terminal_size(width, height);
+        let backend = TestBackend::new(width, height);
+        let term = Terminal::new(backend).map_err(RecordError::SetUpTerminal)?;
+        Ok(Self {
+            recorder,
+            term,
+            is_finished: false,
+        })
+    }
+
+    /// Handle the event, along with any events which it causes, and redraw.
+    /// Returns [`RecordError::Cancelled`] if the event quit without accepting
+    /// the changes. Events are ignored once the session is finished.
+    pub fn handle_event(&mut self, event: event::Event) -> Result<(), RecordError> {
+        let Self {
+            recorder,
+            term,
+            is_finished,
+        } = self;
+        if !*is_finished {
+            *is_finished = recorder.process_events(term, vec![event])?;
+        }
+        Ok(())
+    }
+
+    /// Replay the events which were read from the input in the trace, up to
+    /// and including the given step, along with the events which they cause.
+    /// The recorder should have been created with the same state and
+    /// terminal size as the traced session (see
+    /// [`TransitionTrace::terminal_size`]).
+    #[cfg(feature = "debug")]
+    pub fn replay_to(&mut self, trace: &TransitionTrace, step: usize) -> Result<(), RecordError> {
+        for event in trace.input_events(step) {
+            self.handle_event(event.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Whether the user accepted the changes, ending the session.
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    /// The current state, including the user's selections so far.
+    pub fn state(&self) -> &RecordState<'state> {
+        &self.recorder.app.state
+    }
+
+    /// Which parts of the state the user modified so far.
+    pub fn dirty_state(&self) -> DirtyState {
+        self.recorder.app.dirty
+    }
+
+    /// The item which is currently selected.
+    pub fn selected_item(&self) -> SelectedItem {
+        let app = &self.recorder.app;
+        selected_item(app, app.ui.selection_key)
+    }
+
+    /// Where the components were drawn as of the last event.
+    pub fn layout(&self) -> DrawnLayout {
+        self.recorder.layout()
+    }
+
+    /// The contents of the screen as of the last event, in the same format
+    /// as a [`crate::TestingScreenshot`].
+    pub fn screen(&self) -> String {
+        terminal::buffer_view(self.term.backend().buffer())
+    }
+
+    /// End the session, returning the final state and which parts of it the
+    /// user modified.
+    pub fn into_state(self) -> (RecordState<'state>, DirtyState) {
+        let Self {
+            recorder,
+            term: _,
+            is_finished: _,
+        } = self;
+        (recorder.app.state, recorder.app.dirty)
+    }
+}
+
+/// A [`Recorder`] which is drawn as a pane of a larger `ratatui` app. The
+/// host app owns the event loop: it passes the events meant for the recorder
+/// to [`EmbeddedRecorder::handle_events`], for example by converting them
+/// with `Event::from(crossterm_event)`, and calls
+/// [`EmbeddedRecorder::render`] when drawing each frame.
+///
+/// The recorder is laid out in a buffer of its own with the size of the
+/// pane, which is copied into the frame, so it never draws outside of the
+/// pane.
+pub struct EmbeddedRecorder<'state, 'input> {
+    recorder: Recorder<'state, 'input>,
+    term: Terminal<TestBackend>,
+    is_finished: bool,
+}
+
+impl<'state, 'input> EmbeddedRecorder<'state, 'input> {
+    /// Constructor. Fails if the state of the recorder is invalid. The
+    /// recorder's input is only used to edit commit messages and copy to the
+    /// clipboard; its events aren't used.
+    pub fn new(recorder: Recorder<'state, 'input>) -> Result<Self, RecordError> {
+        recorder.app.state.validate()?;
+        // The actual size is set when the pane is first rendered.
+        let backend = TestBackend::new(1, 1);
+        let term = Terminal::new(backend).map_err(RecordError::SetUpTerminal)?;
+        Ok(Self {
+            recorder,
+            term,
+            is_finished: false,
+        })
+    }
+
+    /// Handle the events, along with any events which they cause. Returns
+    /// whether the user accepted the changes, ending the session, or
+    /// [`RecordError::Cancelled`] if they quit without accepting them.
+    pub fn handle_events(
+        &mut self,
+        events: impl IntoIterator<Item = event::Event>,
+    ) -> Result<bool, RecordError> {
+        let Self {
+            recorder,
+            term,
+            is_finished,
+        } = self;
+        let events: Vec<event::Event> = events.into_iter().collect();
+        if !*is_finished && !events.is_empty() {
+            recorder.last_input_time = Instant::now();
+            *is_finished = recorder.process_events(term, events)?;
+        }
+        Ok(*is_finished)
+    }
+
+    /// When the host app should call [`EmbeddedRecorder::handle_timers`]
+    /// next, even if there are no new events, if ever.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.recorder.next_deadline()
+    }
+
+    /// Handle the timers which have fired, such as giving up on waiting for
+    /// the rest of a key sequence. Returns the same as
+    /// [`EmbeddedRecorder::handle_events`].
+    pub fn handle_timers(&mut self) -> Result<bool, RecordError> {
+        let Self {
+            recorder,
+            term,
+            is_finished,
+        } = self;
+        if !*is_finished {
+            let events = recorder.timer_events(Instant::now());
+            *is_finished = recorder.process_events(term, events)?;
+        }
+        Ok(*is_finished)
+    }
+
+    /// Draw the recorder into the given area of the frame.
+    pub fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: ratatui::layout::Rect,
+    ) -> Result<(), RecordError> {
+        let Self {
+            recorder,
+            term,
+            is_finished: _,
+        } = self;
+        let ratatui::layout::Rect {
+            x: area_x,
+            y: area_y,
+            width,
+            height,
+        } = area;
+        if term.backend().buffer().area.as_size() != area.as_size() {
+            term.backend_mut().resize(width, height);
+            term.resize(ratatui::layout::Rect::new(0, 0, width, height))
+                .map_err(RecordError::RenderFrame)?;
+        }
+        recorder.process_events(term, Vec::new())?;
+
+        let buf = frame.buffer_mut();
+        for y in 0..height {
+            for x in 0..width {
+                if let (Some(cell), Some(frame_cell)) = (
+                    term.backend().buffer().cell((x, y)),
+                    buf.cell_mut((area_x + x, area_y + y)),
+                ) {
+                    *frame_cell = cell.clone();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The current state, including the user's selections so far.
+    pub fn state(&self) -> &RecordState<'state> {
+        &self.recorder.app.state
+    }
+
+    /// Where the components were drawn in the last frame, relative to the
+    /// top-left corner of the pane.
+    pub fn layout(&self) -> DrawnLayout {
+        self.recorder.layout()
+    }
+
+    /// End the session, returning the final state and which parts of it the
+    /// user modified.
+    pub fn into_state(self) -> (RecordState<'state>, DirtyState) {
+        let Self {
+            recorder,
+            term: _,
+            is_finished: _,
+        } = self;
+        (recorder.app.state, recorder.app.dirty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::path::Path;
+
+    use ratatui::style::Modifier;
+
+    use crate::ui::key_event::{KeyCode, KeyModifiers};
+
+    use super::*;
+    use crate::helpers::TestingInput;
+    use crate::types::{ChangeType, Commit, File, SectionChangedLine, SelectedContents};
+    use crate::ui::input::TestingScreenshot;
+
+    fn changed_line(change_type: ChangeType, line: &'static str) -> SectionChangedLine<'static> {
+        SectionChangedLine {
+            is_checked: false,
+            commit_idx: 0,
+            change_type,
+            line: Cow::Borrowed(line),
+            annotation: None,
+        }
+    }
+
+    fn file(path: &'static str, sections: Vec<Section<'static>>) -> File<'static> {
+        File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new(path)),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections,
+            is_deferred: false,
+            is_lossy: false,
+        }
+    }
+
+    fn state() -> RecordState<'static> {
+        RecordState {
+            is_read_only: false,
+            commits: vec![
+                Commit {
+                    message: Some("Subject".to_owned()),
+                },
+                Commit::default(),
+            ],
+            files: vec![
+                file(
+                    "bar",
+                    vec![Section::Changed {
+                        label: None,
+                        context: None,
+                        lines: vec![
+                            changed_line(ChangeType::Removed, "let x = 1;\n"),
+                            changed_line(ChangeType::Added, "let x = 2;\n"),
+                        ],
+                    }],
+                ),
+                file(
+                    "foo",
+                    vec![
+                        Section::Unchanged {
+                            lines: vec![Cow::Borrowed("fn main() {\n")],
+                        },
+                        Section::Changed {
+                            label: None,
+                            context: None,
+                            lines: vec![
+                                changed_line(ChangeType::Removed, "    old();\n"),
+                                changed_line(ChangeType::Added, "    new();\n"),
+                            ],
+                        },
+                        Section::Unchanged {
+                            lines: vec![Cow::Borrowed("}\n")],
+                        },
+                    ],
+                ),
+            ],
+        }
+    }
+
+    fn key(code: KeyCode) -> event::Event {
+        event::Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn type_text(recorder: &mut TestingRecorder, text: &str) -> Result<(), RecordError> {
+        for c in text.chars() {
+            recorder.handle_event(key(KeyCode::Char(c)))?;
+        }
+        Ok(())
+    }
+
+    fn line_item(file_idx: usize, section_idx: usize, line_idx: usize) -> SelectedItem {
+        SelectedItem::Line {
+            commit_idx: 0,
+            file_idx,
+            section_idx,
+            line_idx,
+        }
+    }
+
+    /// The runs of text on the screen which are drawn with the given
+    /// modifier, in order.
+    fn text_with_modifier(recorder: &TestingRecorder, modifier: Modifier) -> Vec<String> {
+        let buffer = recorder.term.backend().buffer();
+        let mut runs = Vec::new();
+        for row in buffer.content.chunks(usize::from(buffer.area.width)) {
+            let mut run = String::new();
+            for cell in row {
+                if cell.modifier.contains(modifier) {
+                    run.push_str(cell.symbol());
+                } else if !run.is_empty() {
+                    runs.push(std::mem::take(&mut run));
+                }
+            }
+            if !run.is_empty() {
+                runs.push(run);
+            }
+        }
+        runs
+    }
+
+    #[test]
+    fn test_search() -> Result<(), RecordError> {
+        let mut input = TestingInput::new(80, 20, []);
+        let mut recorder = TestingRecorder::new(Recorder::new(state(), &mut input), 80, 20)?;
+        recorder.handle_event(key(KeyCode::Char('/')))?;
+        type_text(&mut recorder, "ne")?;
+        // The first match is selected while typing.
+        assert_eq!(recorder.selected_item(), line_item(1, 1, 1));
+        recorder.handle_event(key(KeyCode::Esc))?;
+        assert_eq!(
+            recorder.selected_item(),
+            SelectedItem::File {
+                commit_idx: 0,
+                file_idx: 0
+            }
+        );
+
+        recorder.handle_event(key(KeyCode::Char('/')))?;
+        type_text(&mut recorder, "x =")?;
+        recorder.handle_event(key(KeyCode::Enter))?;
+        assert_eq!(recorder.selected_item(), line_item(0, 0, 0));
+        recorder.handle_event(event::Event::SearchNext)?;
+        assert_eq!(recorder.selected_item(), line_item(0, 0, 1));
+        recorder.handle_event(event::Event::SearchNext)?;
+        assert_eq!(recorder.selected_item(), line_item(0, 0, 0));
+        recorder.handle_event(event::Event::SearchPrev)?;
+        assert_eq!(recorder.selected_item(), line_item(0, 0, 1));
+
+        // The query has an uppercase letter, so it's case-sensitive.
+        recorder.handle_event(key(KeyCode::Char('/')))?;
+        type_text(&mut recorder, "Let")?;
+        recorder.handle_event(key(KeyCode::Enter))?;
+        assert_eq!(recorder.selected_item(), line_item(0, 0, 1));
+        recorder.handle_event(event::Event::SearchNext)?;
+        assert_eq!(recorder.selected_item(), line_item(0, 0, 1));
+        assert!(recorder.screen().contains("Pattern not found: Let"));
+        Ok(())
+    }
+
+    /// Run the recorder with the given `skip_if_nothing_to_select`, returning
+    /// its result and the screen as it was first drawn, if it was.
+    fn run_skipping_if_nothing_to_select(
+        state: RecordState<'static>,
+        skip_if_nothing_to_select: bool,
+    ) -> (Result<RecordState<'static>, RecordError>, String) {
+        let options = RecordOptions {
+            skip_if_nothing_to_select,
+            ..Default::default()
+        };
+        let screenshot = TestingScreenshot::default();
+        // Accepting with nothing selected asks for confirmation first.
+        let mut input = TestingInput::new(
+            80,
+            20,
+            [
+                screenshot.event(),
+                event::Event::QuitAccept,
+                event::Event::QuitAccept,
+            ],
+        );
+        let result = Recorder::new_with_options(state, &mut input, options).run();
+        (result, screenshot.to_string())
+    }
+
+    #[test]
+    fn test_skip_if_nothing_to_select() {
+        let never_drawn = TestingScreenshot::default().to_string();
+
+        let no_files = RecordState {
+            files: Vec::new(),
+            ..state()
+        };
+        let (result, screen) = run_skipping_if_nothing_to_select(no_files, true);
+        assert!(
+            matches!(result, Err(RecordError::NothingToSelect)),
+            "{result:?}"
+        );
+        assert_eq!(screen, never_drawn);
+
+        // Unchanged lines are shown as context, but can't be selected.
+        let only_unchanged = RecordState {
+            files: vec![
+                file(
+                    "foo",
+                    vec![Section::Unchanged {
+                        lines: vec![Cow::Borrowed("fn main() {}\n")],
+                    }],
+                ),
+                file(
+                    "bar",
+                    vec![Section::Unchanged {
+                        lines: vec![Cow::Borrowed("bar\n")],
+                    }],
+                ),
+            ],
+            ..state()
+        };
+        let (result, screen) = run_skipping_if_nothing_to_select(only_unchanged, true);
+        assert!(
+            matches!(result, Err(RecordError::NothingToSelect)),
+            "{result:?}"
+        );
+        assert_eq!(screen, never_drawn);
+    }
+
+    #[test]
+    fn test_skip_if_nothing_to_select_disabled() {
+        let no_files = RecordState {
+            files: Vec::new(),
+            ..state()
         };
-        *message = Some(new_message);
+        let (result, screen) = run_skipping_if_nothing_to_select(no_files, false);
+        assert!(result.is_ok(), "{result:?}");
+        assert!(screen.contains("There are no changes to view."), "{screen}");
+    }
+
+    #[test]
+    fn test_word_diff() -> Result<(), RecordError> {
+        let mut input = TestingInput::new(80, 20, []);
+        let mut recorder = TestingRecorder::new(Recorder::new(state(), &mut input), 80, 20)?;
+        recorder.handle_event(event::Event::ExpandAll)?;
+        assert_eq!(
+            text_with_modifier(&recorder, Modifier::REVERSED),
+            ["1", "2", "old", "new"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_diff_disabled() -> Result<(), RecordError> {
+        let options = RecordOptions {
+            word_diff: false,
+            ..Default::default()
+        };
+        let mut input = TestingInput::new(80, 20, []);
+        let mut recorder = TestingRecorder::new(
+            Recorder::new_with_options(state(), &mut input, options),
+            80,
+            20,
+        )?;
+        recorder.handle_event(event::Event::ExpandAll)?;
+        assert!(recorder.screen().contains("let x = 2;"));
+        assert_eq!(
+            text_with_modifier(&recorder, Modifier::REVERSED),
+            Vec::<String>::new()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_commit_message() -> Result<(), RecordError> {
+        let mut input = TestingInput::new(80, 20, []);
+        let mut recorder = TestingRecorder::new(Recorder::new(state(), &mut input), 80, 20)?;
+        recorder.handle_event(event::Event::EditCommitMessage)?;
+        type_text(&mut recorder, " line")?;
+        recorder.handle_event(key(KeyCode::Esc))?;
+        assert_eq!(
+            recorder.state().commits[0].message.as_deref(),
+            Some("Subject")
+        );
+        assert!(!recorder.dirty_state().is_dirty());
+
+        recorder.handle_event(event::Event::EditCommitMessage)?;
+        type_text(&mut recorder, " line")?;
+        recorder.handle_event(key(KeyCode::Enter))?;
+        type_text(&mut recorder, "Body")?;
+        recorder.handle_event(event::Event::Key(KeyEvent::new(
+            KeyCode::Char('s'),
+            KeyModifiers::CONTROL,
+        )))?;
+        assert_eq!(
+            recorder.state().commits[0].message.as_deref(),
+            Some("Subject line\nBody")
+        );
+        assert_eq!(
+            recorder.dirty_state(),
+            DirtyState {
+                commit_messages: true,
+                ..Default::default()
+            }
+        );
+
+        recorder.handle_event(event::Event::Undo)?;
+        assert_eq!(
+            recorder.state().commits[0].message.as_deref(),
+            Some("Subject")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_item_to_commit() -> Result<(), RecordError> {
+        let mut state = state();
+        state.commits.push(Commit::default());
+        let mut input = TestingInput::new(80, 20, []);
+        let mut recorder = TestingRecorder::new(Recorder::new(state, &mut input), 80, 20)?;
+        recorder.handle_event(event::Event::ExpandAll)?;
+        recorder.handle_event(event::Event::FocusNext)?;
+        recorder.handle_event(event::Event::FocusNext)?;
+        assert_eq!(recorder.selected_item(), line_item(0, 0, 0));
+
+        let assigned_commit_indices = |recorder: &TestingRecorder| -> Vec<usize> {
+            let Section::Changed { lines, .. } = &recorder.state().files[0].sections[0] else {
+                unreachable!()
+            };
+            lines
+                .iter()
+                .map(|line| line.assigned_commit_idx())
+                .collect()
+        };
+        // Unselected lines are in the second commit.
+        assert_eq!(assigned_commit_indices(&recorder), [1, 1]);
+        recorder.handle_event(event::Event::MoveItemToPrevCommit)?;
+        assert_eq!(assigned_commit_indices(&recorder), [1, 1]);
+        assert!(recorder.screen().contains("There is no previous commit"));
+
+        // Items are moved relative to the focused commit.
+        recorder.handle_event(event::Event::FocusNextCommit)?;
+        recorder.handle_event(event::Event::MoveItemToNextCommit)?;
+        assert_eq!(assigned_commit_indices(&recorder), [2, 1]);
+        recorder.handle_event(event::Event::MoveItemToPrevCommit)?;
+        assert_eq!(assigned_commit_indices(&recorder), [0, 1]);
+        assert!(recorder.dirty_state().selections);
+
+        let file = &recorder.state().files[0];
+        assert_eq!(
+            file.get_commit_contents(0).contents,
+            SelectedContents::Text {
+                contents: "".to_owned()
+            }
+        );
         Ok(())
     }
 }
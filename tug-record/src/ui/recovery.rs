@@ -0,0 +1,122 @@
+use std::io;
+use std::path::Path;
+
+use crate::types::{Commit, DirtyState, RecordError, RecordState};
+use crate::ui::state::SelectionKey;
+
+/// The contents of the recovery file, which is written while the user is
+/// idle so that an interrupted session can be restored.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub(crate) struct Recovery<'a> {
+    /// The state at the time the file was written.
+    pub state: RecordState<'a>,
+
+    /// Which parts of the state the user had modified.
+    pub dirty: DirtyState,
+
+    /// The items which were expanded, sorted.
+    pub expanded_items: Vec<SelectionKey>,
+
+    /// The item which was selected.
+    pub selection_key: SelectionKey,
+}
+
+impl Recovery<'_> {
+    /// Whether the recovered state is for the same changes as `state`, such
+    /// that only the user's choices differ. Otherwise, the recovery file was
+    /// written by a session for other changes, and can't be restored.
+    pub fn has_same_shape(&self, state: &RecordState) -> bool {
+        shape(&self.state) == shape(state)
+    }
+}
+
+/// The parts of the state which the user can't change.
+fn shape<'a>(state: &RecordState<'a>) -> RecordState<'a> {
+    let RecordState {
+        is_read_only,
+        commits,
+        files,
+    } = state;
+    RecordState {
+        is_read_only: *is_read_only,
+        commits: commits.iter().map(|_| Commit::default()).collect(),
+        files: files
+            .iter()
+            .map(|file| {
+                let mut file = file.clone();
+                file.set_checked(false);
+                file.is_deferred = false;
+                file
+            })
+            .collect(),
+    }
+}
+
+/// Write the recovery file. The contents are written to a temporary file
+/// first so that a crash partway through doesn't leave a truncated file.
+#[cfg(feature = "serde")]
+pub(crate) fn write_recovery_file(path: &Path, recovery: &Recovery) -> Result<(), RecordError> {
+    let contents = serde_json::to_string(recovery).map_err(RecordError::SerializeJson)?;
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, contents).map_err(|source| RecordError::WriteFile {
+        path: temp_path.clone(),
+        source,
+    })?;
+    std::fs::rename(&temp_path, path).map_err(|source| RecordError::WriteFile {
+        path: path.to_owned(),
+        source,
+    })?;
+    Ok(())
+}
+
+/// Write the recovery file. This requires the `serde` feature.
+#[cfg(not(feature = "serde"))]
+pub(crate) fn write_recovery_file(path: &Path, recovery: &Recovery) -> Result<(), RecordError> {
+    let _ = (path, recovery);
+    Err(RecordError::Other(
+        "saving a recovery file requires the `serde` feature".to_string(),
+    ))
+}
+
+/// Read the recovery file left behind by an interrupted session, if any.
+#[cfg(feature = "serde")]
+pub(crate) fn read_recovery_file(path: &Path) -> Result<Option<Recovery<'static>>, RecordError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => {
+            return Err(RecordError::ReadFile {
+                path: path.to_owned(),
+                source,
+            })
+        }
+    };
+    let recovery =
+        serde_json::from_str(&contents).map_err(|source| RecordError::DeserializeJson {
+            path: path.to_owned(),
+            source,
+        })?;
+    Ok(Some(recovery))
+}
+
+/// Read the recovery file. Without the `serde` feature, no recovery file
+/// can have been written.
+#[cfg(not(feature = "serde"))]
+pub(crate) fn read_recovery_file(path: &Path) -> Result<Option<Recovery<'static>>, RecordError> {
+    let _ = path;
+    Ok(None)
+}
+
+/// Remove the recovery file once the session has ended normally, if it was
+/// written.
+pub(crate) fn remove_recovery_file(path: &Path) -> Result<(), RecordError> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(source) => Err(RecordError::RemoveFile {
+            path: path.to_owned(),
+            source,
+        }),
+    }
+}
@@ -0,0 +1,245 @@
+use std::borrow::Cow;
+
+use crate::types::{File, Section};
+#[cfg(feature = "tui")]
+use crate::ui::components::text_prompt::TextPrompt;
+use crate::ui::state::{FileKey, LineKey, SelectionKey};
+
+/// The prompt to type a search query into, while it's open.
+#[cfg(feature = "tui")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SearchPrompt {
+    pub prompt: TextPrompt,
+
+    /// The item which was selected when the prompt was opened. Matches are
+    /// searched for starting from it, and it's selected again if the search
+    /// is cancelled.
+    pub origin: SelectionKey,
+}
+
+/// Text to search for. The search is case-insensitive, unless the text
+/// contains an uppercase letter.
+#[derive(Clone, Debug)]
+pub(crate) struct SearchQuery<'a> {
+    text: Cow<'a, str>,
+    is_case_sensitive: bool,
+}
+
+impl<'a> SearchQuery<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let is_case_sensitive = text.chars().any(char::is_uppercase);
+        Self {
+            text: if is_case_sensitive {
+                Cow::Borrowed(text)
+            } else {
+                Cow::Owned(text.to_lowercase())
+            },
+            is_case_sensitive,
+        }
+    }
+
+    fn matches(&self, text: &IndexedText) -> bool {
+        let IndexedText { text, folded } = text;
+        if self.is_case_sensitive {
+            text.contains(self.text.as_ref())
+        } else {
+            folded.contains(self.text.as_ref())
+        }
+    }
+}
+
+/// The searchable text of the files, i.e. their paths and the contents of
+/// their changed lines. It's built once, since only the choices of the user
+/// change while recording, not the contents of the files.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SearchIndex {
+    files: Vec<IndexedFile>,
+}
+
+#[derive(Clone, Debug)]
+struct IndexedFile {
+    path: IndexedText,
+
+    /// The changed lines of each section, indexed like `File::sections`. The
+    /// other sections have no lines which can be selected, so they're empty.
+    sections: Vec<Vec<IndexedText>>,
+}
+
+/// The text, along with its lowercase version for case-insensitive searches.
+#[derive(Clone, Debug)]
+struct IndexedText {
+    text: String,
+    folded: String,
+}
+
+impl IndexedText {
+    fn new(text: &str) -> Self {
+        Self {
+            text: text.to_owned(),
+            folded: text.to_lowercase(),
+        }
+    }
+}
+
+impl SearchIndex {
+    pub fn new(files: &[File]) -> Self {
+        let files = files
+            .iter()
+            .map(|file| IndexedFile {
+                path: IndexedText::new(&file.path.to_string_lossy()),
+                sections: file
+                    .sections
+                    .iter()
+                    .map(|section| match section {
+                        Section::Changed { lines, .. } => lines
+                            .iter()
+                            .map(|line| IndexedText::new(&line.line))
+                            .collect(),
+                        Section::Unchanged { .. }
+                        | Section::FileMode { .. }
+                        | Section::Binary { .. } => Vec::new(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self { files }
+    }
+
+    /// Whether the item matches the query. Only files, by their path, and
+    /// changed lines, by their contents, can match.
+    pub fn is_match(&self, selection_key: SelectionKey, query: &SearchQuery) -> bool {
+        match selection_key {
+            SelectionKey::None | SelectionKey::Directory(_) | SelectionKey::Section(_) => false,
+            SelectionKey::File(FileKey {
+                commit_idx: _,
+                file_idx,
+            }) => self
+                .files
+                .get(file_idx)
+                .is_some_and(|file| query.matches(&file.path)),
+            SelectionKey::Line(LineKey {
+                commit_idx: _,
+                file_idx,
+                section_idx,
+                line_idx,
+            }) => self
+                .files
+                .get(file_idx)
+                .and_then(|file| file.sections.get(section_idx))
+                .and_then(|lines| lines.get(line_idx))
+                .is_some_and(|line| query.matches(line)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::types::{ChangeType, FileMode, SectionChangedLine};
+    use crate::ui::state::SectionKey;
+
+    fn index() -> SearchIndex {
+        let line = |change_type, line| SectionChangedLine {
+            is_checked: false,
+            commit_idx: 0,
+            change_type,
+            line: Cow::Borrowed(line),
+            annotation: None,
+        };
+        SearchIndex::new(&[File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new("src/Main.rs")),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections: vec![
+                Section::Unchanged {
+                    lines: vec![Cow::Borrowed("Hello\n")],
+                },
+                Section::Changed {
+                    label: None,
+                    context: None,
+                    lines: vec![
+                        line(ChangeType::Removed, "Hello, World\n"),
+                        line(ChangeType::Added, "hello, été\n"),
+                    ],
+                },
+            ],
+            is_deferred: false,
+            is_lossy: false,
+        }])
+    }
+
+    /// The items matching the query: the file, and the changed lines by their
+    /// index.
+    fn matches(index: &SearchIndex, query: &str) -> (bool, Vec<usize>) {
+        let query = SearchQuery::new(query);
+        let file_key = FileKey {
+            commit_idx: 0,
+            file_idx: 0,
+        };
+        let line_key = |line_idx| LineKey {
+            commit_idx: 0,
+            file_idx: 0,
+            section_idx: 1,
+            line_idx,
+        };
+        (
+            index.is_match(SelectionKey::File(file_key), &query),
+            (0..2)
+                .filter(|line_idx| index.is_match(SelectionKey::Line(line_key(*line_idx)), &query))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_is_match_folds_case_without_uppercase() {
+        let index = index();
+        assert_eq!(matches(&index, "hello"), (false, vec![0, 1]));
+        assert_eq!(matches(&index, "main"), (true, vec![]));
+        assert_eq!(matches(&index, "world"), (false, vec![0]));
+        assert_eq!(matches(&index, "ÉTÉ"), (false, vec![]));
+        assert_eq!(matches(&index, "été"), (false, vec![1]));
+    }
+
+    #[test]
+    fn test_is_match_is_case_sensitive_with_uppercase() {
+        let index = index();
+        assert_eq!(matches(&index, "Hello"), (false, vec![0]));
+        assert_eq!(matches(&index, "Main"), (true, vec![]));
+        assert_eq!(matches(&index, "MAIN"), (false, vec![]));
+        assert_eq!(matches(&index, "hello, World"), (false, vec![]));
+    }
+
+    #[test]
+    fn test_is_match_only_files_and_changed_lines() {
+        let index = index();
+        let query = SearchQuery::new("hello");
+        // The unchanged line contains the text, but it can't be selected.
+        assert!(!index.is_match(
+            SelectionKey::Line(LineKey {
+                commit_idx: 0,
+                file_idx: 0,
+                section_idx: 0,
+                line_idx: 0,
+            }),
+            &query
+        ));
+        assert!(!index.is_match(
+            SelectionKey::Section(SectionKey {
+                commit_idx: 0,
+                file_idx: 0,
+                section_idx: 1,
+            }),
+            &query
+        ));
+        assert!(!index.is_match(SelectionKey::None, &query));
+        assert!(!index.is_match(
+            SelectionKey::File(FileKey {
+                commit_idx: 0,
+                file_idx: 1,
+            }),
+            &SearchQuery::new("")
+        ));
+    }
+}
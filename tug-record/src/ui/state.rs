@@ -0,0 +1,3440 @@
+//! The state of the UI and the logic to update it, such as moving the
+//! selection, toggling and expanding items, and undoing those changes. This
+//! doesn't depend on the terminal, so it's also available without the `tui`
+//! feature through [`Selector`]. The terminal frontend draws this state and
+//! turns key presses into [`StateUpdate`]s, see `ui::view`.
+
+#[cfg(feature = "tui")]
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use web_time::Instant;
+
+use crate::interdiff::FileInterdiff;
+#[cfg(feature = "tui")]
+use crate::types::ChangeType;
+use crate::types::{
+    toggled_commit_idx, Commit, DirtyState, File, FileMode, InitialExpansion, RecordError,
+    RecordOptions, RecordState, Section, SectionChangedLine, Tristate,
+};
+#[cfg(feature = "tui")]
+use crate::ui::components::commit_message_view::CommitViewMode;
+#[cfg(feature = "tui")]
+use crate::ui::components::empty_selection_dialog::EmptySelectionDialog;
+#[cfg(feature = "tui")]
+use crate::ui::components::error_dialog::ErrorDialog;
+#[cfg(feature = "tui")]
+use crate::ui::components::file_contents_dialog::FileContentsDialog;
+#[cfg(feature = "tui")]
+use crate::ui::components::help_dialog::HelpDialog;
+#[cfg(feature = "tui")]
+use crate::ui::components::line::LineHighlights;
+#[cfg(feature = "tui")]
+use crate::ui::components::line_diff_dialog::LineDiffDialog;
+#[cfg(feature = "tui")]
+use crate::ui::components::message_diff_dialog::MessageDiffDialog;
+#[cfg(feature = "tui")]
+use crate::ui::components::message_editor_dialog::MessageEditorDialog;
+#[cfg(feature = "tui")]
+use crate::ui::components::message_history_dialog::MessageHistoryPicker;
+#[cfg(feature = "tui")]
+use crate::ui::components::preview_dialog::PreviewDialog;
+#[cfg(feature = "tui")]
+use crate::ui::components::quit_dialog::QuitDialog;
+#[cfg(feature = "tui")]
+use crate::ui::components::text_prompt::TextPrompt;
+#[cfg(feature = "tui")]
+use crate::ui::components::toggle_all_dialog::ToggleAllDialog;
+#[cfg(feature = "tui")]
+use crate::ui::event;
+use crate::ui::history::{Choices, Command, History};
+#[cfg(feature = "tui")]
+use crate::ui::input::{FileVersion, TestingScreenshot};
+#[cfg(feature = "tui")]
+use crate::ui::keymap::Key;
+#[cfg(feature = "tui")]
+use crate::ui::macros::{MacroPrompt, Macros};
+#[cfg(feature = "tui")]
+use crate::ui::recovery::Recovery;
+#[cfg(feature = "tui")]
+use crate::ui::search::{SearchIndex, SearchPrompt, SearchQuery};
+use crate::util::glob_match;
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum SelectionKey {
+    #[default]
+    None,
+    Directory(DirectoryKey),
+    File(FileKey),
+    Section(SectionKey),
+    Line(LineKey),
+}
+
+/// Identifies a directory node in the file tree. `dir_idx` indexes into the
+/// list of all directories containing changed files, which doesn't change
+/// while the UI is running.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct DirectoryKey {
+    pub commit_idx: usize,
+    pub dir_idx: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct FileKey {
+    pub commit_idx: usize,
+    pub file_idx: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct SectionKey {
+    pub commit_idx: usize,
+    pub file_idx: usize,
+    pub section_idx: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct LineKey {
+    pub commit_idx: usize,
+    pub file_idx: usize,
+    pub section_idx: usize,
+    pub line_idx: usize,
+}
+
+/// The order in which files are displayed. This only affects the UI; the
+/// order of the files in the returned state is unchanged.
+#[cfg(feature = "tui")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum FileSortOrder {
+    /// The order in which the files were provided.
+    #[default]
+    Input,
+    /// Alphabetically by path.
+    Path,
+    /// Largest number of changed lines first.
+    Size,
+    /// Added files first, then deleted files, then modified files.
+    Status,
+}
+
+#[cfg(feature = "tui")]
+impl FileSortOrder {
+    pub fn next(self) -> Self {
+        match self {
+            FileSortOrder::Input => FileSortOrder::Path,
+            FileSortOrder::Path => FileSortOrder::Size,
+            FileSortOrder::Size => FileSortOrder::Status,
+            FileSortOrder::Status => FileSortOrder::Input,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FileSortOrder::Input => "input order",
+            FileSortOrder::Path => "path",
+            FileSortOrder::Size => "size",
+            FileSortOrder::Status => "status",
+        }
+    }
+
+    /// Sort the given file indices according to this order.
+    pub fn sort(self, file_indices: &mut [usize], files: &[File], file_stats: &[FileStats]) {
+        match self {
+            FileSortOrder::Input => file_indices.sort(),
+            FileSortOrder::Path => file_indices.sort_by_key(|&file_idx| &files[file_idx].path),
+            FileSortOrder::Size => file_indices.sort_by_key(|&file_idx| {
+                let FileStats {
+                    num_added,
+                    num_removed,
+                    is_binary: _,
+                    has_mode_change: _,
+                } = file_stats[file_idx];
+                std::cmp::Reverse(num_added + num_removed)
+            }),
+            FileSortOrder::Status => {
+                file_indices.sort_by_key(|&file_idx| file_status_rank(&files[file_idx]))
+            }
+        }
+    }
+}
+
+/// Rank a file for [`FileSortOrder::Status`]: added files, then deleted
+/// files, then modified files.
+#[cfg(feature = "tui")]
+fn file_status_rank(file: &File) -> usize {
+    let new_mode = file.sections.iter().find_map(|section| match section {
+        Section::FileMode {
+            is_checked: _,
+            commit_idx: _,
+            mode,
+        } => Some(*mode),
+        Section::Unchanged { .. } | Section::Changed { .. } | Section::Binary { .. } => None,
+    });
+    match (file.file_mode, new_mode) {
+        (FileMode::Absent, Some(FileMode::Unix(_))) => 0,
+        (FileMode::Unix(_), Some(FileMode::Absent)) => 1,
+        _ => 2,
+    }
+}
+
+/// Summary of the changes in a file, shown in its header.
+#[cfg(feature = "tui")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FileStats {
+    pub num_added: usize,
+    pub num_removed: usize,
+    pub is_binary: bool,
+    pub has_mode_change: bool,
+}
+
+#[cfg(feature = "tui")]
+impl FileStats {
+    pub fn new(file: &File) -> Self {
+        let mut stats = Self::default();
+        for section in &file.sections {
+            match section {
+                Section::Unchanged { .. } => {}
+                Section::Changed { lines, .. } => {
+                    for SectionChangedLine {
+                        is_checked: _,
+                        commit_idx: _,
+                        change_type,
+                        line: _,
+                        annotation: _,
+                    } in lines
+                    {
+                        match change_type {
+                            ChangeType::Added => stats.num_added += 1,
+                            ChangeType::Removed => stats.num_removed += 1,
+                        }
+                    }
+                }
+                Section::FileMode { .. } => stats.has_mode_change = true,
+                Section::Binary { .. } => stats.is_binary = true,
+            }
+        }
+        stats
+    }
+}
+
+/// A short message about the result of the last command, drawn over the top
+/// row of the screen until the next key press. Informational messages also
+/// disappear after a few seconds.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StatusMessage {
+    pub message: String,
+    pub is_error: bool,
+    /// When to stop showing the message, if the user hasn't pressed a key
+    /// by then.
+    pub expires_at: Option<Instant>,
+}
+
+impl StatusMessage {
+    /// How long informational messages are shown for.
+    const INFO_DURATION: Duration = Duration::from_secs(3);
+
+    pub fn info(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            is_error: false,
+            expires_at: Some(Instant::now() + Self::INFO_DURATION),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            is_error: true,
+            expires_at: None,
+        }
+    }
+
+    #[cfg(feature = "tui")]
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// The item which is selected in the UI, as reported by
+/// [`Selector::selected_item`] and `TestingRecorder::selected_item`. The
+/// indices refer to the commits, files, sections and changed lines of the
+/// [`RecordState`].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SelectedItem {
+    None,
+    Directory {
+        commit_idx: usize,
+        path: PathBuf,
+    },
+    File {
+        commit_idx: usize,
+        file_idx: usize,
+    },
+    Section {
+        commit_idx: usize,
+        file_idx: usize,
+        section_idx: usize,
+    },
+    Line {
+        commit_idx: usize,
+        file_idx: usize,
+        section_idx: usize,
+        line_idx: usize,
+    },
+}
+
+pub(crate) fn selected_item(app: &App, selection_key: SelectionKey) -> SelectedItem {
+    match selection_key {
+        SelectionKey::None => SelectedItem::None,
+        SelectionKey::Directory(DirectoryKey {
+            commit_idx,
+            dir_idx,
+        }) => SelectedItem::Directory {
+            commit_idx,
+            path: app.directories[dir_idx].clone(),
+        },
+        SelectionKey::File(FileKey {
+            commit_idx,
+            file_idx,
+        }) => SelectedItem::File {
+            commit_idx,
+            file_idx,
+        },
+        SelectionKey::Section(SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+        }) => SelectedItem::Section {
+            commit_idx,
+            file_idx,
+            section_idx,
+        },
+        SelectionKey::Line(LineKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+            line_idx,
+        }) => SelectedItem::Line {
+            commit_idx,
+            file_idx,
+            section_idx,
+            line_idx,
+        },
+    }
+}
+
+/// Moves the selection and toggles and expands the selected items the same
+/// way as the recorder, but without a terminal. This is available without
+/// the `tui` feature, for frontends which draw the changes themselves.
+///
+/// Items which are collapsed, or hidden by the options, are skipped when
+/// moving the selection, as in the recorder.
+pub struct Selector<'state> {
+    app: App<'state>,
+}
+
+impl<'state> Selector<'state> {
+    /// Start selecting among the changes of the state. The first item is
+    /// selected, and the files are expanded according to
+    /// [`RecordOptions::initial_expansion`].
+    pub fn new(state: RecordState<'state>, options: RecordOptions) -> Self {
+        Self {
+            app: App::new(state, options),
+        }
+    }
+
+    /// The item which is currently selected.
+    pub fn selected_item(&self) -> SelectedItem {
+        selected_item(&self.app, self.app.ui.selection_key)
+    }
+
+    /// Select the previous visible item.
+    pub fn select_prev(&mut self) -> Result<(), RecordError> {
+        let (keys, index) = self.app.find_selection();
+        let selection_key = self.app.select_prev(&keys, index);
+        self.select(selection_key)
+    }
+
+    /// Select the next visible item.
+    pub fn select_next(&mut self) -> Result<(), RecordError> {
+        let (keys, index) = self.app.find_selection();
+        let selection_key = self.app.select_next(&keys, index);
+        self.select(selection_key)
+    }
+
+    /// Select the previous file.
+    pub fn select_prev_file(&mut self) -> Result<(), RecordError> {
+        let selection_key = self.app.select_prev_or_next_file(/*select_previous=*/ true);
+        self.select(selection_key)
+    }
+
+    /// Select the next file.
+    pub fn select_next_file(&mut self) -> Result<(), RecordError> {
+        let selection_key = self
+            .app
+            .select_prev_or_next_file(/*select_previous=*/ false);
+        self.select(selection_key)
+    }
+
+    /// Select the first item inside the selected one, expanding it if needed.
+    pub fn select_inner(&mut self) -> Result<(), RecordError> {
+        let selection_key = self.app.select_inner();
+        self.select(selection_key)
+    }
+
+    /// Select the item containing the selected one. A file or directory is
+    /// collapsed first if it's expanded.
+    pub fn select_outer(&mut self) -> Result<(), RecordError> {
+        let state_update = self.app.select_outer(/*fold_section=*/ false);
+        self.app.apply_state_update(state_update)?;
+        Ok(())
+    }
+
+    /// Toggle whether the changes of the selected item are selected.
+    pub fn toggle_item(&mut self) -> Result<(), RecordError> {
+        let selection_key = self.app.ui.selection_key;
+        self.app
+            .apply_state_update(StateUpdate::ToggleItem(selection_key))?;
+        Ok(())
+    }
+
+    /// Toggle the selected item, then select the next item of the same kind.
+    pub fn toggle_item_and_advance(&mut self) -> Result<(), RecordError> {
+        let selection_key = self.app.ui.selection_key;
+        let advanced_key = self.app.advance_to_next_of_kind();
+        self.app
+            .apply_state_update(StateUpdate::ToggleItemAndAdvance(
+                selection_key,
+                advanced_key,
+            ))?;
+        Ok(())
+    }
+
+    /// Toggle every change in the focused commit: the changes in it are moved
+    /// out of it, and the others are moved into it. With two commits, this
+    /// inverts the selection of every change.
+    pub fn toggle_all(&mut self) -> Result<(), RecordError> {
+        self.app.apply_state_update(StateUpdate::ToggleAll)?;
+        Ok(())
+    }
+
+    /// Expand the selected item, or collapse it if it's expanded.
+    pub fn toggle_expand_item(&mut self) -> Result<(), RecordError> {
+        let selection_key = self.app.ui.selection_key;
+        self.app
+            .apply_state_update(StateUpdate::ToggleExpandItem(selection_key))?;
+        Ok(())
+    }
+
+    /// Undo the last change to the selections. Does nothing if there is
+    /// nothing to undo.
+    pub fn undo(&mut self) -> Result<(), RecordError> {
+        self.app.apply_state_update(StateUpdate::Undo)?;
+        Ok(())
+    }
+
+    /// Redo the last undone change to the selections. Does nothing if there
+    /// is nothing to redo.
+    pub fn redo(&mut self) -> Result<(), RecordError> {
+        self.app.apply_state_update(StateUpdate::Redo)?;
+        Ok(())
+    }
+
+    /// Replace the message of the commit. This can be undone like the
+    /// changes to the selections.
+    pub fn set_commit_message(&mut self, commit_idx: usize, message: String) {
+        self.app
+            .record_command(Command::SetCommitMessage { commit_idx }, |app| {
+                app.set_commit_message(commit_idx, message)
+            });
+    }
+
+    /// The current state, with the selections made so far.
+    pub fn state(&self) -> &RecordState<'state> {
+        &self.app.state
+    }
+
+    /// Which parts of the state the user modified so far.
+    pub fn dirty_state(&self) -> DirtyState {
+        self.app.dirty
+    }
+
+    /// Stop selecting, returning the final state and which parts of it were
+    /// modified.
+    pub fn into_state(self) -> (RecordState<'state>, DirtyState) {
+        (self.app.state, self.app.dirty)
+    }
+
+    fn select(&mut self, selection_key: SelectionKey) -> Result<(), RecordError> {
+        self.app.apply_state_update(StateUpdate::SelectItem {
+            selection_key,
+            ensure_in_viewport: true,
+        })?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum StateUpdate {
+    None,
+    SelectItem {
+        selection_key: SelectionKey,
+        ensure_in_viewport: bool,
+    },
+    ToggleItem(SelectionKey),
+    ToggleItemAndAdvance(SelectionKey, SelectionKey),
+    #[cfg(feature = "tui")]
+    ToggleDeferItem(SelectionKey),
+    #[cfg(feature = "tui")]
+    ToggleBookmark(SelectionKey),
+    #[cfg(feature = "tui")]
+    SetWrappedLine(Option<LineKey>),
+    #[cfg(feature = "tui")]
+    ToggleAnnotations,
+    #[cfg(feature = "tui")]
+    ToggleContext,
+    ToggleAll,
+    #[cfg(feature = "tui")]
+    ToggleAllUniform,
+    SetExpandItem(SelectionKey, bool),
+    ToggleExpandItem(SelectionKey),
+    #[cfg(feature = "tui")]
+    ToggleExpandAll,
+    #[cfg(feature = "tui")]
+    SetFocusedCommit(usize),
+    #[cfg(feature = "tui")]
+    MoveItemToCommit(SelectionKey, usize),
+    #[cfg(feature = "tui")]
+    ToggleCommitMessageExpanded,
+    #[cfg(feature = "tui")]
+    ToggleStatsBar,
+    #[cfg(feature = "tui")]
+    ToggleActionHistory,
+    #[cfg(feature = "tui")]
+    SetFileSortOrder(FileSortOrder),
+    #[cfg(feature = "tui")]
+    SelectSearchMatch {
+        selection_key: SelectionKey,
+        match_idx: usize,
+        num_matches: usize,
+    },
+    #[cfg(feature = "tui")]
+    ToggleHideUnselectedFiles,
+    #[cfg(feature = "tui")]
+    ToggleHideSelectedFiles,
+    #[cfg(feature = "tui")]
+    ToggleHideUnchangedFiles,
+    #[cfg(feature = "tui")]
+    ToggleFileTree,
+    #[cfg(feature = "tui")]
+    ToggleTwoPane,
+    #[cfg(feature = "tui")]
+    SetZoomedFile(Option<usize>),
+    #[cfg(feature = "tui")]
+    ToggleOutline,
+    #[cfg(feature = "tui")]
+    SetStatusMessage(StatusMessage),
+    Undo,
+    Redo,
+    #[cfg(feature = "tui")]
+    RestoreRecovery,
+    #[cfg(feature = "tui")]
+    DiscardRecovery,
+    // The updates below are applied by the terminal frontend, since they
+    // open its dialogs or prompts, or need the terminal or the input.
+    #[cfg(feature = "tui")]
+    QuitAccept,
+    #[cfg(feature = "tui")]
+    QuitCancel,
+    #[cfg(feature = "tui")]
+    SetHelpDialog(Option<HelpDialog>),
+    #[cfg(feature = "tui")]
+    TakeScreenshot(TestingScreenshot),
+    #[cfg(feature = "tui")]
+    Redraw,
+    /// Lay the UI out again for the new size of the terminal, keeping the
+    /// selection on the screen.
+    #[cfg(feature = "tui")]
+    Resize,
+    #[cfg(feature = "tui")]
+    EnsureSelectionInViewport,
+    #[cfg(feature = "tui")]
+    ScrollTo(isize),
+    #[cfg(feature = "tui")]
+    ToggleCommitViewMode,
+    #[cfg(feature = "tui")]
+    SetFileFilterPrompt(Option<TextPrompt>),
+    /// Update the search prompt as it's typed into, selecting the first
+    /// match of its text.
+    #[cfg(feature = "tui")]
+    SetSearchPrompt(SearchPrompt, SelectionKey),
+    /// Close the search prompt, and remember its text to search for it
+    /// again.
+    #[cfg(feature = "tui")]
+    SetSearchQuery(Option<String>),
+    /// Close the search prompt, and select the item which was selected
+    /// before it was opened.
+    #[cfg(feature = "tui")]
+    CancelSearch(SelectionKey),
+    #[cfg(feature = "tui")]
+    SetFileFilter(Option<String>),
+    #[cfg(feature = "tui")]
+    CopyToClipboard {
+        text: String,
+        /// What was copied, for the status message.
+        description: String,
+    },
+    #[cfg(feature = "tui")]
+    SetPendingKeys(Vec<Key>),
+    #[cfg(feature = "tui")]
+    SetCount(Option<usize>),
+    #[cfg(feature = "tui")]
+    SetMacroPrompt(Option<MacroPrompt>),
+    #[cfg(feature = "tui")]
+    StartRecordingMacro(char),
+    #[cfg(feature = "tui")]
+    StopRecordingMacro,
+    #[cfg(feature = "tui")]
+    ReplayMacro {
+        register: char,
+        count: usize,
+    },
+    /// Save the in-progress selections to the recovery file.
+    #[cfg(feature = "tui")]
+    Autosave,
+    /// Handle these events next, in order.
+    #[cfg(feature = "tui")]
+    QueueEvents(Vec<event::Event>),
+    /// Suspend the UI to edit the commit message with the input, starting
+    /// from the given text, or from the current message if there is none.
+    #[cfg(feature = "tui")]
+    EditCommitMessageExternally {
+        commit_idx: usize,
+        message: Option<String>,
+    },
+    #[cfg(feature = "tui")]
+    SetMessageEditorDialog(Option<MessageEditorDialog>),
+    /// Replace the commit message with the one edited in the message editor,
+    /// and close it.
+    #[cfg(feature = "tui")]
+    SaveCommitMessage {
+        commit_idx: usize,
+        message: String,
+    },
+    #[cfg(feature = "tui")]
+    SetMessageHistoryPicker(Option<MessageHistoryPicker>),
+    #[cfg(feature = "tui")]
+    SetMessageDiffDialog(Option<MessageDiffDialog>),
+    #[cfg(feature = "tui")]
+    SetLineDiffDialog(Option<LineDiffDialog>),
+    #[cfg(feature = "tui")]
+    SetPreviewDialog(Option<PreviewDialog>),
+    #[cfg(feature = "tui")]
+    ScrollPreviewDialog(usize),
+    /// Ask the input for the contents of the file, and show them starting
+    /// around the given line, if any.
+    #[cfg(feature = "tui")]
+    ViewFileContents {
+        file_idx: usize,
+        version: FileVersion,
+        line_idx: Option<usize>,
+    },
+    #[cfg(feature = "tui")]
+    SetFileContentsDialog(Option<FileContentsDialog>),
+    #[cfg(feature = "tui")]
+    ScrollFileContentsDialog(usize),
+    /// Ask the input for the images of the file, and show them.
+    #[cfg(feature = "tui")]
+    PreviewImages {
+        file_idx: usize,
+    },
+    #[cfg(feature = "tui")]
+    SetErrorDialog(Option<ErrorDialog>),
+    #[cfg(feature = "tui")]
+    SetToggleAllDialog(Option<ToggleAllDialog>),
+    /// Toggle all changes as confirmed in the toggle-all dialog.
+    #[cfg(feature = "tui")]
+    ConfirmToggleAll,
+    #[cfg(feature = "tui")]
+    SetEmptySelectionDialog(Option<EmptySelectionDialog>),
+    #[cfg(feature = "tui")]
+    SetQuitDialog(Option<QuitDialog>),
+    #[cfg(feature = "tui")]
+    UseHistoryMessage {
+        commit_idx: usize,
+        history_idx: usize,
+        edit: bool,
+    },
+}
+
+#[allow(clippy::enum_variant_names)]
+enum ToggleSideEffects {
+    ToggledModeChangeSection(SectionKey, FileMode, FileMode, bool),
+    ToggledChangedSection(SectionKey, bool),
+    ToggledChangedLine(LineKey, bool),
+}
+
+/// Holds the state of the UI, such as selection, expansion, and dialogs.
+pub(crate) struct UiState {
+    #[cfg(feature = "tui")]
+    pub(crate) commit_view_mode: CommitViewMode,
+    #[cfg(feature = "tui")]
+    pub(crate) is_commit_message_expanded: bool,
+    #[cfg(feature = "tui")]
+    pub(crate) is_stats_bar_visible: bool,
+    /// Show the most recent actions at the bottom of the screen.
+    #[cfg(feature = "tui")]
+    pub(crate) is_action_history_visible: bool,
+    #[cfg(feature = "tui")]
+    pub(crate) file_sort_order: FileSortOrder,
+    /// The indices of the files in `RecordState::files`, in display order.
+    pub(crate) file_order: Vec<usize>,
+    /// Only show files whose paths match this filter.
+    pub(crate) file_filter: Option<String>,
+    /// The prompt to edit `file_filter`. While it is open, its text is used as
+    /// the filter instead.
+    #[cfg(feature = "tui")]
+    pub(crate) file_filter_prompt: Option<TextPrompt>,
+    /// The text last searched for, to go to its other matches.
+    #[cfg(feature = "tui")]
+    pub(crate) search_query: Option<String>,
+    #[cfg(feature = "tui")]
+    pub(crate) search_prompt: Option<SearchPrompt>,
+    /// Hide files which have no changes selected.
+    pub(crate) hide_unselected_files: bool,
+    /// Hide files which have all changes selected.
+    pub(crate) hide_selected_files: bool,
+    /// Hide files which are the same as in the previous state given for the
+    /// interdiff.
+    pub(crate) hide_unchanged_files: bool,
+    /// Group the files under their directories.
+    pub(crate) is_file_tree: bool,
+    /// List the files on the left, and show the changes of the selected file
+    /// on the right.
+    pub(crate) is_two_pane: bool,
+    /// The file to show on its own, without the other files or the commit
+    /// message.
+    pub(crate) zoomed_file: Option<ZoomedFile>,
+    /// Only list the files, without their changes, regardless of which
+    /// files are expanded.
+    pub(crate) is_outline: bool,
+    /// The changed line to show in full, wrapped onto several rows. This is
+    /// reset when the selection moves to another line.
+    pub(crate) wrapped_line: Option<LineKey>,
+    /// Show the annotations of the changed lines in a gutter on the right.
+    #[cfg(feature = "tui")]
+    pub(crate) is_annotation_gutter_visible: bool,
+    /// Hide the unchanged lines around the changes.
+    #[cfg(feature = "tui")]
+    pub(crate) is_context_hidden: bool,
+    /// The result of the last command, if it should be reported to the user.
+    pub(crate) status_message: Option<StatusMessage>,
+    /// The keys pressed so far of a key binding with several keys.
+    #[cfg(feature = "tui")]
+    pub(crate) pending_keys: Vec<Key>,
+    /// The number typed before a command, such as the `3` of `3@a`.
+    #[cfg(feature = "tui")]
+    pub(crate) count: Option<usize>,
+    /// Waiting for the user to name the register of a macro.
+    #[cfg(feature = "tui")]
+    pub(crate) macro_prompt: Option<MacroPrompt>,
+    #[cfg(feature = "tui")]
+    pub(crate) macros: Macros,
+    /// The changes to the user's choices, for undo and redo.
+    pub(crate) history: History,
+    pub(crate) expanded_items: HashSet<SelectionKey>,
+    /// The files and sections which the user bookmarked to come back to.
+    #[cfg(feature = "tui")]
+    pub(crate) bookmarks: HashSet<SelectionKey>,
+    /// The last section or line selected in each file.
+    pub(crate) file_selections: HashMap<FileKey, SelectionKey>,
+    /// How far each collapsed file was scrolled past its top, so that the
+    /// same part of it is shown once it's expanded again.
+    #[cfg(feature = "tui")]
+    pub(crate) file_scroll_offsets: HashMap<FileKey, isize>,
+    pub(crate) selection_key: SelectionKey,
+    pub(crate) focused_commit_idx: usize,
+    #[cfg(feature = "tui")]
+    pub(crate) help_dialog: Option<HelpDialog>,
+    #[cfg(feature = "tui")]
+    pub(crate) message_history_picker: Option<MessageHistoryPicker>,
+    #[cfg(feature = "tui")]
+    pub(crate) message_editor_dialog: Option<MessageEditorDialog>,
+    #[cfg(feature = "tui")]
+    pub(crate) message_diff_dialog: Option<MessageDiffDialog>,
+    #[cfg(feature = "tui")]
+    pub(crate) line_diff_dialog: Option<LineDiffDialog>,
+    #[cfg(feature = "tui")]
+    pub(crate) preview_dialog: Option<PreviewDialog>,
+    #[cfg(feature = "tui")]
+    pub(crate) file_contents_dialog: Option<FileContentsDialog>,
+    /// The saved state of an interrupted session, while the user is asked
+    /// whether to restore it.
+    #[cfg(feature = "tui")]
+    pub(crate) recovery: Option<Recovery<'static>>,
+    #[cfg(feature = "tui")]
+    pub(crate) error_dialog: Option<ErrorDialog>,
+    /// A global toggle waiting for the user to confirm it.
+    #[cfg(feature = "tui")]
+    pub(crate) toggle_all_dialog: Option<ToggleAllDialog>,
+    /// Accepting with nothing selected, waiting for the user to confirm it.
+    #[cfg(feature = "tui")]
+    pub(crate) empty_selection_dialog: Option<EmptySelectionDialog>,
+    #[cfg(feature = "tui")]
+    pub(crate) quit_dialog: Option<QuitDialog>,
+    #[cfg(feature = "tui")]
+    pub(crate) scroll_offset_y: isize,
+}
+
+/// A file shown on its own, using the whole screen.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ZoomedFile {
+    pub(crate) file_idx: usize,
+    /// Whether the file was expanded before it was zoomed into, so that it
+    /// can be collapsed again afterwards.
+    #[cfg(feature = "tui")]
+    pub(crate) was_expanded: bool,
+}
+
+/// Represents the application's state, combining the data model (`RecordState`)
+/// and the UI state (`UiState`). It contains the core logic for updating the state
+/// in response to events.
+pub(crate) struct App<'state> {
+    pub(crate) state: RecordState<'state>,
+    pub(crate) options: RecordOptions,
+    pub(crate) dirty: DirtyState,
+    #[cfg(feature = "tui")]
+    pub(crate) file_stats: Vec<FileStats>,
+    /// All directories which contain changed files, sorted. Indexed by
+    /// `DirectoryKey::dir_idx`.
+    pub(crate) directories: Vec<PathBuf>,
+    #[cfg(feature = "tui")]
+    pub(crate) search_index: SearchIndex,
+    /// The highlights of the lines of each section of each file, computed
+    /// when the file is first drawn.
+    #[cfg(feature = "tui")]
+    pub(crate) line_highlights: Vec<OnceCell<Vec<Vec<LineHighlights>>>>,
+    pub(crate) ui: UiState,
+}
+
+impl<'state> App<'state> {
+    pub(crate) fn new(mut state: RecordState<'state>, options: RecordOptions) -> Self {
+        // Ensure that there are at least two commits.
+        state.commits.extend(
+            iter::repeat_with(Commit::default).take(2_usize.saturating_sub(state.commits.len())),
+        );
+
+        #[cfg(feature = "tui")]
+        let file_stats = state.files.iter().map(FileStats::new).collect();
+        #[cfg(feature = "tui")]
+        let search_index = SearchIndex::new(&state.files);
+        let num_files = state.files.len();
+        let mut directories: Vec<PathBuf> = state
+            .files
+            .iter()
+            .flat_map(|file| file.path.ancestors().skip(1))
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .collect();
+        directories.sort();
+        directories.dedup();
+        let mut app = Self {
+            state,
+            options,
+            dirty: Default::default(),
+            #[cfg(feature = "tui")]
+            file_stats,
+            directories,
+            #[cfg(feature = "tui")]
+            search_index,
+            #[cfg(feature = "tui")]
+            line_highlights: iter::repeat_with(OnceCell::new).take(num_files).collect(),
+            ui: UiState {
+                #[cfg(feature = "tui")]
+                commit_view_mode: CommitViewMode::Inline,
+                #[cfg(feature = "tui")]
+                is_commit_message_expanded: false,
+                #[cfg(feature = "tui")]
+                is_stats_bar_visible: false,
+                #[cfg(feature = "tui")]
+                is_action_history_visible: false,
+                #[cfg(feature = "tui")]
+                file_sort_order: FileSortOrder::Input,
+                file_order: (0..num_files).collect(),
+                file_filter: None,
+                #[cfg(feature = "tui")]
+                file_filter_prompt: None,
+                #[cfg(feature = "tui")]
+                search_query: None,
+                #[cfg(feature = "tui")]
+                search_prompt: None,
+                hide_unselected_files: false,
+                hide_selected_files: false,
+                hide_unchanged_files: false,
+                is_file_tree: false,
+                is_two_pane: false,
+                zoomed_file: None,
+                is_outline: false,
+                wrapped_line: None,
+                #[cfg(feature = "tui")]
+                is_annotation_gutter_visible: false,
+                #[cfg(feature = "tui")]
+                is_context_hidden: false,
+                status_message: None,
+                #[cfg(feature = "tui")]
+                pending_keys: Vec::new(),
+                #[cfg(feature = "tui")]
+                count: None,
+                #[cfg(feature = "tui")]
+                macro_prompt: None,
+                #[cfg(feature = "tui")]
+                macros: Macros::default(),
+                history: History::default(),
+                expanded_items: Default::default(),
+                #[cfg(feature = "tui")]
+                bookmarks: Default::default(),
+                file_selections: Default::default(),
+                #[cfg(feature = "tui")]
+                file_scroll_offsets: Default::default(),
+                selection_key: SelectionKey::None,
+                focused_commit_idx: 0,
+                #[cfg(feature = "tui")]
+                help_dialog: None,
+                #[cfg(feature = "tui")]
+                message_history_picker: None,
+                #[cfg(feature = "tui")]
+                message_editor_dialog: None,
+                #[cfg(feature = "tui")]
+                message_diff_dialog: None,
+                #[cfg(feature = "tui")]
+                line_diff_dialog: None,
+                #[cfg(feature = "tui")]
+                preview_dialog: None,
+                #[cfg(feature = "tui")]
+                file_contents_dialog: None,
+                #[cfg(feature = "tui")]
+                recovery: None,
+                #[cfg(feature = "tui")]
+                error_dialog: None,
+                #[cfg(feature = "tui")]
+                toggle_all_dialog: None,
+                #[cfg(feature = "tui")]
+                empty_selection_dialog: None,
+                #[cfg(feature = "tui")]
+                quit_dialog: None,
+                #[cfg(feature = "tui")]
+                scroll_offset_y: 0,
+            },
+        };
+        app.ui.selection_key = app.first_selection_key();
+        app.expand_initial_items();
+        app
+    }
+
+    /// Apply the update to the state. Returns whether the selected item may
+    /// have moved on the screen, in which case the frontend should scroll it
+    /// back into view.
+    ///
+    /// The terminal frontend applies the updates for its dialogs and prompts
+    /// itself, along with those which need the terminal or the input, so it
+    /// mustn't pass them here.
+    pub(crate) fn apply_state_update(
+        &mut self,
+        state_update: StateUpdate,
+    ) -> Result<bool, RecordError> {
+        match state_update {
+            StateUpdate::None => {}
+            StateUpdate::SelectItem {
+                selection_key,
+                ensure_in_viewport,
+            } => {
+                self.select_item(selection_key);
+                return Ok(ensure_in_viewport);
+            }
+            StateUpdate::ToggleItem(selection_key) => {
+                self.record_command(Command::ToggleItem(selection_key), |app| {
+                    app.toggle_item(selection_key)
+                })?;
+                self.collapse_decided_file(selection_key);
+                self.ensure_selection_visible();
+                return Ok(true);
+            }
+            StateUpdate::ToggleItemAndAdvance(selection_key, new_key) => {
+                self.record_command(Command::ToggleItem(selection_key), |app| {
+                    app.toggle_item(selection_key)
+                })?;
+                self.collapse_decided_file(selection_key);
+                self.ui.selection_key = new_key;
+                self.ensure_selection_visible();
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleDeferItem(selection_key) => {
+                self.record_command(Command::ToggleDeferItem(selection_key), |app| {
+                    app.toggle_defer_item(selection_key)
+                });
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::MoveItemToCommit(selection_key, commit_idx) => {
+                self.record_command(
+                    Command::MoveItemToCommit {
+                        selection_key,
+                        commit_idx,
+                    },
+                    |app| app.move_item_to_commit(selection_key, commit_idx),
+                )?;
+                self.ui.status_message = Some(StatusMessage::info(format!(
+                    "Moved to commit {}",
+                    commit_idx + 1
+                )));
+                self.ensure_selection_visible();
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleBookmark(bookmark_key) => {
+                let status_message = self.toggle_bookmark(bookmark_key);
+                self.ui.status_message = Some(status_message);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::SetWrappedLine(wrapped_line) => {
+                self.ui.wrapped_line = wrapped_line;
+                return Ok(true);
+            }
+            StateUpdate::ToggleAll => {
+                self.record_command(Command::ToggleAll, |app| app.toggle_all());
+                self.ensure_selection_visible();
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleAllUniform => {
+                self.record_command(Command::ToggleAllUniform, |app| app.toggle_all_uniform());
+                self.ensure_selection_visible();
+            }
+            StateUpdate::SetExpandItem(selection_key, is_expanded) => {
+                self.set_expand_item(selection_key, is_expanded);
+                return Ok(true);
+            }
+            StateUpdate::ToggleExpandItem(selection_key) => {
+                self.toggle_expand_item(selection_key)?;
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleExpandAll => {
+                self.toggle_expand_all()?;
+                self.ensure_selection_visible();
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::SetFocusedCommit(commit_idx) => {
+                self.focus_commit(commit_idx);
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleCommitMessageExpanded => {
+                self.ui.is_commit_message_expanded = !self.ui.is_commit_message_expanded;
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleAnnotations => {
+                self.ui.is_annotation_gutter_visible = !self.ui.is_annotation_gutter_visible;
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleContext => {
+                self.ui.is_context_hidden = !self.ui.is_context_hidden;
+                // The selection moves as the lines above it are hidden.
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleStatsBar => {
+                self.ui.is_stats_bar_visible = !self.ui.is_stats_bar_visible;
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleActionHistory => {
+                self.ui.is_action_history_visible = !self.ui.is_action_history_visible;
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::SetFileSortOrder(file_sort_order) => {
+                self.set_file_sort_order(file_sort_order);
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::SelectSearchMatch {
+                selection_key,
+                match_idx,
+                num_matches,
+            } => {
+                self.select_item(selection_key);
+                self.ui.status_message = Some(StatusMessage::info(format!(
+                    "Match {} of {num_matches}",
+                    match_idx + 1
+                )));
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleHideUnselectedFiles => {
+                self.ui.hide_unselected_files = !self.ui.hide_unselected_files;
+                self.ensure_selection_visible();
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleHideSelectedFiles => {
+                self.ui.hide_selected_files = !self.ui.hide_selected_files;
+                self.ensure_selection_visible();
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleHideUnchangedFiles => {
+                self.ui.hide_unchanged_files = !self.ui.hide_unchanged_files;
+                self.ensure_selection_visible();
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::SetZoomedFile(file_idx) => {
+                self.set_zoomed_file(file_idx);
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleOutline => {
+                self.ui.is_outline = !self.ui.is_outline;
+                self.ensure_selection_visible();
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleTwoPane => {
+                self.ui.is_two_pane = !self.ui.is_two_pane;
+                self.ensure_selection_visible();
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::ToggleFileTree => {
+                self.ui.is_file_tree = !self.ui.is_file_tree;
+                self.ensure_selection_visible();
+                return Ok(true);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::SetStatusMessage(status_message) => {
+                self.ui.status_message = Some(status_message);
+            }
+            StateUpdate::Undo => {
+                self.undo_or_redo(/*is_undo=*/ true);
+            }
+            StateUpdate::Redo => {
+                self.undo_or_redo(/*is_undo=*/ false);
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::RestoreRecovery => {
+                if let Some(recovery) = self.ui.recovery.take() {
+                    self.record_command(Command::RestoreRecovery, |app| {
+                        app.restore_recovery(recovery)
+                    });
+                    return Ok(true);
+                }
+            }
+            #[cfg(feature = "tui")]
+            StateUpdate::DiscardRecovery => {
+                self.ui.recovery = None;
+            }
+            #[cfg(feature = "tui")]
+            state_update => {
+                unreachable!("{state_update:?} is applied by the terminal frontend")
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether the item is selected, if it can be.
+    pub(crate) fn item_tristate(&self, selection_key: SelectionKey) -> Option<Tristate> {
+        match selection_key {
+            SelectionKey::None => None,
+            SelectionKey::Directory(directory_key) => Some(self.directory_tristate(directory_key)),
+            SelectionKey::File(file_key) => self.file_tristate(file_key).ok(),
+            SelectionKey::Section(section_key) => self.section_tristate(section_key).ok(),
+            SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+                line_idx,
+            }) => match self.state.files.get(file_idx)?.sections.get(section_idx)? {
+                Section::Changed { lines, .. } => Some(Tristate::from(
+                    lines.get(line_idx)?.assigned_commit_idx() == commit_idx,
+                )),
+                Section::Unchanged { .. } | Section::FileMode { .. } | Section::Binary { .. } => {
+                    None
+                }
+            },
+        }
+    }
+
+    /// Select the next or previous match of the last search, wrapping around.
+    #[cfg(feature = "tui")]
+    pub(crate) fn select_next_search_match(&self, select_previous: bool) -> StateUpdate {
+        let Some(query) = &self.ui.search_query else {
+            return StateUpdate::SetStatusMessage(StatusMessage::error(
+                "No previous search, press / to search",
+            ));
+        };
+        match self.find_search_match(
+            query,
+            self.ui.selection_key,
+            select_previous,
+            /*include_from=*/ false,
+        ) {
+            Some((selection_key, match_idx, num_matches)) => StateUpdate::SelectSearchMatch {
+                selection_key,
+                match_idx,
+                num_matches,
+            },
+            None => StateUpdate::SetStatusMessage(StatusMessage::error(format!(
+                "Pattern not found: {query}"
+            ))),
+        }
+    }
+
+    /// Find the first item matching the query after the given one, or before
+    /// it if `select_previous` is set, wrapping around. If `include_from` is
+    /// set, the given item itself is found if it matches. Returns the item,
+    /// along with its index among all matches and the number of matches.
+    #[cfg(feature = "tui")]
+    pub(crate) fn find_search_match(
+        &self,
+        query: &str,
+        from: SelectionKey,
+        select_previous: bool,
+        include_from: bool,
+    ) -> Option<(SelectionKey, usize, usize)> {
+        if query.is_empty() {
+            return None;
+        }
+        let query = SearchQuery::new(query);
+        let keys = self.all_selection_keys();
+        let matches: Vec<usize> = keys
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| self.search_index.is_match(**key, &query))
+            .map(|(index, _)| index)
+            .collect();
+        if matches.is_empty() {
+            return None;
+        }
+        let from_index = keys.iter().position(|key| *key == from);
+        let match_idx = match from_index {
+            Some(from_index) if select_previous => matches
+                .iter()
+                .rposition(|index| *index < from_index || (include_from && *index == from_index))
+                .unwrap_or(matches.len() - 1),
+            Some(from_index) => matches
+                .iter()
+                .position(|index| *index > from_index || (include_from && *index == from_index))
+                .unwrap_or(0),
+            None if select_previous => matches.len() - 1,
+            None => 0,
+        };
+        Some((keys[matches[match_idx]], match_idx, matches.len()))
+    }
+
+    /// The filter currently applied to the file list, if any.
+    pub(crate) fn file_filter(&self) -> Option<&str> {
+        #[cfg(feature = "tui")]
+        let file_filter = match &self.ui.file_filter_prompt {
+            Some(prompt) => Some(prompt.text.as_str()),
+            None => self.ui.file_filter.as_deref(),
+        };
+        #[cfg(not(feature = "tui"))]
+        let file_filter = self.ui.file_filter.as_deref();
+        file_filter.filter(|file_filter| !file_filter.is_empty())
+    }
+
+    /// The index of the file containing the selected item, if any.
+    pub(crate) fn selected_file_idx(&self) -> Option<usize> {
+        match self.ui.selection_key {
+            SelectionKey::None | SelectionKey::Directory(_) => None,
+            SelectionKey::File(FileKey {
+                commit_idx: _,
+                file_idx,
+            })
+            | SelectionKey::Section(SectionKey {
+                commit_idx: _,
+                file_idx,
+                section_idx: _,
+            })
+            | SelectionKey::Line(LineKey {
+                commit_idx: _,
+                file_idx,
+                section_idx: _,
+                line_idx: _,
+            }) => Some(file_idx),
+        }
+    }
+
+    /// The indices of the files to display, in display order. A zoomed file
+    /// is displayed on its own, regardless of the filters.
+    pub(crate) fn visible_file_indices(&self) -> Vec<usize> {
+        if let Some(zoomed_file) = self.ui.zoomed_file {
+            return vec![zoomed_file.file_idx];
+        }
+        let file_filter = self.file_filter();
+        self.ui
+            .file_order
+            .iter()
+            .copied()
+            .filter(|&file_idx| {
+                let file = &self.state.files[file_idx];
+                let is_hidden_by_selection = match file.commit_tristate(self.ui.focused_commit_idx)
+                {
+                    Tristate::False => self.ui.hide_unselected_files,
+                    Tristate::Partial => false,
+                    Tristate::True => self.ui.hide_selected_files,
+                };
+                if is_hidden_by_selection {
+                    return false;
+                }
+                if self.ui.hide_unchanged_files
+                    && self
+                        .file_interdiff(file_idx)
+                        .is_some_and(|file_interdiff| !file_interdiff.needs_attention())
+                {
+                    return false;
+                }
+                match file_filter {
+                    Some(file_filter) => {
+                        let File { path, old_path, .. } = file;
+                        iter::once(path)
+                            .chain(old_path)
+                            .any(|path| path_matches_filter(&path.to_string_lossy(), file_filter))
+                    }
+                    None => true,
+                }
+            })
+            .collect()
+    }
+
+    /// The files to display, in display order. When the files are displayed
+    /// as a tree, they are grouped under nodes for their directories. Nodes
+    /// inside collapsed directories are included, but marked as not visible.
+    pub(crate) fn file_list(&self, commit_idx: usize) -> Vec<FileListNode> {
+        let visible_file_indices = self.visible_file_indices();
+        // A zoomed file is shown without its directories.
+        if !self.ui.is_file_tree || self.ui.zoomed_file.is_some() {
+            return visible_file_indices
+                .into_iter()
+                .map(|file_idx| FileListNode::File {
+                    file_idx,
+                    depth: 0,
+                    is_visible: true,
+                })
+                .collect();
+        }
+
+        // Only directories which directly contain files, or which contain
+        // several subdirectories, get their own node. Otherwise, deeply nested
+        // paths would need one level of indentation per path component.
+        let mut dirs_with_files: HashSet<&Path> = HashSet::new();
+        let mut all_dirs: HashSet<&Path> = HashSet::new();
+        let mut num_subdirs: HashMap<&Path, usize> = HashMap::new();
+        for &file_idx in &visible_file_indices {
+            let file_dir = parent_dir(&self.state.files[file_idx].path);
+            dirs_with_files.insert(file_dir);
+            for dir in non_empty_ancestors(file_dir) {
+                if !all_dirs.insert(dir) {
+                    break;
+                }
+                *num_subdirs.entry(parent_dir(dir)).or_default() += 1;
+            }
+        }
+        let is_node = |dir: &Path| -> bool {
+            dirs_with_files.contains(dir) || num_subdirs.get(dir).copied().unwrap_or_default() > 1
+        };
+
+        let mut file_indices = visible_file_indices;
+        file_indices.sort_by_key(|&file_idx| parent_dir(&self.state.files[file_idx].path));
+
+        let mut result = Vec::new();
+        // The directory nodes containing the current file, outermost first,
+        // and whether each is expanded.
+        let mut dir_stack: Vec<(&Path, bool)> = Vec::new();
+        for file_idx in file_indices {
+            let mut node_dirs: Vec<&Path> =
+                non_empty_ancestors(parent_dir(&self.state.files[file_idx].path))
+                    .filter(|dir| is_node(dir))
+                    .collect();
+            node_dirs.reverse();
+            let num_common_dirs = dir_stack
+                .iter()
+                .zip(&node_dirs)
+                .take_while(|((stack_dir, _), node_dir)| stack_dir == *node_dir)
+                .count();
+            dir_stack.truncate(num_common_dirs);
+            for &dir in &node_dirs[num_common_dirs..] {
+                let dir_idx = self.directory_idx(dir);
+                #[cfg(feature = "tui")]
+                let label = match dir_stack.last() {
+                    Some((parent_dir, _)) => dir.strip_prefix(parent_dir).unwrap_or(dir),
+                    None => dir,
+                };
+                result.push(FileListNode::Directory {
+                    dir_idx,
+                    #[cfg(feature = "tui")]
+                    label: label.to_string_lossy().into_owned(),
+                    depth: dir_stack.len(),
+                    is_visible: dir_stack.iter().all(|(_, is_expanded)| *is_expanded),
+                });
+                let is_expanded =
+                    self.ui
+                        .expanded_items
+                        .contains(&SelectionKey::Directory(DirectoryKey {
+                            commit_idx,
+                            dir_idx,
+                        }));
+                dir_stack.push((dir, is_expanded));
+            }
+            result.push(FileListNode::File {
+                file_idx,
+                depth: dir_stack.len(),
+                is_visible: dir_stack.iter().all(|(_, is_expanded)| *is_expanded),
+            });
+        }
+        result
+    }
+
+    pub(crate) fn directory_idx(&self, dir: &Path) -> usize {
+        self.directories
+            .binary_search_by(|other_dir| other_dir.as_path().cmp(dir))
+            .expect("directories should contain all ancestors of changed files")
+    }
+
+    /// The visible files inside the given directory, including in its
+    /// subdirectories.
+    pub(crate) fn directory_file_indices(&self, directory_key: DirectoryKey) -> Vec<usize> {
+        let DirectoryKey {
+            commit_idx: _,
+            dir_idx,
+        } = directory_key;
+        let dir = &self.directories[dir_idx];
+        self.visible_file_indices()
+            .into_iter()
+            .filter(|&file_idx| self.state.files[file_idx].path.starts_with(dir))
+            .collect()
+    }
+
+    pub(crate) fn directory_tristate(&self, directory_key: DirectoryKey) -> Tristate {
+        self.directory_file_indices(directory_key)
+            .into_iter()
+            .map(|file_idx| self.state.files[file_idx].commit_tristate(directory_key.commit_idx))
+            .fold(None, |acc, elem| match (acc, elem) {
+                (None, tristate) => Some(tristate),
+                (Some(acc_tristate), tristate) if acc_tristate == tristate => Some(tristate),
+                _ => Some(Tristate::Partial),
+            })
+            .unwrap_or(Tristate::False)
+    }
+
+    /// The keys of all directories, whether or not they're currently shown.
+    pub(crate) fn all_directory_keys(&self) -> impl Iterator<Item = SelectionKey> + '_ {
+        (0..self.state.commits.len()).flat_map(move |commit_idx| {
+            (0..self.directories.len()).map(move |dir_idx| {
+                SelectionKey::Directory(DirectoryKey {
+                    commit_idx,
+                    dir_idx,
+                })
+            })
+        })
+    }
+
+    /// The keys of the directories containing the given item.
+    pub(crate) fn ancestor_directory_keys(&self, selection: SelectionKey) -> Vec<SelectionKey> {
+        let (commit_idx, path) = match selection {
+            SelectionKey::None => return Vec::new(),
+            SelectionKey::Directory(DirectoryKey {
+                commit_idx,
+                dir_idx,
+            }) => (commit_idx, self.directories[dir_idx].as_path()),
+            SelectionKey::File(FileKey {
+                commit_idx,
+                file_idx,
+            })
+            | SelectionKey::Section(SectionKey {
+                commit_idx,
+                file_idx,
+                section_idx: _,
+            })
+            | SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx,
+                section_idx: _,
+                line_idx: _,
+            }) => (commit_idx, self.state.files[file_idx].path.as_ref()),
+        };
+        non_empty_ancestors(parent_dir(path))
+            .map(|dir| {
+                SelectionKey::Directory(DirectoryKey {
+                    commit_idx,
+                    dir_idx: self.directory_idx(dir),
+                })
+            })
+            .collect()
+    }
+
+    /// The directory node directly containing the given node in the file
+    /// tree, if any.
+    pub(crate) fn parent_directory_key(&self, selection: SelectionKey) -> Option<SelectionKey> {
+        let commit_idx = self.ui.focused_commit_idx;
+        let file_list = self.file_list(commit_idx);
+        let node_idx = file_list.iter().position(|node| node.contains(selection))?;
+        let depth = file_list[node_idx].depth();
+        file_list[..node_idx]
+            .iter()
+            .rev()
+            .find(|node| node.depth() < depth)
+            .map(|node| node.selection_key(commit_idx))
+    }
+
+    /// Select the item, expanding the items containing it so that it's shown.
+    pub(crate) fn select_item(&mut self, selection_key: SelectionKey) {
+        self.ui.selection_key = selection_key;
+        self.remember_file_selection();
+        self.expand_item_ancestors(selection_key);
+        if self.ui.wrapped_line.map(SelectionKey::Line) != Some(selection_key) {
+            self.ui.wrapped_line = None;
+        }
+    }
+
+    /// Move the selected item into the commit before or after the focused
+    /// one, if there is such a commit.
+    #[cfg(feature = "tui")]
+    pub(crate) fn move_item_to_adjacent_commit(&self, to_previous: bool) -> StateUpdate {
+        if self.state.is_read_only {
+            return StateUpdate::None;
+        }
+        if self.ui.selection_key == SelectionKey::None {
+            return StateUpdate::SetStatusMessage(StatusMessage::error(
+                "Select an item to move it to another commit",
+            ));
+        }
+        let commit_idx = if to_previous {
+            self.ui.focused_commit_idx.checked_sub(1)
+        } else {
+            Some(self.ui.focused_commit_idx + 1)
+                .filter(|commit_idx| *commit_idx < self.state.commits.len())
+        };
+        match commit_idx {
+            Some(commit_idx) => StateUpdate::MoveItemToCommit(self.ui.selection_key, commit_idx),
+            None if to_previous => {
+                StateUpdate::SetStatusMessage(StatusMessage::error("There is no previous commit"))
+            }
+            None => StateUpdate::SetStatusMessage(StatusMessage::error("There is no next commit")),
+        }
+    }
+
+    /// Focus the commit at `commit_idx`, keeping the same item selected and
+    /// the same items expanded as in the previously focused commit.
+    #[cfg(feature = "tui")]
+    pub(crate) fn focus_commit(&mut self, commit_idx: usize) {
+        let old_commit_idx = self.ui.focused_commit_idx;
+        let expanded_items: Vec<SelectionKey> = self
+            .ui
+            .expanded_items
+            .iter()
+            .filter(|key| with_commit_idx(**key, old_commit_idx) == **key)
+            .map(|key| with_commit_idx(*key, commit_idx))
+            .collect();
+        self.ui.expanded_items.extend(expanded_items);
+        self.ui.selection_key = with_commit_idx(self.ui.selection_key, commit_idx);
+        self.ui.wrapped_line = None;
+        self.ui.focused_commit_idx = commit_idx;
+        self.ensure_selection_visible();
+    }
+
+    /// If the selected item is no longer visible, select the nearest visible
+    /// item instead: the file or directory which it was collapsed into, or
+    /// if it was hidden, the nearest file after it, or before it if there
+    /// are none.
+    pub(crate) fn ensure_selection_visible(&mut self) {
+        let (_keys, index) = self.find_selection();
+        if index.is_some() {
+            return;
+        }
+
+        let selected_file_idx = self.selected_file_idx();
+        let visible_file_indices = self.visible_file_indices();
+        if let Some(selected_file_idx) = selected_file_idx {
+            if !visible_file_indices.contains(&selected_file_idx) {
+                let position = self
+                    .ui
+                    .file_order
+                    .iter()
+                    .position(|&file_idx| file_idx == selected_file_idx)
+                    .unwrap_or_default();
+                let (before, after) = self.ui.file_order.split_at(position);
+                let nearest_file_idx = after
+                    .iter()
+                    .chain(before.iter().rev())
+                    .find(|file_idx| visible_file_indices.contains(file_idx));
+                self.ui.selection_key = match nearest_file_idx {
+                    Some(&file_idx) => SelectionKey::File(FileKey {
+                        commit_idx: self.ui.focused_commit_idx,
+                        file_idx,
+                    }),
+                    None => SelectionKey::None,
+                };
+            }
+        }
+
+        // Select the innermost visible node containing the selected item.
+        let commit_idx = self.ui.focused_commit_idx;
+        let file_list = self.file_list(commit_idx);
+        let node_idx = file_list
+            .iter()
+            .position(|node| node.contains(self.ui.selection_key));
+        self.ui.selection_key = match node_idx {
+            Some(node_idx) => {
+                let mut depth = file_list[node_idx].depth() + 1;
+                file_list[..=node_idx]
+                    .iter()
+                    .rev()
+                    .find(|node| {
+                        if node.depth() >= depth {
+                            return false;
+                        }
+                        depth = node.depth();
+                        node.is_visible()
+                    })
+                    .map(|node| node.selection_key(commit_idx))
+                    .unwrap_or_else(|| self.first_selection_key())
+            }
+            None => self.first_selection_key(),
+        };
+    }
+
+    #[cfg(feature = "tui")]
+    pub(crate) fn set_file_sort_order(&mut self, file_sort_order: FileSortOrder) {
+        self.ui.file_sort_order = file_sort_order;
+        file_sort_order.sort(&mut self.ui.file_order, &self.state.files, &self.file_stats);
+    }
+
+    pub(crate) fn first_selection_key(&self) -> SelectionKey {
+        let commit_idx = self.ui.focused_commit_idx;
+        match self.file_list(commit_idx).first() {
+            Some(node) => node.selection_key(commit_idx),
+            None => SelectionKey::None,
+        }
+    }
+
+    pub(crate) fn all_selection_keys(&self) -> Vec<SelectionKey> {
+        let mut result = Vec::new();
+        for (commit_idx, _) in self.state.commits.iter().enumerate() {
+            if commit_idx != self.ui.focused_commit_idx {
+                continue;
+            }
+            for node in self.file_list(commit_idx) {
+                let file_idx = match node {
+                    FileListNode::Directory { dir_idx, .. } => {
+                        result.push(SelectionKey::Directory(DirectoryKey {
+                            commit_idx,
+                            dir_idx,
+                        }));
+                        continue;
+                    }
+                    FileListNode::File { file_idx, .. } => file_idx,
+                };
+                let file = &self.state.files[file_idx];
+                result.push(SelectionKey::File(FileKey {
+                    commit_idx,
+                    file_idx,
+                }));
+                for (section_idx, section) in file.sections.iter().enumerate() {
+                    match section {
+                        Section::Unchanged { .. } => {}
+                        Section::Changed { lines, .. } => {
+                            result.push(SelectionKey::Section(SectionKey {
+                                commit_idx,
+                                file_idx,
+                                section_idx,
+                            }));
+                            for (line_idx, _line) in lines.iter().enumerate() {
+                                result.push(SelectionKey::Line(LineKey {
+                                    commit_idx,
+                                    file_idx,
+                                    section_idx,
+                                    line_idx,
+                                }));
+                            }
+                        }
+                        Section::FileMode {
+                            is_checked: _,
+                            commit_idx: _,
+                            mode: _,
+                        }
+                        | Section::Binary { .. } => {
+                            result.push(SelectionKey::Section(SectionKey {
+                                commit_idx,
+                                file_idx,
+                                section_idx,
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    pub(crate) fn find_selection(&self) -> (Vec<SelectionKey>, Option<usize>) {
+        // FIXME: finding the selected key is an O(n) algorithm (instead of O(log(n)) or O(1)).
+        let collapsed_keys: HashSet<SelectionKey> = self
+            .file_list(self.ui.focused_commit_idx)
+            .into_iter()
+            .filter(|node| !node.is_visible())
+            .map(|node| node.selection_key(self.ui.focused_commit_idx))
+            .collect();
+        let visible_keys: Vec<_> = self
+            .all_selection_keys()
+            .iter()
+            .cloned()
+            .filter(|key| match key {
+                SelectionKey::None => false,
+                SelectionKey::Directory(_) | SelectionKey::File(_) => !collapsed_keys.contains(key),
+                SelectionKey::Section(section_key) => {
+                    let file_key = FileKey {
+                        commit_idx: section_key.commit_idx,
+                        file_idx: section_key.file_idx,
+                    };
+                    !collapsed_keys.contains(&SelectionKey::File(file_key))
+                        && match self.file_expanded(file_key) {
+                            Tristate::False => false,
+                            Tristate::Partial | Tristate::True => true,
+                        }
+                }
+                SelectionKey::Line(line_key) => {
+                    let file_key = FileKey {
+                        commit_idx: line_key.commit_idx,
+                        file_idx: line_key.file_idx,
+                    };
+                    let section_key = SectionKey {
+                        commit_idx: line_key.commit_idx,
+                        file_idx: line_key.file_idx,
+                        section_idx: line_key.section_idx,
+                    };
+                    !collapsed_keys.contains(&SelectionKey::File(file_key))
+                        && match self.file_expanded(file_key) {
+                            Tristate::False => false,
+                            Tristate::Partial | Tristate::True => true,
+                        }
+                        && self
+                            .ui
+                            .expanded_items
+                            .contains(&SelectionKey::Section(section_key))
+                }
+            })
+            .collect();
+        let index = visible_keys.iter().enumerate().find_map(|(k, v)| {
+            if v == &self.ui.selection_key {
+                Some(k)
+            } else {
+                None
+            }
+        });
+        (visible_keys, index)
+    }
+
+    pub(crate) fn select_prev(&self, keys: &[SelectionKey], index: Option<usize>) -> SelectionKey {
+        match index {
+            None => self.first_selection_key(),
+            Some(index) => match index.checked_sub(1) {
+                Some(prev_index) => keys[prev_index],
+                None => keys[index],
+            },
+        }
+    }
+
+    pub(crate) fn select_next(&self, keys: &[SelectionKey], index: Option<usize>) -> SelectionKey {
+        match index {
+            None => self.first_selection_key(),
+            Some(index) => match keys.get(index + 1) {
+                Some(key) => *key,
+                None => keys[index],
+            },
+        }
+    }
+
+    // Returns the previous or next SelectionKey of the same kind as the current
+    // selection key. If there are no other keys of the same kind, the current
+    // key is returned instead. If `select_previous` is true, the previous key
+    // is returned. Otherwise, the next key is returned.
+    #[cfg(feature = "tui")]
+    pub(crate) fn select_prev_or_next_of_same_kind(&self, select_previous: bool) -> SelectionKey {
+        let (keys, index) = self.find_selection();
+        match index {
+            None => self.first_selection_key(),
+            Some(index) => {
+                let mut iterate_keys: Box<dyn DoubleEndedIterator<Item = _>> = match select_previous
+                {
+                    true => Box::new(keys[..index].iter().rev()),
+                    false => Box::new(keys[index + 1..].iter()),
+                };
+
+                match iterate_keys
+                    .find(|k| std::mem::discriminant(*k) == std::mem::discriminant(&keys[index]))
+                {
+                    None => keys[index],
+                    Some(key) => *key,
+                }
+            }
+        }
+    }
+
+    /// Returns the previous or next file, skipping the file containing the
+    /// current selection. If there is no such file, the current key is
+    /// returned instead.
+    pub(crate) fn select_prev_or_next_file(&self, select_previous: bool) -> SelectionKey {
+        let (keys, index) = self.find_selection();
+        let index = match index {
+            Some(index) => index,
+            None => return self.first_selection_key(),
+        };
+        let current_file_key = file_selection_key(keys[index]);
+        let mut iterate_keys: Box<dyn Iterator<Item = _>> = if select_previous {
+            Box::new(keys[..index].iter().rev())
+        } else {
+            Box::new(keys[index + 1..].iter())
+        };
+        iterate_keys
+            .find(|key| matches!(key, SelectionKey::File(_)) && **key != current_file_key)
+            .copied()
+            .unwrap_or(keys[index])
+    }
+
+    /// Select the previous or next bookmark in display order, wrapping around
+    /// at either end. Bookmarks in collapsed files are included, since
+    /// selecting them expands their files.
+    #[cfg(feature = "tui")]
+    pub(crate) fn select_prev_or_next_bookmark(&self, select_previous: bool) -> StateUpdate {
+        if self.ui.bookmarks.is_empty() {
+            return StateUpdate::SetStatusMessage(StatusMessage::error(
+                "No bookmarks, press m to bookmark the selected file or section",
+            ));
+        }
+        let keys = self.all_selection_keys();
+        let index = keys.iter().position(|key| *key == self.ui.selection_key);
+        // Start after the selection, and end with it.
+        let mut iterate_keys: Box<dyn Iterator<Item = _>> = if select_previous {
+            let end = index.unwrap_or(keys.len());
+            Box::new(keys[..end].iter().rev().chain(keys[end..].iter().rev()))
+        } else {
+            let start = index.map_or(0, |index| index + 1);
+            Box::new(keys[start..].iter().chain(keys[..start].iter()))
+        };
+        match iterate_keys.find(|key| self.ui.bookmarks.contains(key)) {
+            Some(selection_key) => StateUpdate::SelectItem {
+                selection_key: *selection_key,
+                ensure_in_viewport: true,
+            },
+            None => StateUpdate::SetStatusMessage(StatusMessage::error(
+                "No bookmarks in the files being shown",
+            )),
+        }
+    }
+
+    pub(crate) fn select_inner(&self) -> SelectionKey {
+        self.all_selection_keys()
+            .into_iter()
+            .skip_while(|selection_key| selection_key != &self.ui.selection_key)
+            .skip(1)
+            .find(|selection_key| {
+                match (self.ui.selection_key, selection_key) {
+                    (SelectionKey::None, _) => true,
+                    (_, SelectionKey::None) => false, // shouldn't happen
+
+                    (SelectionKey::Directory(_), SelectionKey::Directory(_))
+                    | (SelectionKey::Directory(_), SelectionKey::File(_)) => true,
+                    (SelectionKey::Directory(_), SelectionKey::Section(_))
+                    | (SelectionKey::Directory(_), SelectionKey::Line(_)) => false, // shouldn't happen
+
+                    (SelectionKey::File(_), SelectionKey::Directory(_))
+                    | (SelectionKey::File(_), SelectionKey::File(_)) => false,
+                    (SelectionKey::File(_), SelectionKey::Section(_)) => true,
+                    (SelectionKey::File(_), SelectionKey::Line(_)) => false, // shouldn't happen
+
+                    (SelectionKey::Section(_), SelectionKey::Directory(_))
+                    | (SelectionKey::Section(_), SelectionKey::File(_))
+                    | (SelectionKey::Section(_), SelectionKey::Section(_)) => false,
+                    (SelectionKey::Section(_), SelectionKey::Line(_)) => true,
+
+                    (SelectionKey::Line(_), _) => false,
+                }
+            })
+            .unwrap_or(self.ui.selection_key)
+    }
+
+    pub(crate) fn select_outer(&self, fold_section: bool) -> StateUpdate {
+        match self.ui.selection_key {
+            SelectionKey::None => StateUpdate::None,
+            selection_key @ (SelectionKey::Directory(_) | SelectionKey::File(_)) => {
+                // Collapse the item, or if it's already collapsed, move the
+                // selection to its directory in the file tree.
+                match self.parent_directory_key(selection_key) {
+                    Some(parent_key) if !self.ui.expanded_items.contains(&selection_key) => {
+                        StateUpdate::SelectItem {
+                            selection_key: parent_key,
+                            ensure_in_viewport: true,
+                        }
+                    }
+                    Some(_) | None => StateUpdate::SetExpandItem(selection_key, false),
+                }
+            }
+            selection_key @ SelectionKey::Section(SectionKey {
+                commit_idx,
+                file_idx,
+                section_idx: _,
+            }) => {
+                // If folding is requested and the selection is expanded,
+                // collapse it. Otherwise, move the selection to the file.
+                if fold_section && self.ui.expanded_items.contains(&selection_key) {
+                    StateUpdate::SetExpandItem(selection_key, false)
+                } else {
+                    StateUpdate::SelectItem {
+                        selection_key: SelectionKey::File(FileKey {
+                            commit_idx,
+                            file_idx,
+                        }),
+                        ensure_in_viewport: true,
+                    }
+                }
+            }
+            SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+                line_idx: _,
+            }) => StateUpdate::SelectItem {
+                selection_key: SelectionKey::Section(SectionKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx,
+                }),
+                ensure_in_viewport: true,
+            },
+        }
+    }
+
+    pub(crate) fn advance_to_next_of_kind(&self) -> SelectionKey {
+        let (keys, index) = self.find_selection();
+        let index = match index {
+            Some(index) => index,
+            None => return SelectionKey::None,
+        };
+        keys.iter()
+            .skip(index + 1)
+            .copied()
+            .find(|key| match (self.ui.selection_key, key) {
+                (SelectionKey::None, _)
+                | (SelectionKey::Directory(_), SelectionKey::Directory(_))
+                | (SelectionKey::File(_), SelectionKey::File(_))
+                | (SelectionKey::Section(_), SelectionKey::Section(_))
+                | (SelectionKey::Line(_), SelectionKey::Line(_)) => true,
+                (
+                    SelectionKey::Directory(_),
+                    SelectionKey::None
+                    | SelectionKey::File(_)
+                    | SelectionKey::Section(_)
+                    | SelectionKey::Line(_),
+                )
+                | (
+                    SelectionKey::File(_),
+                    SelectionKey::None
+                    | SelectionKey::Directory(_)
+                    | SelectionKey::Section(_)
+                    | SelectionKey::Line(_),
+                )
+                | (
+                    SelectionKey::Section(_),
+                    SelectionKey::None
+                    | SelectionKey::Directory(_)
+                    | SelectionKey::File(_)
+                    | SelectionKey::Line(_),
+                )
+                | (
+                    SelectionKey::Line(_),
+                    SelectionKey::None
+                    | SelectionKey::Directory(_)
+                    | SelectionKey::File(_)
+                    | SelectionKey::Section(_),
+                ) => false,
+            })
+            .unwrap_or(self.ui.selection_key)
+    }
+
+    /// The commit to assign an item to when it's toggled in the view of the
+    /// commit at `commit_idx`, given whether the item is assigned to that
+    /// commit: unassigned items are moved into that commit, and assigned or
+    /// partially assigned ones are moved out of it, into the next commit if
+    /// there is one, or otherwise into the previous one. This is an index into
+    /// the commits, which `assign_to_commit` turns into the `is_checked` and
+    /// `commit_idx` of the changes.
+    pub(crate) fn toggled_commit_idx(&self, commit_idx: usize, tristate: Tristate) -> usize {
+        let is_in_commit = match tristate {
+            Tristate::False => false,
+            Tristate::Partial | Tristate::True => true,
+        };
+        toggled_commit_idx(commit_idx, is_in_commit, self.state.commits.len())
+    }
+
+    pub(crate) fn toggle_item(&mut self, selection: SelectionKey) -> Result<(), RecordError> {
+        if self.state.is_read_only {
+            return Ok(());
+        }
+
+        let num_commits = self.state.commits.len();
+        let side_effects = match selection {
+            SelectionKey::None => None,
+            SelectionKey::Directory(directory_key) => {
+                let tristate = self.directory_tristate(directory_key);
+                let new_commit_idx = self.toggled_commit_idx(directory_key.commit_idx, tristate);
+                for file_idx in self.directory_file_indices(directory_key) {
+                    self.state.files[file_idx].assign_to_commit(new_commit_idx, num_commits);
+                }
+
+                None
+            }
+            SelectionKey::File(file_key) => {
+                let tristate = self.file_tristate(file_key)?;
+                let new_commit_idx = self.toggled_commit_idx(file_key.commit_idx, tristate);
+                self.visit_file(file_key, |file| {
+                    file.assign_to_commit(new_commit_idx, num_commits);
+                })?;
+
+                None
+            }
+            SelectionKey::Section(section_key) => {
+                let tristate = self.section_tristate(section_key)?;
+                let new_commit_idx = self.toggled_commit_idx(section_key.commit_idx, tristate);
+                let is_checked_new = new_commit_idx + 1 < num_commits;
+
+                let old_file_mode = self.visit_file_for_section(section_key, |f| f.file_mode)?;
+
+                self.visit_section(section_key, |section| {
+                    section.assign_to_commit(new_commit_idx, num_commits);
+
+                    if let Section::FileMode { mode, .. } = section {
+                        return Some(ToggleSideEffects::ToggledModeChangeSection(
+                            section_key,
+                            old_file_mode,
+                            *mode,
+                            is_checked_new,
+                        ));
+                    }
+
+                    if let Section::Changed { .. } = section {
+                        return Some(ToggleSideEffects::ToggledChangedSection(
+                            section_key,
+                            is_checked_new,
+                        ));
+                    }
+
+                    None
+                })?
+            }
+            SelectionKey::Line(line_key) => {
+                let tristate = self.item_tristate(selection).unwrap_or(Tristate::False);
+                let new_commit_idx = self.toggled_commit_idx(line_key.commit_idx, tristate);
+                self.visit_line(line_key, |line| {
+                    line.assign_to_commit(new_commit_idx, num_commits);
+
+                    Some(ToggleSideEffects::ToggledChangedLine(
+                        line_key,
+                        line.is_checked,
+                    ))
+                })?
+            }
+        };
+
+        if let Some(side_effects) = side_effects {
+            match side_effects {
+                ToggleSideEffects::ToggledModeChangeSection(
+                    section_key,
+                    old_mode,
+                    new_mode,
+                    toggled_to,
+                ) => {
+                    // If we check a deletion, all lines in the file must be deleted
+                    if toggled_to && new_mode == FileMode::Absent {
+                        self.visit_file_for_section(section_key, |file| {
+                            for section in &mut file.sections {
+                                if matches!(section, Section::Changed { .. }) {
+                                    section.set_checked(true);
+                                }
+                            }
+                        })?;
+                    }
+
+                    // If we uncheck a creation, no lines in the file can be added
+                    if !toggled_to && old_mode == FileMode::Absent {
+                        self.visit_file_for_section(section_key, |file| {
+                            for section in &mut file.sections {
+                                section.set_checked(false);
+                            }
+                        })?;
+                    }
+                }
+                ToggleSideEffects::ToggledChangedSection(section_key, toggled_to) => {
+                    self.visit_file_for_section(section_key, |file| {
+                        for section in &mut file.sections {
+                            if let Section::FileMode {
+                                mode,
+                                is_checked,
+                                commit_idx: _,
+                            } = section
+                            {
+                                // If we removed a line and the file was being deleted, it can no longer
+                                // be deleted as it needs to contain that line
+                                if !toggled_to && *mode == FileMode::Absent {
+                                    *is_checked = false;
+                                }
+
+                                // If we added a line and the file was not being created, it must be created
+                                // in order to contain that line
+                                if toggled_to && file.file_mode == FileMode::Absent {
+                                    *is_checked = true;
+                                }
+                            }
+                        }
+                    })?;
+                }
+                ToggleSideEffects::ToggledChangedLine(line_key, toggled_to) => {
+                    self.visit_file_for_line(line_key, |file| {
+                        for section in &mut file.sections {
+                            if let Section::FileMode {
+                                mode,
+                                is_checked,
+                                commit_idx: _,
+                            } = section
+                            {
+                                // If we removed a line and the file was being deleted, it can no longer
+                                // be deleted as it needs to contain that line
+                                if !toggled_to && *mode == FileMode::Absent {
+                                    *is_checked = false;
+                                }
+
+                                // If we added a line and the file was not being created, it must be created
+                                // in order to contain that line
+                                if toggled_to && file.file_mode == FileMode::Absent {
+                                    *is_checked = true;
+                                }
+                            }
+                        }
+                    })?;
+                }
+            }
+        };
+
+        match selection {
+            SelectionKey::None => {}
+            SelectionKey::Directory(_)
+            | SelectionKey::File(_)
+            | SelectionKey::Section(_)
+            | SelectionKey::Line(_) => {
+                self.dirty.selections = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `f` to change the user's choices, and record the change as
+    /// `command` so that it can be undone.
+    pub(crate) fn record_command<T>(
+        &mut self,
+        command: Command,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let before = Choices::new(&self.state);
+        let result = f(self);
+        self.ui.history.record(command, before, &self.state);
+        result
+    }
+
+    /// Revert or reapply the last change to the user's choices, reporting
+    /// which one it was.
+    pub(crate) fn undo_or_redo(&mut self, is_undo: bool) {
+        let command = if is_undo {
+            self.ui.history.undo(&mut self.state, &mut self.dirty)
+        } else {
+            self.ui.history.redo(&mut self.state, &mut self.dirty)
+        };
+        let (verb, nothing) = if is_undo {
+            ("Undid", "Nothing to undo")
+        } else {
+            ("Redid", "Nothing to redo")
+        };
+        self.ui.status_message = Some(match command {
+            Some(command) => StatusMessage::info(format!("{verb} {}", command.describe())),
+            None => StatusMessage::error(nothing),
+        });
+        self.ensure_selection_visible();
+    }
+
+    /// Assign all the changes of the given item to the commit at `commit_idx`,
+    /// regardless of which commits they were assigned to before.
+    #[cfg(feature = "tui")]
+    pub(crate) fn move_item_to_commit(
+        &mut self,
+        selection: SelectionKey,
+        commit_idx: usize,
+    ) -> Result<(), RecordError> {
+        if self.state.is_read_only {
+            return Ok(());
+        }
+
+        let num_commits = self.state.commits.len();
+        match selection {
+            SelectionKey::None => {}
+            SelectionKey::Directory(directory_key) => {
+                for file_idx in self.directory_file_indices(directory_key) {
+                    self.state.files[file_idx].assign_to_commit(commit_idx, num_commits);
+                }
+            }
+            SelectionKey::File(file_key) => {
+                self.visit_file(file_key, |file| {
+                    file.assign_to_commit(commit_idx, num_commits);
+                })?;
+            }
+            SelectionKey::Section(section_key) => {
+                self.visit_section(section_key, |section| {
+                    section.assign_to_commit(commit_idx, num_commits);
+                })?;
+            }
+            SelectionKey::Line(line_key) => {
+                self.visit_line(line_key, |line| {
+                    line.assign_to_commit(commit_idx, num_commits);
+                    Some(())
+                })?;
+            }
+        }
+
+        match selection {
+            SelectionKey::None => {}
+            SelectionKey::Directory(_)
+            | SelectionKey::File(_)
+            | SelectionKey::Section(_)
+            | SelectionKey::Line(_) => {
+                self.dirty.selections = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark the files of the selected item as deferred, or unmark them if they
+    /// all already are.
+    #[cfg(feature = "tui")]
+    pub(crate) fn toggle_defer_item(&mut self, selection: SelectionKey) {
+        if self.state.is_read_only {
+            return;
+        }
+
+        let file_indices = match selection {
+            SelectionKey::None => return,
+            SelectionKey::Directory(directory_key) => self.directory_file_indices(directory_key),
+            SelectionKey::File(FileKey {
+                commit_idx: _,
+                file_idx,
+            })
+            | SelectionKey::Section(SectionKey {
+                commit_idx: _,
+                file_idx,
+                section_idx: _,
+            })
+            | SelectionKey::Line(LineKey {
+                commit_idx: _,
+                file_idx,
+                section_idx: _,
+                line_idx: _,
+            }) => vec![file_idx],
+        };
+        let is_deferred_new = !file_indices
+            .iter()
+            .all(|&file_idx| self.state.files[file_idx].is_deferred);
+        for file_idx in file_indices {
+            self.state.files[file_idx].is_deferred = is_deferred_new;
+            self.dirty.deferred_files = true;
+        }
+    }
+
+    /// Bookmark the given file or section, or remove its bookmark.
+    #[cfg(feature = "tui")]
+    pub(crate) fn toggle_bookmark(&mut self, bookmark_key: SelectionKey) -> StatusMessage {
+        if self.ui.bookmarks.remove(&bookmark_key) {
+            StatusMessage::info("Removed bookmark")
+        } else {
+            self.ui.bookmarks.insert(bookmark_key);
+            StatusMessage::info(format!(
+                "Bookmarked ({} in total), press ' or \" to jump between bookmarks",
+                self.ui.bookmarks.len()
+            ))
+        }
+    }
+
+    /// Set the message of the given commit. If this changed the message,
+    /// returns the previous message.
+    pub(crate) fn set_commit_message(
+        &mut self,
+        commit_idx: usize,
+        message: String,
+    ) -> Option<String> {
+        let commit = &mut self.state.commits[commit_idx];
+        let old_message = commit.message.replace(message);
+        if commit.message == old_message {
+            None
+        } else {
+            self.dirty.commit_messages = true;
+            old_message
+        }
+    }
+
+    /// Whether accepting now should be confirmed first, because nothing was
+    /// selected into the focused commit or edited.
+    #[cfg(feature = "tui")]
+    pub(crate) fn is_empty_selection(&self) -> bool {
+        let commit_idx = self.ui.focused_commit_idx;
+        self.options.confirm_empty_selection
+            && !self.state.is_read_only
+            && !self.dirty.commit_messages
+            && self
+                .state
+                .files
+                .iter()
+                .all(|file| file.commit_tristate(commit_idx) == Tristate::False)
+    }
+
+    /// Toggle every change in the focused commit: the changes in it are moved
+    /// out of it, and the others are moved into it.
+    pub(crate) fn toggle_all(&mut self) {
+        if self.state.is_read_only {
+            return;
+        }
+
+        let commit_idx = self.ui.focused_commit_idx;
+        let num_commits = self.state.commits.len();
+        for file in &mut self.state.files {
+            file.toggle_all(commit_idx, num_commits);
+            self.dirty.selections = true;
+        }
+    }
+
+    /// Move every change into the focused commit, or out of it if they're all
+    /// in it already.
+    #[cfg(feature = "tui")]
+    pub(crate) fn toggle_all_uniform(&mut self) {
+        if self.state.is_read_only {
+            return;
+        }
+
+        let commit_idx = self.ui.focused_commit_idx;
+        let num_commits = self.state.commits.len();
+        let new_commit_idx = {
+            let tristate = self
+                .state
+                .files
+                .iter()
+                .map(|file| file.commit_tristate(commit_idx))
+                .fold(None, |acc, elem| match (acc, elem) {
+                    (None, tristate) => Some(tristate),
+                    (Some(acc_tristate), tristate) if acc_tristate == tristate => Some(tristate),
+                    _ => Some(Tristate::Partial),
+                })
+                .unwrap_or(Tristate::False);
+            match tristate {
+                Tristate::False | Tristate::Partial => commit_idx,
+                Tristate::True => self.toggled_commit_idx(commit_idx, Tristate::True),
+            }
+        };
+        for file in &mut self.state.files {
+            file.assign_to_commit(new_commit_idx, num_commits);
+            self.dirty.selections = true;
+        }
+    }
+
+    pub(crate) fn expand_item_ancestors(&mut self, selection: SelectionKey) {
+        let ancestor_directory_keys = self.ancestor_directory_keys(selection);
+        self.ui.expanded_items.extend(ancestor_directory_keys);
+        match selection {
+            SelectionKey::None | SelectionKey::Directory(_) | SelectionKey::File(_) => {}
+            SelectionKey::Section(SectionKey {
+                commit_idx,
+                file_idx,
+                section_idx: _,
+            }) => {
+                self.ui.expanded_items.insert(SelectionKey::File(FileKey {
+                    commit_idx,
+                    file_idx,
+                }));
+            }
+            SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+                line_idx: _,
+            }) => {
+                self.ui.expanded_items.insert(SelectionKey::File(FileKey {
+                    commit_idx,
+                    file_idx,
+                }));
+                self.ui
+                    .expanded_items
+                    .insert(SelectionKey::Section(SectionKey {
+                        commit_idx,
+                        file_idx,
+                        section_idx,
+                    }));
+            }
+        }
+    }
+
+    /// Zoom into the file, expanding it while it is zoomed into, or show all
+    /// files again if `file_idx` is `None`.
+    #[cfg(feature = "tui")]
+    pub(crate) fn set_zoomed_file(&mut self, file_idx: Option<usize>) {
+        let commit_idx = self.ui.focused_commit_idx;
+        if let Some(ZoomedFile {
+            file_idx,
+            was_expanded,
+        }) = self.ui.zoomed_file.take()
+        {
+            if !was_expanded {
+                self.set_expand_item(
+                    SelectionKey::File(FileKey {
+                        commit_idx,
+                        file_idx,
+                    }),
+                    false,
+                );
+            }
+        }
+        self.ui.zoomed_file = file_idx.map(|file_idx| {
+            let file_key = SelectionKey::File(FileKey {
+                commit_idx,
+                file_idx,
+            });
+            let was_expanded = self.ui.expanded_items.contains(&file_key);
+            self.set_expand_item(file_key, true);
+            ZoomedFile {
+                file_idx,
+                was_expanded,
+            }
+        });
+        self.ensure_selection_visible();
+    }
+
+    /// Remember the selection if it's inside a file, so that it can be
+    /// restored once the file is collapsed and expanded again.
+    pub(crate) fn remember_file_selection(&mut self) {
+        match self.ui.selection_key {
+            SelectionKey::None | SelectionKey::Directory(_) | SelectionKey::File(_) => {}
+            selection_key @ (SelectionKey::Section(SectionKey {
+                commit_idx,
+                file_idx,
+                section_idx: _,
+            })
+            | SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx,
+                section_idx: _,
+                line_idx: _,
+            })) => {
+                self.ui.file_selections.insert(
+                    FileKey {
+                        commit_idx,
+                        file_idx,
+                    },
+                    selection_key,
+                );
+            }
+        }
+    }
+
+    /// Collapse the file containing the item if none of its changes are
+    /// partially selected anymore. See
+    /// [`RecordOptions::auto_collapse_decided_files`].
+    pub(crate) fn collapse_decided_file(&mut self, selection: SelectionKey) {
+        if !self.options.auto_collapse_decided_files {
+            return;
+        }
+        let file_key = file_selection_key(selection);
+        let (commit_idx, file_idx) = match file_key {
+            SelectionKey::File(FileKey {
+                commit_idx,
+                file_idx,
+            }) => (commit_idx, file_idx),
+            SelectionKey::None
+            | SelectionKey::Directory(_)
+            | SelectionKey::Section(_)
+            | SelectionKey::Line(_) => return,
+        };
+        match self.state.files[file_idx].commit_tristate(commit_idx) {
+            Tristate::False | Tristate::True => self.set_expand_item(file_key, false),
+            Tristate::Partial => {}
+        }
+    }
+
+    pub(crate) fn set_expand_item(&mut self, selection: SelectionKey, is_expanded: bool) {
+        if is_expanded {
+            self.ui.expanded_items.insert(selection);
+        } else {
+            self.ui.expanded_items.remove(&selection);
+        }
+    }
+
+    pub(crate) fn toggle_expand_item(
+        &mut self,
+        selection: SelectionKey,
+    ) -> Result<(), RecordError> {
+        match selection {
+            SelectionKey::None => {}
+            selection_key @ (SelectionKey::Directory(_) | SelectionKey::File(_)) => {
+                if !self.ui.expanded_items.insert(selection_key) {
+                    self.ui.expanded_items.remove(&selection_key);
+                }
+            }
+            SelectionKey::Section(section_key) => {
+                if !self
+                    .ui
+                    .expanded_items
+                    .insert(SelectionKey::Section(section_key))
+                {
+                    self.ui
+                        .expanded_items
+                        .remove(&SelectionKey::Section(section_key));
+                }
+            }
+            SelectionKey::Line(_) => {
+                // Do nothing.
+            }
+        }
+        Ok(())
+    }
+
+    /// Snapshot the user's choices so far, to be saved to the recovery file.
+    #[cfg(feature = "tui")]
+    pub(crate) fn recovery(&self) -> Recovery<'state> {
+        let mut expanded_items: Vec<SelectionKey> =
+            self.ui.expanded_items.iter().copied().collect();
+        expanded_items.sort();
+        Recovery {
+            state: self.state.clone(),
+            dirty: self.dirty,
+            expanded_items,
+            selection_key: self.ui.selection_key,
+        }
+    }
+
+    /// Offer to restore the session saved in the recovery file, if it was for
+    /// the same changes.
+    #[cfg(feature = "tui")]
+    pub(crate) fn offer_recovery(&mut self, recovery: Recovery<'static>) {
+        if recovery.has_same_shape(&self.state) {
+            self.ui.recovery = Some(recovery);
+        }
+    }
+
+    /// Restore the choices of an interrupted session.
+    #[cfg(feature = "tui")]
+    pub(crate) fn restore_recovery(&mut self, recovery: Recovery<'state>) {
+        let Recovery {
+            state,
+            dirty,
+            expanded_items,
+            selection_key,
+        } = recovery;
+        self.state = state;
+        self.dirty = dirty;
+        self.ui.expanded_items = expanded_items.into_iter().collect();
+        self.ui.selection_key = selection_key;
+        self.ensure_selection_visible();
+    }
+
+    pub(crate) fn expand_initial_items(&mut self) {
+        let all_selection_keys = self.all_selection_keys();
+        let first_file_key = all_selection_keys
+            .iter()
+            .find(|selection_key| matches!(selection_key, SelectionKey::File(_)))
+            .copied();
+        let initial_expansion = self.options.initial_expansion;
+        self.ui.expanded_items = all_selection_keys
+            .into_iter()
+            .filter(|selection_key| match selection_key {
+                SelectionKey::None | SelectionKey::Line(_) => false,
+                SelectionKey::File(FileKey {
+                    commit_idx: _,
+                    file_idx,
+                }) => match initial_expansion {
+                    InitialExpansion::ExpandAll => true,
+                    InitialExpansion::CollapseAll => false,
+                    InitialExpansion::ExpandFirstFile => Some(*selection_key) == first_file_key,
+                    InitialExpansion::ExpandFilesUnderLines(max_num_lines) => {
+                        let num_changed_lines: usize = self.state.files[*file_idx]
+                            .sections
+                            .iter()
+                            .map(|section| match section {
+                                Section::Changed { lines, .. } => lines.len(),
+                                Section::Unchanged { .. }
+                                | Section::FileMode { .. }
+                                | Section::Binary { .. } => 0,
+                            })
+                            .sum();
+                        num_changed_lines < max_num_lines
+                    }
+                },
+                SelectionKey::Directory(_) | SelectionKey::Section(_) => true,
+            })
+            .chain(self.all_directory_keys())
+            .collect();
+    }
+
+    #[cfg(feature = "tui")]
+    pub(crate) fn toggle_expand_all(&mut self) -> Result<(), RecordError> {
+        let all_selection_keys: HashSet<_> = self
+            .all_selection_keys()
+            .into_iter()
+            .chain(self.all_directory_keys())
+            .collect();
+        self.ui.expanded_items = if self.ui.expanded_items == all_selection_keys {
+            // Select an ancestor file key that will still be visible.
+            self.ui.selection_key = match self.ui.selection_key {
+                selection_key @ (SelectionKey::None
+                | SelectionKey::Directory(_)
+                | SelectionKey::File(_)) => selection_key,
+                SelectionKey::Section(SectionKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx: _,
+                })
+                | SelectionKey::Line(LineKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx: _,
+                    line_idx: _,
+                }) => SelectionKey::File(FileKey {
+                    commit_idx,
+                    file_idx,
+                }),
+            };
+            Default::default()
+        } else {
+            all_selection_keys
+        };
+        Ok(())
+    }
+
+    pub(crate) fn file(&self, file_key: FileKey) -> Result<&File<'_>, RecordError> {
+        let FileKey {
+            commit_idx: _,
+            file_idx,
+        } = file_key;
+        match self.state.files.get(file_idx) {
+            Some(file) => Ok(file),
+            None => Err(RecordError::Bug(format!(
+                "Out-of-bounds file key: {file_key:?}"
+            ))),
+        }
+    }
+
+    pub(crate) fn section(&self, section_key: SectionKey) -> Result<&Section<'_>, RecordError> {
+        let SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+        } = section_key;
+        let file = self.file(FileKey {
+            commit_idx,
+            file_idx,
+        })?;
+        match file.sections.get(section_idx) {
+            Some(section) => Ok(section),
+            None => Err(RecordError::Bug(format!(
+                "Out-of-bounds section key: {section_key:?}"
+            ))),
+        }
+    }
+
+    pub(crate) fn visit_file_for_section<T>(
+        &mut self,
+        section_key: SectionKey,
+        f: impl Fn(&mut File) -> T,
+    ) -> Result<T, RecordError> {
+        let SectionKey {
+            commit_idx: _,
+            file_idx,
+            section_idx: _,
+        } = section_key;
+
+        match self.state.files.get_mut(file_idx) {
+            Some(file) => Ok(f(file)),
+            None => Err(RecordError::Bug(format!(
+                "Out-of-bounds file key: {file_idx:?}"
+            ))),
+        }
+    }
+
+    pub(crate) fn visit_file_for_line<T>(
+        &mut self,
+        line_key: LineKey,
+        f: impl Fn(&mut File) -> T,
+    ) -> Result<T, RecordError> {
+        let LineKey {
+            commit_idx: _,
+            file_idx,
+            section_idx: _,
+            line_idx: _,
+        } = line_key;
+
+        match self.state.files.get_mut(file_idx) {
+            Some(file) => Ok(f(file)),
+            None => Err(RecordError::Bug(format!(
+                "Out-of-bounds file key: {file_idx:?}"
+            ))),
+        }
+    }
+
+    pub(crate) fn visit_file<T>(
+        &mut self,
+        file_key: FileKey,
+        f: impl Fn(&mut File) -> T,
+    ) -> Result<T, RecordError> {
+        let FileKey {
+            commit_idx: _,
+            file_idx,
+        } = file_key;
+        match self.state.files.get_mut(file_idx) {
+            Some(file) => Ok(f(file)),
+            None => Err(RecordError::Bug(format!(
+                "Out-of-bounds file key: {file_key:?}"
+            ))),
+        }
+    }
+
+    pub(crate) fn file_tristate(&self, file_key: FileKey) -> Result<Tristate, RecordError> {
+        let file = self.file(file_key)?;
+        Ok(file.commit_tristate(file_key.commit_idx))
+    }
+
+    /// How the file compares to the previous state, if one was given.
+    pub(crate) fn file_interdiff(&self, file_idx: usize) -> Option<&FileInterdiff> {
+        self.options
+            .interdiff
+            .as_ref()
+            .and_then(|interdiff| interdiff.files.get(file_idx))
+    }
+
+    /// Whether the files are listed without their changes. A zoomed file is
+    /// always shown with its changes.
+    pub(crate) fn is_outline(&self) -> bool {
+        self.ui.is_outline && self.ui.zoomed_file.is_none() && !self.ui.is_two_pane
+    }
+
+    /// Whether the files are listed in a pane of their own, with the changes
+    /// of the selected file next to them. A zoomed file uses the whole
+    /// screen instead.
+    pub(crate) fn is_two_pane(&self) -> bool {
+        self.ui.is_two_pane && self.ui.zoomed_file.is_none()
+    }
+
+    pub(crate) fn file_expanded(&self, file_key: FileKey) -> Tristate {
+        if self.is_outline() {
+            return Tristate::False;
+        }
+        // The changes of the selected file are always shown in the right
+        // pane, so moving past a file's header goes through its changes.
+        let is_expanded = self.is_two_pane()
+            || self
+                .ui
+                .expanded_items
+                .contains(&SelectionKey::File(file_key));
+        if !is_expanded {
+            Tristate::False
+        } else {
+            let any_section_unexpanded = self
+                .file(file_key)
+                .unwrap()
+                .sections
+                .iter()
+                .enumerate()
+                .any(|(section_idx, section)| {
+                    match section {
+                        Section::Unchanged { .. }
+                        | Section::FileMode { .. }
+                        | Section::Binary { .. } => {
+                            // Not collapsible/expandable.
+                            false
+                        }
+                        Section::Changed { .. } => {
+                            let section_key = SectionKey {
+                                commit_idx: file_key.commit_idx,
+                                file_idx: file_key.file_idx,
+                                section_idx,
+                            };
+                            !self
+                                .ui
+                                .expanded_items
+                                .contains(&SelectionKey::Section(section_key))
+                        }
+                    }
+                });
+            if any_section_unexpanded {
+                Tristate::Partial
+            } else {
+                Tristate::True
+            }
+        }
+    }
+
+    pub(crate) fn visit_section<T>(
+        &mut self,
+        section_key: SectionKey,
+        f: impl Fn(&mut Section) -> T,
+    ) -> Result<T, RecordError> {
+        let SectionKey {
+            commit_idx: _,
+            file_idx,
+            section_idx,
+        } = section_key;
+        let file = match self.state.files.get_mut(file_idx) {
+            Some(file) => file,
+            None => {
+                return Err(RecordError::Bug(format!(
+                    "Out-of-bounds file for section key: {section_key:?}"
+                )));
+            }
+        };
+        match file.sections.get_mut(section_idx) {
+            Some(section) => Ok(f(section)),
+            None => Err(RecordError::Bug(format!(
+                "Out-of-bounds section key: {section_key:?}"
+            ))),
+        }
+    }
+
+    pub(crate) fn section_tristate(
+        &self,
+        section_key: SectionKey,
+    ) -> Result<Tristate, RecordError> {
+        let section = self.section(section_key)?;
+        Ok(section.commit_tristate(section_key.commit_idx))
+    }
+
+    pub(crate) fn visit_line<T>(
+        &mut self,
+        line_key: LineKey,
+        f: impl FnOnce(&mut SectionChangedLine) -> Option<T>,
+    ) -> Result<Option<T>, RecordError> {
+        let LineKey {
+            commit_idx: _,
+            file_idx,
+            section_idx,
+            line_idx,
+        } = line_key;
+        let section = &mut self.state.files[file_idx].sections[section_idx];
+        match section {
+            Section::Changed { lines, .. } => {
+                let line = &mut lines[line_idx];
+                Ok(f(line))
+            }
+            Section::Unchanged { .. } | Section::FileMode { .. } | Section::Binary { .. } => {
+                // Do nothing.
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Whether `path` should be shown for the given file filter. Filters
+/// containing `*` or `?` are matched as globs against the whole path;
+/// otherwise, the filter is matched case-insensitively as a substring.
+fn path_matches_filter(path: &str, file_filter: &str) -> bool {
+    if file_filter.contains(['*', '?']) {
+        glob_match(file_filter, path)
+    } else {
+        path.to_lowercase().contains(&file_filter.to_lowercase())
+    }
+}
+
+/// A node in the list of files, as returned by [`App::file_list`].
+#[derive(Clone, Debug)]
+pub(crate) enum FileListNode {
+    Directory {
+        dir_idx: usize,
+        #[cfg(feature = "tui")]
+        label: String,
+        depth: usize,
+        is_visible: bool,
+    },
+    File {
+        file_idx: usize,
+        depth: usize,
+        is_visible: bool,
+    },
+}
+
+impl FileListNode {
+    pub(crate) fn depth(&self) -> usize {
+        match self {
+            FileListNode::Directory { depth, .. } | FileListNode::File { depth, .. } => *depth,
+        }
+    }
+
+    pub(crate) fn is_visible(&self) -> bool {
+        match self {
+            FileListNode::Directory { is_visible, .. } | FileListNode::File { is_visible, .. } => {
+                *is_visible
+            }
+        }
+    }
+
+    pub(crate) fn selection_key(&self, commit_idx: usize) -> SelectionKey {
+        match self {
+            FileListNode::Directory { dir_idx, .. } => SelectionKey::Directory(DirectoryKey {
+                commit_idx,
+                dir_idx: *dir_idx,
+            }),
+            FileListNode::File { file_idx, .. } => SelectionKey::File(FileKey {
+                commit_idx,
+                file_idx: *file_idx,
+            }),
+        }
+    }
+
+    /// Whether this node is the given item, or the file containing it.
+    pub(crate) fn contains(&self, selection: SelectionKey) -> bool {
+        match (self, selection) {
+            (
+                FileListNode::Directory { dir_idx, .. },
+                SelectionKey::Directory(DirectoryKey {
+                    commit_idx: _,
+                    dir_idx: selected_dir_idx,
+                }),
+            ) => *dir_idx == selected_dir_idx,
+            (
+                FileListNode::File { file_idx, .. },
+                SelectionKey::File(FileKey {
+                    commit_idx: _,
+                    file_idx: selected_file_idx,
+                })
+                | SelectionKey::Section(SectionKey {
+                    commit_idx: _,
+                    file_idx: selected_file_idx,
+                    section_idx: _,
+                })
+                | SelectionKey::Line(LineKey {
+                    commit_idx: _,
+                    file_idx: selected_file_idx,
+                    section_idx: _,
+                    line_idx: _,
+                }),
+            ) => *file_idx == selected_file_idx,
+            (FileListNode::Directory { .. }, _) | (FileListNode::File { .. }, _) => false,
+        }
+    }
+}
+
+/// The directory containing `path`, which is empty for top-level paths.
+fn parent_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+/// The ancestors of `dir`, starting with `dir` itself, excluding the empty
+/// path.
+fn non_empty_ancestors(dir: &Path) -> impl Iterator<Item = &Path> {
+    dir.ancestors()
+        .take_while(|ancestor| !ancestor.as_os_str().is_empty())
+}
+
+/// The same item as the given one, as shown in the commit at `commit_idx`.
+#[cfg(feature = "tui")]
+fn with_commit_idx(selection_key: SelectionKey, commit_idx: usize) -> SelectionKey {
+    match selection_key {
+        SelectionKey::None => SelectionKey::None,
+        SelectionKey::Directory(directory_key) => SelectionKey::Directory(DirectoryKey {
+            commit_idx,
+            ..directory_key
+        }),
+        SelectionKey::File(file_key) => SelectionKey::File(FileKey {
+            commit_idx,
+            ..file_key
+        }),
+        SelectionKey::Section(section_key) => SelectionKey::Section(SectionKey {
+            commit_idx,
+            ..section_key
+        }),
+        SelectionKey::Line(line_key) => SelectionKey::Line(LineKey {
+            commit_idx,
+            ..line_key
+        }),
+    }
+}
+
+/// The item to bookmark when the given item is selected: a line is bookmarked
+/// as part of its section. Only files and sections can be bookmarked.
+#[cfg(feature = "tui")]
+pub(crate) fn bookmark_key(selection_key: SelectionKey) -> Option<SelectionKey> {
+    match selection_key {
+        SelectionKey::None | SelectionKey::Directory(_) => None,
+        SelectionKey::File(_) | SelectionKey::Section(_) => Some(selection_key),
+        SelectionKey::Line(LineKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+            line_idx: _,
+        }) => Some(SelectionKey::Section(SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+        })),
+    }
+}
+
+/// The file containing the given item, or the item itself if it isn't inside
+/// a file.
+pub(crate) fn file_selection_key(selection_key: SelectionKey) -> SelectionKey {
+    match selection_key {
+        SelectionKey::None | SelectionKey::Directory(_) | SelectionKey::File(_) => selection_key,
+        SelectionKey::Section(SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx: _,
+        })
+        | SelectionKey::Line(LineKey {
+            commit_idx,
+            file_idx,
+            section_idx: _,
+            line_idx: _,
+        }) => SelectionKey::File(FileKey {
+            commit_idx,
+            file_idx,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ChangeType;
+
+    #[test]
+    fn test_set_commit_message_keeps_message_as_entered() {
+        let state = RecordState {
+            commits: vec![Commit {
+                message: Some(String::new()),
+            }],
+            ..Default::default()
+        };
+        let mut app = App::new(state, RecordOptions::default());
+        let message = "Subject\ncontinued on a second line\n\nBody\n\n\n".to_owned();
+
+        assert_eq!(
+            app.set_commit_message(0, message.clone()),
+            Some(String::new())
+        );
+        assert_eq!(
+            app.state.commits[0].message.as_deref(),
+            Some(message.as_str())
+        );
+        assert!(app.dirty.commit_messages);
+        assert_eq!(app.state.commits[0].subject(), Some("Subject"));
+        assert_eq!(
+            app.state.commits[0].body(),
+            Some("continued on a second line\n\nBody")
+        );
+
+        // Setting the same message again isn't a change.
+        assert_eq!(app.set_commit_message(0, message.clone()), None);
+        // Only trailing whitespace differs, which the user may have meant.
+        assert_eq!(
+            app.set_commit_message(0, "Subject\ncontinued on a second line\n\nBody".to_owned()),
+            Some(message)
+        );
+    }
+
+    fn changed_line(change_type: ChangeType, line: &'static str) -> SectionChangedLine<'static> {
+        SectionChangedLine {
+            is_checked: false,
+            commit_idx: 0,
+            change_type,
+            line: line.into(),
+            annotation: None,
+        }
+    }
+
+    /// Two files, each with one section replacing a line.
+    fn two_file_state() -> RecordState<'static> {
+        let file = |path: &'static str| File {
+            old_path: None,
+            path: Path::new(path).into(),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections: vec![Section::Changed {
+                lines: vec![
+                    changed_line(ChangeType::Removed, "old\n"),
+                    changed_line(ChangeType::Added, "new\n"),
+                ],
+                label: None,
+                context: None,
+            }],
+            is_deferred: false,
+            is_lossy: false,
+        };
+        RecordState {
+            is_read_only: false,
+            commits: Vec::new(),
+            files: vec![file("bar"), file("foo")],
+        }
+    }
+
+    #[test]
+    fn test_selector_moves_selection() -> Result<(), RecordError> {
+        let mut selector = Selector::new(two_file_state(), RecordOptions::default());
+        assert_eq!(
+            selector.selected_item(),
+            SelectedItem::File {
+                commit_idx: 0,
+                file_idx: 0
+            }
+        );
+
+        // Collapsed files are moved over without entering them.
+        selector.select_next()?;
+        assert_eq!(
+            selector.selected_item(),
+            SelectedItem::File {
+                commit_idx: 0,
+                file_idx: 1
+            }
+        );
+        selector.select_next()?;
+        assert_eq!(
+            selector.selected_item(),
+            SelectedItem::File {
+                commit_idx: 0,
+                file_idx: 1
+            }
+        );
+
+        selector.select_inner()?;
+        assert_eq!(
+            selector.selected_item(),
+            SelectedItem::Section {
+                commit_idx: 0,
+                file_idx: 1,
+                section_idx: 0
+            }
+        );
+        selector.select_inner()?;
+        assert_eq!(
+            selector.selected_item(),
+            SelectedItem::Line {
+                commit_idx: 0,
+                file_idx: 1,
+                section_idx: 0,
+                line_idx: 0
+            }
+        );
+        selector.select_outer()?;
+        selector.select_outer()?;
+        assert_eq!(
+            selector.selected_item(),
+            SelectedItem::File {
+                commit_idx: 0,
+                file_idx: 1
+            }
+        );
+
+        selector.select_prev_file()?;
+        assert_eq!(
+            selector.selected_item(),
+            SelectedItem::File {
+                commit_idx: 0,
+                file_idx: 0
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_selector_toggles_and_undoes() -> Result<(), RecordError> {
+        let mut selector = Selector::new(two_file_state(), RecordOptions::default());
+        let file_tristates = |selector: &Selector| -> Vec<Tristate> {
+            selector
+                .state()
+                .files
+                .iter()
+                .map(|file| file.commit_tristate(0))
+                .collect()
+        };
+
+        selector.toggle_item()?;
+        assert_eq!(file_tristates(&selector), [Tristate::True, Tristate::False]);
+        assert!(selector.dirty_state().selections);
+
+        selector.toggle_expand_item()?;
+        selector.select_next()?;
+        selector.select_next()?;
+        assert_eq!(
+            selector.selected_item(),
+            SelectedItem::Line {
+                commit_idx: 0,
+                file_idx: 0,
+                section_idx: 0,
+                line_idx: 0
+            }
+        );
+        selector.toggle_item_and_advance()?;
+        assert_eq!(
+            file_tristates(&selector),
+            [Tristate::Partial, Tristate::False]
+        );
+        assert_eq!(
+            selector.selected_item(),
+            SelectedItem::Line {
+                commit_idx: 0,
+                file_idx: 0,
+                section_idx: 0,
+                line_idx: 1
+            }
+        );
+
+        selector.undo()?;
+        assert_eq!(file_tristates(&selector), [Tristate::True, Tristate::False]);
+        selector.undo()?;
+        assert_eq!(
+            file_tristates(&selector),
+            [Tristate::False, Tristate::False]
+        );
+        selector.redo()?;
+        assert_eq!(file_tristates(&selector), [Tristate::True, Tristate::False]);
+
+        selector.toggle_all()?;
+        assert_eq!(file_tristates(&selector), [Tristate::False, Tristate::True]);
+
+        let (state, dirty) = selector.into_state();
+        assert!(dirty.selections);
+        assert!(state.files[1].sections.iter().all(|section| match section {
+            Section::Changed { lines, .. } => lines.iter().all(|line| line.is_checked),
+            Section::Unchanged { .. } | Section::FileMode { .. } | Section::Binary { .. } => {
+                false
+            }
+        }));
+        Ok(())
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_move_item_to_commit_marks_selections_dirty() -> Result<(), RecordError> {
+        let mut state = two_file_state();
+        state.commits = vec![Commit::default(); 3];
+        let mut app = App::new(state, RecordOptions::default());
+        let file_key = FileKey {
+            commit_idx: 0,
+            file_idx: 0,
+        };
+        app.apply_state_update(StateUpdate::MoveItemToCommit(
+            SelectionKey::File(file_key),
+            2,
+        ))?;
+        assert_eq!(app.state.files[0].commit_tristate(2), Tristate::True);
+        assert!(app.dirty.selections);
+        Ok(())
+    }
+
+    #[test]
+    fn test_toggle_item_with_three_commits() -> Result<(), RecordError> {
+        let mut state = two_file_state();
+        state.commits = vec![Commit::default(); 3];
+        let mut app = App::new(state, RecordOptions::default());
+        let file_key = |commit_idx| {
+            SelectionKey::File(FileKey {
+                commit_idx,
+                file_idx: 0,
+            })
+        };
+        let line_key = |commit_idx| {
+            SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx: 0,
+                section_idx: 0,
+                line_idx: 0,
+            })
+        };
+        let commit_tristates = |app: &App| -> Vec<Tristate> {
+            (0..3)
+                .map(|commit_idx| app.state.files[0].commit_tristate(commit_idx))
+                .collect()
+        };
+        // Unchecked changes start out in the second commit.
+        assert_eq!(
+            commit_tristates(&app),
+            [Tristate::False, Tristate::True, Tristate::False]
+        );
+
+        // Toggling an item moves it into the commit being viewed, or out of
+        // it into the next commit.
+        app.toggle_item(file_key(0))?;
+        assert_eq!(
+            commit_tristates(&app),
+            [Tristate::True, Tristate::False, Tristate::False]
+        );
+        app.toggle_item(file_key(0))?;
+        assert_eq!(
+            commit_tristates(&app),
+            [Tristate::False, Tristate::True, Tristate::False]
+        );
+        app.toggle_item(file_key(1))?;
+        assert_eq!(
+            commit_tristates(&app),
+            [Tristate::False, Tristate::False, Tristate::True]
+        );
+
+        // Items are moved out of the last commit into the one before it.
+        app.toggle_item(line_key(2))?;
+        assert_eq!(
+            commit_tristates(&app),
+            [Tristate::False, Tristate::Partial, Tristate::Partial]
+        );
+        assert_eq!(app.item_tristate(line_key(1)), Some(Tristate::True));
+        app.toggle_item(line_key(0))?;
+        assert_eq!(
+            commit_tristates(&app),
+            [Tristate::Partial, Tristate::False, Tristate::Partial]
+        );
+
+        // A file partly in the commit is moved out of it as a whole.
+        app.toggle_item(file_key(2))?;
+        assert_eq!(
+            commit_tristates(&app),
+            [Tristate::False, Tristate::True, Tristate::False]
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_toggle_all_with_three_commits() {
+        let mut state = two_file_state();
+        state.commits = vec![Commit::default(); 3];
+        state.files[0].assign_to_commit(0, 3);
+        state.files[1].assign_to_commit(2, 3);
+        let mut app = App::new(
+            state,
+            RecordOptions {
+                confirm_empty_selection: true,
+                ..Default::default()
+            },
+        );
+        let commit_tristates = |app: &App| -> Vec<Vec<Tristate>> {
+            app.state
+                .files
+                .iter()
+                .map(|file| {
+                    (0..3)
+                        .map(|commit_idx| file.commit_tristate(commit_idx))
+                        .collect()
+                })
+                .collect()
+        };
+        assert!(!app.is_empty_selection());
+
+        // The changes in the focused commit are moved out of it, and the others
+        // are moved into it, rather than from the third commit to the second.
+        app.toggle_all();
+        assert_eq!(
+            commit_tristates(&app),
+            [
+                [Tristate::False, Tristate::True, Tristate::False],
+                [Tristate::True, Tristate::False, Tristate::False],
+            ]
+        );
+        app.toggle_all();
+        assert_eq!(
+            commit_tristates(&app),
+            [
+                [Tristate::True, Tristate::False, Tristate::False],
+                [Tristate::False, Tristate::True, Tristate::False],
+            ]
+        );
+
+        // Only the focused commit is toggled.
+        app.ui.focused_commit_idx = 2;
+        assert!(app.is_empty_selection());
+        app.toggle_all();
+        assert_eq!(
+            commit_tristates(&app),
+            [
+                [Tristate::False, Tristate::False, Tristate::True],
+                [Tristate::False, Tristate::False, Tristate::True],
+            ]
+        );
+        assert!(!app.is_empty_selection());
+
+        // Changes are moved into the focused commit unless they're all in it
+        // already.
+        app.ui.focused_commit_idx = 1;
+        app.state.files[0].assign_to_commit(0, 3);
+        app.toggle_all_uniform();
+        assert_eq!(
+            commit_tristates(&app),
+            [
+                [Tristate::False, Tristate::True, Tristate::False],
+                [Tristate::False, Tristate::True, Tristate::False],
+            ]
+        );
+        app.toggle_all_uniform();
+        assert_eq!(
+            commit_tristates(&app),
+            [
+                [Tristate::False, Tristate::False, Tristate::True],
+                [Tristate::False, Tristate::False, Tristate::True],
+            ]
+        );
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_find_search_match_wraps_around() {
+        let app = App::new(two_file_state(), RecordOptions::default());
+        let line_key = |file_idx| {
+            SelectionKey::Line(LineKey {
+                commit_idx: 0,
+                file_idx,
+                section_idx: 0,
+                line_idx: 1,
+            })
+        };
+        let find = |from, select_previous, include_from| {
+            app.find_search_match("new", from, select_previous, include_from)
+        };
+
+        assert_eq!(find(line_key(0), false, false), Some((line_key(1), 1, 2)));
+        assert_eq!(find(line_key(1), false, false), Some((line_key(0), 0, 2)));
+        assert_eq!(find(line_key(0), true, false), Some((line_key(1), 1, 2)));
+        assert_eq!(find(line_key(1), true, false), Some((line_key(0), 0, 2)));
+        assert_eq!(find(line_key(1), false, true), Some((line_key(1), 1, 2)));
+        assert_eq!(find(line_key(1), true, true), Some((line_key(1), 1, 2)));
+
+        // Without a selection, the search starts from either end.
+        assert_eq!(
+            find(SelectionKey::None, false, false),
+            Some((line_key(0), 0, 2))
+        );
+        assert_eq!(
+            find(SelectionKey::None, true, false),
+            Some((line_key(1), 1, 2))
+        );
+
+        assert_eq!(
+            app.find_search_match("NEW", line_key(0), false, false),
+            None
+        );
+        assert_eq!(app.find_search_match("", line_key(0), false, false), None);
+    }
+}
@@ -1,17 +1,46 @@
-use std::{fmt::Write, io, panic};
+use std::env;
+#[cfg(not(target_family = "wasm"))]
+use std::io::{self, Write as _};
+#[cfg(not(target_family = "wasm"))]
+use std::panic;
+#[cfg(all(feature = "image-preview", not(target_family = "wasm")))]
+use std::path::Path;
+#[cfg(not(target_family = "wasm"))]
+use std::process::{Command, Stdio};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::{Arc, OnceLock};
 
+#[cfg(not(target_family = "wasm"))]
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, is_raw_mode_enabled, EnterAlternateScreen,
     LeaveAlternateScreen,
 };
+#[cfg(all(feature = "image-preview", not(target_family = "wasm")))]
+use crossterm::{
+    cursor::MoveTo,
+    terminal::{Clear, ClearType},
+};
 use ratatui::buffer::Buffer;
+use ratatui::style::Color;
+#[cfg(feature = "tracing")]
+use tracing::warn;
 use unicode_width::UnicodeWidthStr;
 
+#[cfg(all(feature = "image-preview", not(target_family = "wasm")))]
+use crate::ui::input::{FileVersion, ImagePreview};
+#[cfg(not(target_family = "wasm"))]
+use crate::util::base64_encode;
+use crate::util::UsizeExt;
+#[cfg(not(target_family = "wasm"))]
 use crate::RecordError;
 
 /// The terminal backend to use.
 pub enum TerminalKind {
-    /// Use the `CrosstermBackend` backend.
+    /// Use the `CrosstermBackend` backend. This isn't available on
+    /// WebAssembly.
+    #[cfg(not(target_family = "wasm"))]
     Crossterm,
 
     /// Use the `TestingBackend` backend.
@@ -24,31 +53,266 @@ pub enum TerminalKind {
     },
 }
 
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorSupport {
+    /// The 16 named ANSI colors.
+    Ansi16,
+
+    /// The 256 colors of the xterm palette.
+    Ansi256,
+
+    /// Any RGB color.
+    TrueColor,
+}
+
+/// What the terminal can display. On limited terminals, such as the Linux
+/// console or the old Windows console, the UI is drawn with the colors which
+/// they support and with ASCII glyphs, so that it stays readable. This is
+/// reported by [`crate::RecordInput::terminal_capabilities`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TerminalCapabilities {
+    /// How many colors the terminal can show.
+    pub colors: ColorSupport,
+
+    /// Whether the terminal can show symbols other than ASCII, such as
+    /// arrows and box-drawing characters.
+    pub unicode: bool,
+}
+
+impl Default for TerminalCapabilities {
+    /// A terminal which can show anything.
+    fn default() -> Self {
+        Self {
+            colors: ColorSupport::TrueColor,
+            unicode: true,
+        }
+    }
+}
+
+impl TerminalCapabilities {
+    /// Guess what the terminal can display from the environment. Terminals
+    /// which aren't known to be limited are assumed to display anything.
+    pub fn detect() -> Self {
+        let var = |name: &str| env::var(name).unwrap_or_default();
+        let term = var("TERM");
+        let is_limited_console = matches!(
+            term.as_str(),
+            "linux" | "vt100" | "vt102" | "vt220" | "ansi" | "cons25" | "dumb"
+        );
+        // Windows Terminal sets `WT_SESSION`, and terminals ported from Unix
+        // set `TERM`, but the old console sets neither.
+        let is_legacy_windows_console = cfg!(windows)
+            && env::var_os("WT_SESSION").is_none()
+            && term.is_empty()
+            && var("TERM_PROGRAM").is_empty();
+        let colors = if matches!(var("COLORTERM").as_str(), "truecolor" | "24bit") {
+            ColorSupport::TrueColor
+        } else if is_limited_console || is_legacy_windows_console {
+            ColorSupport::Ansi16
+        } else if term.contains("256color") {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::TrueColor
+        };
+        // Like `ncurses`, take an explicitly set locale which isn't UTF-8,
+        // such as `C`, to mean that the terminal only shows ASCII.
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .map(var)
+            .find(|locale| !locale.is_empty());
+        let is_utf8_locale = locale.is_none_or(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        });
+        Self {
+            colors,
+            unicode: is_utf8_locale && !is_limited_console && !is_legacy_windows_console,
+        }
+    }
+}
+
+/// Replace the colors and symbols in the buffer which the terminal can't
+/// display with the closest ones which it can.
+pub fn apply_capabilities(buffer: &mut Buffer, capabilities: TerminalCapabilities) {
+    let TerminalCapabilities { colors, unicode } = capabilities;
+    if capabilities == TerminalCapabilities::default() {
+        return;
+    }
+    for cell in buffer.content.iter_mut() {
+        if !unicode {
+            if let Some(symbol) = ascii_fallback(cell.symbol()) {
+                cell.set_symbol(symbol);
+            }
+        }
+        cell.fg = fallback_color(cell.fg, colors, /*is_background=*/ false);
+        cell.bg = fallback_color(cell.bg, colors, /*is_background=*/ true);
+    }
+}
+
+/// An ASCII symbol which looks like the given one, if it isn't ASCII
+/// already. Symbols without a lookalike, such as text in other scripts,
+/// are replaced with `?`, which takes up a single cell like the original
+/// symbol did, even if it was wide.
+fn ascii_fallback(symbol: &str) -> Option<&'static str> {
+    if symbol.is_ascii() {
+        return None;
+    }
+    let fallback = match symbol {
+        "▶" | "→" => ">",
+        "◀" | "←" => "<",
+        "▼" | "↓" => "v",
+        "▲" | "↑" => "^",
+        "│" | "┃" | "║" | "╵" => "|",
+        "─" | "━" | "═" => "-",
+        "┌" | "┐" | "└" | "┘" | "├" | "┤" | "┬" | "┴" | "┼" | "╭" | "╮" | "╰" | "╯" => {
+            "+"
+        }
+        "•" | "●" => "*",
+        "…" => ".",
+        "\u{22EE}" => ":",
+        "⏎" => "$",
+        _ => "?",
+    };
+    Some(fallback)
+}
+
+/// The 16 ANSI colors, with their usual RGB values in xterm.
+const ANSI_16_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The levels of each component of the colors in the 6x6x6 cube of the
+/// xterm palette, which starts at index 16.
+const ANSI_256_CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The closest color to `color` which the terminal can show.
+fn fallback_color(color: Color, colors: ColorSupport, is_background: bool) -> Color {
+    let rgb = match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(index) if colors == ColorSupport::Ansi16 => {
+            match ANSI_16_COLORS.get(usize::from(index)) {
+                Some((color, _)) => return *color,
+                None => indexed_rgb(index),
+            }
+        }
+        _ => return color,
+    };
+    match colors {
+        ColorSupport::TrueColor => color,
+        ColorSupport::Ansi256 => Color::Indexed(closest_indexed(rgb)),
+        ColorSupport::Ansi16 => {
+            // A background which became black would vanish against the usual
+            // background of the terminal, such as the highlight of the
+            // selected item.
+            let is_black = rgb == (0, 0, 0);
+            ANSI_16_COLORS
+                .iter()
+                .filter(|(color, _)| !is_background || is_black || *color != Color::Black)
+                .min_by_key(|(_, candidate)| distance(rgb, *candidate))
+                .map(|(color, _)| *color)
+                .unwrap_or(color)
+        }
+    }
+}
+
+/// The RGB value of a color of the xterm palette past the 16 ANSI colors.
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index.checked_sub(16) {
+        Some(cube_index @ 0..=215) => {
+            let level = |i: u8| ANSI_256_CUBE_LEVELS[usize::from(i % 6)];
+            (
+                level(cube_index / 36),
+                level(cube_index / 6),
+                level(cube_index),
+            )
+        }
+        Some(gray_index) => {
+            let level = 8 + (gray_index - 216) * 10;
+            (level, level, level)
+        }
+        None => (0, 0, 0),
+    }
+}
+
+/// The index of the color of the xterm palette closest to `rgb`, among the
+/// colors of the cube and the grays, whose values don't depend on the
+/// terminal's theme.
+fn closest_indexed(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255)
+        .min_by_key(|&index| distance(rgb, indexed_rgb(index)))
+        .unwrap_or(16)
+}
+
+/// The squared distance between two colors.
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    [(r1, r2), (g1, g2), (b1, b2)]
+        .into_iter()
+        .map(|(c1, c2)| u32::from(c1.abs_diff(c2)).pow(2))
+        .sum()
+}
+
+/// Check the dimensions of a virtual terminal. Terminals can't be larger than
+/// `u16::MAX` in either dimension, so larger dimensions are clamped, with a
+/// warning.
+pub fn validate_terminal_size(width: usize, height: usize) -> (u16, u16) {
+    match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => {
+            let clamped_width = width.clamp_into_u16();
+            let clamped_height = height.clamp_into_u16();
+            warn!(
+                width,
+                height,
+                clamped_width,
+                clamped_height,
+                "terminal dimensions are too large; clamping them"
+            );
+            (clamped_width, clamped_height)
+        }
+    }
+}
+
 /// Copied from internal implementation of `tui`.
+///
+/// The cells covered by a multi-width symbol are skipped, since they are
+/// never displayed. They may still hold the contents of an earlier frame,
+/// because `ratatui` doesn't update cells hidden by a wide symbol.
 pub fn buffer_view(buffer: &Buffer) -> String {
     let mut view =
         String::with_capacity(buffer.content.len() + usize::from(buffer.area.height) * 3);
-    for cells in buffer.content.chunks(buffer.area.width.into()) {
-        let mut overwritten = vec![];
+    // A zero-width buffer has no cells, so any nonzero chunk size will do.
+    for cells in buffer.content.chunks(usize::from(buffer.area.width).max(1)) {
         let mut skip: usize = 0;
         view.push('"');
-        for (x, c) in cells.iter().enumerate() {
+        for c in cells {
             if skip == 0 {
                 view.push_str(c.symbol());
-            } else {
-                overwritten.push((x, c.symbol()))
             }
             skip = std::cmp::max(skip, c.symbol().width()).saturating_sub(1);
         }
         view.push('"');
-        if !overwritten.is_empty() {
-            write!(&mut view, " Hidden by multi-width symbols: {overwritten:?}").unwrap();
-        }
         view.push('\n');
     }
     view
 }
 
+#[cfg(not(target_family = "wasm"))]
 pub fn install_panic_hook() {
     // HACK: installing a global hook here. This could be installed multiple
     // times, and there's no way to uninstall it once we return.
@@ -67,15 +331,98 @@ pub fn install_panic_hook() {
     }));
 }
 
+/// The flags set by the handlers of the termination signals.
+#[cfg(not(target_family = "wasm"))]
+struct TerminationSignalFlags {
+    /// Set when the process is asked to terminate.
+    is_requested: Arc<AtomicBool>,
+
+    /// Set to run the default action of the signals instead, which
+    /// terminates the process.
+    use_default_action: Arc<AtomicBool>,
+}
+
+/// The handlers can't be uninstalled without leaving the signals ignored, so
+/// they're installed once and then enabled or disabled with the flags.
+#[cfg(not(target_family = "wasm"))]
+static TERMINATION_SIGNAL_FLAGS: OnceLock<TerminationSignalFlags> = OnceLock::new();
+
+/// While this guard is alive, the signals which ask the process to terminate
+/// (`SIGTERM`, and `SIGHUP` when the terminal is closed) don't kill the
+/// process. Instead, [`is_termination_requested`] starts returning `true`, so
+/// that the UI can restore the terminal before returning
+/// [`RecordError::Cancelled`]. Once dropped, the signals terminate the process
+/// again.
+///
+/// Signals are only handled on Unix.
+#[cfg(not(target_family = "wasm"))]
+pub struct TerminationSignalGuard(());
+
+#[cfg(not(target_family = "wasm"))]
+impl TerminationSignalGuard {
+    /// Start handling the termination signals.
+    pub fn new() -> Result<Self, RecordError> {
+        let mut install_result = Ok(());
+        let flags = TERMINATION_SIGNAL_FLAGS.get_or_init(|| {
+            let flags = TerminationSignalFlags {
+                is_requested: Default::default(),
+                use_default_action: Default::default(),
+            };
+            #[cfg(unix)]
+            {
+                install_result = [signal_hook::consts::SIGTERM, signal_hook::consts::SIGHUP]
+                    .into_iter()
+                    .try_for_each(|signal| {
+                        signal_hook::flag::register_conditional_default(
+                            signal,
+                            Arc::clone(&flags.use_default_action),
+                        )?;
+                        signal_hook::flag::register(signal, Arc::clone(&flags.is_requested))?;
+                        Ok(())
+                    });
+            }
+            flags
+        });
+        install_result.map_err(RecordError::SetUpTerminal)?;
+        flags.is_requested.store(false, Ordering::SeqCst);
+        flags.use_default_action.store(false, Ordering::SeqCst);
+        Ok(Self(()))
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl Drop for TerminationSignalGuard {
+    fn drop(&mut self) {
+        if let Some(flags) = TERMINATION_SIGNAL_FLAGS.get() {
+            flags.use_default_action.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Whether the process was asked to terminate while a
+/// [`TerminationSignalGuard`] was alive.
+#[cfg(not(target_family = "wasm"))]
+pub fn is_termination_requested() -> bool {
+    TERMINATION_SIGNAL_FLAGS
+        .get()
+        .is_some_and(|flags| flags.is_requested.load(Ordering::SeqCst))
+}
+
+#[cfg(not(target_family = "wasm"))]
 pub fn set_up_crossterm() -> Result<(), RecordError> {
     if !is_raw_mode_enabled().map_err(RecordError::SetUpTerminal)? {
         crossterm::execute!(io::stdout(), EnterAlternateScreen)
             .map_err(RecordError::SetUpTerminal)?;
-        enable_raw_mode().map_err(RecordError::SetUpTerminal)?;
+        if let Err(err) = enable_raw_mode() {
+            // Don't leave the terminal half set up.
+            let _ = crossterm::execute!(io::stdout(), LeaveAlternateScreen);
+            return Err(RecordError::SetUpTerminal(err));
+        }
     }
     Ok(())
 }
 
+#[cfg(not(target_family = "wasm"))]
 pub fn clean_up_crossterm() -> Result<(), RecordError> {
     if is_raw_mode_enabled().map_err(RecordError::CleanUpTerminal)? {
         disable_raw_mode().map_err(RecordError::CleanUpTerminal)?;
@@ -84,3 +431,224 @@ pub fn clean_up_crossterm() -> Result<(), RecordError> {
     }
     Ok(())
 }
+
+/// Sets up the terminal for the UI, and restores it when dropped, so that the
+/// terminal is left usable on every path out of the UI, including early
+/// returns because of errors.
+#[cfg(not(target_family = "wasm"))]
+pub struct CrosstermGuard(());
+
+#[cfg(not(target_family = "wasm"))]
+impl CrosstermGuard {
+    /// Set up the terminal.
+    pub fn new() -> Result<Self, RecordError> {
+        set_up_crossterm()?;
+        Ok(Self(()))
+    }
+
+    /// Restore the terminal, reporting any error. Errors are ignored when
+    /// the guard is dropped instead.
+    pub fn clean_up(self) -> Result<(), RecordError> {
+        clean_up_crossterm()
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl Drop for CrosstermGuard {
+    fn drop(&mut self) {
+        // Cleaning up again after `clean_up` does nothing.
+        let _ = clean_up_crossterm();
+    }
+}
+
+/// Copy `text` to the system clipboard.
+///
+/// The text is sent to the terminal with the OSC 52 escape sequence, which
+/// also works over SSH. Since not all terminals support it, the text is also
+/// passed to the first clipboard command which is available locally.
+#[cfg(not(target_family = "wasm"))]
+pub fn copy_to_clipboard(text: &str) -> Result<(), RecordError> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))
+        .and_then(|()| stdout.flush())
+        .map_err(|source| RecordError::CopyToClipboard { source })?;
+
+    const CLIPBOARD_COMMANDS: &[(&str, &[&str])] = &[
+        ("pbcopy", &[]),
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+        ("clip.exe", &[]),
+    ];
+    for (program, args) in CLIPBOARD_COMMANDS.iter().copied() {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        let write_result = match child.stdin.take() {
+            Some(mut stdin) => stdin.write_all(text.as_bytes()),
+            None => Ok(()),
+        };
+        match (write_result, child.wait()) {
+            (Ok(()), Ok(status)) if status.success() => break,
+            _ => continue,
+        }
+    }
+    Ok(())
+}
+
+/// A protocol with which terminals can draw images.
+#[cfg(all(feature = "image-preview", not(target_family = "wasm")))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GraphicsProtocol {
+    /// The kitty graphics protocol, which is also supported by Ghostty.
+    Kitty,
+
+    /// The inline images protocol of iTerm2, which is also supported by
+    /// WezTerm.
+    Iterm2,
+
+    /// Sixel graphics, which are drawn with the `img2sixel` program.
+    Sixel,
+}
+
+/// The environment variable which overrides the detected graphics protocol,
+/// for terminals which aren't recognized. It can be set to `kitty`, `iterm2`,
+/// `sixel` or `none`.
+#[cfg(all(feature = "image-preview", not(target_family = "wasm")))]
+pub const GRAPHICS_PROTOCOL_ENV_VAR: &str = "TUG_RECORD_GRAPHICS";
+
+/// Guess which graphics protocol the terminal supports from the environment.
+/// Asking the terminal instead would mean reading its answer from the input,
+/// along with the user's key presses.
+#[cfg(all(feature = "image-preview", not(target_family = "wasm")))]
+pub fn graphics_protocol() -> Option<GraphicsProtocol> {
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    match var(GRAPHICS_PROTOCOL_ENV_VAR).as_str() {
+        "kitty" => return Some(GraphicsProtocol::Kitty),
+        "iterm2" => return Some(GraphicsProtocol::Iterm2),
+        "sixel" => return Some(GraphicsProtocol::Sixel),
+        "none" => return None,
+        _ => {}
+    }
+    let term = var("TERM");
+    if matches!(term.as_str(), "xterm-kitty" | "xterm-ghostty")
+        || env::var_os("KITTY_WINDOW_ID").is_some()
+    {
+        Some(GraphicsProtocol::Kitty)
+    } else if matches!(var("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm")
+        || var("LC_TERMINAL") == "iTerm2"
+    {
+        Some(GraphicsProtocol::Iterm2)
+    } else if term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel") {
+        Some(GraphicsProtocol::Sixel)
+    } else {
+        None
+    }
+}
+
+/// Show the images one above the other, and wait until the user presses
+/// Enter. This draws on the normal screen, so the terminal must not be set up
+/// for the UI.
+#[cfg(all(feature = "image-preview", not(target_family = "wasm")))]
+pub fn show_images(
+    protocol: GraphicsProtocol,
+    path: &Path,
+    images: &[ImagePreview],
+) -> io::Result<()> {
+    let (_, term_height) = crossterm::terminal::size()?;
+    // Leave a row for the label of each image, a row between the images, and
+    // rows for the path and the prompt.
+    let num_images = images.len().max(1);
+    let image_height =
+        (usize::from(term_height).saturating_sub(2 * num_images + 2) / num_images).max(1);
+
+    let mut stdout = io::stdout();
+    crossterm::execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+    writeln!(stdout, "{}", path.display())?;
+    for ImagePreview { version, contents } in images {
+        let label = match version {
+            FileVersion::Old => "Old version:",
+            FileVersion::New => "New version:",
+        };
+        writeln!(stdout, "{label}")?;
+        write_image(&mut stdout, protocol, contents, image_height)?;
+        writeln!(stdout)?;
+    }
+    write!(stdout, "Press Enter to go back to the changes")?;
+    stdout.flush()?;
+    io::stdin().read_line(&mut String::new())?;
+    if protocol == GraphicsProtocol::Kitty {
+        // Free the images, which the terminal keeps around otherwise.
+        write!(stdout, "\x1b_Ga=d\x1b\\")?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+/// Draw the image at the cursor, scaled to `num_rows` rows.
+#[cfg(all(feature = "image-preview", not(target_family = "wasm")))]
+fn write_image(
+    out: &mut io::Stdout,
+    protocol: GraphicsProtocol,
+    contents: &[u8],
+    num_rows: usize,
+) -> io::Result<()> {
+    match protocol {
+        GraphicsProtocol::Kitty => {
+            // Other formats would have to be decoded into pixels first.
+            if !contents.starts_with(b"\x89PNG\r\n\x1a\n") {
+                return write!(out, "(only PNG images can be shown in this terminal)");
+            }
+            // The data has to be sent in chunks of at most 4096 bytes.
+            let data = base64_encode(contents);
+            let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+            for (chunk_idx, chunk) in chunks.iter().enumerate() {
+                let has_more = u8::from(chunk_idx + 1 < chunks.len());
+                if chunk_idx == 0 {
+                    write!(out, "\x1b_Ga=T,f=100,r={num_rows},m={has_more};")?;
+                } else {
+                    write!(out, "\x1b_Gm={has_more};")?;
+                }
+                out.write_all(chunk)?;
+                write!(out, "\x1b\\")?;
+            }
+            Ok(())
+        }
+        GraphicsProtocol::Iterm2 => write!(
+            out,
+            "\x1b]1337;File=inline=1;size={};height={num_rows};preserveAspectRatio=1:{}\x07",
+            contents.len(),
+            base64_encode(contents)
+        ),
+        GraphicsProtocol::Sixel => {
+            // Not all terminals report their size in pixels.
+            let window_size = crossterm::terminal::window_size()?;
+            let row_height = match window_size.height.checked_div(window_size.rows) {
+                Some(row_height) if row_height > 0 => usize::from(row_height),
+                Some(_) | None => 16,
+            };
+            out.flush()?;
+            let mut child = Command::new("img2sixel")
+                .arg(format!("--height={}", num_rows * row_height))
+                .stdin(Stdio::piped())
+                .spawn()?;
+            // Close the input once it's written, so that the image is drawn.
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(contents)?;
+            }
+            let status = child.wait()?;
+            if status.success() {
+                Ok(())
+            } else {
+                Err(io::Error::other(format!("img2sixel exited with {status}")))
+            }
+        }
+    }
+}
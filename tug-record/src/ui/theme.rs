@@ -0,0 +1,129 @@
+//! The colors of the interface.
+
+use ratatui::style::{Color, Modifier, Style};
+
+/// The styles used to draw each part of the interface. This is set with
+/// [`crate::RecordOptions::theme`], so that embedding tools can match the
+/// colors of their own output or of the user's terminal.
+///
+/// The default theme is meant for terminals with a dark background. Use
+/// [`Theme::light`] for terminals with a light background. Modifiers which
+/// carry meaning, like the bold text of badges, are added on top of these
+/// styles, so a theme usually only needs to set colors.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Theme {
+    /// The path of a file.
+    pub file_header: Style,
+    /// The path of a directory, in the file list or when grouping files by
+    /// directory.
+    pub directory_header: Style,
+    /// The header of a section of changed lines.
+    pub section_header: Style,
+    /// The header of a file mode or binary section, and metadata which is
+    /// the same in both versions of a file.
+    pub metadata: Style,
+    /// Added lines, and the counts of added lines.
+    pub added: Style,
+    /// Removed lines, and the counts of removed lines.
+    pub removed: Style,
+    /// Unchanged lines shown as context around the changes.
+    pub unchanged: Style,
+    /// The words which changed between a removed line and the added line
+    /// which replaced it. This is applied on top of [`Theme::added`] or
+    /// [`Theme::removed`].
+    pub changed_word: Style,
+    /// The background of the selected item.
+    pub selected: Style,
+    /// A toggle box whose changes aren't selected.
+    pub toggle_box_unchecked: Style,
+    /// A toggle box whose changes are all selected.
+    pub toggle_box_checked: Style,
+    /// A toggle box whose changes are only partly selected.
+    pub toggle_box_partial: Style,
+    /// The box to expand or collapse a file or directory.
+    pub expand_box: Style,
+    /// Labels of prompts and panels, and badges like "(bookmarked)".
+    pub accent: Style,
+    /// Things which need the user's attention, like deferred files or
+    /// sections which changed since a previous review.
+    pub warning: Style,
+    /// Error messages, and commit message subjects which are far too long.
+    pub error: Style,
+    /// Messages reporting that an action succeeded.
+    pub success: Style,
+    /// The separator between the file list and the changes.
+    pub separator: Style,
+    /// The background of the bars at the bottom of the screen, like prompts
+    /// and status messages.
+    pub bar: Style,
+    /// The contents and borders of dialogs.
+    pub dialog: Style,
+    /// Keywords, with the `syntax-highlighting` feature.
+    pub syntax_keyword: Style,
+    /// String literals, with the `syntax-highlighting` feature.
+    pub syntax_string: Style,
+    /// Number literals, with the `syntax-highlighting` feature.
+    pub syntax_number: Style,
+    /// Comments, with the `syntax-highlighting` feature.
+    pub syntax_comment: Style,
+}
+
+impl Theme {
+    /// The default theme, for terminals with a dark background.
+    pub const fn dark() -> Self {
+        Self {
+            file_header: Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            directory_header: Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD),
+            section_header: Style::new().fg(Color::LightMagenta),
+            metadata: Style::new().fg(Color::Magenta),
+            added: Style::new().fg(Color::Green),
+            removed: Style::new().fg(Color::Red),
+            unchanged: Style::new().fg(Color::Gray).add_modifier(Modifier::DIM),
+            // Reversing the colors keeps the color of the line, so the change
+            // is still recognizable as an addition or a removal.
+            changed_word: Style::new().add_modifier(Modifier::REVERSED),
+            selected: Style::new().bg(Color::Rgb(38, 38, 38)),
+            toggle_box_unchecked: Style::new().fg(Color::DarkGray),
+            toggle_box_checked: Style::new().fg(Color::Blue),
+            toggle_box_partial: Style::new().fg(Color::Yellow),
+            expand_box: Style::new().fg(Color::Magenta),
+            accent: Style::new().fg(Color::Cyan),
+            warning: Style::new().fg(Color::Yellow),
+            error: Style::new().fg(Color::Red),
+            success: Style::new().fg(Color::Green),
+            separator: Style::new().fg(Color::DarkGray),
+            bar: Style::new().bg(Color::DarkGray),
+            dialog: Style::new(),
+            syntax_keyword: Style::new().fg(Color::Magenta),
+            syntax_string: Style::new().fg(Color::Yellow),
+            syntax_number: Style::new().fg(Color::Cyan),
+            syntax_comment: Style::new()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC),
+        }
+    }
+
+    /// A theme for terminals with a light background. The selected item and
+    /// the bars get a light background, and yellow, which is hard to read on
+    /// white, is replaced with a darker shade.
+    pub const fn light() -> Self {
+        const DARK_YELLOW: Color = Color::Rgb(175, 135, 0);
+        Self {
+            unchanged: Style::new().fg(Color::DarkGray),
+            selected: Style::new().bg(Color::Rgb(225, 225, 225)),
+            toggle_box_unchecked: Style::new().fg(Color::Gray),
+            toggle_box_partial: Style::new().fg(DARK_YELLOW),
+            warning: Style::new().fg(DARK_YELLOW),
+            bar: Style::new().bg(Color::Gray),
+            syntax_string: Style::new().fg(DARK_YELLOW),
+            syntax_comment: Style::new().fg(Color::Gray).add_modifier(Modifier::ITALIC),
+            ..Self::dark()
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
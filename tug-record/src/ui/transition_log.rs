@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::types::RecordError;
+use crate::ui::event::Event;
+use crate::ui::state::SelectionKey;
+
+/// A transition of the UI: an event, how the app decided to update its state
+/// in response, and where the selection ended up afterwards. This is written
+/// as one line of the file named by
+/// [`crate::consts::ENV_VAR_TRACE_TRANSITIONS`].
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub(crate) struct Transition {
+    pub step: usize,
+    pub event: Event,
+    /// Whether the event came from the input, rather than being caused by an
+    /// earlier event. Only these events are replayed.
+    pub is_from_input: bool,
+    /// The `Debug` representation of the `StateUpdate`, which is only meant
+    /// to be read by people.
+    pub state_update: String,
+    pub selection_key: SelectionKey,
+    pub scroll_offset_y: isize,
+    pub term_width: usize,
+    pub term_height: usize,
+}
+
+/// Appends each transition of the UI to a file as a line of JSON.
+pub(crate) struct TransitionLog {
+    path: PathBuf,
+    file: File,
+    next_step: usize,
+}
+
+impl TransitionLog {
+    /// Create the log, replacing the file at `path` if it exists.
+    pub fn create(path: &Path) -> Result<Self, RecordError> {
+        let file = File::create(path).map_err(|source| RecordError::WriteFile {
+            path: path.to_owned(),
+            source,
+        })?;
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+            next_step: 0,
+        })
+    }
+
+    /// The number which the next transition will be logged with.
+    pub fn next_step(&self) -> usize {
+        self.next_step
+    }
+
+    /// Append the transition. Each line is written at once, so that the log
+    /// is complete up to the last transition if the UI crashes.
+    pub fn write(&mut self, transition: &Transition) -> Result<(), RecordError> {
+        let mut line = serde_json::to_string(transition).map_err(RecordError::SerializeJson)?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .map_err(|source| RecordError::WriteFile {
+                path: self.path.clone(),
+                source,
+            })?;
+        self.next_step += 1;
+        Ok(())
+    }
+}
+
+/// The transitions of a session, as logged to the file named by
+/// [`crate::consts::ENV_VAR_TRACE_TRANSITIONS`], to be replayed with
+/// [`crate::TestingRecorder::replay_to`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TransitionTrace {
+    transitions: Vec<Transition>,
+}
+
+impl TransitionTrace {
+    /// Read the trace from a file written by a session.
+    pub fn load(path: &Path) -> Result<Self, RecordError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| RecordError::ReadFile {
+            path: path.to_owned(),
+            source,
+        })?;
+        let transitions = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line).map_err(|source| RecordError::DeserializeJson {
+                    path: path.to_owned(),
+                    source,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { transitions })
+    }
+
+    /// The number of logged transitions.
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+
+    /// Whether no transitions were logged.
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+
+    /// The width and height of the terminal as of the first transition, so
+    /// that the replay can be laid out the same way.
+    pub fn terminal_size(&self) -> Option<(usize, usize)> {
+        self.transitions
+            .first()
+            .map(|transition| (transition.term_width, transition.term_height))
+    }
+
+    /// The events from the input up to and including the given step.
+    pub(crate) fn input_events(&self, step: usize) -> impl Iterator<Item = &Event> {
+        self.transitions
+            .iter()
+            .filter(move |transition| transition.is_from_input && transition.step <= step)
+            .map(|transition| &transition.event)
+    }
+}
@@ -0,0 +1,2446 @@
+//! The terminal side of the [`App`]: building the components which draw its
+//! state, and turning key presses and mouse clicks into [`StateUpdate`]s.
+
+use std::path::Path;
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use crate::render::{DrawnRect, DrawnRects, Rect};
+use crate::types::{ChangeType, RecordError, RecordState, Tristate};
+use crate::ui::components::action_history_panel::ActionHistoryPanel;
+use crate::ui::components::app::{AppDebugInfo, AppView};
+use crate::ui::components::commit_message_view::{CommitMessageView, CommitViewMode};
+use crate::ui::components::commit_view::{CommitView, FileListItem};
+use crate::ui::components::directory::DirectoryView;
+use crate::ui::components::empty_selection_dialog::EmptySelectionDialog;
+use crate::ui::components::file::FileView;
+use crate::ui::components::file_contents_dialog::FileContentsDialog;
+use crate::ui::components::file_list_pane::{FileListPane, FileListPaneItem};
+use crate::ui::components::help_dialog::HelpDialog;
+use crate::ui::components::line::{merge_highlights, LineHighlights};
+use crate::ui::components::line_diff_dialog::LineDiffDialog;
+use crate::ui::components::message_editor_dialog::{MessageEditorAction, MessageEditorDialog};
+use crate::ui::components::message_history_dialog::{MessageHistoryDialog, MessageHistoryPicker};
+use crate::ui::components::preview_dialog::{PreviewDialog, PreviewLineKind};
+use crate::ui::components::quit_dialog::{QuitDialog, QuitDialogButton};
+use crate::ui::components::recovery_dialog::RecoveryDialog;
+use crate::ui::components::stats_bar::StatsBar;
+use crate::ui::components::text_prompt::{TextPrompt, TextPromptAction};
+use crate::ui::components::toggle_all_dialog::ToggleAllDialog;
+use crate::ui::components::view_indicators::{InterdiffIndicator, ViewIndicators};
+use crate::ui::components::which_key_popup::WhichKeyPopup;
+use crate::ui::components::widgets::{TristateBox, TristateIconStyle};
+use crate::ui::components::{section, ComponentId};
+#[cfg(feature = "syntax-highlighting")]
+use crate::ui::highlight;
+use crate::ui::history::Command;
+use crate::ui::input::FileVersion;
+use crate::ui::key_event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crate::ui::keymap::{Key, KeymapMatch};
+use crate::ui::macros::{MacroPrompt, Macros};
+use crate::ui::search::SearchPrompt;
+use crate::ui::state::{
+    bookmark_key, file_selection_key, App, DirectoryKey, FileKey, FileListNode, FileStats, LineKey,
+    SectionKey, SelectionKey, StateUpdate, StatusMessage,
+};
+use crate::ui::{event, word_diff};
+use crate::util::UsizeExt;
+use crate::{File, FileMode, Section, SectionChangedLine};
+
+impl<'state> App<'state> {
+    /// Generates the `AppView` used for rendering.
+    pub(crate) fn view(&'state self, debug_info: Option<AppDebugInfo>) -> AppView<'state> {
+        let RecordState {
+            is_read_only,
+            commits,
+            files,
+        } = &self.state;
+        let commit_views = match self.ui.commit_view_mode {
+            CommitViewMode::Inline => {
+                vec![CommitView {
+                    debug_info: None,
+                    commit_message_view: self.ui.zoomed_file.is_none().then_some(
+                        CommitMessageView {
+                            commit_idx: self.ui.focused_commit_idx,
+                            commit: &commits[self.ui.focused_commit_idx],
+                            is_expanded: self.ui.is_commit_message_expanded,
+                        },
+                    ),
+                    file_list_items: self.make_file_list_items(
+                        self.ui.focused_commit_idx,
+                        files,
+                        &debug_info,
+                        *is_read_only,
+                    ),
+                    empty_message: self.empty_message(),
+                }]
+            }
+
+            CommitViewMode::Adjacent => commits
+                .iter()
+                .enumerate()
+                .map(|(commit_idx, commit)| CommitView {
+                    debug_info: None,
+                    commit_message_view: self.ui.zoomed_file.is_none().then_some(
+                        CommitMessageView {
+                            commit_idx,
+                            commit,
+                            is_expanded: self.ui.is_commit_message_expanded,
+                        },
+                    ),
+                    file_list_items: self.make_file_list_items(
+                        commit_idx,
+                        files,
+                        &debug_info,
+                        *is_read_only,
+                    ),
+                    empty_message: self.empty_message(),
+                })
+                .collect(),
+        };
+        AppView {
+            debug_info: None,
+            theme: self.options.theme,
+            commit_view_mode: self.ui.commit_view_mode,
+            commit_views,
+            focused_commit_idx: match self.ui.commit_view_mode {
+                CommitViewMode::Inline => 0,
+                CommitViewMode::Adjacent => self.ui.focused_commit_idx,
+            },
+            stats_bar: if self.ui.is_stats_bar_visible {
+                Some(self.stats_bar())
+            } else {
+                None
+            },
+            view_indicators: {
+                let view_indicators = ViewIndicators {
+                    is_read_only: *is_read_only,
+                    file_sort_order: self.ui.file_sort_order,
+                    file_filter: self.file_filter().map(str::to_owned),
+                    hide_unselected_files: self.ui.hide_unselected_files,
+                    hide_selected_files: self.ui.hide_selected_files,
+                    interdiff: self.options.interdiff.as_ref().map(|interdiff| {
+                        InterdiffIndicator {
+                            num_changed_files: interdiff
+                                .files
+                                .iter()
+                                .filter(|file_interdiff| file_interdiff.needs_attention())
+                                .count(),
+                            num_disappeared_files: interdiff.disappeared_files.len(),
+                            hide_unchanged_files: self.ui.hide_unchanged_files,
+                        }
+                    }),
+                    is_outline: self.is_outline(),
+                    zoomed_file: self.ui.zoomed_file.map(|zoomed_file| {
+                        files[zoomed_file.file_idx]
+                            .path
+                            .to_string_lossy()
+                            .into_owned()
+                    }),
+                    num_visible_files: self.visible_file_indices().len(),
+                    num_files: files.len(),
+                    recording_macro: self.ui.macros.recording_register(),
+                };
+                if view_indicators.is_empty() {
+                    None
+                } else {
+                    Some(view_indicators)
+                }
+            },
+            text_prompt: self.ui.file_filter_prompt.clone().or_else(|| {
+                self.ui
+                    .search_prompt
+                    .as_ref()
+                    .map(|search_prompt| search_prompt.prompt.clone())
+            }),
+            status_message: self.ui.status_message.clone(),
+            which_key_popup: self.which_key_popup(),
+            action_history_panel: self.action_history_panel(),
+            file_list_pane: self.file_list_pane(),
+            help_dialog: self.ui.help_dialog.clone(),
+            message_history_dialog: self.ui.message_history_picker.map(|picker| {
+                MessageHistoryDialog {
+                    picker,
+                    messages: &self.options.message_history,
+                }
+            }),
+            message_editor_dialog: self.ui.message_editor_dialog.clone(),
+            message_diff_dialog: self.ui.message_diff_dialog.clone(),
+            line_diff_dialog: self.ui.line_diff_dialog.clone(),
+            preview_dialog: self.ui.preview_dialog.clone(),
+            file_contents_dialog: self.ui.file_contents_dialog.clone(),
+            recovery_dialog: self.ui.recovery.as_ref().map(|recovery| {
+                let mut num_checked = 0;
+                for section in recovery.state.files.iter().flat_map(|file| &file.sections) {
+                    num_checked += match section {
+                        Section::Unchanged { .. } => 0,
+                        Section::Changed { lines, .. } => {
+                            lines.iter().filter(|line| line.is_checked).count()
+                        }
+                        Section::FileMode { is_checked, .. }
+                        | Section::Binary { is_checked, .. } => usize::from(*is_checked),
+                    };
+                }
+                RecoveryDialog {
+                    num_checked,
+                    num_deferred_files: recovery
+                        .state
+                        .files
+                        .iter()
+                        .filter(|file| file.is_deferred)
+                        .count(),
+                }
+            }),
+            error_dialog: self.ui.error_dialog.clone(),
+            toggle_all_dialog: self.ui.toggle_all_dialog,
+            empty_selection_dialog: self.ui.empty_selection_dialog,
+            quit_dialog: self.ui.quit_dialog,
+        }
+    }
+
+    /// The index of the line in the given version of the selected file at
+    /// which the selected section or line starts, if a section or line is
+    /// selected.
+    fn selected_line_idx(&self, version: FileVersion) -> Option<usize> {
+        let (file_idx, section_idx, line_idx) = match self.ui.selection_key {
+            SelectionKey::None | SelectionKey::Directory(_) | SelectionKey::File(_) => return None,
+            SelectionKey::Section(SectionKey {
+                commit_idx: _,
+                file_idx,
+                section_idx,
+            }) => (file_idx, section_idx, 0),
+            SelectionKey::Line(LineKey {
+                commit_idx: _,
+                file_idx,
+                section_idx,
+                line_idx,
+            }) => (file_idx, section_idx, line_idx),
+        };
+        let is_in_version = |change_type: ChangeType| match (version, change_type) {
+            (FileVersion::Old, ChangeType::Removed) | (FileVersion::New, ChangeType::Added) => true,
+            (FileVersion::Old, ChangeType::Added) | (FileVersion::New, ChangeType::Removed) => {
+                false
+            }
+        };
+        let sections = &self.state.files[file_idx].sections;
+        let num_lines_before: usize = sections[..section_idx]
+            .iter()
+            .map(|section| match section {
+                Section::Unchanged { lines } => lines.len(),
+                Section::Changed { lines, .. } => lines
+                    .iter()
+                    .filter(|line| is_in_version(line.change_type))
+                    .count(),
+                Section::FileMode { .. } | Section::Binary { .. } => 0,
+            })
+            .sum();
+        let num_lines_in_section = match &sections[section_idx] {
+            Section::Changed { lines, .. } => lines[..line_idx]
+                .iter()
+                .filter(|line| is_in_version(line.change_type))
+                .count(),
+            Section::Unchanged { .. } | Section::FileMode { .. } | Section::Binary { .. } => 0,
+        };
+        Some(num_lines_before + num_lines_in_section)
+    }
+
+    /// What to show in place of the files when none are shown.
+    fn empty_message(&self) -> &'static str {
+        if self.is_two_pane() && !self.visible_file_indices().is_empty() {
+            "Select a file to view its changes."
+        } else {
+            "There are no changes to view."
+        }
+    }
+
+    /// The list of files on the left of the two-pane layout, if it is used.
+    fn file_list_pane(&self) -> Option<FileListPane> {
+        if !self.is_two_pane() {
+            return None;
+        }
+        let commit_idx = self.ui.focused_commit_idx;
+        let file_list: Vec<FileListNode> = self
+            .file_list(commit_idx)
+            .into_iter()
+            .filter(|node| node.is_visible())
+            .collect();
+        let selected_idx = file_list
+            .iter()
+            .position(|node| node.contains(self.ui.selection_key));
+        let items = file_list
+            .into_iter()
+            .map(|node| match node {
+                FileListNode::Directory {
+                    dir_idx,
+                    label,
+                    depth,
+                    is_visible: _,
+                } => {
+                    let directory_key = DirectoryKey {
+                        commit_idx,
+                        dir_idx,
+                    };
+                    let selection_key = SelectionKey::Directory(directory_key);
+                    FileListPaneItem {
+                        selection_key,
+                        label,
+                        depth,
+                        is_expanded: Some(self.ui.expanded_items.contains(&selection_key)),
+                        tristate: self.directory_tristate(directory_key),
+                        stats: self.directory_stats(directory_key),
+                        is_deferred: false,
+                        is_bookmarked: false,
+                    }
+                }
+                FileListNode::File {
+                    file_idx,
+                    depth,
+                    is_visible: _,
+                } => {
+                    let file = &self.state.files[file_idx];
+                    let selection_key = SelectionKey::File(FileKey {
+                        commit_idx,
+                        file_idx,
+                    });
+                    FileListPaneItem {
+                        selection_key,
+                        label: match file.path.file_name() {
+                            Some(file_name) if self.ui.is_file_tree => {
+                                file_name.to_string_lossy().into_owned()
+                            }
+                            Some(_) | None => file.path.to_string_lossy().into_owned(),
+                        },
+                        depth,
+                        is_expanded: None,
+                        tristate: file.commit_tristate(commit_idx),
+                        stats: self.file_stats[file_idx],
+                        is_deferred: file.is_deferred,
+                        is_bookmarked: self.ui.bookmarks.contains(&selection_key),
+                    }
+                }
+            })
+            .collect();
+        Some(FileListPane {
+            items,
+            selected_idx,
+        })
+    }
+
+    /// The most recent actions, if the user asked to see them.
+    fn action_history_panel(&self) -> Option<ActionHistoryPanel> {
+        if !self.ui.is_action_history_visible {
+            return None;
+        }
+        let actions = self
+            .ui
+            .history
+            .commands()
+            .rev()
+            .map(|(command, is_undone)| (self.describe_command(command), is_undone))
+            .collect();
+        Some(ActionHistoryPanel { actions })
+    }
+
+    /// Describe what a command did, including what it applied to, for the
+    /// action history.
+    fn describe_command(&self, command: Command) -> String {
+        match command {
+            Command::ToggleItem(selection_key) => {
+                format!("toggled {}", self.describe_selection_key(selection_key))
+            }
+            Command::ToggleDeferItem(selection_key) => format!(
+                "toggled deferral of {}",
+                self.describe_selection_key(selection_key)
+            ),
+            Command::MoveItemToCommit {
+                selection_key,
+                commit_idx,
+            } => format!(
+                "moved {} to commit {}",
+                self.describe_selection_key(selection_key),
+                commit_idx + 1
+            ),
+            Command::ToggleAll => "inverted all".to_string(),
+            Command::ToggleAllUniform => "toggled all".to_string(),
+            Command::SetCommitMessage { commit_idx } => {
+                if self.state.commits.len() > 1 {
+                    format!("edited the message of commit {}", commit_idx + 1)
+                } else {
+                    "edited the commit message".to_string()
+                }
+            }
+            Command::RestoreRecovery => "restored the previous session".to_string(),
+        }
+    }
+
+    /// Describe an item as the user sees it, e.g. `src/foo.rs section 2`,
+    /// where sections are numbered as in their headers.
+    fn describe_selection_key(&self, selection_key: SelectionKey) -> String {
+        let describe_section = |file_idx: usize, section_idx: usize| {
+            let File { path, sections, .. } = &self.state.files[file_idx];
+            let section_num = sections[..=section_idx]
+                .iter()
+                .filter(|section| section.is_editable())
+                .count();
+            format!("{} section {section_num}", path.display())
+        };
+        match selection_key {
+            SelectionKey::None => "nothing".to_string(),
+            SelectionKey::Directory(DirectoryKey {
+                commit_idx: _,
+                dir_idx,
+            }) => format!("{}/", self.directories[dir_idx].display()),
+            SelectionKey::File(FileKey {
+                commit_idx: _,
+                file_idx,
+            }) => self.state.files[file_idx].path.display().to_string(),
+            SelectionKey::Section(SectionKey {
+                commit_idx: _,
+                file_idx,
+                section_idx,
+            }) => describe_section(file_idx, section_idx),
+            SelectionKey::Line(LineKey {
+                commit_idx: _,
+                file_idx,
+                section_idx,
+                line_idx,
+            }) => format!(
+                "{} line {}",
+                describe_section(file_idx, section_idx),
+                line_idx + 1
+            ),
+        }
+    }
+
+    /// Describe an item for the announcer, including where it is among its
+    /// siblings, e.g. `src/foo.rs section 2 of 3`, since listeners can't see
+    /// the rest of the screen.
+    fn announce_selection_key(&self, selection_key: SelectionKey) -> String {
+        let describe_section = |file_idx: usize, section_idx: usize| {
+            let File { path, sections, .. } = &self.state.files[file_idx];
+            let num_sections = sections
+                .iter()
+                .filter(|section| section.is_editable())
+                .count();
+            let section_num = sections[..=section_idx]
+                .iter()
+                .filter(|section| section.is_editable())
+                .count();
+            format!("{} section {section_num} of {num_sections}", path.display())
+        };
+        match selection_key {
+            SelectionKey::None => "nothing".to_string(),
+            SelectionKey::Directory(DirectoryKey {
+                commit_idx: _,
+                dir_idx,
+            }) => format!("directory {}/", self.directories[dir_idx].display()),
+            SelectionKey::File(FileKey {
+                commit_idx: _,
+                file_idx,
+            }) => {
+                // Number the files as they are listed, after sorting and
+                // filtering.
+                let file_indices = self.visible_file_indices();
+                let file_num = file_indices
+                    .iter()
+                    .position(|&visible_file_idx| visible_file_idx == file_idx)
+                    .map_or(0, |position| position + 1);
+                format!(
+                    "{} file {file_num} of {}",
+                    self.state.files[file_idx].path.display(),
+                    file_indices.len()
+                )
+            }
+            SelectionKey::Section(SectionKey {
+                commit_idx: _,
+                file_idx,
+                section_idx,
+            }) => describe_section(file_idx, section_idx),
+            SelectionKey::Line(LineKey {
+                commit_idx: _,
+                file_idx,
+                section_idx,
+                line_idx,
+            }) => {
+                let num_lines = match &self.state.files[file_idx].sections[section_idx] {
+                    Section::Changed { lines, .. } => lines.len(),
+                    Section::Unchanged { .. }
+                    | Section::FileMode { .. }
+                    | Section::Binary { .. } => 0,
+                };
+                format!(
+                    "{} line {} of {num_lines}",
+                    describe_section(file_idx, section_idx),
+                    line_idx + 1
+                )
+            }
+        }
+    }
+
+    /// What changed since the given item was selected and toggled as given,
+    /// for the announcer.
+    pub(crate) fn announcements(
+        &self,
+        old_selection_key: SelectionKey,
+        old_tristate: Option<Tristate>,
+    ) -> Vec<String> {
+        let mut announcements = Vec::new();
+        let tristate = self.item_tristate(old_selection_key);
+        if tristate != old_tristate {
+            if let Some(tristate) = tristate {
+                announcements.push(
+                    match tristate {
+                        Tristate::False => "toggled off",
+                        Tristate::Partial => "partially toggled on",
+                        Tristate::True => "toggled on",
+                    }
+                    .to_string(),
+                );
+            }
+        }
+        if self.ui.selection_key != old_selection_key {
+            announcements.push(format!(
+                "selected {}",
+                self.announce_selection_key(self.ui.selection_key)
+            ));
+        }
+        announcements
+    }
+
+    /// List the ways to continue the key binding which the user started
+    /// typing, if any.
+    fn which_key_popup(&self) -> Option<WhichKeyPopup> {
+        match self.ui.macro_prompt {
+            Some(MacroPrompt::Record) => {
+                return Some(WhichKeyPopup {
+                    pending: "Q".to_owned(),
+                    continuations: vec![(
+                        "a-z".to_owned(),
+                        "Record macro into register".to_owned(),
+                    )],
+                });
+            }
+            Some(MacroPrompt::Replay { count }) => {
+                let pending = if count > 1 {
+                    format!("{count}@")
+                } else {
+                    "@".to_owned()
+                };
+                let mut continuations =
+                    vec![("a-z".to_owned(), "Replay macro from register".to_owned())];
+                if let Some(register) = self.ui.macros.last_replayed() {
+                    continuations.push(("@".to_owned(), format!("Replay macro @{register}")));
+                }
+                return Some(WhichKeyPopup {
+                    pending,
+                    continuations,
+                });
+            }
+            None => {}
+        }
+        if let Some(count) = self.ui.count {
+            return Some(WhichKeyPopup {
+                pending: count.to_string(),
+                continuations: vec![("@".to_owned(), format!("Replay macro {count} times"))],
+            });
+        }
+        let pending_keys = &self.ui.pending_keys;
+        if pending_keys.is_empty() {
+            return None;
+        }
+        let format_keys = |keys: &[Key]| {
+            keys.iter()
+                .map(|key| key.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        Some(WhichKeyPopup {
+            pending: format_keys(pending_keys),
+            continuations: self
+                .options
+                .keymap
+                .continuations(pending_keys)
+                .map(|binding| {
+                    (
+                        format_keys(&binding.keys[pending_keys.len()..]),
+                        binding.description.clone(),
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    fn stats_bar(&self) -> StatsBar {
+        let mut stats_bar = StatsBar {
+            num_files: self.state.files.len(),
+            ..Default::default()
+        };
+        for (file, file_stats) in self.state.files.iter().zip(&self.file_stats) {
+            let FileStats {
+                num_added,
+                num_removed,
+                is_binary: _,
+                has_mode_change: _,
+            } = file_stats;
+            stats_bar.num_added += num_added;
+            stats_bar.num_removed += num_removed;
+            match file.tristate() {
+                Tristate::False => {}
+                Tristate::Partial | Tristate::True => stats_bar.num_selected_files += 1,
+            }
+            for section in &file.sections {
+                if let Section::Changed { lines, .. } = section {
+                    stats_bar.num_selected_lines +=
+                        lines.iter().filter(|line| line.is_checked).count();
+                }
+            }
+        }
+        stats_bar
+    }
+
+    fn make_file_list_items(
+        &'state self,
+        commit_idx: usize,
+        files: &'state [File<'state>],
+        debug_info: &Option<AppDebugInfo>,
+        is_read_only: bool,
+    ) -> Vec<FileListItem<'state>> {
+        let mut file_list = self.file_list(commit_idx);
+        // Only the selected file is shown next to the file list pane, so it
+        // isn't indented under its directories.
+        if self.is_two_pane() {
+            let selected_file_idx = self.selected_file_idx();
+            file_list = file_list
+                .into_iter()
+                .filter_map(|node| match node {
+                    FileListNode::File {
+                        file_idx,
+                        depth: _,
+                        is_visible,
+                    } if Some(file_idx) == selected_file_idx => Some(FileListNode::File {
+                        file_idx,
+                        depth: 0,
+                        is_visible,
+                    }),
+                    FileListNode::Directory { .. } | FileListNode::File { .. } => None,
+                })
+                .collect();
+        }
+        file_list
+            .into_iter()
+            .filter_map(|node| {
+                let (file_idx, depth) = match node {
+                    FileListNode::Directory {
+                        is_visible: false, ..
+                    }
+                    | FileListNode::File {
+                        is_visible: false, ..
+                    } => return None,
+                    FileListNode::Directory {
+                        dir_idx,
+                        label,
+                        depth,
+                        is_visible: true,
+                    } => {
+                        return Some(FileListItem::Directory(self.make_directory_view(
+                            DirectoryKey {
+                                commit_idx,
+                                dir_idx,
+                            },
+                            label,
+                            depth,
+                            is_read_only,
+                        )));
+                    }
+                    FileListNode::File {
+                        file_idx,
+                        depth,
+                        is_visible: true,
+                    } => (file_idx, depth),
+                };
+                let file = &files[file_idx];
+                let file_key = FileKey {
+                    commit_idx,
+                    file_idx,
+                };
+                let file_toggled = self.file_tristate(file_key).unwrap();
+                let file_expanded = self.file_expanded(file_key);
+                let file_interdiff = self.file_interdiff(file_idx);
+                let is_focused = match self.ui.selection_key {
+                    SelectionKey::None
+                    | SelectionKey::Directory(_)
+                    | SelectionKey::Section(_)
+                    | SelectionKey::Line(_) => false,
+                    SelectionKey::File(selected_file_key) => file_key == selected_file_key,
+                };
+                Some(FileListItem::File(FileView {
+                    debug: debug_info.is_some(),
+                    file_key,
+                    toggle_box: TristateBox {
+                        id: ComponentId::ToggleBox(SelectionKey::File(file_key)),
+                        icon_style: TristateIconStyle::Check,
+                        tristate: file_toggled,
+                        is_read_only,
+                    },
+                    expand_box: TristateBox {
+                        id: ComponentId::ExpandBox(SelectionKey::File(file_key)),
+                        icon_style: TristateIconStyle::Expand,
+                        tristate: file_expanded,
+                        is_read_only: false,
+                    },
+                    is_header_selected: is_focused,
+                    old_path: file.old_path.as_deref(),
+                    path: match file.path.file_name() {
+                        Some(file_name) if self.ui.is_file_tree && !self.is_two_pane() => {
+                            Path::new(file_name)
+                        }
+                        Some(_) | None => &file.path,
+                    },
+                    stats: self.file_stats[file_idx],
+                    is_deferred: file.is_deferred,
+                    is_bookmarked: self.ui.bookmarks.contains(&SelectionKey::File(file_key)),
+                    interdiff: file_interdiff,
+                    depth,
+                    section_views: {
+                        let mut section_views = Vec::new();
+                        let total_num_sections = file.sections.len();
+                        let total_num_editable_sections = file
+                            .sections
+                            .iter()
+                            .filter(|section| section.is_editable())
+                            .count();
+
+                        let mut old_line_num = 1;
+                        let mut new_line_num = 1;
+                        let mut editable_section_num = 0;
+                        for (section_idx, section) in file.sections.iter().enumerate() {
+                            let section_key = SectionKey {
+                                commit_idx,
+                                file_idx,
+                                section_idx,
+                            };
+                            let section_toggled = self.section_tristate(section_key).unwrap();
+                            let section_expanded = Tristate::from(
+                                self.ui
+                                    .expanded_items
+                                    .contains(&SelectionKey::Section(section_key)),
+                            );
+                            if section.is_editable() {
+                                editable_section_num += 1;
+                            }
+                            section_views.push(section::SectionView {
+                                is_read_only,
+                                section_key,
+                                toggle_box: TristateBox {
+                                    is_read_only,
+                                    id: ComponentId::ToggleBox(SelectionKey::Section(section_key)),
+                                    tristate: section_toggled,
+                                    icon_style: TristateIconStyle::Check,
+                                },
+                                expand_box: TristateBox {
+                                    is_read_only: false,
+                                    id: ComponentId::ExpandBox(SelectionKey::Section(section_key)),
+                                    tristate: section_expanded,
+                                    icon_style: TristateIconStyle::Expand,
+                                },
+                                selection: match self.ui.selection_key {
+                                    SelectionKey::None
+                                    | SelectionKey::Directory(_)
+                                    | SelectionKey::File(_) => None,
+                                    SelectionKey::Section(selected_section_key) => {
+                                        if selected_section_key == section_key {
+                                            Some(section::SectionSelection::SectionHeader)
+                                        } else {
+                                            None
+                                        }
+                                    }
+                                    SelectionKey::Line(LineKey {
+                                        commit_idx,
+                                        file_idx,
+                                        section_idx,
+                                        line_idx,
+                                    }) => {
+                                        let selected_section_key = SectionKey {
+                                            commit_idx,
+                                            file_idx,
+                                            section_idx,
+                                        };
+                                        if selected_section_key == section_key {
+                                            Some(section::SectionSelection::ChangedLine(line_idx))
+                                        } else {
+                                            None
+                                        }
+                                    }
+                                },
+                                total_num_sections,
+                                editable_section_num,
+                                total_num_editable_sections,
+                                section,
+                                old_line_start_num: old_line_num,
+                                new_line_start_num: new_line_num,
+                                wrapped_line_idx: self.ui.wrapped_line.and_then(|line_key| {
+                                    let LineKey {
+                                        commit_idx,
+                                        file_idx,
+                                        section_idx,
+                                        line_idx,
+                                    } = line_key;
+                                    let wrapped_section_key = SectionKey {
+                                        commit_idx,
+                                        file_idx,
+                                        section_idx,
+                                    };
+                                    (wrapped_section_key == section_key).then_some(line_idx)
+                                }),
+                                show_annotations: self.ui.is_annotation_gutter_visible,
+                                interdiff: file_interdiff.and_then(|file_interdiff| {
+                                    file_interdiff.sections.get(section_idx).copied()
+                                }),
+                                num_context_lines: if self.ui.is_context_hidden {
+                                    0
+                                } else {
+                                    self.options.num_context_lines
+                                },
+                                is_bookmarked: self
+                                    .ui
+                                    .bookmarks
+                                    .contains(&SelectionKey::Section(section_key)),
+                                line_highlights: self.line_highlights(file_idx, section_idx),
+                            });
+
+                            match section {
+                                Section::Unchanged { lines } => {
+                                    old_line_num += lines.len();
+                                    new_line_num += lines.len();
+                                }
+                                Section::Changed { lines, .. } => {
+                                    for changed_line in lines {
+                                        match changed_line.change_type {
+                                            ChangeType::Added => new_line_num += 1,
+                                            ChangeType::Removed => old_line_num += 1,
+                                        }
+                                    }
+                                }
+                                Section::FileMode { .. } | Section::Binary { .. } => {}
+                            }
+                        }
+                        section_views
+                    },
+                }))
+            })
+            .collect()
+    }
+
+    fn make_directory_view(
+        &self,
+        directory_key: DirectoryKey,
+        label: String,
+        depth: usize,
+        is_read_only: bool,
+    ) -> DirectoryView {
+        let selection_key = SelectionKey::Directory(directory_key);
+        let stats = self.directory_stats(directory_key);
+        DirectoryView {
+            directory_key,
+            label,
+            depth,
+            stats,
+            is_selected: self.ui.selection_key == selection_key,
+            toggle_box: TristateBox {
+                id: ComponentId::ToggleBox(selection_key),
+                icon_style: TristateIconStyle::Check,
+                tristate: self.directory_tristate(directory_key),
+                is_read_only,
+            },
+            expand_box: TristateBox {
+                id: ComponentId::ExpandBox(selection_key),
+                icon_style: TristateIconStyle::Expand,
+                tristate: Tristate::from(self.ui.expanded_items.contains(&selection_key)),
+                is_read_only: false,
+            },
+        }
+    }
+
+    pub(crate) fn handle_event(
+        &self,
+        event: event::Event,
+        term_width: usize,
+        term_height: usize,
+        drawn_rects: &DrawnRects<ComponentId>,
+    ) -> Result<StateUpdate, RecordError> {
+        // Only quitting with a key press is confirmed. Other events come from
+        // the input, which is trusted to know what it's doing.
+        let is_key_press = matches!(
+            event,
+            event::Event::Key(_) | event::Event::KeySequenceTimeout
+        );
+
+        // Resolve key presses to actions, unless they're typed into a prompt.
+        let event = match event {
+            event::Event::Key(key_event) => {
+                let is_interrupt = key_event.code == KeyCode::Char('c')
+                    && key_event.modifiers == KeyModifiers::CONTROL;
+                match &self.ui.file_filter_prompt {
+                    Some(prompt) if !is_interrupt => {
+                        let mut prompt = prompt.clone();
+                        return Ok(match prompt.handle_key(key_event) {
+                            TextPromptAction::Edit => {
+                                StateUpdate::SetFileFilterPrompt(Some(prompt))
+                            }
+                            TextPromptAction::Submit => {
+                                StateUpdate::SetFileFilter(Some(prompt.text))
+                            }
+                            TextPromptAction::Cancel => StateUpdate::SetFileFilterPrompt(None),
+                        });
+                    }
+                    Some(_) | None if self.ui.search_prompt.is_some() && !is_interrupt => {
+                        return Ok(self.handle_search_prompt_key(key_event));
+                    }
+                    Some(_) | None if self.ui.macro_prompt.is_some() && !is_interrupt => {
+                        return Ok(self.handle_macro_prompt_key(key_event));
+                    }
+                    Some(_) | None if self.ui.message_editor_dialog.is_some() && !is_interrupt => {
+                        return Ok(self.handle_message_editor_key(key_event, term_width));
+                    }
+                    Some(_) | None if self.ui.quit_dialog.is_some() && !is_interrupt => {
+                        return Ok(self.handle_quit_dialog_key(key_event));
+                    }
+                    Some(_) | None => {
+                        if let Some(count) = self.count_after_key(key_event) {
+                            return Ok(StateUpdate::SetCount(Some(count)));
+                        }
+                        match self
+                            .options
+                            .keymap
+                            .resolve(&self.ui.pending_keys, key_event)
+                        {
+                            KeymapMatch::Event(event) => event,
+                            KeymapMatch::Pending(keys) => {
+                                return Ok(StateUpdate::SetPendingKeys(keys));
+                            }
+                            KeymapMatch::Interrupted(Some(event)) => {
+                                // Handle the key again once the bound event
+                                // for the keys before it has been handled.
+                                return Ok(StateUpdate::QueueEvents(vec![
+                                    event,
+                                    event::Event::Key(key_event),
+                                ]));
+                            }
+                            KeymapMatch::Interrupted(None) => event::Event::None,
+                            KeymapMatch::Unbound(key) => {
+                                return Ok(StateUpdate::SetStatusMessage(StatusMessage::info(
+                                    format!("Unbound key {key} — press ? for help"),
+                                )));
+                            }
+                        }
+                    }
+                }
+            }
+            event::Event::KeySequenceTimeout => {
+                match self.options.keymap.lookup(&self.ui.pending_keys) {
+                    Some(event) => event.clone(),
+                    None => event::Event::None,
+                }
+            }
+            event => event,
+        };
+
+        // If the terminal is too small, then nothing but the placeholder was
+        // drawn, so only handle events which don't depend on the layout.
+        if drawn_rects.contains_key(&ComponentId::TerminalTooSmall) {
+            match event {
+                event::Event::None
+                | event::Event::QuitCancel
+                | event::Event::QuitInterrupt
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::Idle => {}
+                _ => return Ok(StateUpdate::None),
+            }
+        }
+
+        // Keys are typed into the message editor above, and other actions
+        // wait until it's closed, except for quitting, which the input may
+        // ask for directly.
+        if self.ui.message_editor_dialog.is_some() {
+            match event {
+                event::Event::None
+                | event::Event::QuitAccept
+                | event::Event::QuitCancel
+                | event::Event::QuitInterrupt
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::Idle => {}
+                _ => return Ok(StateUpdate::None),
+            }
+        }
+
+        // If an interrupted session can be restored, ask about it first.
+        if self.ui.recovery.is_some() {
+            match event {
+                event::Event::None
+                | event::Event::QuitInterrupt
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::Idle => {}
+                event::Event::QuitAccept
+                | event::Event::ToggleItem
+                | event::Event::ToggleItemAndAdvance => return Ok(StateUpdate::RestoreRecovery),
+                event::Event::QuitCancel | event::Event::QuitEscape => {
+                    return Ok(StateUpdate::DiscardRecovery)
+                }
+                _ => return Ok(StateUpdate::None),
+            }
+        }
+
+        // If a global toggle is waiting for confirmation, ask about it first.
+        if self.ui.toggle_all_dialog.is_some() {
+            match event {
+                event::Event::None
+                | event::Event::QuitInterrupt
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::Idle => {}
+                event::Event::QuitAccept
+                | event::Event::ToggleItem
+                | event::Event::ToggleItemAndAdvance => return Ok(StateUpdate::ConfirmToggleAll),
+                _ => return Ok(StateUpdate::SetToggleAllDialog(None)),
+            }
+        }
+
+        // Likewise if accepting an empty selection is waiting for
+        // confirmation.
+        if self.ui.empty_selection_dialog.is_some() {
+            match event {
+                event::Event::None
+                | event::Event::QuitInterrupt
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::Idle => {}
+                event::Event::QuitAccept => return Ok(StateUpdate::QuitAccept),
+                _ => return Ok(StateUpdate::SetEmptySelectionDialog(None)),
+            }
+        }
+
+        // Keys are handled above while quitting is waiting for confirmation,
+        // so these are events from the input.
+        if self.ui.quit_dialog.is_some() {
+            match event {
+                event::Event::None
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::Idle => {}
+                event::Event::QuitCancel | event::Event::QuitInterrupt => {
+                    return Ok(StateUpdate::QuitCancel)
+                }
+                _ => return Ok(StateUpdate::SetQuitDialog(None)),
+            }
+        }
+
+        // If the help dialog is open, certain keys will close it.
+        if self.ui.help_dialog.is_some()
+            && matches!(
+                event,
+                event::Event::Help
+                    | event::Event::QuitEscape
+                    | event::Event::QuitCancel
+                    | event::Event::ToggleItem
+                    | event::Event::ToggleItemAndAdvance
+            )
+        {
+            return Ok(StateUpdate::SetHelpDialog(None));
+        }
+
+        // If the message diff dialog is open, any key will close it.
+        if self.ui.message_diff_dialog.is_some() {
+            match event {
+                event::Event::None
+                | event::Event::QuitInterrupt
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::Idle => {}
+                _ => return Ok(StateUpdate::SetMessageDiffDialog(None)),
+            }
+        }
+
+        // Likewise for the character diff dialog.
+        if self.ui.line_diff_dialog.is_some() {
+            match event {
+                event::Event::None
+                | event::Event::QuitInterrupt
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::Idle => {}
+                _ => return Ok(StateUpdate::SetLineDiffDialog(None)),
+            }
+        }
+
+        // The preview can be scrolled, and any other key will close it.
+        if let Some(preview_dialog) = &self.ui.preview_dialog {
+            let page_size = PreviewDialog::num_visible_lines(term_height);
+            let max_scroll_offset = preview_dialog.max_scroll_offset(term_height);
+            let scroll_offset = preview_dialog.scroll_offset.min(max_scroll_offset);
+            let scroll_offset = match event {
+                event::Event::None
+                | event::Event::QuitInterrupt
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::Idle => None,
+                event::Event::FocusPrev | event::Event::ScrollUp => {
+                    Some(scroll_offset.saturating_sub(1))
+                }
+                event::Event::FocusNext | event::Event::ScrollDown => Some(scroll_offset + 1),
+                event::Event::PageUp | event::Event::FocusPrevPage => {
+                    Some(scroll_offset.saturating_sub(page_size))
+                }
+                event::Event::PageDown | event::Event::FocusNextPage => {
+                    Some(scroll_offset + page_size)
+                }
+                event::Event::FocusFirst => Some(0),
+                event::Event::FocusLast => Some(max_scroll_offset),
+                _ => return Ok(StateUpdate::SetPreviewDialog(None)),
+            };
+            if let Some(scroll_offset) = scroll_offset {
+                return Ok(StateUpdate::ScrollPreviewDialog(
+                    scroll_offset.min(max_scroll_offset),
+                ));
+            }
+        }
+
+        // Likewise for the contents of a file.
+        if let Some(file_contents_dialog) = &self.ui.file_contents_dialog {
+            let page_size = FileContentsDialog::num_visible_lines(term_height);
+            let max_scroll_offset = file_contents_dialog.max_scroll_offset(term_height);
+            let scroll_offset = file_contents_dialog.scroll_offset.min(max_scroll_offset);
+            let scroll_offset = match event {
+                event::Event::None
+                | event::Event::QuitInterrupt
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::Idle => None,
+                event::Event::FocusPrev | event::Event::ScrollUp => {
+                    Some(scroll_offset.saturating_sub(1))
+                }
+                event::Event::FocusNext | event::Event::ScrollDown => Some(scroll_offset + 1),
+                event::Event::PageUp | event::Event::FocusPrevPage => {
+                    Some(scroll_offset.saturating_sub(page_size))
+                }
+                event::Event::PageDown | event::Event::FocusNextPage => {
+                    Some(scroll_offset + page_size)
+                }
+                event::Event::FocusFirst => Some(0),
+                event::Event::FocusLast => Some(max_scroll_offset),
+                _ => return Ok(StateUpdate::SetFileContentsDialog(None)),
+            };
+            if let Some(scroll_offset) = scroll_offset {
+                return Ok(StateUpdate::ScrollFileContentsDialog(
+                    scroll_offset.min(max_scroll_offset),
+                ));
+            }
+        }
+
+        // Likewise for the error dialog.
+        if self.ui.error_dialog.is_some() {
+            match event {
+                event::Event::None
+                | event::Event::QuitInterrupt
+                | event::Event::TakeScreenshot(_)
+                | event::Event::Redraw
+                | event::Event::Resize
+                | event::Event::EnsureSelectionInViewport
+                | event::Event::Idle => {}
+                _ => return Ok(StateUpdate::SetErrorDialog(None)),
+            }
+        }
+
+        // If the message history picker is open, it captures most keys.
+        if let Some(picker) = self.ui.message_history_picker {
+            if let Some(state_update) = self.handle_message_history_event(picker, &event) {
+                return Ok(state_update);
+            }
+        }
+
+        let state_update = match event {
+            event::Event::None => StateUpdate::None,
+            event::Event::Redraw => StateUpdate::Redraw,
+            event::Event::Resize => StateUpdate::Resize,
+            event::Event::EnsureSelectionInViewport => StateUpdate::EnsureSelectionInViewport,
+
+            event::Event::Help => StateUpdate::SetHelpDialog(Some(HelpDialog())),
+
+            // Confirm changes and quit.
+            event::Event::QuitAccept => {
+                if self.is_empty_selection() {
+                    StateUpdate::SetEmptySelectionDialog(Some(EmptySelectionDialog))
+                } else {
+                    StateUpdate::QuitAccept
+                }
+            }
+            // Cancel changes and quit, asking first if changes would be lost.
+            event::Event::QuitCancel
+                if is_key_press && self.options.confirm_quit && self.dirty.is_dirty() =>
+            {
+                StateUpdate::SetQuitDialog(Some(QuitDialog::new(self.dirty)))
+            }
+            event::Event::QuitCancel | event::Event::QuitInterrupt => StateUpdate::QuitCancel,
+
+            event::Event::TakeScreenshot(screenshot) => StateUpdate::TakeScreenshot(screenshot),
+            event::Event::ScrollUp => {
+                StateUpdate::ScrollTo(self.ui.scroll_offset_y.saturating_sub(1))
+            }
+            event::Event::ScrollDown => {
+                StateUpdate::ScrollTo(self.ui.scroll_offset_y.saturating_add(1))
+            }
+            event::Event::PageUp => StateUpdate::ScrollTo(
+                self.ui
+                    .scroll_offset_y
+                    .saturating_sub(term_height.unwrap_isize()),
+            ),
+            event::Event::PageDown => StateUpdate::ScrollTo(
+                self.ui
+                    .scroll_offset_y
+                    .saturating_add(term_height.unwrap_isize()),
+            ),
+            event::Event::FocusPrev => {
+                let (keys, index) = self.find_selection();
+                let selection_key = self.select_prev(&keys, index);
+                StateUpdate::SelectItem {
+                    selection_key,
+                    ensure_in_viewport: true,
+                }
+            }
+            event::Event::FocusNext => {
+                let (keys, index) = self.find_selection();
+                let selection_key = self.select_next(&keys, index);
+                StateUpdate::SelectItem {
+                    selection_key,
+                    ensure_in_viewport: true,
+                }
+            }
+            event::Event::FocusPrevFile => StateUpdate::SelectItem {
+                selection_key: self.select_prev_or_next_file(/*select_previous=*/ true),
+                ensure_in_viewport: true,
+            },
+            event::Event::FocusNextFile => StateUpdate::SelectItem {
+                selection_key: self.select_prev_or_next_file(/*select_previous=*/ false),
+                ensure_in_viewport: true,
+            },
+            event::Event::FocusFirst => {
+                let (keys, _) = self.find_selection();
+                StateUpdate::SelectItem {
+                    selection_key: keys.first().copied().unwrap_or(SelectionKey::None),
+                    ensure_in_viewport: true,
+                }
+            }
+            event::Event::FocusLast => {
+                let (keys, _) = self.find_selection();
+                StateUpdate::SelectItem {
+                    selection_key: keys.last().copied().unwrap_or(SelectionKey::None),
+                    ensure_in_viewport: true,
+                }
+            }
+            event::Event::RecordMacro => {
+                if self.ui.macros.recording_register().is_some() {
+                    StateUpdate::StopRecordingMacro
+                } else {
+                    StateUpdate::SetMacroPrompt(Some(MacroPrompt::Record))
+                }
+            }
+            event::Event::ReplayMacro => StateUpdate::SetMacroPrompt(Some(MacroPrompt::Replay {
+                count: self.ui.count.unwrap_or(1),
+            })),
+            event::Event::Undo => StateUpdate::Undo,
+            event::Event::Redo => StateUpdate::Redo,
+            event::Event::ScrollSelectionToCenter => {
+                match self.selection_key_y(drawn_rects, self.ui.selection_key) {
+                    Some(y) => StateUpdate::ScrollTo(y - (term_height / 2).unwrap_isize()),
+                    None => StateUpdate::None,
+                }
+            }
+            event::Event::FocusPrevSameKind => {
+                let selection_key =
+                    self.select_prev_or_next_of_same_kind(/*select_previous=*/ true);
+                StateUpdate::SelectItem {
+                    selection_key,
+                    ensure_in_viewport: true,
+                }
+            }
+            event::Event::FocusNextSameKind => {
+                let selection_key =
+                    self.select_prev_or_next_of_same_kind(/*select_previous=*/ false);
+                StateUpdate::SelectItem {
+                    selection_key,
+                    ensure_in_viewport: true,
+                }
+            }
+            event::Event::FocusPrevPage => {
+                let selection_key = self.select_prev_page(term_height, drawn_rects);
+                StateUpdate::SelectItem {
+                    selection_key,
+                    ensure_in_viewport: true,
+                }
+            }
+            event::Event::FocusNextPage => {
+                let selection_key = self.select_next_page(term_height, drawn_rects);
+                StateUpdate::SelectItem {
+                    selection_key,
+                    ensure_in_viewport: true,
+                }
+            }
+            event::Event::FocusOuter { fold_section } => self.select_outer(fold_section),
+            // Moving into or expanding a file leaves the outline, which shows
+            // the files as they were expanded before.
+            event::Event::FocusInner | event::Event::ExpandItem
+                if self.is_outline()
+                    && !matches!(self.ui.selection_key, SelectionKey::Directory(_)) =>
+            {
+                StateUpdate::ToggleOutline
+            }
+            event::Event::ExpandAll if self.is_outline() => StateUpdate::ToggleOutline,
+            event::Event::FocusInner => {
+                let selection_key = self.select_inner();
+                StateUpdate::SelectItem {
+                    selection_key,
+                    ensure_in_viewport: true,
+                }
+            }
+            // Nothing can be toggled in read-only mode, so Space pages down
+            // instead, as in a pager.
+            event::Event::ToggleItem if self.state.is_read_only => StateUpdate::SelectItem {
+                selection_key: self.select_next_page(term_height, drawn_rects),
+                ensure_in_viewport: true,
+            },
+            event::Event::ToggleItem => StateUpdate::ToggleItem(self.ui.selection_key),
+            event::Event::ToggleFile => {
+                StateUpdate::ToggleItem(file_selection_key(self.ui.selection_key))
+            }
+            event::Event::ToggleSection => match self.ui.selection_key {
+                SelectionKey::Line(LineKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx,
+                    line_idx: _,
+                }) => StateUpdate::ToggleItem(SelectionKey::Section(SectionKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx,
+                })),
+                selection_key @ SelectionKey::Section(_) => StateUpdate::ToggleItem(selection_key),
+                SelectionKey::None | SelectionKey::Directory(_) | SelectionKey::File(_) => {
+                    StateUpdate::SetStatusMessage(StatusMessage::error(
+                        "Select a line to toggle its section",
+                    ))
+                }
+            },
+            event::Event::ToggleItemAndAdvance => {
+                let advanced_key = self.advance_to_next_of_kind();
+                StateUpdate::ToggleItemAndAdvance(self.ui.selection_key, advanced_key)
+            }
+            event::Event::ToggleDeferItem => StateUpdate::ToggleDeferItem(self.ui.selection_key),
+            event::Event::ToggleBookmark => match bookmark_key(self.ui.selection_key) {
+                Some(bookmark_key) => StateUpdate::ToggleBookmark(bookmark_key),
+                None => StateUpdate::SetStatusMessage(StatusMessage::error(
+                    "Select a file or section to bookmark it",
+                )),
+            },
+            event::Event::FocusPrevBookmark => {
+                self.select_prev_or_next_bookmark(/*select_previous=*/ true)
+            }
+            event::Event::FocusNextBookmark => {
+                self.select_prev_or_next_bookmark(/*select_previous=*/ false)
+            }
+            event::Event::ShowLineDiff => self.show_line_diff(),
+            event::Event::ToggleWrapLine => match self.ui.selection_key {
+                SelectionKey::Line(line_key) if self.ui.wrapped_line == Some(line_key) => {
+                    StateUpdate::SetWrappedLine(None)
+                }
+                SelectionKey::Line(line_key) => StateUpdate::SetWrappedLine(Some(line_key)),
+                SelectionKey::None
+                | SelectionKey::Directory(_)
+                | SelectionKey::File(_)
+                | SelectionKey::Section(_) => StateUpdate::SetStatusMessage(StatusMessage::error(
+                    "Select a changed line to show it in full",
+                )),
+            },
+            event::Event::ToggleAnnotations => StateUpdate::ToggleAnnotations,
+            event::Event::ToggleContext => StateUpdate::ToggleContext,
+            event::Event::ToggleAll => match self.toggle_all_dialog(false) {
+                Some(toggle_all_dialog) => StateUpdate::SetToggleAllDialog(Some(toggle_all_dialog)),
+                None => StateUpdate::ToggleAll,
+            },
+            event::Event::ToggleAllUniform => match self.toggle_all_dialog(true) {
+                Some(toggle_all_dialog) => StateUpdate::SetToggleAllDialog(Some(toggle_all_dialog)),
+                None => StateUpdate::ToggleAllUniform,
+            },
+            event::Event::ExpandItem => StateUpdate::ToggleExpandItem(self.ui.selection_key),
+            event::Event::ExpandAll => StateUpdate::ToggleExpandAll,
+            event::Event::EditCommitMessage => {
+                let commit_idx = self.ui.focused_commit_idx;
+                match &self.state.commits[commit_idx].message {
+                    Some(message) => StateUpdate::SetMessageEditorDialog(Some(
+                        MessageEditorDialog::new(commit_idx, message.as_str()),
+                    )),
+                    None => StateUpdate::None,
+                }
+            }
+            event::Event::EditCommitMessageExternally => StateUpdate::EditCommitMessageExternally {
+                commit_idx: self.ui.focused_commit_idx,
+                message: None,
+            },
+
+            event::Event::ToggleCommitViewMode => StateUpdate::ToggleCommitViewMode,
+            event::Event::FocusPrevCommit => match self.ui.focused_commit_idx.checked_sub(1) {
+                Some(commit_idx) => StateUpdate::SetFocusedCommit(commit_idx),
+                None => StateUpdate::SetStatusMessage(StatusMessage::error(
+                    "Already at the first commit",
+                )),
+            },
+            event::Event::MoveItemToPrevCommit => {
+                self.move_item_to_adjacent_commit(/*to_previous=*/ true)
+            }
+            event::Event::MoveItemToNextCommit => {
+                self.move_item_to_adjacent_commit(/*to_previous=*/ false)
+            }
+            event::Event::FocusNextCommit => {
+                let commit_idx = self.ui.focused_commit_idx + 1;
+                if commit_idx < self.state.commits.len() {
+                    StateUpdate::SetFocusedCommit(commit_idx)
+                } else {
+                    StateUpdate::SetStatusMessage(StatusMessage::error(
+                        "Already at the last commit",
+                    ))
+                }
+            }
+            event::Event::ToggleCommitMessageExpanded => StateUpdate::ToggleCommitMessageExpanded,
+            event::Event::ToggleStatsBar => StateUpdate::ToggleStatsBar,
+            event::Event::ToggleActionHistory => StateUpdate::ToggleActionHistory,
+            event::Event::CycleFileSortOrder => {
+                StateUpdate::SetFileSortOrder(self.ui.file_sort_order.next())
+            }
+            event::Event::FilterFiles => StateUpdate::SetFileFilterPrompt(Some(TextPrompt::new(
+                "Filter files",
+                self.ui.file_filter.clone().unwrap_or_default(),
+            ))),
+            event::Event::Search => StateUpdate::SetSearchPrompt(
+                SearchPrompt {
+                    prompt: TextPrompt::new("Search", ""),
+                    origin: self.ui.selection_key,
+                },
+                self.ui.selection_key,
+            ),
+            event::Event::SearchNext => {
+                self.select_next_search_match(/*select_previous=*/ false)
+            }
+            event::Event::SearchPrev => {
+                self.select_next_search_match(/*select_previous=*/ true)
+            }
+            event::Event::ClearFileFilter => match self.ui.file_filter {
+                Some(_) => StateUpdate::SetFileFilter(None),
+                None => StateUpdate::None,
+            },
+            event::Event::ToggleHideUnselectedFiles => StateUpdate::ToggleHideUnselectedFiles,
+            event::Event::ToggleHideSelectedFiles => StateUpdate::ToggleHideSelectedFiles,
+            event::Event::ToggleHideUnchangedFiles => match self.options.interdiff {
+                Some(_) => StateUpdate::ToggleHideUnchangedFiles,
+                None => StateUpdate::SetStatusMessage(StatusMessage::error(
+                    "There is no previous state to compare with".to_string(),
+                )),
+            },
+            event::Event::ToggleFileTree => StateUpdate::ToggleFileTree,
+            event::Event::ToggleTwoPane => StateUpdate::ToggleTwoPane,
+            event::Event::PreviewSelection => self.preview_dialog(),
+            event::Event::ToggleOutline => StateUpdate::ToggleOutline,
+            event::Event::ToggleZoomFile => match self.ui.zoomed_file {
+                Some(_) => StateUpdate::SetZoomedFile(None),
+                None => match self.selected_file_idx() {
+                    Some(file_idx) => StateUpdate::SetZoomedFile(Some(file_idx)),
+                    None => StateUpdate::SetStatusMessage(StatusMessage::error(
+                        "Select a file to zoom into it".to_string(),
+                    )),
+                },
+            },
+            event::Event::CopyPath => self.copy_path(),
+            event::Event::CopySelectedLines { as_diff } => self.copy_selected_lines(as_diff),
+            event::Event::ViewFileContents { version } => match self.selected_file_idx() {
+                Some(file_idx) => StateUpdate::ViewFileContents {
+                    file_idx,
+                    version,
+                    line_idx: self.selected_line_idx(version),
+                },
+                None => StateUpdate::SetStatusMessage(StatusMessage::error(
+                    "Select a file to view it".to_string(),
+                )),
+            },
+            event::Event::PreviewImages => match self.selected_file_idx() {
+                Some(file_idx)
+                    if self.state.files[file_idx]
+                        .sections
+                        .iter()
+                        .any(|section| matches!(section, Section::Binary { .. })) =>
+                {
+                    StateUpdate::PreviewImages { file_idx }
+                }
+                Some(_) | None => StateUpdate::SetStatusMessage(StatusMessage::error(
+                    "Select a binary file to preview it".to_string(),
+                )),
+            },
+            // Key presses are resolved above.
+            event::Event::Key(_) | event::Event::KeySequenceTimeout => StateUpdate::None,
+            // There's nothing to recover if the user hasn't changed anything.
+            event::Event::Idle => {
+                if self.options.recovery_path.is_some() && self.dirty.is_dirty() {
+                    StateUpdate::Autosave
+                } else {
+                    StateUpdate::None
+                }
+            }
+            event::Event::OpenMessageHistory => {
+                let commit_idx = self.ui.focused_commit_idx;
+                if self.options.message_history.is_empty()
+                    || self.state.commits[commit_idx].message.is_none()
+                {
+                    StateUpdate::None
+                } else {
+                    StateUpdate::SetMessageHistoryPicker(Some(MessageHistoryPicker {
+                        commit_idx,
+                        selected_idx: 0,
+                    }))
+                }
+            }
+
+            // generally ignore escape key, except to leave a zoomed file
+            event::Event::QuitEscape => match self.ui.zoomed_file {
+                Some(_) => StateUpdate::SetZoomedFile(None),
+                None => StateUpdate::None,
+            },
+        };
+        Ok(state_update)
+    }
+
+    /// Handle an event while the message history picker is open. Returns
+    /// `None` if the event should be handled as usual.
+    fn handle_message_history_event(
+        &self,
+        picker: MessageHistoryPicker,
+        event: &event::Event,
+    ) -> Option<StateUpdate> {
+        let MessageHistoryPicker {
+            commit_idx,
+            selected_idx,
+        } = picker;
+        let state_update = match event {
+            event::Event::FocusPrev => {
+                StateUpdate::SetMessageHistoryPicker(Some(MessageHistoryPicker {
+                    commit_idx,
+                    selected_idx: selected_idx.saturating_sub(1),
+                }))
+            }
+            event::Event::FocusNext => {
+                StateUpdate::SetMessageHistoryPicker(Some(MessageHistoryPicker {
+                    commit_idx,
+                    selected_idx: (selected_idx + 1)
+                        .min(self.options.message_history.len().saturating_sub(1)),
+                }))
+            }
+            event::Event::QuitAccept
+            | event::Event::ToggleItem
+            | event::Event::ToggleItemAndAdvance => StateUpdate::UseHistoryMessage {
+                commit_idx,
+                history_idx: selected_idx,
+                edit: false,
+            },
+            event::Event::EditCommitMessage => StateUpdate::UseHistoryMessage {
+                commit_idx,
+                history_idx: selected_idx,
+                edit: true,
+            },
+            event::Event::QuitCancel
+            | event::Event::QuitEscape
+            | event::Event::OpenMessageHistory => StateUpdate::SetMessageHistoryPicker(None),
+
+            event::Event::None
+            | event::Event::QuitInterrupt
+            | event::Event::TakeScreenshot(_)
+            | event::Event::Redraw
+            | event::Event::Resize
+            | event::Event::EnsureSelectionInViewport
+            | event::Event::Idle
+            | event::Event::Help => return None,
+
+            event::Event::ScrollUp
+            | event::Event::ScrollDown
+            | event::Event::PageUp
+            | event::Event::PageDown
+            | event::Event::FocusPrevSameKind
+            | event::Event::FocusPrevPage
+            | event::Event::FocusNextSameKind
+            | event::Event::FocusNextPage
+            | event::Event::FocusInner
+            | event::Event::FocusOuter { .. }
+            | event::Event::ToggleAll
+            | event::Event::ToggleAllUniform
+            | event::Event::ExpandItem
+            | event::Event::ExpandAll
+            | event::Event::ToggleCommitViewMode
+            | event::Event::FocusPrevCommit
+            | event::Event::FocusNextCommit
+            | event::Event::MoveItemToPrevCommit
+            | event::Event::MoveItemToNextCommit
+            | event::Event::EditCommitMessageExternally
+            | event::Event::ToggleCommitMessageExpanded
+            | event::Event::ToggleStatsBar
+            | event::Event::ToggleActionHistory
+            | event::Event::CycleFileSortOrder
+            | event::Event::FilterFiles
+            | event::Event::ClearFileFilter
+            | event::Event::Search
+            | event::Event::SearchNext
+            | event::Event::SearchPrev
+            | event::Event::ToggleHideUnselectedFiles
+            | event::Event::ToggleHideSelectedFiles
+            | event::Event::ToggleHideUnchangedFiles
+            | event::Event::ToggleFileTree
+            | event::Event::ToggleTwoPane
+            | event::Event::ToggleZoomFile
+            | event::Event::PreviewSelection
+            | event::Event::ToggleOutline
+            | event::Event::CopyPath
+            | event::Event::CopySelectedLines { .. }
+            | event::Event::ViewFileContents { .. }
+            | event::Event::PreviewImages
+            | event::Event::ToggleDeferItem
+            | event::Event::ToggleBookmark
+            | event::Event::FocusPrevBookmark
+            | event::Event::FocusNextBookmark
+            | event::Event::ShowLineDiff
+            | event::Event::ToggleWrapLine
+            | event::Event::ToggleAnnotations
+            | event::Event::ToggleContext
+            | event::Event::FocusPrevFile
+            | event::Event::FocusNextFile
+            | event::Event::ToggleFile
+            | event::Event::ToggleSection
+            | event::Event::FocusFirst
+            | event::Event::FocusLast
+            | event::Event::ScrollSelectionToCenter
+            | event::Event::RecordMacro
+            | event::Event::ReplayMacro
+            | event::Event::Undo
+            | event::Event::Redo
+            | event::Event::KeySequenceTimeout
+            | event::Event::Key(_) => StateUpdate::None,
+        };
+        Some(state_update)
+    }
+
+    /// The count typed so far, if the key press is another digit of it. A
+    /// count can't start with zero.
+    fn count_after_key(&self, key_event: KeyEvent) -> Option<usize> {
+        if !self.ui.pending_keys.is_empty()
+            || key_event.kind != KeyEventKind::Press
+            || key_event.modifiers != KeyModifiers::NONE
+        {
+            return None;
+        }
+        let digit = match key_event.code {
+            KeyCode::Char(c) => usize::try_from(c.to_digit(10)?).ok()?,
+            _ => return None,
+        };
+        match self.ui.count {
+            None if digit == 0 => None,
+            count => Some(
+                count
+                    .unwrap_or_default()
+                    .saturating_mul(10)
+                    .saturating_add(digit),
+            ),
+        }
+    }
+
+    /// Edit the search prompt, selecting the first match after the item
+    /// which was selected when it was opened.
+    fn handle_search_prompt_key(&self, key_event: KeyEvent) -> StateUpdate {
+        let Some(SearchPrompt { prompt, origin }) = &self.ui.search_prompt else {
+            return StateUpdate::None;
+        };
+        let mut prompt = prompt.clone();
+        match prompt.handle_key(key_event) {
+            TextPromptAction::Edit => {
+                let selection_key = self
+                    .find_search_match(
+                        &prompt.text,
+                        *origin,
+                        /*select_previous=*/ false,
+                        /*include_from=*/ true,
+                    )
+                    .map_or(*origin, |(selection_key, _, _)| selection_key);
+                StateUpdate::SetSearchPrompt(
+                    SearchPrompt {
+                        prompt,
+                        origin: *origin,
+                    },
+                    selection_key,
+                )
+            }
+            TextPromptAction::Submit => {
+                StateUpdate::SetSearchQuery(Some(prompt.text).filter(|text| !text.is_empty()))
+            }
+            TextPromptAction::Cancel => StateUpdate::CancelSearch(*origin),
+        }
+    }
+
+    /// Resolve the name of the register which the macro prompt is waiting
+    /// for. Any other key cancels the prompt.
+    fn handle_macro_prompt_key(&self, key_event: KeyEvent) -> StateUpdate {
+        let macro_prompt = match self.ui.macro_prompt {
+            Some(macro_prompt) if key_event.kind == KeyEventKind::Press => macro_prompt,
+            Some(_) | None => return StateUpdate::None,
+        };
+        let register = match (macro_prompt, key_event.code) {
+            (_, KeyCode::Char(c)) if Macros::is_register(c) => Some(c),
+            (MacroPrompt::Replay { .. }, KeyCode::Char('@')) => self.ui.macros.last_replayed(),
+            _ => None,
+        };
+        match (macro_prompt, register) {
+            (_, None) => StateUpdate::SetMacroPrompt(None),
+            (MacroPrompt::Record, Some(register)) => StateUpdate::StartRecordingMacro(register),
+            (MacroPrompt::Replay { count }, Some(register)) => {
+                if self.ui.macros.recording_register() == Some(register) {
+                    StateUpdate::SetStatusMessage(StatusMessage::error(format!(
+                        "Can't replay macro @{register} while recording it"
+                    )))
+                } else {
+                    StateUpdate::ReplayMacro { register, count }
+                }
+            }
+        }
+    }
+
+    /// Edit the message in the message editor, or close it.
+    fn handle_message_editor_key(&self, key_event: KeyEvent, term_width: usize) -> StateUpdate {
+        let Some(message_editor_dialog) = &self.ui.message_editor_dialog else {
+            return StateUpdate::None;
+        };
+        let mut message_editor_dialog = message_editor_dialog.clone();
+        match message_editor_dialog.handle_key(key_event, term_width) {
+            MessageEditorAction::Edit => {
+                StateUpdate::SetMessageEditorDialog(Some(message_editor_dialog))
+            }
+            MessageEditorAction::Save => StateUpdate::SaveCommitMessage {
+                commit_idx: message_editor_dialog.commit_idx,
+                message: message_editor_dialog.text,
+            },
+            MessageEditorAction::Cancel => StateUpdate::SetMessageEditorDialog(None),
+            MessageEditorAction::EditExternally => StateUpdate::EditCommitMessageExternally {
+                commit_idx: message_editor_dialog.commit_idx,
+                message: Some(message_editor_dialog.text),
+            },
+        }
+    }
+
+    /// Move the focus between the buttons of the quit dialog, or choose one.
+    fn handle_quit_dialog_key(&self, key_event: KeyEvent) -> StateUpdate {
+        let quit_dialog = match self.ui.quit_dialog {
+            Some(quit_dialog) if key_event.kind == KeyEventKind::Press => quit_dialog,
+            Some(_) | None => return StateUpdate::None,
+        };
+        let button = match key_event.code {
+            KeyCode::Tab
+            | KeyCode::BackTab
+            | KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Char('h')
+            | KeyCode::Char('l') => {
+                return StateUpdate::SetQuitDialog(Some(QuitDialog {
+                    focused_button: quit_dialog.focused_button.other(),
+                    ..quit_dialog
+                }));
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => quit_dialog.focused_button,
+            KeyCode::Char('q' | 'y') => QuitDialogButton::Quit,
+            KeyCode::Esc | KeyCode::Char('n') => QuitDialogButton::GoBack,
+            _ => return StateUpdate::None,
+        };
+        match button {
+            QuitDialogButton::Quit => StateUpdate::QuitCancel,
+            QuitDialogButton::GoBack => StateUpdate::SetQuitDialog(None),
+        }
+    }
+
+    /// Copy the path of the selected file or directory.
+    fn copy_path(&self) -> StateUpdate {
+        let path = match self.ui.selection_key {
+            SelectionKey::None => return StateUpdate::None,
+            SelectionKey::Directory(DirectoryKey {
+                commit_idx: _,
+                dir_idx,
+            }) => self.directories[dir_idx].as_path(),
+            SelectionKey::File(FileKey {
+                commit_idx: _,
+                file_idx,
+            })
+            | SelectionKey::Section(SectionKey {
+                commit_idx: _,
+                file_idx,
+                section_idx: _,
+            })
+            | SelectionKey::Line(LineKey {
+                commit_idx: _,
+                file_idx,
+                section_idx: _,
+                line_idx: _,
+            }) => &self.state.files[file_idx].path,
+        };
+        let path = path.to_string_lossy().into_owned();
+        StateUpdate::CopyToClipboard {
+            description: path.clone(),
+            text: path,
+        }
+    }
+
+    /// Copy the checked lines of the selected section, or of all sections of
+    /// the selected file or directory. As a diff, each line is prefixed with
+    /// `+` or `-` and each file and section gets a header.
+    fn copy_selected_lines(&self, as_diff: bool) -> StateUpdate {
+        let (file_indices, section_idx) = match self.ui.selection_key {
+            SelectionKey::None => return StateUpdate::None,
+            SelectionKey::Directory(directory_key) => {
+                (self.directory_file_indices(directory_key), None)
+            }
+            SelectionKey::File(FileKey {
+                commit_idx: _,
+                file_idx,
+            }) => (vec![file_idx], None),
+            SelectionKey::Section(SectionKey {
+                commit_idx: _,
+                file_idx,
+                section_idx,
+            })
+            | SelectionKey::Line(LineKey {
+                commit_idx: _,
+                file_idx,
+                section_idx,
+                line_idx: _,
+            }) => (vec![file_idx], Some(section_idx)),
+        };
+
+        let mut text = String::new();
+        let mut num_lines = 0;
+        for SelectedFileDiff {
+            old_path,
+            path,
+            mode_change: _,
+            binary_change: _,
+            sections,
+        } in self.selected_diffs(&file_indices, section_idx)
+        {
+            if as_diff && !sections.is_empty() {
+                text.push_str(&format!(
+                    "--- a/{}\n+++ b/{}\n",
+                    old_path.display(),
+                    path.display()
+                ));
+            }
+            for SelectedSectionDiff {
+                old_line_num,
+                new_line_num,
+                lines,
+            } in sections
+            {
+                if as_diff {
+                    text.push_str(&format!("@@ -{old_line_num} +{new_line_num} @@\n"));
+                }
+                for (change_type, line) in lines {
+                    if as_diff {
+                        text.push(match change_type {
+                            ChangeType::Added => '+',
+                            ChangeType::Removed => '-',
+                        });
+                    }
+                    text.push_str(line.trim_end_matches(['\n', '\r']));
+                    text.push('\n');
+                    num_lines += 1;
+                }
+            }
+        }
+
+        match num_lines {
+            0 => StateUpdate::SetStatusMessage(StatusMessage::error("No selected lines to copy")),
+            1 => StateUpdate::CopyToClipboard {
+                text,
+                description: "1 selected line".to_string(),
+            },
+            num_lines => StateUpdate::CopyToClipboard {
+                text,
+                description: format!("{num_lines} selected lines"),
+            },
+        }
+    }
+
+    /// The checked changes of the files, or only of one section of each file
+    /// if `section_idx` is given. Files without checked changes are omitted.
+    fn selected_diffs(
+        &self,
+        file_indices: &[usize],
+        section_idx: Option<usize>,
+    ) -> Vec<SelectedFileDiff<'_>> {
+        let mut result = Vec::new();
+        for &file_idx in file_indices {
+            let File {
+                old_path,
+                path,
+                file_mode,
+                sections,
+                is_deferred: _,
+                is_lossy: _,
+            } = &self.state.files[file_idx];
+            let mut file_diff = SelectedFileDiff {
+                old_path: old_path.as_deref().unwrap_or(path),
+                path,
+                mode_change: None,
+                binary_change: None,
+                sections: Vec::new(),
+            };
+            // Line numbers of the next line in the old and new file.
+            let mut old_line_num = 1;
+            let mut new_line_num = 1;
+            for (idx, section) in sections.iter().enumerate() {
+                let is_included = section_idx.is_none_or(|section_idx| section_idx == idx);
+                match section {
+                    Section::Unchanged { lines } => {
+                        old_line_num += lines.len();
+                        new_line_num += lines.len();
+                    }
+                    Section::Changed { lines, .. } => {
+                        let mut section_diff = SelectedSectionDiff {
+                            old_line_num,
+                            new_line_num,
+                            lines: Vec::new(),
+                        };
+                        for SectionChangedLine {
+                            is_checked,
+                            commit_idx: _,
+                            change_type,
+                            line,
+                            annotation: _,
+                        } in lines
+                        {
+                            match change_type {
+                                ChangeType::Added => new_line_num += 1,
+                                ChangeType::Removed => old_line_num += 1,
+                            }
+                            if *is_checked && is_included {
+                                section_diff.lines.push((*change_type, line.as_ref()));
+                            }
+                        }
+                        if !section_diff.lines.is_empty() {
+                            file_diff.sections.push(section_diff);
+                        }
+                    }
+                    Section::FileMode {
+                        is_checked,
+                        commit_idx: _,
+                        mode,
+                    } => {
+                        if *is_checked && is_included {
+                            file_diff.mode_change = Some((*file_mode, *mode));
+                        }
+                    }
+                    Section::Binary {
+                        is_checked,
+                        commit_idx: _,
+                        old_description,
+                        new_description,
+                        old_metadata: _,
+                        new_metadata: _,
+                    } => {
+                        if *is_checked && is_included {
+                            file_diff.binary_change =
+                                Some((old_description.as_deref(), new_description.as_deref()));
+                        }
+                    }
+                }
+            }
+            if file_diff.mode_change.is_some()
+                || file_diff.binary_change.is_some()
+                || !file_diff.sections.is_empty()
+            {
+                result.push(file_diff);
+            }
+        }
+        result
+    }
+
+    /// Preview the diff of all checked changes.
+    fn preview_dialog(&self) -> StateUpdate {
+        let diffs = self.selected_diffs(&self.ui.file_order, None);
+        if diffs.is_empty() {
+            return StateUpdate::SetStatusMessage(StatusMessage::error("No changes are selected"));
+        }
+
+        let mut preview = PreviewDialog {
+            lines: Vec::new(),
+            num_files: diffs.len(),
+            num_added: 0,
+            num_removed: 0,
+            scroll_offset: 0,
+        };
+        for SelectedFileDiff {
+            old_path,
+            path,
+            mode_change,
+            binary_change,
+            sections,
+        } in diffs
+        {
+            let header = if old_path == path {
+                path.display().to_string()
+            } else {
+                format!("{} => {}", old_path.display(), path.display())
+            };
+            preview.lines.push((PreviewLineKind::FileHeader, header));
+            if let Some((old_mode, new_mode)) = mode_change {
+                let text = match new_mode {
+                    FileMode::Unix(_) => format!("mode {old_mode} -> {new_mode}"),
+                    FileMode::Absent => "file deleted".to_owned(),
+                };
+                preview.lines.push((PreviewLineKind::Metadata, text));
+            }
+            if let Some((old_description, new_description)) = binary_change {
+                let description: Vec<&str> = [old_description, new_description]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                let text = if description.is_empty() {
+                    "binary contents".to_owned()
+                } else {
+                    format!("binary contents: {}", description.join(" -> "))
+                };
+                preview.lines.push((PreviewLineKind::Metadata, text));
+            }
+            for SelectedSectionDiff {
+                old_line_num,
+                new_line_num,
+                lines,
+            } in sections
+            {
+                preview.lines.push((
+                    PreviewLineKind::SectionHeader,
+                    format!("@@ -{old_line_num} +{new_line_num} @@"),
+                ));
+                for (change_type, line) in lines {
+                    let line = line.trim_end_matches(['\n', '\r']);
+                    preview.lines.push(match change_type {
+                        ChangeType::Added => {
+                            preview.num_added += 1;
+                            (PreviewLineKind::Added, format!("+{line}"))
+                        }
+                        ChangeType::Removed => {
+                            preview.num_removed += 1;
+                            (PreviewLineKind::Removed, format!("-{line}"))
+                        }
+                    });
+                }
+            }
+        }
+        StateUpdate::SetPreviewDialog(Some(preview))
+    }
+
+    /// Compare the selected changed line with the line it replaced or was
+    /// replaced by. The `n`th removed line of a section is paired with its `n`th
+    /// added line.
+    fn show_line_diff(&self) -> StateUpdate {
+        let (section_key, line_idx) = match self.ui.selection_key {
+            SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+                line_idx,
+            }) => (
+                SectionKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx,
+                },
+                line_idx,
+            ),
+            SelectionKey::None
+            | SelectionKey::Directory(_)
+            | SelectionKey::File(_)
+            | SelectionKey::Section(_) => {
+                return StateUpdate::SetStatusMessage(StatusMessage::error(
+                    "Select a changed line to see its character changes",
+                ))
+            }
+        };
+        let lines = match &self.state.files[section_key.file_idx].sections[section_key.section_idx]
+        {
+            Section::Changed { lines, .. } => lines,
+            Section::Unchanged { .. } | Section::FileMode { .. } | Section::Binary { .. } => {
+                return StateUpdate::None
+            }
+        };
+
+        let lines_of_type = |change_type: ChangeType| {
+            lines
+                .iter()
+                .enumerate()
+                .filter(move |(_, line)| line.change_type == change_type)
+                .map(|(line_idx, line)| (line_idx, line.line.as_ref()))
+        };
+        let change_type = lines[line_idx].change_type;
+        let pair_idx = lines_of_type(change_type)
+            .position(|(other_line_idx, _)| other_line_idx == line_idx)
+            .unwrap();
+        let other_change_type = match change_type {
+            ChangeType::Added => ChangeType::Removed,
+            ChangeType::Removed => ChangeType::Added,
+        };
+        let Some((_, other_line)) = lines_of_type(other_change_type).nth(pair_idx) else {
+            return StateUpdate::SetStatusMessage(StatusMessage::error(
+                "This line has no counterpart to compare it with",
+            ));
+        };
+        let line = lines[line_idx].line.as_ref();
+        let (old_line, new_line) = match change_type {
+            ChangeType::Added => (other_line, line),
+            ChangeType::Removed => (line, other_line),
+        };
+        StateUpdate::SetLineDiffDialog(Some(LineDiffDialog {
+            old_line: old_line.to_owned(),
+            new_line: new_line.to_owned(),
+        }))
+    }
+
+    /// The combined statistics of the visible files inside the given
+    /// directory.
+    fn directory_stats(&self, directory_key: DirectoryKey) -> FileStats {
+        self.directory_file_indices(directory_key)
+            .into_iter()
+            .map(|file_idx| self.file_stats[file_idx])
+            .fold(FileStats::default(), |acc, stats| FileStats {
+                num_added: acc.num_added + stats.num_added,
+                num_removed: acc.num_removed + stats.num_removed,
+                is_binary: acc.is_binary || stats.is_binary,
+                has_mode_change: acc.has_mode_change || stats.has_mode_change,
+            })
+    }
+
+    fn select_prev_page(
+        &self,
+        term_height: usize,
+        drawn_rects: &DrawnRects<ComponentId>,
+    ) -> SelectionKey {
+        let (keys, index) = self.find_selection();
+        let mut index = match index {
+            Some(index) => index,
+            None => return SelectionKey::None,
+        };
+
+        let original_y = match self.selection_key_y(drawn_rects, self.ui.selection_key) {
+            Some(original_y) => original_y,
+            None => {
+                return SelectionKey::None;
+            }
+        };
+        let target_y = original_y.saturating_sub(term_height.unwrap_isize() / 2);
+        while index > 0 {
+            index -= 1;
+            let selection_key_y = self.selection_key_y(drawn_rects, keys[index]);
+            if let Some(selection_key_y) = selection_key_y {
+                if selection_key_y <= target_y {
+                    break;
+                }
+            }
+        }
+        keys[index]
+    }
+
+    fn select_next_page(
+        &self,
+        term_height: usize,
+        drawn_rects: &DrawnRects<ComponentId>,
+    ) -> SelectionKey {
+        let (keys, index) = self.find_selection();
+        let mut index = match index {
+            Some(index) => index,
+            None => return SelectionKey::None,
+        };
+
+        let original_y = match self.selection_key_y(drawn_rects, self.ui.selection_key) {
+            Some(original_y) => original_y,
+            None => return SelectionKey::None,
+        };
+        let target_y = original_y.saturating_add(term_height.unwrap_isize() / 2);
+        while index + 1 < keys.len() {
+            index += 1;
+            let selection_key_y = self.selection_key_y(drawn_rects, keys[index]);
+            if let Some(selection_key_y) = selection_key_y {
+                if selection_key_y >= target_y {
+                    break;
+                }
+            }
+        }
+        keys[index]
+    }
+
+    pub(crate) fn selection_key_y(
+        &self,
+        drawn_rects: &DrawnRects<ComponentId>,
+        selection_key: SelectionKey,
+    ) -> Option<isize> {
+        let rect = self.selection_rect(drawn_rects, selection_key)?;
+        Some(rect.y)
+    }
+
+    pub(crate) fn selection_rect(
+        &self,
+        drawn_rects: &DrawnRects<ComponentId>,
+        selection_key: SelectionKey,
+    ) -> Option<Rect> {
+        let id = match selection_key {
+            SelectionKey::None => return None,
+            SelectionKey::Directory(_)
+            | SelectionKey::File(_)
+            | SelectionKey::Section(_)
+            | SelectionKey::Line(_) => ComponentId::SelectableItem(selection_key),
+        };
+        let drawn_rect = drawn_rects.get(&id);
+        if drawn_rect.is_none() {
+            warn!(component_id = ?id, "could not look up drawn rect for component; was it drawn?");
+        }
+        drawn_rect.map(|DrawnRect { rect, timestamp: _ }| *rect)
+    }
+
+    /// If the selected item wasn't drawn, such as when the layout changed
+    /// underneath it, get the nearest item before it which was drawn, or
+    /// else the nearest one after it. Returns `None` if the selected item was
+    /// drawn, or if nothing was drawn because the terminal is too small.
+    pub(crate) fn repair_selection(
+        &self,
+        drawn_rects: &DrawnRects<ComponentId>,
+    ) -> Option<SelectionKey> {
+        let selection_key = self.ui.selection_key;
+        // Files and directories may only be drawn in the file list pane.
+        let is_drawn = |selection_key: &SelectionKey| {
+            drawn_rects.contains_key(&ComponentId::SelectableItem(*selection_key))
+                || drawn_rects.contains_key(&ComponentId::FileListPaneItem(*selection_key))
+        };
+        if selection_key == SelectionKey::None
+            || is_drawn(&selection_key)
+            || drawn_rects.contains_key(&ComponentId::TerminalTooSmall)
+        {
+            return None;
+        }
+
+        let keys = self.all_selection_keys();
+        let nearest_key = match keys.iter().position(|key| key == &selection_key) {
+            Some(index) => keys[..index]
+                .iter()
+                .rev()
+                .find(|key| is_drawn(key))
+                .or_else(|| keys[index + 1..].iter().find(|key| is_drawn(key))),
+            None => keys.iter().find(|key| is_drawn(key)),
+        };
+        let nearest_key = nearest_key.copied().unwrap_or(SelectionKey::None);
+        warn!(
+            ?selection_key,
+            ?nearest_key,
+            "selected item was not drawn; selecting the nearest drawn item instead"
+        );
+        Some(nearest_key)
+    }
+
+    pub(crate) fn ensure_in_viewport(
+        &self,
+        term_height: usize,
+        drawn_rects: &DrawnRects<ComponentId>,
+        selection_key: SelectionKey,
+    ) -> Option<isize> {
+        let sticky_file_header_height = match selection_key {
+            SelectionKey::None | SelectionKey::Directory(_) | SelectionKey::File(_) => 0,
+            SelectionKey::Section(_) | SelectionKey::Line(_) => 1,
+        };
+        // The row of the commit message's "Edit message" button stays pinned
+        // above the sticky file header.
+        let sticky_commit_message_height = match self.ui.zoomed_file {
+            None if self.state.commits[self.ui.focused_commit_idx]
+                .message
+                .is_some() =>
+            {
+                1
+            }
+            Some(_) | None => 0,
+        };
+        let top_margin = sticky_file_header_height + sticky_commit_message_height;
+        // The action history is drawn over the bottom of the screen.
+        let bottom_margin = self
+            .action_history_panel()
+            .map(|panel| panel.height(term_height).unwrap_isize())
+            .unwrap_or_default();
+
+        let viewport_top_y = self.ui.scroll_offset_y + top_margin;
+        let viewport_height = (term_height.unwrap_isize() - top_margin - bottom_margin).max(0);
+        let viewport_bottom_y = viewport_top_y + viewport_height;
+
+        let selection_rect = self.selection_rect(drawn_rects, selection_key)?;
+        let selection_top_y = selection_rect.y;
+        let selection_height = selection_rect.height.unwrap_isize();
+        let selection_bottom_y = selection_top_y + selection_height;
+
+        // Idea: scroll the entire component into the viewport, not just the
+        // first line, if possible. If the entire component is smaller than
+        // the viewport, then we scroll only enough so that the entire
+        // component becomes visible, i.e. align the component's bottom edge
+        // with the viewport's bottom edge. Otherwise, we scroll such that
+        // the component's top edge is aligned with the viewport's top edge.
+        //
+        // FIXME: if we scroll up from below, we would want to align the top
+        // edge of the component, not the bottom edge. Thus, we should also
+        // accept the previous `SelectionKey` and use that when making the
+        // decision of where to scroll.
+        let result = if viewport_top_y <= selection_top_y && selection_bottom_y < viewport_bottom_y
+        {
+            // Component is completely within the viewport, no need to scroll.
+            self.ui.scroll_offset_y
+        } else if (
+            // Component doesn't fit in the viewport; just render the top.
+            selection_height >= viewport_height
+        ) || (
+            // Component is at least partially above the viewport.
+            selection_top_y < viewport_top_y
+        ) {
+            selection_top_y - top_margin
+        } else {
+            // Component is at least partially below the viewport. Want to satisfy:
+            // scroll_offset_y + term_height == rect_bottom_y
+            selection_bottom_y - top_margin - viewport_height
+        };
+        Some(result)
+    }
+
+    /// The dialog asking to confirm toggling all changes, if there are enough
+    /// files for a mistake to be costly.
+    fn toggle_all_dialog(&self, is_uniform: bool) -> Option<ToggleAllDialog> {
+        let num_files = self.state.files.len();
+        match self.options.toggle_all_confirmation_threshold {
+            Some(threshold) if !self.state.is_read_only && num_files > threshold => {
+                Some(ToggleAllDialog {
+                    num_files,
+                    is_uniform,
+                })
+            }
+            Some(_) | None => None,
+        }
+    }
+
+    /// Expand or collapse the item. When a file is collapsed, remember how
+    /// far it was scrolled, and when it's expanded again, go back to the same
+    /// place in it.
+    pub(crate) fn set_expand_item_and_position(
+        &mut self,
+        drawn_rects: &DrawnRects<ComponentId>,
+        selection: SelectionKey,
+        is_expanded: bool,
+    ) {
+        let was_expanded = self.ui.expanded_items.contains(&selection);
+        self.set_expand_item(selection, is_expanded);
+        let file_key = match selection {
+            SelectionKey::File(file_key) if was_expanded != is_expanded => file_key,
+            SelectionKey::None
+            | SelectionKey::Directory(_)
+            | SelectionKey::File(_)
+            | SelectionKey::Section(_)
+            | SelectionKey::Line(_) => return,
+        };
+        // Expanding or collapsing the file doesn't move its header, so the
+        // position drawn before is still valid.
+        let Some(file_rect) = self.selection_rect(drawn_rects, selection) else {
+            return;
+        };
+        if is_expanded {
+            if let Some(scroll_offset_y) = self.ui.file_scroll_offsets.remove(&file_key) {
+                self.ui.scroll_offset_y = (file_rect.y + scroll_offset_y).max(0);
+                if let Some(selection_key) = self.ui.file_selections.get(&file_key) {
+                    self.ui.selection_key = *selection_key;
+                }
+            }
+        } else {
+            self.ui
+                .file_scroll_offsets
+                .insert(file_key, self.ui.scroll_offset_y - file_rect.y);
+        }
+    }
+
+    /// The highlights of the lines of a section: their syntax highlighting,
+    /// and their changed words, if enabled.
+    pub(crate) fn line_highlights(&self, file_idx: usize, section_idx: usize) -> &[LineHighlights] {
+        self.line_highlights[file_idx]
+            .get_or_init(|| self.file_highlights(file_idx))
+            .get(section_idx)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    fn file_highlights(&self, file_idx: usize) -> Vec<Vec<LineHighlights>> {
+        let file = &self.state.files[file_idx];
+        #[cfg(feature = "syntax-highlighting")]
+        let mut highlights = highlight::highlight_file(file, &self.options.theme);
+        #[cfg(not(feature = "syntax-highlighting"))]
+        let mut highlights: Vec<Vec<LineHighlights>> = Vec::new();
+
+        if self.options.word_diff {
+            highlights.resize_with(file.sections.len(), Vec::new);
+            for (section, section_highlights) in file.sections.iter().zip(&mut highlights) {
+                if let Section::Changed { lines, .. } = section {
+                    section_highlights.resize_with(lines.len(), Vec::new);
+                    let word_diff = word_diff::section_word_diff(lines, &self.options.theme);
+                    for (line_highlights, word_diff) in section_highlights.iter_mut().zip(word_diff)
+                    {
+                        *line_highlights = merge_highlights(line_highlights, &word_diff);
+                    }
+                }
+            }
+        }
+        highlights
+    }
+}
+
+/// The checked changes of a file.
+struct SelectedFileDiff<'a> {
+    /// The path before the change, which is `path` unless the file was
+    /// renamed.
+    old_path: &'a Path,
+    path: &'a Path,
+    /// The old and new mode, if the mode change is checked.
+    mode_change: Option<(FileMode, FileMode)>,
+    /// The old and new descriptions, if the binary change is checked.
+    binary_change: Option<(Option<&'a str>, Option<&'a str>)>,
+    sections: Vec<SelectedSectionDiff<'a>>,
+}
+
+/// The checked lines of a section, along with the line numbers where the
+/// section starts in the old and new file.
+struct SelectedSectionDiff<'a> {
+    old_line_num: usize,
+    new_line_num: usize,
+    lines: Vec<(ChangeType, &'a str)>,
+}
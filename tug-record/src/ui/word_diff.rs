@@ -0,0 +1,232 @@
+use std::ops::Range;
+
+use crate::types::{ChangeType, SectionChangedLine};
+use crate::ui::components::line::LineHighlights;
+use crate::ui::components::line_diff_dialog::diff_items;
+use crate::ui::theme::Theme;
+
+/// Split the line into words, runs of whitespace and single punctuation
+/// characters, as byte ranges. The line ending isn't included, since it never
+/// differs in a meaningful way.
+fn split_words(line: &str) -> Vec<Range<usize>> {
+    #[derive(Clone, Copy, Eq, PartialEq)]
+    enum CharClass {
+        Word,
+        Whitespace,
+        Other,
+    }
+    let char_class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            CharClass::Word
+        } else if c.is_whitespace() {
+            CharClass::Whitespace
+        } else {
+            CharClass::Other
+        }
+    };
+
+    let line = line.trim_end_matches(['\n', '\r']);
+    let mut words: Vec<Range<usize>> = Vec::new();
+    let mut last_class = None;
+    for (idx, c) in line.char_indices() {
+        let class = char_class(c);
+        match words.last_mut() {
+            Some(word) if last_class == Some(class) && class != CharClass::Other => {
+                word.end = idx + c.len_utf8();
+            }
+            _ => words.push(idx..idx + c.len_utf8()),
+        }
+        last_class = Some(class);
+    }
+    words
+}
+
+/// The byte ranges of the words which differ between a removed line and the
+/// added line which replaced it, in each of them. Adjacent changed words are
+/// merged into one range. If the lines have no words in common, nothing is
+/// emphasized, since the whole line changed.
+fn diff_words(old_line: &str, new_line: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let old_words = split_words(old_line);
+    let new_words = split_words(new_line);
+    let old_texts: Vec<&str> = old_words
+        .iter()
+        .map(|word| &old_line[word.clone()])
+        .collect();
+    let new_texts: Vec<&str> = new_words
+        .iter()
+        .map(|word| &new_line[word.clone()])
+        .collect();
+    let (is_removed, is_added) = diff_items(&old_texts, &new_texts);
+    if is_removed.iter().all(|is_removed| *is_removed) && is_added.iter().all(|is_added| *is_added)
+    {
+        return Default::default();
+    }
+
+    let changed_ranges = |words: &[Range<usize>], is_changed: &[bool]| {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (word, _) in words
+            .iter()
+            .zip(is_changed)
+            .filter(|(_, is_changed)| **is_changed)
+        {
+            match ranges.last_mut() {
+                Some(range) if range.end == word.start => range.end = word.end,
+                _ => ranges.push(word.clone()),
+            }
+        }
+        ranges
+    };
+    (
+        changed_ranges(&old_words, &is_removed),
+        changed_ranges(&new_words, &is_added),
+    )
+}
+
+/// Emphasize the changed words of each line of a changed section. The removed
+/// lines are compared with the added lines which replaced them in order, like
+/// the line diff dialog does; lines without a counterpart aren't emphasized.
+/// The changed words are drawn with [`Theme::changed_word`].
+pub(crate) fn section_word_diff(
+    lines: &[SectionChangedLine],
+    theme: &Theme,
+) -> Vec<LineHighlights> {
+    let mut highlights = vec![LineHighlights::new(); lines.len()];
+    let line_indices_of_type = |change_type: ChangeType| {
+        lines
+            .iter()
+            .enumerate()
+            .filter(move |(_, line)| line.change_type == change_type)
+            .map(|(line_idx, _)| line_idx)
+    };
+    for (old_idx, new_idx) in
+        line_indices_of_type(ChangeType::Removed).zip(line_indices_of_type(ChangeType::Added))
+    {
+        let (removed, added) = diff_words(&lines[old_idx].line, &lines[new_idx].line);
+        highlights[old_idx] = removed
+            .into_iter()
+            .map(|range| (range, theme.changed_word))
+            .collect();
+        highlights[new_idx] = added
+            .into_iter()
+            .map(|range| (range, theme.changed_word))
+            .collect();
+    }
+    highlights
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+
+    fn words(line: &str) -> Vec<&str> {
+        split_words(line)
+            .into_iter()
+            .map(|word| &line[word])
+            .collect()
+    }
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            words("let x_1 = foo(bar);\n"),
+            ["let", " ", "x_1", " ", "=", " ", "foo", "(", "bar", ")", ";"]
+        );
+        // Punctuation is split into single characters, but whitespace isn't.
+        assert_eq!(words("  a->b\t \r\n"), ["  ", "a", "-", ">", "b", "\t "]);
+        assert_eq!(words("été, 日本語"), ["été", ",", " ", "日本語"]);
+        assert_eq!(words("\n"), Vec::<&str>::new());
+    }
+
+    /// The text of the changed words of each line.
+    fn changed_words<'a>(old_line: &'a str, new_line: &'a str) -> (Vec<&'a str>, Vec<&'a str>) {
+        let (removed, added) = diff_words(old_line, new_line);
+        (
+            removed.into_iter().map(|range| &old_line[range]).collect(),
+            added.into_iter().map(|range| &new_line[range]).collect(),
+        )
+    }
+
+    #[test]
+    fn test_diff_words() {
+        assert_eq!(
+            changed_words("let x = 1;\n", "let x = 2;\n"),
+            (vec!["1"], vec!["2"])
+        );
+        // Adjacent changed words are merged.
+        assert_eq!(
+            changed_words("call(a)\n", "call(b, c)\n"),
+            (vec!["a"], vec!["b, c"])
+        );
+        assert_eq!(changed_words("a b\n", "a b c\n"), (vec![], vec![" c"]));
+    }
+
+    #[test]
+    fn test_diff_words_with_nothing_in_common() {
+        assert_eq!(changed_words("foo\n", "bar\n"), (vec![], vec![]));
+        assert_eq!(changed_words("foo bar\n", "baz\n"), (vec![], vec![]));
+        assert_eq!(changed_words("", "new\n"), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_section_word_diff_pairs_lines_in_order() {
+        let theme = Theme::default();
+        let line = |change_type, line| SectionChangedLine {
+            is_checked: false,
+            commit_idx: 0,
+            change_type,
+            line: Cow::Borrowed(line),
+            annotation: None,
+        };
+        let emphasized = |lines: &[SectionChangedLine<'static>]| -> Vec<Vec<&'static str>> {
+            section_word_diff(lines, &theme)
+                .into_iter()
+                .zip(lines)
+                .map(|(highlights, line)| {
+                    let Cow::Borrowed(line) = line.line else {
+                        unreachable!()
+                    };
+                    highlights
+                        .into_iter()
+                        .map(|(range, style)| {
+                            assert_eq!(style, theme.changed_word);
+                            &line[range]
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+
+        // The extra removed line has no counterpart.
+        assert_eq!(
+            emphasized(&[
+                line(ChangeType::Removed, "a = 1\n"),
+                line(ChangeType::Removed, "b = 2\n"),
+                line(ChangeType::Added, "a = 3\n"),
+            ]),
+            [vec!["1"], vec![], vec!["3"]]
+        );
+        // Nor do the extra added lines.
+        assert_eq!(
+            emphasized(&[
+                line(ChangeType::Removed, "a = 1\n"),
+                line(ChangeType::Added, "a = 3\n"),
+                line(ChangeType::Added, "b = 2\n"),
+                line(ChangeType::Added, "c = 4\n"),
+            ]),
+            [vec!["1"], vec!["3"], vec![], vec![]]
+        );
+        // Lines are paired by their order among the lines of the same type,
+        // even if they aren't adjacent.
+        assert_eq!(
+            emphasized(&[
+                line(ChangeType::Removed, "a = 1\n"),
+                line(ChangeType::Removed, "b = 2\n"),
+                line(ChangeType::Added, "a = 3\n"),
+                line(ChangeType::Added, "b = 4\n"),
+            ]),
+            [vec!["1"], vec!["2"], vec!["3"], vec!["4"]]
+        );
+    }
+}
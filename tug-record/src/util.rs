@@ -1,8 +1,10 @@
+#[cfg(feature = "tui")]
 pub trait UsizeExt {
     fn unwrap_isize(self) -> isize;
     fn clamp_into_u16(self) -> u16;
 }
 
+#[cfg(feature = "tui")]
 impl UsizeExt for usize {
     fn unwrap_isize(self) -> isize {
         isize::try_from(self).unwrap()
@@ -17,6 +19,7 @@ impl UsizeExt for usize {
     }
 }
 
+#[cfg(feature = "tui")]
 pub trait IsizeExt {
     fn unwrap_usize(self) -> usize;
     #[allow(dead_code)]
@@ -24,6 +27,7 @@ pub trait IsizeExt {
     fn clamp_into_usize(self) -> usize;
 }
 
+#[cfg(feature = "tui")]
 impl IsizeExt for isize {
     fn unwrap_usize(self) -> usize {
         usize::try_from(self).unwrap()
@@ -45,3 +49,60 @@ impl IsizeExt for isize {
         }
     }
 }
+
+/// Match `text` against a glob `pattern`, where `*` matches any sequence of
+/// characters (including `/`) and `?` matches any single character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Encode `bytes` as standard base64, with padding.
+#[cfg(all(feature = "tui", not(target_family = "wasm")))]
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (usize::from(b[0]) << 16) | (usize::from(b[1]) << 8) | usize::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(char::from(ALPHABET[(n >> (18 - 6 * i)) & 0x3f]));
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,296 @@
+//! Experimental support for embedding the recorder in a web page, behind the
+//! `wasm` feature.
+//!
+//! A browser has no blocking event loop, so the recorder is driven with an
+//! [`crate::EmbeddedRecorder`]: the page forwards its `keydown` events to a
+//! [`BrowserEvents`] queue, passes the queued events to
+//! [`crate::EmbeddedRecorder::handle_events`] on each animation frame, draws
+//! the recorder into a `ratatui` buffer with the size of the terminal on the
+//! page, and writes [`buffer_to_ansi`] of that buffer to an `xterm.js`
+//! terminal.
+//!
+//! [`BrowserInput`] is the [`RecordInput`] to create the recorder with. It
+//! edits commit messages with `window.prompt()` and copies to the clipboard
+//! with the asynchronous clipboard API.
+//!
+//! This module is experimental: its API may change in any release. Build it
+//! for `wasm32-unknown-unknown`, where the key types below are stand-ins for
+//! `crossterm`'s, which doesn't build there.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier};
+use unicode_width::UnicodeWidthStr;
+
+pub use crate::ui::key_event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+#[cfg(target_family = "wasm")]
+pub use crate::ui::key_event::{KeyEventState, ModifierKeyCode};
+use crate::{Event, RecordError, RecordInput, TerminalKind};
+
+/// Convert the `key` of a DOM `KeyboardEvent`, along with the state of its
+/// modifier keys, into a key event. Returns `None` for keys which the
+/// recorder doesn't handle, such as modifier keys pressed on their own.
+///
+/// As with terminal input, `Shift` is only reported for characters if they
+/// are uppercase, since the character already reflects it otherwise.
+pub fn key_event_from_dom(
+    key: &str,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    meta: bool,
+) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    modifiers.set(KeyModifiers::CONTROL, ctrl);
+    modifiers.set(KeyModifiers::ALT, alt);
+    modifiers.set(KeyModifiers::SHIFT, shift);
+    modifiers.set(KeyModifiers::SUPER, meta);
+
+    let code = match key {
+        "Enter" => KeyCode::Enter,
+        "Escape" => KeyCode::Esc,
+        "Backspace" => KeyCode::Backspace,
+        "Tab" if shift => KeyCode::BackTab,
+        "Tab" => KeyCode::Tab,
+        "Delete" => KeyCode::Delete,
+        "Insert" => KeyCode::Insert,
+        "ArrowUp" => KeyCode::Up,
+        "ArrowDown" => KeyCode::Down,
+        "ArrowLeft" => KeyCode::Left,
+        "ArrowRight" => KeyCode::Right,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Spacebar" => KeyCode::Char(' '),
+        key => {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => {
+                    if !c.is_uppercase() {
+                        modifiers.remove(KeyModifiers::SHIFT);
+                    }
+                    KeyCode::Char(c)
+                }
+                _ => match key.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=24) => KeyCode::F(n),
+                    // Modifier keys, dead keys, and keys the browser
+                    // couldn't identify.
+                    _ => return None,
+                },
+            }
+        }
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+/// A queue of events from the page, shared between the page's event
+/// listeners and the recorder. Cloning it returns another handle to the same
+/// queue.
+#[derive(Clone, Debug, Default)]
+pub struct BrowserEvents {
+    events: Rc<RefCell<VecDeque<Event>>>,
+}
+
+impl BrowserEvents {
+    /// Create an empty queue.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Queue an event.
+    pub fn push(&self, event: Event) {
+        let Self { events } = self;
+        events.borrow_mut().push_back(event);
+    }
+
+    /// Queue the key pressed in a `keydown` event. Returns whether the key is
+    /// one the recorder handles, in which case the page should call
+    /// `preventDefault()` on the event, so that the browser doesn't also
+    /// handle it, such as by scrolling.
+    pub fn push_keyboard_event(&self, event: &web_sys::KeyboardEvent) -> bool {
+        match key_event_from_dom(
+            &event.key(),
+            event.ctrl_key(),
+            event.alt_key(),
+            event.shift_key(),
+            event.meta_key(),
+        ) {
+            Some(key_event) => {
+                self.push(Event::Key(key_event));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Queue a resize, such as after the page's terminal was fitted to a new
+    /// size.
+    pub fn push_resize(&self) {
+        self.push(Event::Resize);
+    }
+
+    /// Remove and return all queued events.
+    pub fn take(&self) -> Vec<Event> {
+        let Self { events } = self;
+        events.borrow_mut().drain(..).collect()
+    }
+}
+
+/// User input from a web page.
+#[derive(Debug)]
+pub struct BrowserInput {
+    width: usize,
+    height: usize,
+    events: BrowserEvents,
+}
+
+impl BrowserInput {
+    /// Create an input for a terminal on the page with the given size,
+    /// reading events from `events`.
+    pub fn new(width: usize, height: usize, events: BrowserEvents) -> Self {
+        Self {
+            width,
+            height,
+            events,
+        }
+    }
+}
+
+impl RecordInput for BrowserInput {
+    fn terminal_kind(&self) -> TerminalKind {
+        let Self {
+            width,
+            height,
+            events: _,
+        } = self;
+        TerminalKind::Testing {
+            width: *width,
+            height: *height,
+        }
+    }
+
+    /// The page can't block waiting for events, so this returns the queued
+    /// events immediately, or [`Event::None`] if there are none.
+    fn next_events(&mut self) -> Result<Vec<Event>, RecordError> {
+        let events = self.events.take();
+        if events.is_empty() {
+            Ok(vec![Event::None])
+        } else {
+            Ok(events)
+        }
+    }
+
+    fn edit_commit_message(&mut self, message: &str) -> Result<String, RecordError> {
+        let window = web_sys::window()
+            .ok_or_else(|| RecordError::Other("no browser window is available".to_string()))?;
+        match window.prompt_with_message_and_default("Commit message:", message) {
+            Ok(Some(message)) => Ok(message),
+            // The user cancelled the prompt, so keep the original message.
+            Ok(None) => Ok(message.to_owned()),
+            Err(err) => Err(RecordError::Other(format!(
+                "failed to prompt for the commit message: {err:?}"
+            ))),
+        }
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<(), RecordError> {
+        let window = web_sys::window()
+            .ok_or_else(|| RecordError::Other("no browser window is available".to_string()))?;
+        // The write finishes asynchronously, and there is no way to wait for
+        // it here, so failures to write aren't reported.
+        let _promise = window.navigator().clipboard().write_text(text);
+        Ok(())
+    }
+}
+
+/// Render a buffer as ANSI escape sequences which redraw the whole screen of
+/// a terminal emulator, such as `xterm.js`, with the buffer's contents and
+/// styles.
+pub fn buffer_to_ansi(buffer: &Buffer) -> String {
+    let mut ansi = String::with_capacity(buffer.content.len() * 2);
+    let mut current_style = None;
+    // A zero-width buffer has no cells, so any nonzero chunk size will do.
+    for (y, cells) in buffer
+        .content
+        .chunks(usize::from(buffer.area.width).max(1))
+        .enumerate()
+    {
+        write!(&mut ansi, "\x1b[{};1H", y + 1).unwrap();
+        let mut skip: usize = 0;
+        for cell in cells {
+            if skip == 0 {
+                let style = (cell.fg, cell.bg, cell.modifier);
+                if current_style != Some(style) {
+                    push_sgr(&mut ansi, cell.fg, cell.bg, cell.modifier);
+                    current_style = Some(style);
+                }
+                ansi.push_str(cell.symbol());
+            }
+            skip = std::cmp::max(skip, cell.symbol().width()).saturating_sub(1);
+        }
+    }
+    ansi.push_str("\x1b[0m");
+    ansi
+}
+
+/// Write a "Select Graphic Rendition" sequence which resets the style and
+/// then sets the given colors and modifiers.
+fn push_sgr(ansi: &mut String, fg: Color, bg: Color, modifier: Modifier) {
+    ansi.push_str("\x1b[0");
+    for (flag, code) in [
+        (Modifier::BOLD, 1),
+        (Modifier::DIM, 2),
+        (Modifier::ITALIC, 3),
+        (Modifier::UNDERLINED, 4),
+        (Modifier::SLOW_BLINK, 5),
+        (Modifier::RAPID_BLINK, 6),
+        (Modifier::REVERSED, 7),
+        (Modifier::HIDDEN, 8),
+        (Modifier::CROSSED_OUT, 9),
+    ] {
+        if modifier.contains(flag) {
+            write!(ansi, ";{code}").unwrap();
+        }
+    }
+    push_color(ansi, fg, 30);
+    push_color(ansi, bg, 40);
+    ansi.push('m');
+}
+
+/// Write the parameters which set a color, where `base` is 30 for the
+/// foreground and 40 for the background.
+fn push_color(ansi: &mut String, color: Color, base: u8) {
+    let code = match color {
+        Color::Reset => return,
+        Color::Black => base,
+        Color::Red => base + 1,
+        Color::Green => base + 2,
+        Color::Yellow => base + 3,
+        Color::Blue => base + 4,
+        Color::Magenta => base + 5,
+        Color::Cyan => base + 6,
+        Color::Gray => base + 7,
+        Color::DarkGray => base + 60,
+        Color::LightRed => base + 61,
+        Color::LightGreen => base + 62,
+        Color::LightYellow => base + 63,
+        Color::LightBlue => base + 64,
+        Color::LightMagenta => base + 65,
+        Color::LightCyan => base + 66,
+        Color::White => base + 67,
+        Color::Indexed(i) => {
+            write!(ansi, ";{};5;{i}", base + 8).unwrap();
+            return;
+        }
+        Color::Rgb(r, g, b) => {
+            write!(ansi, ";{};2;{r};{g};{b}", base + 8).unwrap();
+            return;
+        }
+    };
+    write!(ansi, ";{code}").unwrap();
+}